@@ -132,6 +132,7 @@ impl DepthExample {
 
         let material = draw_list_renderer
             .create_material_from_shader("main", SHADER)
+            .unwrap_or_else(|error| panic!("{error}"))
             .depth_buffer(depth_buffer, DepthCompare::LessEqual)
             .uniform(0, 0, camera);
         let material = draw_list_renderer.create_material(material);
@@ -168,6 +169,11 @@ impl granite::scene::Scene for DepthExample {
         }
     }
 
+    fn device_lost(&mut self, renderer: &granite::renderer::Renderer) {
+        self.draw_list_renderer
+            .replace_device(renderer.device.clone(), renderer.queue.clone());
+    }
+
     fn frame(
         &mut self,
         _renderer: &granite::renderer::Renderer,
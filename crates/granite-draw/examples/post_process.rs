@@ -0,0 +1,140 @@
+//! Demonstrates the built-in post-processing chain by rendering an HDR triangle into the chain's
+//! input, then applying bloom, tonemapping, a vignette, and FXAA on the way to the surface.
+use glam::{UVec2, Vec4};
+use granite::{
+    app::SceneBuilder,
+    renderer::{Frame, Renderer},
+    scene::Scene,
+};
+use granite_draw::{
+    BlendMode, DrawListRenderer, FrameContext, MaterialId, MeshId,
+    draw_list::{DrawList, RenderTarget},
+    post_process::{PostEffect, PostProcess, PostProcessChain, TonemapOperator},
+    render_target::RenderTargetFormat,
+};
+use granite_macros::vertex_buffer;
+
+const SCENE_SHADER: &str = r"
+struct VertexIn {
+    @location(0) position: vec4<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+struct VertexOut {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+}
+
+@vertex fn vertex(v: VertexIn) -> VertexOut {
+    return VertexOut(vec4<f32>(v.position.xy, 0.0, 1.0), v.color);
+}
+
+@fragment fn fragment(v: VertexOut) -> @location(0) vec4<f32> {
+    return v.color;
+}
+";
+
+#[vertex_buffer]
+struct Vertex {
+    position: Vec4,
+    color: Vec4,
+}
+
+struct PostProcessBuilder;
+
+struct PostProcessScene {
+    draw_list_renderer: DrawListRenderer,
+    scene_mesh: MeshId,
+    scene_material: MaterialId,
+    post_process: PostProcessChain,
+}
+
+impl SceneBuilder for PostProcessBuilder {
+    type Target = PostProcessScene;
+
+    fn build(self, renderer: &mut Renderer) -> Self::Target {
+        let mut draw_list_renderer =
+            DrawListRenderer::new(renderer.device.clone(), renderer.queue.clone());
+
+        // Colors above 1.0 only survive in an HDR target and are picked up by bloom.
+        let vertices = &[
+            Vertex {
+                position: Vec4::new(-0.5, -0.5, 0.0, 0.0),
+                color: Vec4::new(4.0, 0.2, 0.2, 1.0),
+            },
+            Vertex {
+                position: Vec4::new(0.0, 0.5, 0.0, 0.0),
+                color: Vec4::new(0.2, 4.0, 0.2, 1.0),
+            },
+            Vertex {
+                position: Vec4::new(0.5, -0.5, 0.0, 0.0),
+                color: Vec4::new(0.2, 0.2, 4.0, 1.0),
+            },
+        ];
+        let scene_mesh = draw_list_renderer.create_mesh("triangle", vertices, &[0, 1, 2]);
+        let scene_material = draw_list_renderer
+            .create_material_from_shader("scene", SCENE_SHADER)
+            .unwrap_or_else(|error| panic!("{error}"))
+            .blend_mode(BlendMode::Opaque);
+        let scene_material = draw_list_renderer.create_material(scene_material);
+
+        let post_process = draw_list_renderer.create_post_process_chain(
+            "post_process",
+            PostProcess::new(RenderTargetFormat::Rgba16Float)
+                .effect(PostEffect::Bloom {
+                    threshold: 1.0,
+                    intensity: 0.8,
+                    sigma: 8.0,
+                })
+                .effect(PostEffect::Tonemap {
+                    operator: TonemapOperator::Aces,
+                    exposure: 1.0,
+                })
+                .effect(PostEffect::Vignette {
+                    intensity: 0.6,
+                    radius: 0.5,
+                    smoothness: 0.5,
+                })
+                .effect(PostEffect::Fxaa),
+        );
+
+        PostProcessScene {
+            draw_list_renderer,
+            scene_mesh,
+            scene_material,
+            post_process,
+        }
+    }
+}
+
+impl Scene for PostProcessScene {
+    fn device_lost(&mut self, renderer: &Renderer) {
+        self.draw_list_renderer
+            .replace_device(renderer.device.clone(), renderer.queue.clone());
+    }
+
+    fn frame(&mut self, _renderer: &Renderer, frame: &Frame, _delta_time: f32) {
+        let mut draw_list = DrawList::new();
+
+        draw_list.draw_mesh(
+            RenderTarget::Custom(self.post_process.input()),
+            self.scene_mesh,
+            self.scene_material,
+        );
+        self.post_process
+            .record(&mut draw_list, RenderTarget::Surface);
+
+        self.draw_list_renderer.submit_draw_list(
+            FrameContext::new(
+                &frame.view,
+                UVec2::from(frame.surface_size),
+                frame.surface_format,
+            ),
+            &draw_list,
+        );
+    }
+}
+
+fn main() {
+    granite::run(PostProcessBuilder);
+}
@@ -112,6 +112,7 @@ impl SceneBuilder for PostProcessBuilder {
         let scene_mesh = draw_list_renderer.create_mesh("triangle", vertices, &[0, 1, 2]);
         let scene_material = draw_list_renderer
             .create_material_from_shader("scene", SCENE_SHADER)
+            .unwrap_or_else(|error| panic!("{error}"))
             .blend_mode(BlendMode::Opaque);
         let scene_material = draw_list_renderer.create_material(scene_material);
 
@@ -123,6 +124,7 @@ impl SceneBuilder for PostProcessBuilder {
         );
         let post_material = draw_list_renderer
             .create_material_from_shader("post", POST_SHADER)
+            .unwrap_or_else(|error| panic!("{error}"))
             .render_target_texture(0, 0, render_target)
             .sampler(0, 1, sampler)
             .blend_mode(BlendMode::Opaque);
@@ -139,6 +141,11 @@ impl SceneBuilder for PostProcessBuilder {
 }
 
 impl Scene for PostProcess {
+    fn device_lost(&mut self, renderer: &Renderer) {
+        self.draw_list_renderer
+            .replace_device(renderer.device.clone(), renderer.queue.clone());
+    }
+
     fn frame(&mut self, _renderer: &Renderer, frame: &Frame, _delta_time: f32) {
         let mut draw_list = DrawList::new();
 
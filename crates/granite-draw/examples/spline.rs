@@ -118,6 +118,7 @@ impl SceneBuilder for SplineBuilder {
 
         let material = draw_list_renderer
             .create_material_from_shader("spline", SHADER)
+            .unwrap_or_else(|error| panic!("{error}"))
             .uniform(0, 0, projection_uniform);
         let material = draw_list_renderer.create_material(material);
 
@@ -142,6 +143,11 @@ impl Scene for Spline {
         }
     }
 
+    fn device_lost(&mut self, renderer: &Renderer) {
+        self.draw_list_renderer
+            .replace_device(renderer.device.clone(), renderer.queue.clone());
+    }
+
     fn frame(&mut self, _renderer: &Renderer, frame: &Frame, _delta_time: f32) {
         let mut draw_list = DrawList::new();
 
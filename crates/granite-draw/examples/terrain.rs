@@ -92,8 +92,9 @@ impl TerrainExample {
             draw_list_renderer.create_depth_buffer("main", DepthBufferSize::SurfaceSize);
 
         let terrain_material = {
-            let shader =
-                draw_list_renderer.create_shader("terrain", include_str!("../assets/terrain.wgsl"));
+            let shader = draw_list_renderer
+                .create_shader("terrain", include_str!("../assets/terrain.wgsl"))
+                .unwrap_or_else(|error| panic!("{error}"));
 
             let vertex_shader = draw_list_renderer.create_vertex_shader(shader, "vertex");
             let fragment_shader = draw_list_renderer.create_fragment_shader(shader, "fragment");
@@ -156,6 +157,11 @@ impl granite::scene::Scene for TerrainExample {
         }
     }
 
+    fn device_lost(&mut self, renderer: &granite::renderer::Renderer) {
+        self.draw_list_renderer
+            .replace_device(renderer.device.clone(), renderer.queue.clone());
+    }
+
     fn frame(
         &mut self,
         _renderer: &granite::renderer::Renderer,
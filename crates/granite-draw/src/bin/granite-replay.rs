@@ -0,0 +1,143 @@
+//! Renders a draw list capture headlessly and writes the result to a PNG.
+//!
+//! Usage: `granite-replay <capture> <output.png>`
+//!
+//! Captures are created with
+//! [`DrawListRenderer::capture_next_draw_list`](granite_draw::DrawListRenderer::capture_next_draw_list).
+
+use std::{path::Path, process::ExitCode};
+
+use glam::UVec2;
+use granite_draw::{DrawListRenderer, FrameContext, capture::DrawListCapture};
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().collect();
+    let [_, capture_path, output_path] = args.as_slice() else {
+        eprintln!("Usage: granite-replay <capture> <output.png>");
+        return ExitCode::FAILURE;
+    };
+
+    match replay(Path::new(capture_path), Path::new(output_path)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn replay(capture_path: &Path, output_path: &Path) -> Result<(), String> {
+    let capture = DrawListCapture::load(capture_path).map_err(|error| {
+        format!(
+            "Could not load capture `{}`: {error}",
+            capture_path.display()
+        )
+    })?;
+
+    let (device, queue) = request_device()?;
+    let mut renderer = DrawListRenderer::new(device.clone(), queue.clone());
+    let draw_list = capture
+        .restore(&mut renderer)
+        .ok_or_else(|| format!("Could not restore capture `{}`.", capture_path.display()))?;
+
+    let size = capture.surface_size();
+    let format = capture.surface_format();
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("replay_surface"),
+        size: wgpu::Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    renderer.submit_draw_list(FrameContext::new(&view, size, format), &draw_list);
+
+    let pixels = read_texture(&device, &queue, &texture, size)?;
+    image::save_buffer(
+        output_path,
+        &pixels,
+        size.x,
+        size.y,
+        image::ColorType::Rgba8,
+    )
+    .map_err(|error| format!("Could not write `{}`: {error}", output_path.display()))
+}
+
+/// Requests a device from the default adapter, with the optional features the renderer uses
+/// when they are available.
+fn request_device() -> Result<(wgpu::Device, wgpu::Queue), String> {
+    let instance = wgpu::Instance::default();
+    let adapter =
+        pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))
+            .map_err(|error| format!("Could not request a graphics adapter! ({error})"))?;
+
+    let optional_features = adapter.features() & wgpu::Features::PUSH_CONSTANTS;
+    let max_push_constant_size = if optional_features.contains(wgpu::Features::PUSH_CONSTANTS) {
+        adapter.limits().max_push_constant_size
+    } else {
+        0
+    };
+
+    pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+        required_features: optional_features,
+        required_limits: wgpu::Limits {
+            max_push_constant_size,
+            ..Default::default()
+        },
+        ..Default::default()
+    }))
+    .map_err(|error| format!("Could not request a device and queue from the adapter! ({error})"))
+}
+
+/// Copies an RGBA8 texture into tightly packed rows.
+fn read_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    size: UVec2,
+) -> Result<Vec<u8>, String> {
+    let row_len = size.x * 4;
+    let padded_row_len = row_len.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("replay_readback"),
+        size: u64::from(padded_row_len) * u64::from(size.y),
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("replay_readback_encoder"),
+    });
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::TexelCopyBufferInfo {
+            buffer: &buffer,
+            layout: wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_row_len),
+                rows_per_image: None,
+            },
+        },
+        texture.size(),
+    );
+    queue.submit(std::iter::once(encoder.finish()));
+
+    buffer.slice(..).map_async(wgpu::MapMode::Read, |_| {});
+    device
+        .poll(wgpu::PollType::wait_indefinitely())
+        .map_err(|error| format!("Could not read back the replayed frame! ({error})"))?;
+
+    let mapped = buffer.slice(..).get_mapped_range();
+    let mut pixels = Vec::with_capacity((row_len * size.y) as usize);
+    for row in mapped.chunks_exact(padded_row_len as usize) {
+        pixels.extend_from_slice(&row[..row_len as usize]);
+    }
+    Ok(pixels)
+}
@@ -13,6 +13,8 @@ pub(super) enum DrawBindingResource {
     StorageBuffer {
         storage_buffer: StorageBufferId,
         visibility: ShaderVisibility,
+        /// `false` for `var<storage, read_write>` bindings that shaders write.
+        read_only: bool,
     },
     Texture {
         texture: TextureId,
@@ -38,7 +40,7 @@ impl DrawBinding {
         }
     }
 
-    /// Creates a storage buffer binding descriptor with fragment visibility.
+    /// Creates a read-only storage buffer binding descriptor.
     pub fn storage_buffer(
         group: u32,
         binding: u32,
@@ -51,6 +53,25 @@ impl DrawBinding {
             resource: DrawBindingResource::StorageBuffer {
                 storage_buffer,
                 visibility,
+                read_only: true,
+            },
+        }
+    }
+
+    /// Creates a read-write storage buffer binding descriptor.
+    pub fn storage_buffer_read_write(
+        group: u32,
+        binding: u32,
+        storage_buffer: StorageBufferId,
+        visibility: ShaderVisibility,
+    ) -> Self {
+        Self {
+            group,
+            binding,
+            resource: DrawBindingResource::StorageBuffer {
+                storage_buffer,
+                visibility,
+                read_only: false,
             },
         }
     }
@@ -0,0 +1,303 @@
+//! Bounded caches for GPU objects created on demand while drawing.
+//!
+//! Bind groups, bind group layouts, pipeline layouts, and render pipelines are cached by the
+//! resources they were created from. Materials and textures that come and go would otherwise
+//! leave their cached objects behind forever, so every submission records which entries it used
+//! and, once a cache holds more entries than its budget, the least recently used ones are
+//! dropped. Evicted objects are simply recreated if they are needed again.
+
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+};
+
+use crate::{DrawListRenderer, RenderPipelineKey, common::Id};
+
+/// Maximum number of entries kept in each of the renderer's internal caches.
+///
+/// Entries used by the most recent submission are never evicted, so a cache can temporarily
+/// exceed its budget when a single draw list needs more objects than that.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CacheBudgets {
+    pub bind_groups: usize,
+    pub bind_group_layouts: usize,
+    pub pipeline_layouts: usize,
+    pub render_pipelines: usize,
+}
+
+impl Default for CacheBudgets {
+    fn default() -> Self {
+        Self {
+            bind_groups: 4096,
+            bind_group_layouts: 256,
+            pipeline_layouts: 256,
+            render_pipelines: 512,
+        }
+    }
+}
+
+/// The submission each cache entry was last used in.
+pub(super) struct CacheUsage<K> {
+    last_used: HashMap<K, u64>,
+}
+
+impl<K> Default for CacheUsage<K> {
+    fn default() -> Self {
+        Self {
+            last_used: HashMap::default(),
+        }
+    }
+}
+
+impl<K: Copy + Eq + Hash> CacheUsage<K> {
+    #[inline]
+    pub(super) fn touch(&mut self, key: K, submission: u64) {
+        self.last_used.insert(key, submission);
+    }
+
+    /// Forgets entries that were removed from the cache by other means.
+    fn retain(&mut self, mut exists: impl FnMut(&K) -> bool) {
+        self.last_used.retain(|key, _| exists(key));
+    }
+
+    /// Removes and returns the least recently used entries that exceed `budget`, never
+    /// including entries used in `submission`.
+    fn take_evictions(&mut self, budget: usize, submission: u64) -> Vec<K> {
+        let excess = self.last_used.len().saturating_sub(budget);
+        if excess == 0 {
+            return Vec::new();
+        }
+
+        let mut candidates: Vec<(u64, K)> = self
+            .last_used
+            .iter()
+            .filter(|(_, last_used)| **last_used < submission)
+            .map(|(key, last_used)| (*last_used, *key))
+            .collect();
+        candidates.sort_unstable_by_key(|(last_used, _)| *last_used);
+        candidates.truncate(excess);
+
+        candidates
+            .into_iter()
+            .map(|(_, key)| {
+                self.last_used.remove(&key);
+                key
+            })
+            .collect()
+    }
+}
+
+/// Usage tracking for every bounded cache.
+#[derive(Default)]
+pub(super) struct CacheUsages {
+    pub bind_groups: CacheUsage<Id>,
+    pub bind_group_layouts: CacheUsage<Id>,
+    pub pipeline_layouts: CacheUsage<Id>,
+    pub render_pipelines: CacheUsage<RenderPipelineKey>,
+}
+
+impl DrawListRenderer {
+    /// Sets how many entries the renderer's internal caches may hold before the least recently
+    /// used ones are evicted.
+    ///
+    /// The new budgets are applied after the next submission.
+    pub fn set_cache_budgets(&mut self, budgets: CacheBudgets) {
+        self.cache_budgets = budgets;
+    }
+
+    /// Returns the current cache budgets.
+    pub fn cache_budgets(&self) -> CacheBudgets {
+        self.cache_budgets
+    }
+
+    /// Marks a render pipeline, and the pipeline layout it was created with, as used by the
+    /// current submission.
+    pub(super) fn touch_render_pipeline(&mut self, key: RenderPipelineKey) {
+        let submission = self.submission_index;
+        self.cache_usages.render_pipelines.touch(key, submission);
+        self.cache_usages
+            .pipeline_layouts
+            .touch(key.pipeline_layout, submission);
+    }
+
+    /// Ends the current submission, evicting the least recently used cache entries that exceed
+    /// their budgets.
+    pub(super) fn end_submission(&mut self) {
+        let submission = self.submission_index;
+        self.submission_index += 1;
+
+        let usages = &mut self.cache_usages;
+        let budgets = self.cache_budgets;
+
+        usages
+            .bind_groups
+            .retain(|id| self.bind_groups.get(*id).is_some());
+        let evicted: HashSet<Id> = usages
+            .bind_groups
+            .take_evictions(budgets.bind_groups, submission)
+            .into_iter()
+            .collect();
+        if !evicted.is_empty() {
+            self.bind_groups.retain(|_, id| !evicted.contains(&id));
+        }
+
+        usages
+            .bind_group_layouts
+            .retain(|id| self.bind_group_layouts.get(*id).is_some());
+        let evicted: HashSet<Id> = usages
+            .bind_group_layouts
+            .take_evictions(budgets.bind_group_layouts, submission)
+            .into_iter()
+            .collect();
+        if !evicted.is_empty() {
+            self.bind_group_layouts
+                .retain(|_, id| !evicted.contains(&id));
+        }
+
+        usages
+            .pipeline_layouts
+            .retain(|id| self.pipeline_layouts.get(*id).is_some());
+        let evicted: HashSet<Id> = usages
+            .pipeline_layouts
+            .take_evictions(budgets.pipeline_layouts, submission)
+            .into_iter()
+            .collect();
+        if !evicted.is_empty() {
+            self.pipeline_layouts.retain(|_, id| !evicted.contains(&id));
+            // Compute pipelines are not tracked on their own; they go with their layout.
+            self.compute_pipeline_cache
+                .retain(|key, _| !evicted.contains(&key.pipeline_layout));
+        }
+
+        usages
+            .render_pipelines
+            .retain(|key| self.render_pipeline_cache.contains_key(key));
+        for key in usages
+            .render_pipelines
+            .take_evictions(budgets.render_pipelines, submission)
+        {
+            self.render_pipeline_cache.remove(&key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        MaterialId,
+        draw_list::{DrawList, RenderTarget},
+        test_support::Scene,
+    };
+
+    fn draw(scene: &mut Scene, materials: &[MaterialId]) {
+        let mut draw_list = DrawList::new();
+        for material in materials {
+            draw_list.draw(RenderTarget::Surface, *material, 3);
+        }
+        scene.submit(&draw_list);
+    }
+
+    #[test]
+    fn least_recently_used_entries_are_evicted_first() {
+        let mut usage = CacheUsage::default();
+        usage.touch(1, 0);
+        usage.touch(2, 1);
+        usage.touch(3, 2);
+        usage.touch(1, 3);
+
+        assert_eq!(usage.take_evictions(2, 4), [2]);
+        assert_eq!(usage.take_evictions(2, 4), Vec::<i32>::new());
+        assert_eq!(usage.take_evictions(0, 4), [3, 1]);
+    }
+
+    #[test]
+    fn entries_used_by_the_current_submission_are_kept() {
+        let mut usage = CacheUsage::default();
+        usage.touch(1, 5);
+        usage.touch(2, 5);
+        usage.touch(3, 4);
+
+        assert_eq!(usage.take_evictions(0, 5), [3]);
+        assert_eq!(usage.last_used.len(), 2);
+    }
+
+    #[test]
+    fn removed_entries_do_not_count_against_the_budget() {
+        let mut usage = CacheUsage::default();
+        usage.touch(1, 0);
+        usage.touch(2, 1);
+        usage.retain(|key| *key != 1);
+
+        assert!(usage.take_evictions(1, 2).is_empty());
+    }
+
+    #[test]
+    fn render_pipelines_over_budget_are_evicted_and_recreated() {
+        let mut scene = Scene::new();
+        scene.renderer.set_cache_budgets(CacheBudgets {
+            render_pipelines: 1,
+            ..CacheBudgets::default()
+        });
+        let first = scene.fill_material("first");
+        let second = scene.fill_material("second");
+
+        draw(&mut scene, &[first]);
+        draw(&mut scene, &[second]);
+        assert_eq!(scene.renderer.render_pipeline_cache.len(), 1);
+
+        draw(&mut scene, &[second]);
+        let stats = scene.renderer.stats().last_submission;
+        assert_eq!(
+            (stats.pipeline_cache_hits, stats.pipeline_cache_misses),
+            (1, 0)
+        );
+
+        draw(&mut scene, &[first]);
+        let stats = scene.renderer.stats().last_submission;
+        assert_eq!(
+            (stats.pipeline_cache_hits, stats.pipeline_cache_misses),
+            (0, 1)
+        );
+        assert_eq!(scene.renderer.render_pipeline_cache.len(), 1);
+    }
+
+    #[test]
+    fn a_submission_may_exceed_its_budget() {
+        let mut scene = Scene::new();
+        scene.renderer.set_cache_budgets(CacheBudgets {
+            render_pipelines: 1,
+            ..CacheBudgets::default()
+        });
+        let materials = [
+            scene.fill_material("first"),
+            scene.fill_material("second"),
+            scene.fill_material("third"),
+        ];
+
+        draw(&mut scene, &materials);
+        assert_eq!(scene.renderer.render_pipeline_cache.len(), 3);
+
+        draw(&mut scene, &materials[..1]);
+        assert_eq!(scene.renderer.render_pipeline_cache.len(), 1);
+    }
+
+    #[test]
+    fn evicted_pipeline_layouts_are_recreated() {
+        let mut scene = Scene::new();
+        let material = scene.fill_material("fill");
+        draw(&mut scene, &[material]);
+        assert_eq!(scene.renderer.pipeline_layouts.len(), 1);
+
+        scene.renderer.set_cache_budgets(CacheBudgets {
+            pipeline_layouts: 0,
+            ..CacheBudgets::default()
+        });
+        draw(&mut scene, &[]);
+        assert_eq!(scene.renderer.pipeline_layouts.len(), 0);
+
+        draw(&mut scene, &[material]);
+        let stats = scene.renderer.stats().last_submission;
+        assert_eq!(stats.draws, 1);
+    }
+}
@@ -0,0 +1,1751 @@
+//! Capturing submitted draw lists to reproduce frames elsewhere.
+//!
+//! A [`DrawListCapture`] holds a draw list along with everything it uses: shader sources,
+//! materials, compute materials, meshes, uniform and storage buffer contents, texture pixels,
+//! samplers, render targets, depth buffers, and draw bundles. It is saved to a single file, and
+//! restored on any renderer with [`DrawListCapture::restore`], including a headless one. The
+//! `granite-replay` binary, built with the `replay` feature, renders a capture to a PNG:
+//!
+//! ```ignore
+//! renderer.capture_next_draw_list();
+//! renderer.submit_draw_list(frame_context, &draw_list);
+//! if let Some(capture) = renderer.take_draw_list_capture() {
+//!     capture.save("frame.capture")?;
+//! }
+//! ```
+//!
+//! Textures and uniforms are captured with the contents last written from the CPU, and other
+//! buffers with the data they were created with, or zeros once they were written again, unless
+//! [`DrawListRenderer::set_keep_buffer_contents`] is enabled. Contents produced on the GPU in
+//! earlier submissions, such as the surface or render targets drawn into by a previous frame,
+//! are not captured, and depth buffers that were cleared before are cleared to `1.0` at the
+//! start of the restored draw list. Shaders are captured after preprocessing, so draws with
+//! push constants only replay on devices with the same native push constant support.
+
+use std::{collections::HashMap, path::Path};
+
+use glam::{UVec2, UVec3, Vec2};
+
+use crate::{
+    BlendMode, DepthCompare, DrawListRenderer, FrameContext, Material, MaterialDepthState,
+    ShaderVisibility,
+    bindings::{DrawBinding, DrawBindingResource},
+    commands::{
+        ClearDepthBuffer, Dispatch, Draw, DrawIndirect, DrawMesh, DrawMeshIndirect,
+        DrawMeshInstanced, ExecuteDrawBundle, FrameCommand, IndirectArgs, PassRegion,
+        ResizeDepthBuffer, ResizeRenderTarget, UpdateStorageBuffer, UpdateTextureRegion,
+        UpdateUniform,
+    },
+    common::Id,
+    compute::{ComputeMaterial, ComputeShader},
+    depth_buffer::DepthBufferSize,
+    draw_list::{DrawList, DrawSortMode, RenderTarget, ScissorRect, Viewport},
+    mesh::{Mesh, VertexAttribute, VertexBufferLayout},
+    push_constants::PushConstantLayout,
+    render_target::{RenderTargetFormat, RenderTargetSize},
+    resources::{FragmentShader, VertexShader},
+    sampler::{SamplerAddressing, SamplerFiltering},
+    textures::TextureFormat,
+};
+
+/// Identifies capture files.
+const MAGIC: [u8; 8] = *b"GRNTCAP\0";
+/// Incremented whenever the layout of capture files changes.
+const VERSION: u32 = 3;
+
+/// A draw list and the resources it uses, serialized into a self-contained blob.
+pub struct DrawListCapture {
+    bytes: Vec<u8>,
+}
+
+impl DrawListCapture {
+    /// Writes the capture to a file at `path`.
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, &self.bytes)
+    }
+
+    /// Reads a capture saved with [`DrawListCapture::save`].
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let capture = Self {
+            bytes: std::fs::read(path)?,
+        };
+        if capture.header().is_none() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "not a draw list capture, or captured by an incompatible version",
+            ));
+        }
+        Ok(capture)
+    }
+
+    /// Returns the size of the surface the draw list was submitted to.
+    pub fn surface_size(&self) -> UVec2 {
+        self.header()
+            .map(|(surface_size, _)| surface_size)
+            .unwrap_or(UVec2::ONE)
+    }
+
+    /// Returns an RGBA format with the color space of the surface the draw list was submitted
+    /// to.
+    pub fn surface_format(&self) -> wgpu::TextureFormat {
+        match self.header() {
+            Some((_, false)) => wgpu::TextureFormat::Rgba8Unorm,
+            _ => wgpu::TextureFormat::Rgba8UnormSrgb,
+        }
+    }
+
+    /// Creates the captured resources on `renderer` and returns the captured draw list, which
+    /// uses them.
+    ///
+    /// Returns `None` when the capture is malformed or a resource could not be created.
+    pub fn restore(&self, renderer: &mut DrawListRenderer) -> Option<DrawList> {
+        let mut reader = Reader::new(&self.bytes);
+        let draw_list =
+            read_header(&mut reader).and_then(|_| renderer.restore_capture(&mut reader));
+        if draw_list.is_none() {
+            tracing::warn!("Could not restore the draw list capture; it is malformed.");
+        }
+        draw_list
+    }
+
+    /// Returns the surface size and whether the surface is sRGB.
+    fn header(&self) -> Option<(UVec2, bool)> {
+        read_header(&mut Reader::new(&self.bytes))
+    }
+}
+
+fn read_header(reader: &mut Reader<'_>) -> Option<(UVec2, bool)> {
+    if reader.take(MAGIC.len())? != MAGIC || reader.u32()? != VERSION {
+        return None;
+    }
+    Some((reader.uvec2()?, reader.bool()?))
+}
+
+/// Whether the next submitted draw list is captured.
+#[derive(Default)]
+pub(super) enum CaptureState {
+    #[default]
+    Idle,
+    Requested,
+    Captured(DrawListCapture),
+}
+
+impl DrawListRenderer {
+    /// Captures the next draw list submitted with [`DrawListRenderer::submit_draw_list`].
+    ///
+    /// Take the capture with [`DrawListRenderer::take_draw_list_capture`] after submitting.
+    pub fn capture_next_draw_list(&mut self) {
+        self.capture = CaptureState::Requested;
+    }
+
+    /// Returns the capture requested with [`DrawListRenderer::capture_next_draw_list`], once the
+    /// draw list was submitted.
+    pub fn take_draw_list_capture(&mut self) -> Option<DrawListCapture> {
+        match std::mem::take(&mut self.capture) {
+            CaptureState::Captured(capture) => Some(capture),
+            state => {
+                self.capture = state;
+                None
+            }
+        }
+    }
+
+    /// Captures `draw_list` as it would be submitted to `frame_context` right now.
+    ///
+    /// Commands that reference invalid resources are left out, as they would be skipped when
+    /// submitted.
+    pub fn capture_draw_list(
+        &self,
+        frame_context: &FrameContext<'_>,
+        draw_list: &DrawList,
+    ) -> DrawListCapture {
+        let mut encoder = CaptureEncoder {
+            renderer: self,
+            sections: Sections::default(),
+        };
+        let commands = encoder.commands(&draw_list.commands);
+
+        let mut writer = Writer::default();
+        writer.bytes.extend_from_slice(&MAGIC);
+        writer.u32(VERSION);
+        writer.uvec2(frame_context.size);
+        writer.bool(frame_context.format.is_srgb());
+        for section in encoder.sections.in_order() {
+            writer.u32(section.indices.len() as u32);
+            writer.append(&section.writer);
+        }
+        writer.append(&commands);
+
+        DrawListCapture {
+            bytes: writer.bytes,
+        }
+    }
+
+    /// Captures `draw_list` when a capture was requested.
+    pub(super) fn capture_submitted_draw_list(
+        &mut self,
+        frame_context: &FrameContext<'_>,
+        draw_list: &DrawList,
+    ) {
+        if matches!(self.capture, CaptureState::Requested) {
+            self.capture = CaptureState::Captured(self.capture_draw_list(frame_context, draw_list));
+        }
+    }
+
+    /// Creates the resources of a capture, read past its header, and returns its draw list.
+    ///
+    /// Resources created before a malformed section are removed again, so a capture that fails
+    /// to restore leaves the renderer as it was.
+    fn restore_capture(&mut self, reader: &mut Reader<'_>) -> Option<DrawList> {
+        let mut ids = RestoredIds::default();
+        let draw_list = self.restore_resources(reader, &mut ids);
+        if draw_list.is_none() {
+            self.remove_restored(ids);
+        }
+        draw_list
+    }
+
+    /// Creates the resources of a capture, recording them in `ids` as they are created.
+    fn restore_resources(
+        &mut self,
+        reader: &mut Reader<'_>,
+        ids: &mut RestoredIds,
+    ) -> Option<DrawList> {
+        for _ in 0..reader.u32()? {
+            let name = reader.str()?;
+            let source = reader.str()?;
+            let shader = self
+                .create_shader_module(name, source, None)
+                .inspect_err(|error| tracing::warn!("{error}"))
+                .ok()?;
+            ids.shaders.push(shader);
+        }
+
+        for _ in 0..reader.u32()? {
+            let shader = ids.shader(reader)?;
+            let entry_point = reader.option(Reader::str)?;
+            ids.vertex_shaders.push(
+                self.vertex_shaders
+                    .push(VertexShader::create(shader, entry_point)),
+            );
+        }
+
+        for _ in 0..reader.u32()? {
+            let shader = ids.shader(reader)?;
+            let entry_point = reader.option(Reader::str)?;
+            ids.fragment_shaders.push(
+                self.fragment_shaders
+                    .push(FragmentShader::create(shader, entry_point)),
+            );
+        }
+
+        for _ in 0..reader.u32()? {
+            let shader = ids.shader(reader)?;
+            let entry_point = reader.option(Reader::str)?;
+            ids.compute_shaders.push(
+                self.compute_shaders
+                    .push(ComputeShader::create(shader, entry_point)),
+            );
+        }
+
+        for _ in 0..reader.u32()? {
+            let name = reader.str()?;
+            let visibility = reader.variant(&SHADER_VISIBILITIES)?;
+            let min_binding_size = wgpu::BufferSize::new(reader.u64()?)?;
+            let data = reader.bytes()?;
+            ids.uniforms
+                .push(self.create_uniform_bytes(name, visibility, min_binding_size, data));
+        }
+
+        for _ in 0..reader.u32()? {
+            let name = reader.str()?;
+            let min_binding_size = wgpu::BufferSize::new(reader.u64()?)?;
+            let usage = if reader.bool()? {
+                wgpu::BufferUsages::INDIRECT
+            } else {
+                wgpu::BufferUsages::empty()
+            };
+            let data = reader.bytes()?;
+            ids.storage_buffers
+                .push(self.create_storage_buffer_bytes_with_usage(
+                    name,
+                    min_binding_size,
+                    data,
+                    usage,
+                )?);
+        }
+
+        for _ in 0..reader.u32()? {
+            let name = reader.str()?;
+            let size = reader.uvec2()?;
+            let format = reader.variant(&TEXTURE_FORMATS)?;
+            let mip_level_count = reader.u32()?;
+            let pixels = reader.bytes()?;
+            ids.textures.push(self.create_texture_with_mips(
+                name,
+                size,
+                format,
+                mip_level_count,
+                pixels,
+            )?);
+        }
+
+        for _ in 0..reader.u32()? {
+            let name = reader.str()?;
+            let addressing = reader.variant(&SAMPLER_ADDRESSINGS)?;
+            let filtering = reader.variant(&SAMPLER_FILTERINGS)?;
+            ids.samplers
+                .push(self.create_sampler(name, addressing, filtering));
+        }
+
+        for _ in 0..reader.u32()? {
+            let name = reader.str()?;
+            let size = match reader.option(Reader::uvec2)? {
+                Some(size) => RenderTargetSize::Custom(size),
+                None => RenderTargetSize::SurfaceSize,
+            };
+            let format = reader.variant(&RENDER_TARGET_FORMATS)?;
+            ids.render_targets
+                .push(self.create_render_target(name, size, format));
+        }
+
+        let mut commands = Vec::new();
+        for _ in 0..reader.u32()? {
+            let name = reader.str()?;
+            let size = match reader.option(Reader::uvec2)? {
+                Some(size) => DepthBufferSize::Custom(size),
+                None => DepthBufferSize::SurfaceSize,
+            };
+            let depth_buffer = self.create_depth_buffer(name, size);
+            if reader.bool()? {
+                // The contents are not captured, so start from a cleared buffer instead.
+                commands.push(FrameCommand::ClearDepthBuffer(ClearDepthBuffer {
+                    depth_buffer,
+                    value: 1.0,
+                }));
+            }
+            ids.depth_buffers.push(depth_buffer);
+        }
+
+        for _ in 0..reader.u32()? {
+            let name = reader.str()?;
+            let layout = reader.vertex_buffer_layout()?;
+            let vertex_bytes = reader.bytes()?.to_vec();
+            let index_bytes = reader.bytes()?.to_vec();
+            let vertex_buffer_layout_id = self.get_or_create_vertex_buffer_layout(layout);
+            self.submission_stats.bytes_uploaded += (vertex_bytes.len() + index_bytes.len()) as u64;
+            ids.meshes.push(self.meshes.push(Mesh::from_bytes(
+                &self.device,
+                name,
+                vertex_buffer_layout_id,
+                vertex_bytes,
+                index_bytes,
+            )));
+        }
+
+        for _ in 0..reader.u32()? {
+            let material = ids.material(reader)?;
+            ids.materials.push(self.create_material(material));
+        }
+
+        for _ in 0..reader.u32()? {
+            let compute_material = ids.compute_material(reader)?;
+            ids.compute_materials
+                .push(self.create_compute_material(compute_material));
+        }
+
+        for _ in 0..reader.u32()? {
+            let name = reader.str()?;
+            let draw_list = ids.draw_list(reader, Vec::new())?;
+            ids.draw_bundles
+                .push(self.create_draw_bundle(name, draw_list)?);
+        }
+
+        ids.draw_list(reader, commands)
+    }
+
+    fn remove_restored(&mut self, ids: RestoredIds) {
+        for id in ids.draw_bundles {
+            self.draw_bundles.remove(id);
+        }
+        for id in ids.compute_materials {
+            self.compute_materials.remove(id);
+        }
+        for id in ids.materials {
+            self.materials.remove(id);
+        }
+        for id in ids.meshes {
+            self.meshes.remove(id);
+        }
+        for id in ids.depth_buffers {
+            self.depth_buffers.remove(id);
+        }
+        for id in ids.render_targets {
+            self.render_targets.remove(id);
+        }
+        for id in ids.samplers {
+            self.samplers.remove(id);
+        }
+        for id in ids.textures {
+            self.textures.remove(id);
+        }
+        for id in ids.storage_buffers {
+            self.storage_buffers.remove(id);
+        }
+        for id in ids.uniforms {
+            self.uniforms.remove(id);
+        }
+        for id in ids.compute_shaders {
+            self.compute_shaders.remove(id);
+        }
+        for id in ids.fragment_shaders {
+            self.fragment_shaders.remove(id);
+        }
+        for id in ids.vertex_shaders {
+            self.vertex_shaders.remove(id);
+        }
+        for id in ids.shaders {
+            self.shaders.remove(id);
+        }
+    }
+}
+
+/// Enum variants in the order they are numbered in capture files.
+const SHADER_VISIBILITIES: [ShaderVisibility; 4] = [
+    ShaderVisibility::Vertex,
+    ShaderVisibility::Fragment,
+    ShaderVisibility::VertexFragment,
+    ShaderVisibility::Compute,
+];
+const BLEND_MODES: [BlendMode; 4] = [
+    BlendMode::Opaque,
+    BlendMode::AlphaBlend,
+    BlendMode::Additive,
+    BlendMode::Premultiplied,
+];
+const DEPTH_COMPARES: [DepthCompare; 8] = [
+    DepthCompare::Never,
+    DepthCompare::Less,
+    DepthCompare::Equal,
+    DepthCompare::LessEqual,
+    DepthCompare::Greater,
+    DepthCompare::NotEqual,
+    DepthCompare::GreaterEqual,
+    DepthCompare::Always,
+];
+const SORT_MODES: [DrawSortMode; 4] = [
+    DrawSortMode::None,
+    DrawSortMode::FrontToBack,
+    DrawSortMode::BackToFront,
+    DrawSortMode::State,
+];
+const SAMPLER_ADDRESSINGS: [SamplerAddressing; 2] =
+    [SamplerAddressing::ClampToEdge, SamplerAddressing::Repeat];
+const SAMPLER_FILTERINGS: [SamplerFiltering; 2] =
+    [SamplerFiltering::Linear, SamplerFiltering::Nearest];
+const TEXTURE_FORMATS: [TextureFormat; 15] = [
+    TextureFormat::Rgba,
+    TextureFormat::RgbaSrgb,
+    TextureFormat::Mono,
+    TextureFormat::Rgba16Float,
+    TextureFormat::Bc1,
+    TextureFormat::Bc1Srgb,
+    TextureFormat::Bc2,
+    TextureFormat::Bc2Srgb,
+    TextureFormat::Bc3,
+    TextureFormat::Bc3Srgb,
+    TextureFormat::Bc4,
+    TextureFormat::Bc5,
+    TextureFormat::Bc6h,
+    TextureFormat::Bc7,
+    TextureFormat::Bc7Srgb,
+];
+const RENDER_TARGET_FORMATS: [RenderTargetFormat; 4] = [
+    RenderTargetFormat::Rgba,
+    RenderTargetFormat::RgbaSrgb,
+    RenderTargetFormat::Rgba16Float,
+    RenderTargetFormat::R32Uint,
+];
+const STEP_MODES: [wgpu::VertexStepMode; 2] =
+    [wgpu::VertexStepMode::Vertex, wgpu::VertexStepMode::Instance];
+const VERTEX_FORMATS: [wgpu::VertexFormat; 45] = {
+    use wgpu::VertexFormat::*;
+    [
+        Uint8,
+        Uint8x2,
+        Uint8x4,
+        Sint8,
+        Sint8x2,
+        Sint8x4,
+        Unorm8,
+        Unorm8x2,
+        Unorm8x4,
+        Snorm8,
+        Snorm8x2,
+        Snorm8x4,
+        Uint16,
+        Uint16x2,
+        Uint16x4,
+        Sint16,
+        Sint16x2,
+        Sint16x4,
+        Unorm16,
+        Unorm16x2,
+        Unorm16x4,
+        Snorm16,
+        Snorm16x2,
+        Snorm16x4,
+        Float16,
+        Float16x2,
+        Float16x4,
+        Float32,
+        Float32x2,
+        Float32x3,
+        Float32x4,
+        Uint32,
+        Uint32x2,
+        Uint32x3,
+        Uint32x4,
+        Sint32,
+        Sint32x2,
+        Sint32x3,
+        Sint32x4,
+        Float64,
+        Float64x2,
+        Float64x3,
+        Float64x4,
+        Unorm10_10_10_2,
+        Unorm8x4Bgra,
+    ]
+};
+
+/// Encoded resources of one kind, numbered in the order they were first referenced.
+#[derive(Default)]
+struct Section {
+    indices: HashMap<Id, u32>,
+    writer: Writer,
+}
+
+impl Section {
+    fn get(&self, id: Id) -> Option<u32> {
+        self.indices.get(&id).copied()
+    }
+
+    fn push(&mut self, id: Id, entry: Writer) -> u32 {
+        let index = self.indices.len() as u32;
+        self.indices.insert(id, index);
+        self.writer.append(&entry);
+        index
+    }
+}
+
+/// Sections of a capture, each of which only references the ones before it.
+#[derive(Default)]
+struct Sections {
+    shaders: Section,
+    vertex_shaders: Section,
+    fragment_shaders: Section,
+    compute_shaders: Section,
+    uniforms: Section,
+    storage_buffers: Section,
+    textures: Section,
+    samplers: Section,
+    render_targets: Section,
+    depth_buffers: Section,
+    meshes: Section,
+    materials: Section,
+    compute_materials: Section,
+    draw_bundles: Section,
+}
+
+impl Sections {
+    fn in_order(&self) -> [&Section; 14] {
+        [
+            &self.shaders,
+            &self.vertex_shaders,
+            &self.fragment_shaders,
+            &self.compute_shaders,
+            &self.uniforms,
+            &self.storage_buffers,
+            &self.textures,
+            &self.samplers,
+            &self.render_targets,
+            &self.depth_buffers,
+            &self.meshes,
+            &self.materials,
+            &self.compute_materials,
+            &self.draw_bundles,
+        ]
+    }
+}
+
+/// Encodes commands, and every resource they reference the first time it is referenced.
+///
+/// Resources are looked up by id; `None` is returned for invalid ids.
+struct CaptureEncoder<'a> {
+    renderer: &'a DrawListRenderer,
+    sections: Sections,
+}
+
+impl CaptureEncoder<'_> {
+    fn commands(&mut self, commands: &[FrameCommand]) -> Writer {
+        let mut writer = Writer::default();
+        let mut count = 0;
+        for command in commands {
+            if let Some(entry) = self.command(command) {
+                writer.append(&entry);
+                count += 1;
+            }
+        }
+
+        if count < commands.len() {
+            tracing::warn!(
+                "Left {} commands referencing invalid resources out of the capture.",
+                commands.len() - count
+            );
+        }
+
+        let mut counted = Writer::default();
+        counted.u32(count as u32);
+        counted.append(&writer);
+        counted
+    }
+
+    fn command(&mut self, command: &FrameCommand) -> Option<Writer> {
+        let mut entry = Writer::default();
+        match command {
+            FrameCommand::UpdateUniform(update) => {
+                entry.u8(0);
+                entry.u32(self.uniform(update.uniform)?);
+                entry.bytes(&update.data);
+            }
+            FrameCommand::UpdateStorageBuffer(update) => {
+                entry.u8(1);
+                entry.u32(self.storage_buffer(update.storage_buffer)?);
+                entry.bytes(&update.data);
+            }
+            FrameCommand::UpdateTextureRegion(update) => {
+                entry.u8(2);
+                entry.u32(self.texture(update.texture)?);
+                entry.uvec2(update.origin);
+                entry.uvec2(update.size);
+                entry.bytes(&update.data);
+            }
+            FrameCommand::ClearDepthBuffer(clear) => {
+                entry.u8(3);
+                entry.u32(self.depth_buffer(clear.depth_buffer)?);
+                entry.f32(clear.value);
+            }
+            FrameCommand::ResizeDepthBuffer(resize) => {
+                entry.u8(4);
+                entry.u32(self.depth_buffer(resize.depth_buffer)?);
+                entry.uvec2(resize.size);
+            }
+            FrameCommand::ResizeRenderTarget(resize) => {
+                entry.u8(5);
+                entry.u32(self.render_target(resize.render_target)?);
+                entry.uvec2(resize.size);
+            }
+            FrameCommand::Draw(draw) => {
+                entry.u8(6);
+                self.render_target_ref(&mut entry, draw.render_target)?;
+                entry.u32(self.material(draw.material)?);
+                entry.u32(draw.vertex_count);
+                entry.option(draw.push_constants.as_deref(), Writer::bytes);
+                entry.f32(draw.sort_depth);
+                entry.region(draw.region);
+                entry.option(draw.label.as_deref(), Writer::str);
+            }
+            FrameCommand::DrawMesh(draw) => {
+                entry.u8(7);
+                self.render_target_ref(&mut entry, draw.render_target)?;
+                entry.u32(self.mesh(draw.mesh)?);
+                entry.u32(self.material(draw.material)?);
+                entry.option(draw.push_constants.as_deref(), Writer::bytes);
+                entry.f32(draw.sort_depth);
+                entry.region(draw.region);
+                entry.option(draw.label.as_deref(), Writer::str);
+            }
+            FrameCommand::DrawMeshInstanced(draw) => {
+                entry.u8(8);
+                self.render_target_ref(&mut entry, draw.render_target)?;
+                entry.u32(self.mesh(draw.mesh)?);
+                entry.u32(self.material(draw.material)?);
+                entry.vertex_buffer_layout(&draw.instance_buffer_layout);
+                entry.bytes(&draw.instance_data);
+                entry.u32(draw.instance_count);
+                entry.f32(draw.sort_depth);
+                entry.region(draw.region);
+                entry.option(draw.label.as_deref(), Writer::str);
+            }
+            FrameCommand::DrawIndirect(draw) => {
+                entry.u8(9);
+                self.render_target_ref(&mut entry, draw.render_target)?;
+                entry.u32(self.material(draw.material)?);
+                self.indirect(&mut entry, draw.indirect)?;
+                entry.f32(draw.sort_depth);
+                entry.region(draw.region);
+                entry.option(draw.label.as_deref(), Writer::str);
+            }
+            FrameCommand::DrawMeshIndirect(draw) => {
+                entry.u8(10);
+                self.render_target_ref(&mut entry, draw.render_target)?;
+                entry.u32(self.mesh(draw.mesh)?);
+                entry.u32(self.material(draw.material)?);
+                self.indirect(&mut entry, draw.indirect)?;
+                entry.f32(draw.sort_depth);
+                entry.region(draw.region);
+                entry.option(draw.label.as_deref(), Writer::str);
+            }
+            FrameCommand::ExecuteDrawBundle(execute) => {
+                entry.u8(11);
+                entry.u32(self.draw_bundle(execute.draw_bundle)?);
+                entry.region(execute.region);
+            }
+            FrameCommand::BeginSort(mode) => {
+                entry.u8(12);
+                entry.variant(&SORT_MODES, *mode);
+            }
+            FrameCommand::EndSort => entry.u8(13),
+            FrameCommand::SetProfileLabel(label) => {
+                entry.u8(14);
+                entry.option(label.as_deref(), Writer::str);
+            }
+            FrameCommand::PushDebugGroup(label) => {
+                entry.u8(15);
+                entry.str(label);
+            }
+            FrameCommand::PopDebugGroup => entry.u8(16),
+            FrameCommand::InsertDebugMarker(label) => {
+                entry.u8(17);
+                entry.str(label);
+            }
+            FrameCommand::Dispatch(dispatch) => {
+                entry.u8(18);
+                entry.u32(self.compute_material(dispatch.compute_material)?);
+                entry.u32(dispatch.workgroup_count.x);
+                entry.u32(dispatch.workgroup_count.y);
+                entry.u32(dispatch.workgroup_count.z);
+                entry.option(dispatch.label.as_deref(), Writer::str);
+            }
+        }
+        Some(entry)
+    }
+
+    fn render_target_ref(&mut self, entry: &mut Writer, render_target: RenderTarget) -> Option<()> {
+        match render_target {
+            RenderTarget::Surface => entry.u8(0),
+            RenderTarget::Custom(id) => {
+                entry.u8(1);
+                entry.u32(self.render_target(id)?);
+            }
+        }
+        Some(())
+    }
+
+    fn indirect(&mut self, entry: &mut Writer, indirect: IndirectArgs) -> Option<()> {
+        entry.u32(self.storage_buffer(indirect.buffer)?);
+        entry.u64(indirect.offset);
+        entry.u32(indirect.draw_count);
+        Some(())
+    }
+
+    fn shader(&mut self, id: Id) -> Option<u32> {
+        if let Some(index) = self.sections.shaders.get(id) {
+            return Some(index);
+        }
+        let shader = self.renderer.shaders.get(id)?;
+
+        let mut entry = Writer::default();
+        entry.str(&shader.name);
+        entry.str(&shader.source);
+        Some(self.sections.shaders.push(id, entry))
+    }
+
+    fn vertex_shader(&mut self, id: Id) -> Option<u32> {
+        if let Some(index) = self.sections.vertex_shaders.get(id) {
+            return Some(index);
+        }
+        let vertex_shader = self.renderer.vertex_shaders.get(id)?;
+
+        let mut entry = Writer::default();
+        entry.u32(self.shader(vertex_shader.shader_module)?);
+        entry.option(vertex_shader.entry_point.as_deref(), Writer::str);
+        Some(self.sections.vertex_shaders.push(id, entry))
+    }
+
+    fn fragment_shader(&mut self, id: Id) -> Option<u32> {
+        if let Some(index) = self.sections.fragment_shaders.get(id) {
+            return Some(index);
+        }
+        let fragment_shader = self.renderer.fragment_shaders.get(id)?;
+
+        let mut entry = Writer::default();
+        entry.u32(self.shader(fragment_shader.shader_module)?);
+        entry.option(fragment_shader.entry_point.as_deref(), Writer::str);
+        Some(self.sections.fragment_shaders.push(id, entry))
+    }
+
+    fn uniform(&mut self, id: Id) -> Option<u32> {
+        if let Some(index) = self.sections.uniforms.get(id) {
+            return Some(index);
+        }
+        let uniform = self.renderer.uniforms.get(id)?;
+        let buffer = self.renderer.buffers.get(uniform.buffer)?;
+
+        let mut entry = Writer::default();
+        entry.str(resource_name(&buffer.label, "_uniform"));
+        entry.variant(&SHADER_VISIBILITIES, uniform.visibility);
+        entry.u64(uniform.min_binding_size.get());
+        entry.bytes(&buffer.contents_or_zeroed());
+        Some(self.sections.uniforms.push(id, entry))
+    }
+
+    fn storage_buffer(&mut self, id: Id) -> Option<u32> {
+        if let Some(index) = self.sections.storage_buffers.get(id) {
+            return Some(index);
+        }
+        let storage_buffer = self.renderer.storage_buffers.get(id)?;
+        let buffer = self.renderer.buffers.get(storage_buffer.buffer)?;
+
+        let mut entry = Writer::default();
+        entry.str(resource_name(&buffer.label, "_storage"));
+        entry.u64(storage_buffer.min_binding_size.get());
+        entry.bool(storage_buffer.usage.contains(wgpu::BufferUsages::INDIRECT));
+        entry.bytes(&buffer.contents_or_zeroed());
+        Some(self.sections.storage_buffers.push(id, entry))
+    }
+
+    fn texture(&mut self, id: Id) -> Option<u32> {
+        if let Some(index) = self.sections.textures.get(id) {
+            return Some(index);
+        }
+        let texture = self.renderer.textures.get(id)?;
+
+        let mut entry = Writer::default();
+        entry.str(resource_name(&texture.label, "_texture"));
+        entry.uvec2(texture.size);
+        entry.variant(&TEXTURE_FORMATS, texture.format);
+        entry.u32(texture.mip_level_count);
+        entry.bytes(&texture.pixels);
+        Some(self.sections.textures.push(id, entry))
+    }
+
+    fn sampler(&mut self, id: Id) -> Option<u32> {
+        if let Some(index) = self.sections.samplers.get(id) {
+            return Some(index);
+        }
+        let sampler = self.renderer.samplers.get(id)?;
+
+        let mut entry = Writer::default();
+        entry.str(&sampler.name);
+        entry.variant(&SAMPLER_ADDRESSINGS, sampler.addressing);
+        entry.variant(&SAMPLER_FILTERINGS, sampler.filtering);
+        Some(self.sections.samplers.push(id, entry))
+    }
+
+    fn render_target(&mut self, id: Id) -> Option<u32> {
+        if let Some(index) = self.sections.render_targets.get(id) {
+            return Some(index);
+        }
+        let render_target = self.renderer.render_targets.get(id)?;
+
+        let mut entry = Writer::default();
+        entry.str(&render_target.name);
+        let custom_size = match render_target.size_mode {
+            RenderTargetSize::SurfaceSize => None,
+            RenderTargetSize::Custom(size) => Some(size),
+        };
+        entry.option(custom_size, Writer::uvec2);
+        entry.variant(&RENDER_TARGET_FORMATS, render_target.format);
+        Some(self.sections.render_targets.push(id, entry))
+    }
+
+    fn depth_buffer(&mut self, id: Id) -> Option<u32> {
+        if let Some(index) = self.sections.depth_buffers.get(id) {
+            return Some(index);
+        }
+        let depth_buffer = self.renderer.depth_buffers.get(id)?;
+
+        let mut entry = Writer::default();
+        entry.str(&depth_buffer.name);
+        let custom_size = match depth_buffer.size_mode {
+            DepthBufferSize::SurfaceSize => None,
+            DepthBufferSize::Custom(size) => Some(size),
+        };
+        entry.option(custom_size, Writer::uvec2);
+        entry.bool(depth_buffer.initialized);
+        Some(self.sections.depth_buffers.push(id, entry))
+    }
+
+    fn mesh(&mut self, id: Id) -> Option<u32> {
+        if let Some(index) = self.sections.meshes.get(id) {
+            return Some(index);
+        }
+        let mesh = self.renderer.meshes.get(id)?;
+        let layout = self
+            .renderer
+            .vertex_buffer_layouts
+            .get(mesh.vertex_buffer_layout_id)?;
+
+        let mut entry = Writer::default();
+        entry.str(&mesh.name);
+        entry.vertex_buffer_layout(layout);
+        entry.bytes(&mesh.vertex_bytes);
+        entry.bytes(&mesh.index_bytes);
+        Some(self.sections.meshes.push(id, entry))
+    }
+
+    fn material(&mut self, id: Id) -> Option<u32> {
+        if let Some(index) = self.sections.materials.get(id) {
+            return Some(index);
+        }
+        let material = self.renderer.materials.get(id)?;
+
+        let mut entry = Writer::default();
+        entry.u32(self.vertex_shader(material.vertex_shader)?);
+        entry.u32(self.fragment_shader(material.fragment_shader)?);
+        self.bindings(&mut entry, &material.bindings)?;
+        entry.variant(&BLEND_MODES, material.blend_mode);
+        match material.depth_state {
+            Some(depth_state) => {
+                entry.bool(true);
+                entry.u32(self.depth_buffer(depth_state.depth_buffer)?);
+                entry.variant(&DEPTH_COMPARES, depth_state.compare);
+                entry.bool(depth_state.write_enabled);
+            }
+            None => entry.bool(false),
+        }
+        entry.option(material.push_constants, |entry, push_constants| {
+            entry.u32(push_constants.size);
+            entry.variant(&SHADER_VISIBILITIES, push_constants.visibility);
+            entry.u32(push_constants.fallback_group);
+        });
+        entry.option(material.name.as_deref(), Writer::str);
+        Some(self.sections.materials.push(id, entry))
+    }
+
+    fn bindings(&mut self, entry: &mut Writer, bindings: &[DrawBinding]) -> Option<()> {
+        entry.u32(bindings.len() as u32);
+        for binding in bindings.iter() {
+            entry.u32(binding.group);
+            entry.u32(binding.binding);
+            match binding.resource {
+                DrawBindingResource::Uniform(uniform) => {
+                    entry.u8(0);
+                    entry.u32(self.uniform(uniform)?);
+                }
+                DrawBindingResource::StorageBuffer {
+                    storage_buffer,
+                    visibility,
+                    read_only,
+                } => {
+                    entry.u8(1);
+                    entry.u32(self.storage_buffer(storage_buffer)?);
+                    entry.variant(&SHADER_VISIBILITIES, visibility);
+                    entry.bool(read_only);
+                }
+                DrawBindingResource::Texture {
+                    texture,
+                    visibility,
+                } => {
+                    entry.u8(2);
+                    entry.u32(self.texture(texture)?);
+                    entry.variant(&SHADER_VISIBILITIES, visibility);
+                }
+                DrawBindingResource::RenderTarget {
+                    render_target,
+                    visibility,
+                } => {
+                    entry.u8(3);
+                    entry.u32(self.render_target(render_target)?);
+                    entry.variant(&SHADER_VISIBILITIES, visibility);
+                }
+                DrawBindingResource::Sampler {
+                    sampler,
+                    visibility,
+                } => {
+                    entry.u8(4);
+                    entry.u32(self.sampler(sampler)?);
+                    entry.variant(&SHADER_VISIBILITIES, visibility);
+                }
+            }
+        }
+        Some(())
+    }
+
+    fn compute_shader(&mut self, id: Id) -> Option<u32> {
+        if let Some(index) = self.sections.compute_shaders.get(id) {
+            return Some(index);
+        }
+        let compute_shader = self.renderer.compute_shaders.get(id)?;
+
+        let mut entry = Writer::default();
+        entry.u32(self.shader(compute_shader.shader_module)?);
+        entry.option(compute_shader.entry_point.as_deref(), Writer::str);
+        Some(self.sections.compute_shaders.push(id, entry))
+    }
+
+    fn compute_material(&mut self, id: Id) -> Option<u32> {
+        if let Some(index) = self.sections.compute_materials.get(id) {
+            return Some(index);
+        }
+        let compute_material = self.renderer.compute_materials.get(id)?;
+
+        let mut entry = Writer::default();
+        entry.u32(self.compute_shader(compute_material.compute_shader)?);
+        self.bindings(&mut entry, &compute_material.bindings)?;
+        entry.option(compute_material.name.as_deref(), Writer::str);
+        Some(self.sections.compute_materials.push(id, entry))
+    }
+
+    fn draw_bundle(&mut self, id: Id) -> Option<u32> {
+        if let Some(index) = self.sections.draw_bundles.get(id) {
+            return Some(index);
+        }
+        let draw_bundle = self.renderer.draw_bundles.get(id)?;
+
+        let mut entry = Writer::default();
+        entry.str(&draw_bundle.name);
+        let commands = self.commands(&draw_bundle.commands);
+        entry.append(&commands);
+        Some(self.sections.draw_bundles.push(id, entry))
+    }
+}
+
+/// Returns the name a resource was created with, from the label of its buffer or texture.
+fn resource_name<'a>(label: &'a str, suffix: &str) -> &'a str {
+    label.strip_suffix(suffix).unwrap_or(label)
+}
+
+/// Ids of the resources created from a capture, by their index in the capture.
+#[derive(Default)]
+struct RestoredIds {
+    shaders: Vec<Id>,
+    vertex_shaders: Vec<Id>,
+    fragment_shaders: Vec<Id>,
+    compute_shaders: Vec<Id>,
+    uniforms: Vec<Id>,
+    storage_buffers: Vec<Id>,
+    textures: Vec<Id>,
+    samplers: Vec<Id>,
+    render_targets: Vec<Id>,
+    depth_buffers: Vec<Id>,
+    meshes: Vec<Id>,
+    materials: Vec<Id>,
+    compute_materials: Vec<Id>,
+    draw_bundles: Vec<Id>,
+}
+
+impl RestoredIds {
+    fn shader(&self, reader: &mut Reader<'_>) -> Option<Id> {
+        reader.index(&self.shaders)
+    }
+
+    fn material(&self, reader: &mut Reader<'_>) -> Option<Material> {
+        let vertex_shader = reader.index(&self.vertex_shaders)?;
+        let fragment_shader = reader.index(&self.fragment_shaders)?;
+
+        let bindings = self.bindings(reader)?;
+
+        let blend_mode = reader.variant(&BLEND_MODES)?;
+        let depth_state = if reader.bool()? {
+            Some(MaterialDepthState {
+                depth_buffer: reader.index(&self.depth_buffers)?,
+                compare: reader.variant(&DEPTH_COMPARES)?,
+                write_enabled: reader.bool()?,
+            })
+        } else {
+            None
+        };
+        let push_constants = reader.option(|reader| {
+            Some(PushConstantLayout {
+                size: reader.u32()?,
+                visibility: reader.variant(&SHADER_VISIBILITIES)?,
+                fallback_group: reader.u32()?,
+            })
+        })?;
+        let name = reader.option(Reader::str)?;
+
+        Some(Material {
+            vertex_shader,
+            fragment_shader,
+            bindings,
+            blend_mode,
+            depth_state,
+            push_constants,
+            name: name.map(str::to_string),
+        })
+    }
+
+    fn bindings(&self, reader: &mut Reader<'_>) -> Option<Vec<DrawBinding>> {
+        let binding_count = reader.u32()?;
+        let mut bindings = Vec::new();
+        for _ in 0..binding_count {
+            let group = reader.u32()?;
+            let binding = reader.u32()?;
+            let resource = match reader.u8()? {
+                0 => DrawBindingResource::Uniform(reader.index(&self.uniforms)?),
+                1 => DrawBindingResource::StorageBuffer {
+                    storage_buffer: reader.index(&self.storage_buffers)?,
+                    visibility: reader.variant(&SHADER_VISIBILITIES)?,
+                    read_only: reader.bool()?,
+                },
+                2 => DrawBindingResource::Texture {
+                    texture: reader.index(&self.textures)?,
+                    visibility: reader.variant(&SHADER_VISIBILITIES)?,
+                },
+                3 => DrawBindingResource::RenderTarget {
+                    render_target: reader.index(&self.render_targets)?,
+                    visibility: reader.variant(&SHADER_VISIBILITIES)?,
+                },
+                4 => DrawBindingResource::Sampler {
+                    sampler: reader.index(&self.samplers)?,
+                    visibility: reader.variant(&SHADER_VISIBILITIES)?,
+                },
+                _ => return None,
+            };
+            bindings.push(DrawBinding {
+                group,
+                binding,
+                resource,
+            });
+        }
+        Some(bindings)
+    }
+
+    fn compute_material(&self, reader: &mut Reader<'_>) -> Option<ComputeMaterial> {
+        let compute_shader = reader.index(&self.compute_shaders)?;
+        let bindings = self.bindings(reader)?;
+        let name = reader.option(Reader::str)?;
+
+        Some(ComputeMaterial {
+            compute_shader,
+            bindings,
+            name: name.map(str::to_string),
+        })
+    }
+
+    /// Reads a list of commands and appends them to `commands`.
+    fn draw_list(
+        &self,
+        reader: &mut Reader<'_>,
+        mut commands: Vec<FrameCommand>,
+    ) -> Option<DrawList> {
+        for _ in 0..reader.u32()? {
+            commands.push(self.command(reader)?);
+        }
+
+        let mut draw_list = DrawList::new();
+        draw_list.commands = commands;
+        Some(draw_list)
+    }
+
+    fn command(&self, reader: &mut Reader<'_>) -> Option<FrameCommand> {
+        let command = match reader.u8()? {
+            0 => FrameCommand::UpdateUniform(UpdateUniform {
+                uniform: reader.index(&self.uniforms)?,
+                data: reader.bytes()?.to_vec(),
+            }),
+            1 => FrameCommand::UpdateStorageBuffer(UpdateStorageBuffer {
+                storage_buffer: reader.index(&self.storage_buffers)?,
+                data: reader.bytes()?.to_vec(),
+            }),
+            2 => FrameCommand::UpdateTextureRegion(UpdateTextureRegion {
+                texture: reader.index(&self.textures)?,
+                origin: reader.uvec2()?,
+                size: reader.uvec2()?,
+                data: reader.bytes()?.to_vec(),
+            }),
+            3 => FrameCommand::ClearDepthBuffer(ClearDepthBuffer {
+                depth_buffer: reader.index(&self.depth_buffers)?,
+                value: reader.f32()?,
+            }),
+            4 => FrameCommand::ResizeDepthBuffer(ResizeDepthBuffer {
+                depth_buffer: reader.index(&self.depth_buffers)?,
+                size: reader.uvec2()?,
+            }),
+            5 => FrameCommand::ResizeRenderTarget(ResizeRenderTarget {
+                render_target: reader.index(&self.render_targets)?,
+                size: reader.uvec2()?,
+            }),
+            6 => FrameCommand::Draw(Draw {
+                render_target: self.render_target(reader)?,
+                material: reader.index(&self.materials)?,
+                vertex_count: reader.u32()?,
+                push_constants: reader.option(Reader::bytes)?.map(<[u8]>::to_vec),
+                sort_depth: reader.f32()?,
+                region: reader.region()?,
+                label: reader.option(Reader::str)?.map(str::to_string),
+            }),
+            7 => FrameCommand::DrawMesh(DrawMesh {
+                render_target: self.render_target(reader)?,
+                mesh: reader.index(&self.meshes)?,
+                material: reader.index(&self.materials)?,
+                push_constants: reader.option(Reader::bytes)?.map(<[u8]>::to_vec),
+                sort_depth: reader.f32()?,
+                region: reader.region()?,
+                label: reader.option(Reader::str)?.map(str::to_string),
+            }),
+            8 => FrameCommand::DrawMeshInstanced(DrawMeshInstanced {
+                render_target: self.render_target(reader)?,
+                mesh: reader.index(&self.meshes)?,
+                material: reader.index(&self.materials)?,
+                instance_buffer_layout: reader.vertex_buffer_layout()?,
+                instance_data: reader.bytes()?.to_vec(),
+                instance_count: reader.u32()?,
+                sort_depth: reader.f32()?,
+                region: reader.region()?,
+                label: reader.option(Reader::str)?.map(str::to_string),
+            }),
+            9 => FrameCommand::DrawIndirect(DrawIndirect {
+                render_target: self.render_target(reader)?,
+                material: reader.index(&self.materials)?,
+                indirect: self.indirect(reader)?,
+                sort_depth: reader.f32()?,
+                region: reader.region()?,
+                label: reader.option(Reader::str)?.map(str::to_string),
+            }),
+            10 => FrameCommand::DrawMeshIndirect(DrawMeshIndirect {
+                render_target: self.render_target(reader)?,
+                mesh: reader.index(&self.meshes)?,
+                material: reader.index(&self.materials)?,
+                indirect: self.indirect(reader)?,
+                sort_depth: reader.f32()?,
+                region: reader.region()?,
+                label: reader.option(Reader::str)?.map(str::to_string),
+            }),
+            11 => FrameCommand::ExecuteDrawBundle(ExecuteDrawBundle {
+                draw_bundle: reader.index(&self.draw_bundles)?,
+                region: reader.region()?,
+            }),
+            12 => FrameCommand::BeginSort(reader.variant(&SORT_MODES)?),
+            13 => FrameCommand::EndSort,
+            14 => FrameCommand::SetProfileLabel(reader.option(Reader::str)?.map(str::to_string)),
+            15 => FrameCommand::PushDebugGroup(reader.str()?.to_string()),
+            16 => FrameCommand::PopDebugGroup,
+            17 => FrameCommand::InsertDebugMarker(reader.str()?.to_string()),
+            18 => FrameCommand::Dispatch(Dispatch {
+                compute_material: reader.index(&self.compute_materials)?,
+                workgroup_count: UVec3::new(reader.u32()?, reader.u32()?, reader.u32()?),
+                label: reader.option(Reader::str)?.map(str::to_string),
+            }),
+            _ => return None,
+        };
+        Some(command)
+    }
+
+    fn render_target(&self, reader: &mut Reader<'_>) -> Option<RenderTarget> {
+        match reader.u8()? {
+            0 => Some(RenderTarget::Surface),
+            1 => Some(RenderTarget::Custom(reader.index(&self.render_targets)?)),
+            _ => None,
+        }
+    }
+
+    fn indirect(&self, reader: &mut Reader<'_>) -> Option<IndirectArgs> {
+        Some(IndirectArgs {
+            buffer: reader.index(&self.storage_buffers)?,
+            offset: reader.u64()?,
+            draw_count: reader.u32()?,
+        })
+    }
+}
+
+/// Appends little-endian values to a byte buffer.
+#[derive(Default)]
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn append(&mut self, other: &Writer) {
+        self.bytes.extend_from_slice(&other.bytes);
+    }
+
+    fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn f32(&mut self, value: f32) {
+        self.u32(value.to_bits());
+    }
+
+    fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    fn bytes(&mut self, value: &[u8]) {
+        self.u64(value.len() as u64);
+        self.bytes.extend_from_slice(value);
+    }
+
+    fn str(&mut self, value: &str) {
+        self.bytes(value.as_bytes());
+    }
+
+    fn vec2(&mut self, value: Vec2) {
+        self.f32(value.x);
+        self.f32(value.y);
+    }
+
+    fn uvec2(&mut self, value: UVec2) {
+        self.u32(value.x);
+        self.u32(value.y);
+    }
+
+    fn option<T>(&mut self, value: Option<T>, write: impl FnOnce(&mut Self, T)) {
+        self.bool(value.is_some());
+        if let Some(value) = value {
+            write(self, value);
+        }
+    }
+
+    /// Writes the position of `value` in `variants`.
+    fn variant<T: PartialEq>(&mut self, variants: &[T], value: T) {
+        let index = variants.iter().position(|variant| *variant == value);
+        self.u8(index.unwrap_or(u8::MAX as usize) as u8);
+    }
+
+    fn region(&mut self, region: PassRegion) {
+        self.option(region.viewport, |writer, viewport| {
+            writer.vec2(viewport.origin);
+            writer.vec2(viewport.size);
+            writer.f32(viewport.min_depth);
+            writer.f32(viewport.max_depth);
+        });
+        self.option(region.scissor_rect, |writer, scissor_rect| {
+            writer.uvec2(scissor_rect.origin);
+            writer.uvec2(scissor_rect.size);
+        });
+    }
+
+    fn vertex_buffer_layout(&mut self, layout: &VertexBufferLayout) {
+        self.u64(layout.size);
+        self.variant(&STEP_MODES, layout.step_mode);
+        self.u32(layout.attributes.len() as u32);
+        for attribute in layout.attributes.iter() {
+            self.variant(&VERTEX_FORMATS, attribute.format);
+            self.u64(attribute.offset);
+        }
+    }
+}
+
+/// Reads values written by [`Writer`], returning `None` once the input is exhausted or invalid.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let (taken, rest) = self.bytes.split_at_checked(len)?;
+        self.bytes = rest;
+        Some(taken)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+
+    fn f32(&mut self) -> Option<f32> {
+        Some(f32::from_bits(self.u32()?))
+    }
+
+    fn bool(&mut self) -> Option<bool> {
+        match self.u8()? {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        }
+    }
+
+    fn bytes(&mut self) -> Option<&'a [u8]> {
+        let len = usize::try_from(self.u64()?).ok()?;
+        self.take(len)
+    }
+
+    fn str(&mut self) -> Option<&'a str> {
+        std::str::from_utf8(self.bytes()?).ok()
+    }
+
+    fn vec2(&mut self) -> Option<Vec2> {
+        Some(Vec2::new(self.f32()?, self.f32()?))
+    }
+
+    fn uvec2(&mut self) -> Option<UVec2> {
+        Some(UVec2::new(self.u32()?, self.u32()?))
+    }
+
+    /// Reads an optional value; the outer `Option` is `None` when the input is invalid.
+    fn option<T>(&mut self, read: impl FnOnce(&mut Self) -> Option<T>) -> Option<Option<T>> {
+        if self.bool()? {
+            read(self).map(Some)
+        } else {
+            Some(None)
+        }
+    }
+
+    fn variant<T: Copy>(&mut self, variants: &[T]) -> Option<T> {
+        variants.get(usize::from(self.u8()?)).copied()
+    }
+
+    /// Reads an index into `ids`.
+    fn index(&mut self, ids: &[Id]) -> Option<Id> {
+        ids.get(usize::try_from(self.u32()?).ok()?).copied()
+    }
+
+    fn region(&mut self) -> Option<PassRegion> {
+        Some(PassRegion {
+            viewport: self.option(|reader| {
+                Some(Viewport {
+                    origin: reader.vec2()?,
+                    size: reader.vec2()?,
+                    min_depth: reader.f32()?,
+                    max_depth: reader.f32()?,
+                })
+            })?,
+            scissor_rect: self.option(|reader| {
+                Some(ScissorRect {
+                    origin: reader.uvec2()?,
+                    size: reader.uvec2()?,
+                })
+            })?,
+        })
+    }
+
+    fn vertex_buffer_layout(&mut self) -> Option<VertexBufferLayout> {
+        let size = self.u64()?;
+        let step_mode = self.variant(&STEP_MODES)?;
+        let attribute_count = self.u32()?;
+        let mut attributes = Vec::new();
+        for _ in 0..attribute_count {
+            attributes.push(VertexAttribute {
+                format: self.variant(&VERTEX_FORMATS)?,
+                offset: self.u64()?,
+            });
+        }
+
+        Some(VertexBufferLayout {
+            size,
+            step_mode,
+            attributes,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec4;
+
+    use super::*;
+    use crate::{
+        AsUniformBuffer,
+        depth_buffer::DepthBufferSize,
+        mesh::{AsVertexBufferLayout, VertexAttribute, VertexFormat},
+        recording::{RecordedSubmission, RecordingSurface},
+        render_target::{RenderTargetFormat, RenderTargetSize},
+        sampler::{SamplerAddressing, SamplerFiltering},
+        test_support::{SURFACE_SIZE, fill_material},
+        textures::TextureFormat,
+    };
+
+    const MESH_SHADER: &str = "
+        struct Tint {
+            color: vec4<f32>,
+        }
+
+        @group(0) @binding(0) var<uniform> tint: Tint;
+        @group(0) @binding(1) var albedo: texture_2d<f32>;
+        @group(0) @binding(2) var albedo_sampler: sampler;
+        @group(0) @binding(3) var scene: texture_2d<f32>;
+
+        @vertex
+        fn vs_main(@location(0) position: vec4<f32>) -> @builtin(position) vec4<f32> {
+            return position;
+        }
+
+        @fragment
+        fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+            let uv = position.xy / 8.0;
+            return tint.color * textureSample(albedo, albedo_sampler, uv)
+                + textureSample(scene, albedo_sampler, uv);
+        }
+    ";
+
+    const CULL_SHADER: &str = "
+        @group(0) @binding(0) var<storage, read> counts: array<u32>;
+        @group(0) @binding(1) var<storage, read_write> draw_args: array<u32>;
+
+        @compute @workgroup_size(64)
+        fn cull(@builtin(global_invocation_id) id: vec3<u32>) {
+            draw_args[id.x] = counts[id.x];
+        }
+    ";
+
+    #[derive(crate::encase::ShaderType)]
+    struct Tint {
+        color: Vec4,
+    }
+
+    impl AsUniformBuffer for Tint {
+        const VISIBILITY: ShaderVisibility = ShaderVisibility::Fragment;
+    }
+
+    #[derive(crate::encase::ShaderType)]
+    struct Vertex {
+        position: Vec4,
+    }
+
+    impl AsVertexBufferLayout for Vertex {
+        const STRIDE: u64 = 16;
+        const ATTRIBUTES: &'static [VertexAttribute] = &[VertexAttribute {
+            format: VertexFormat::Float32x4,
+            offset: 0,
+        }];
+    }
+
+    /// Builds a draw list that uses every kind of captured resource.
+    fn scene(renderer: &mut DrawListRenderer) -> DrawList {
+        let sampler = renderer.create_sampler(
+            "sampler",
+            SamplerAddressing::ClampToEdge,
+            SamplerFiltering::Linear,
+        );
+        let albedo = renderer
+            .create_texture(
+                "albedo",
+                UVec2::new(2, 2),
+                TextureFormat::Rgba,
+                &[
+                    255, 0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 255, 255, 255, 255, 255,
+                ],
+            )
+            .unwrap();
+        let tint = renderer.create_uniform("tint", &Tint { color: Vec4::ONE });
+        let scene_color = renderer.create_render_target(
+            "scene_color",
+            RenderTargetSize::SurfaceSize,
+            RenderTargetFormat::RgbaSrgb,
+        );
+        let depth_buffer = renderer.create_depth_buffer("depth", DepthBufferSize::SurfaceSize);
+
+        let fill = fill_material(renderer, "fill");
+        let mesh_material = renderer
+            .create_material_from_shader("mesh", MESH_SHADER)
+            .unwrap()
+            .uniform(0, 0, tint)
+            .texture(0, 1, albedo)
+            .sampler(0, 2, sampler)
+            .render_target_texture(0, 3, scene_color)
+            .depth_buffer(depth_buffer, DepthCompare::Less);
+        let mesh_material = renderer.create_material(mesh_material);
+        let quad = renderer.create_mesh(
+            "quad",
+            &[Vec4::ZERO, Vec4::X, Vec4::Y, Vec4::ONE].map(|position| Vertex { position }),
+            &[0, 1, 2, 2, 1, 3],
+        );
+
+        let counts = renderer
+            .create_storage_buffer_bytes(
+                "counts",
+                wgpu::BufferSize::new(16).unwrap(),
+                &[3, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+            )
+            .unwrap();
+        let draw_args = renderer
+            .create_indirect_storage_buffer_bytes(
+                "draw_args",
+                wgpu::BufferSize::new(16).unwrap(),
+                &[0; 16],
+            )
+            .unwrap();
+        let cull_shader = renderer.create_shader("cull", CULL_SHADER).unwrap();
+        let cull_shader = renderer.create_compute_shader(cull_shader, "cull");
+        let cull = renderer.create_compute_material(
+            ComputeMaterial::new(cull_shader)
+                .name("cull")
+                .storage_buffer(0, 0, counts)
+                .storage_buffer_read_write(0, 1, draw_args),
+        );
+
+        let mut bundle_list = DrawList::new();
+        bundle_list.draw(RenderTarget::Surface, fill, 3);
+        let bundle = renderer.create_draw_bundle("overlay", bundle_list).unwrap();
+
+        let mut draw_list = DrawList::new();
+        draw_list.dispatch(cull, UVec3::new(1, 1, 1));
+        draw_list.set_draw_label(Some("scene"));
+        draw_list.draw(RenderTarget::Custom(scene_color), fill, 3);
+        draw_list.draw_indirect(RenderTarget::Custom(scene_color), fill, draw_args, 0);
+        draw_list.set_draw_label(None);
+        draw_list.clear_depth_buffer(depth_buffer, 1.0);
+        draw_list.begin_sorted(crate::draw_list::DrawSortMode::FrontToBack);
+        draw_list.set_sort_depth(2.0);
+        draw_list.draw_mesh(RenderTarget::Surface, quad, mesh_material);
+        draw_list.set_sort_depth(1.0);
+        draw_list.draw(RenderTarget::Surface, fill, 6);
+        draw_list.end_sorted();
+        draw_list.execute_draw_bundle(bundle);
+        draw_list
+    }
+
+    /// A recorded submission with the ids, which differ between renderers, left out.
+    fn shape(submission: &RecordedSubmission) -> Vec<String> {
+        submission
+            .passes
+            .iter()
+            .map(|pass| {
+                let draws: Vec<String> = pass
+                    .draws
+                    .iter()
+                    .map(|draw| {
+                        format!(
+                            "{:?} {} {} {} {} {:?}",
+                            draw.elements,
+                            draw.instances,
+                            draw.draw_count,
+                            draw.mesh.is_some(),
+                            draw.draw_bundle.is_some(),
+                            draw.label
+                        )
+                    })
+                    .collect();
+                format!(
+                    "{} {:?} {:?} {:?} {:?}",
+                    pass.label,
+                    pass.render_target
+                        .map(|target| target == RenderTarget::Surface),
+                    pass.depth_clear_value,
+                    pass.dispatch
+                        .as_ref()
+                        .map(|dispatch| dispatch.workgroup_count),
+                    draws
+                )
+            })
+            .collect()
+    }
+
+    fn submit(renderer: &mut DrawListRenderer, draw_list: &DrawList) -> RecordedSubmission {
+        let surface = RecordingSurface::new(renderer, SURFACE_SIZE);
+        renderer.submit_draw_list(surface.frame_context(), draw_list);
+        renderer.take_recorded_submissions().pop().unwrap()
+    }
+
+    fn capture(renderer: &mut DrawListRenderer, draw_list: &DrawList) -> DrawListCapture {
+        let surface = RecordingSurface::new(renderer, SURFACE_SIZE);
+        renderer.capture_draw_list(&surface.frame_context(), draw_list)
+    }
+
+    #[test]
+    fn restored_captures_replay_the_same_passes_and_draws() {
+        let mut renderer = DrawListRenderer::new_recording().unwrap();
+        let draw_list = scene(&mut renderer);
+
+        renderer.capture_next_draw_list();
+        let original = submit(&mut renderer, &draw_list);
+        let capture = renderer.take_draw_list_capture().unwrap();
+        assert!(renderer.take_draw_list_capture().is_none());
+        assert_eq!(capture.surface_size(), SURFACE_SIZE);
+        assert!(capture.surface_format().is_srgb());
+
+        let path = std::env::temp_dir().join(format!(
+            "granite_capture_round_trip_{}.capture",
+            std::process::id()
+        ));
+        capture.save(&path).unwrap();
+        let loaded = DrawListCapture::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.bytes, capture.bytes);
+
+        let mut replay = DrawListRenderer::new_recording().unwrap();
+        let restored = loaded.restore(&mut replay).unwrap();
+        let replayed = submit(&mut replay, &restored);
+
+        assert_eq!(original.dispatches().count(), 1);
+        assert_eq!(original.draws().count(), 5);
+        assert_eq!(shape(&replayed), shape(&original));
+    }
+
+    #[test]
+    fn capturing_a_restored_draw_list_reproduces_the_capture() {
+        let mut renderer = DrawListRenderer::new_recording().unwrap();
+        let draw_list = scene(&mut renderer);
+        let original = capture(&mut renderer, &draw_list);
+
+        let mut replay = DrawListRenderer::new_recording().unwrap();
+        let restored = original.restore(&mut replay).unwrap();
+        let recaptured = capture(&mut replay, &restored);
+
+        assert!(recaptured.bytes == original.bytes);
+    }
+
+    /// The number of resources of each kind a capture restores.
+    fn resource_counts(renderer: &DrawListRenderer) -> [usize; 14] {
+        [
+            renderer.shaders.len(),
+            renderer.vertex_shaders.len(),
+            renderer.fragment_shaders.len(),
+            renderer.compute_shaders.len(),
+            renderer.uniforms.len(),
+            renderer.storage_buffers.len(),
+            renderer.textures.len(),
+            renderer.samplers.len(),
+            renderer.render_targets.len(),
+            renderer.depth_buffers.len(),
+            renderer.meshes.len(),
+            renderer.materials.len(),
+            renderer.compute_materials.len(),
+            renderer.draw_bundles.len(),
+        ]
+    }
+
+    #[test]
+    fn truncated_captures_are_rejected() {
+        let mut renderer = DrawListRenderer::new_recording().unwrap();
+        let draw_list = scene(&mut renderer);
+        let capture = capture(&mut renderer, &draw_list);
+
+        let len = capture.bytes.len();
+        for cut in (0..len).step_by(len / 16).chain([len - 1]) {
+            let truncated = DrawListCapture {
+                bytes: capture.bytes[..cut].to_vec(),
+            };
+            let mut replay = DrawListRenderer::new_recording().unwrap();
+            let before = resource_counts(&replay);
+            assert!(truncated.restore(&mut replay).is_none(), "cut at {cut}");
+            assert_eq!(resource_counts(&replay), before, "cut at {cut}");
+        }
+    }
+
+    #[test]
+    fn files_that_are_not_captures_fail_to_load() {
+        let path = std::env::temp_dir().join(format!(
+            "granite_capture_invalid_{}.capture",
+            std::process::id()
+        ));
+        std::fs::write(&path, b"not a capture").unwrap();
+        let error = DrawListCapture::load(&path).err().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    }
+}
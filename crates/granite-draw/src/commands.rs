@@ -1,10 +1,16 @@
-use glam::UVec2;
+use glam::{UVec2, UVec3};
 use wgpu::{self, util::DeviceExt};
 
 use crate::{
-    DepthBufferId, DrawListRenderer, FrameContext, MaterialId, MeshId, RenderTargetId,
-    StorageBufferId, TextureId, UniformId, draw_list::RenderTarget, mesh::VertexBufferLayout,
+    ComputeMaterialId, DepthBufferId, DrawBundleId, DrawListRenderer, FrameContext, MaterialId,
+    MeshId, RenderTargetId, StorageBufferId, TextureId, UniformId,
+    draw_encoder::{DrawCall, DrawEncoder},
+    draw_list::{DrawSortMode, RenderTarget, ScissorRect, Viewport},
+    mesh::VertexBufferLayout,
     prepared_draw::PreparedDraw,
+    push_constants::{DrawPushConstants, PushConstantBuffer},
+    recording::RecordedWriteTarget,
+    resources::WriteTarget,
 };
 
 pub(super) enum FrameCommand {
@@ -17,12 +23,104 @@ pub(super) enum FrameCommand {
     Draw(Draw),
     DrawMesh(DrawMesh),
     DrawMeshInstanced(DrawMeshInstanced),
+    DrawIndirect(DrawIndirect),
+    DrawMeshIndirect(DrawMeshIndirect),
+    ExecuteDrawBundle(ExecuteDrawBundle),
+    Dispatch(Dispatch),
+    BeginSort(DrawSortMode),
+    EndSort,
+    SetProfileLabel(Option<String>),
+    PushDebugGroup(String),
+    PopDebugGroup,
+    InsertDebugMarker(String),
+}
+
+impl FrameCommand {
+    /// Returns the name commands are reported under when profiling, or `None` for commands that
+    /// only change the state of later commands.
+    pub(super) fn profile_name(&self) -> Option<&'static str> {
+        match self {
+            Self::UpdateUniform(_) => Some("update_uniform"),
+            Self::UpdateStorageBuffer(_) => Some("update_storage_buffer"),
+            Self::UpdateTextureRegion(_) => Some("update_texture_region"),
+            Self::ClearDepthBuffer(_) => Some("clear_depth_buffer"),
+            Self::ResizeDepthBuffer(_) => Some("resize_depth_buffer"),
+            Self::ResizeRenderTarget(_) => Some("resize_render_target"),
+            Self::Draw(_) => Some("draw"),
+            Self::DrawMesh(_) => Some("draw_mesh"),
+            Self::DrawMeshInstanced(_) => Some("draw_mesh_instanced"),
+            Self::DrawIndirect(_) => Some("draw_indirect"),
+            Self::DrawMeshIndirect(_) => Some("draw_mesh_indirect"),
+            Self::ExecuteDrawBundle(_) => Some("execute_draw_bundle"),
+            Self::Dispatch(_) => Some("dispatch"),
+            Self::BeginSort(_)
+            | Self::EndSort
+            | Self::SetProfileLabel(_)
+            | Self::PushDebugGroup(_)
+            | Self::PopDebugGroup
+            | Self::InsertDebugMarker(_) => None,
+        }
+    }
+
+    /// Returns the render target drawn into by draw commands.
+    pub(super) fn render_target(&self) -> Option<RenderTarget> {
+        match self {
+            Self::Draw(draw) => Some(draw.render_target),
+            Self::DrawMesh(draw) => Some(draw.render_target),
+            Self::DrawMeshInstanced(draw) => Some(draw.render_target),
+            Self::DrawIndirect(draw) => Some(draw.render_target),
+            Self::DrawMeshIndirect(draw) => Some(draw.render_target),
+            _ => None,
+        }
+    }
+
+    /// Returns the material used by draw commands.
+    pub(super) fn material(&self) -> Option<MaterialId> {
+        match self {
+            Self::Draw(draw) => Some(draw.material),
+            Self::DrawMesh(draw) => Some(draw.material),
+            Self::DrawMeshInstanced(draw) => Some(draw.material),
+            Self::DrawIndirect(draw) => Some(draw.material),
+            Self::DrawMeshIndirect(draw) => Some(draw.material),
+            _ => None,
+        }
+    }
+
+    /// Returns the debug label recorded with draw commands.
+    pub(super) fn label(&self) -> Option<&str> {
+        match self {
+            Self::Draw(draw) => draw.label.as_deref(),
+            Self::DrawMesh(draw) => draw.label.as_deref(),
+            Self::DrawMeshInstanced(draw) => draw.label.as_deref(),
+            Self::DrawIndirect(draw) => draw.label.as_deref(),
+            Self::DrawMeshIndirect(draw) => draw.label.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Returns the viewport and scissor rectangle recorded with draw commands.
+    pub(super) fn region(&self) -> Option<PassRegion> {
+        match self {
+            Self::Draw(draw) => Some(draw.region),
+            Self::DrawMesh(draw) => Some(draw.region),
+            Self::DrawMeshInstanced(draw) => Some(draw.region),
+            Self::DrawIndirect(draw) => Some(draw.region),
+            Self::DrawMeshIndirect(draw) => Some(draw.region),
+            _ => None,
+        }
+    }
 }
 
 pub(super) struct Draw {
     pub render_target: RenderTarget,
     pub material: MaterialId,
     pub vertex_count: u32,
+    /// Encoded push constants passed with the draw.
+    pub push_constants: Option<Vec<u8>>,
+    pub sort_depth: f32,
+    pub region: PassRegion,
+    /// Name of the draw in graphics debuggers.
+    pub label: Option<String>,
 }
 
 impl Draw {
@@ -52,20 +150,33 @@ impl Draw {
             renderer.ensure_depth_buffer_ready(&frame_context, depth_state.depth_buffer);
         }
 
-        let Some(mut render_pass) = renderer.create_render_pass_for_draw(
-            encoder,
+        let push_constants = PushConstantBuffer::new(
+            renderer,
+            "draw_push_constants",
+            [(&prepared_draw, self.push_constants.as_deref())],
+        );
+
+        encode_draw(
+            renderer,
             &frame_context,
+            encoder,
             self.render_target,
-            prepared_draw.depth_state,
-        ) else {
-            return;
-        };
-
-        if !bind_pipeline_and_groups(renderer, &mut render_pass, &prepared_draw) {
-            return;
-        }
-
-        render_pass.draw(0..self.vertex_count, 0..1);
+            self.region,
+            DrawCall {
+                prepared_draw: &prepared_draw,
+                material: self.material,
+                mesh: None,
+                vertex_count: self.vertex_count,
+                instance_count: 1,
+                instance_buffer: None,
+                indirect: None,
+                push_constants: DrawPushConstants::new(
+                    self.push_constants.as_deref(),
+                    push_constants.as_ref(),
+                ),
+                label: self.label.as_deref(),
+            },
+        );
     }
 }
 
@@ -73,6 +184,12 @@ pub(super) struct DrawMesh {
     pub render_target: RenderTarget,
     pub mesh: MeshId,
     pub material: MaterialId,
+    /// Encoded push constants passed with the draw.
+    pub push_constants: Option<Vec<u8>>,
+    pub sort_depth: f32,
+    pub region: PassRegion,
+    /// Name of the draw in graphics debuggers.
+    pub label: Option<String>,
 }
 
 impl DrawMesh {
@@ -98,22 +215,33 @@ impl DrawMesh {
             renderer.ensure_depth_buffer_ready(&frame_context, depth_state.depth_buffer);
         }
 
-        let Some(mut render_pass) = renderer.create_render_pass_for_draw(
-            encoder,
+        let push_constants = PushConstantBuffer::new(
+            renderer,
+            "draw_push_constants",
+            [(&prepared_draw, self.push_constants.as_deref())],
+        );
+
+        encode_draw(
+            renderer,
             &frame_context,
+            encoder,
             self.render_target,
-            prepared_draw.depth_state,
-        ) else {
-            return;
-        };
-
-        let Some(index_count) =
-            bind_draw_state(renderer, &mut render_pass, &prepared_draw, self.mesh)
-        else {
-            return;
-        };
-
-        render_pass.draw_indexed(0..index_count, 0, 0..1);
+            self.region,
+            DrawCall {
+                prepared_draw: &prepared_draw,
+                material: self.material,
+                mesh: Some(self.mesh),
+                vertex_count: 0,
+                instance_count: 1,
+                instance_buffer: None,
+                indirect: None,
+                push_constants: DrawPushConstants::new(
+                    self.push_constants.as_deref(),
+                    push_constants.as_ref(),
+                ),
+                label: self.label.as_deref(),
+            },
+        );
     }
 }
 
@@ -124,6 +252,10 @@ pub(super) struct DrawMeshInstanced {
     pub instance_buffer_layout: VertexBufferLayout,
     pub instance_data: Vec<u8>,
     pub instance_count: u32,
+    pub sort_depth: f32,
+    pub region: PassRegion,
+    /// Name of the draw in graphics debuggers.
+    pub label: Option<String>,
 }
 
 impl DrawMeshInstanced {
@@ -154,6 +286,10 @@ impl DrawMeshInstanced {
             renderer.ensure_depth_buffer_ready(&frame_context, depth_state.depth_buffer);
         }
 
+        let push_constants =
+            PushConstantBuffer::new(renderer, "draw_push_constants", [(&prepared_draw, None)]);
+
+        renderer.submission_stats.bytes_uploaded += self.instance_data.len() as u64;
         frame_instance_buffers.push(renderer.device.create_buffer_init(
             &wgpu::util::BufferInitDescriptor {
                 label: Some("frame_instance_buffer"),
@@ -163,23 +299,270 @@ impl DrawMeshInstanced {
         ));
         let instance_buffer = frame_instance_buffers.last().unwrap();
 
-        let Some(mut render_pass) = renderer.create_render_pass_for_draw(
-            encoder,
+        encode_draw(
+            renderer,
             &frame_context,
+            encoder,
+            self.render_target,
+            self.region,
+            DrawCall {
+                prepared_draw: &prepared_draw,
+                material: self.material,
+                mesh: Some(self.mesh),
+                vertex_count: 0,
+                instance_count: self.instance_count,
+                instance_buffer: Some(instance_buffer),
+                indirect: None,
+                push_constants: DrawPushConstants::new(None, push_constants.as_ref()),
+                label: self.label.as_deref(),
+            },
+        );
+    }
+}
+
+/// Viewport and scissor rectangle applied to a draw's render pass. `None` covers the whole
+/// render target.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(super) struct PassRegion {
+    pub viewport: Option<Viewport>,
+    pub scissor_rect: Option<ScissorRect>,
+}
+
+impl PassRegion {
+    /// Returns `true` when neither a viewport nor a scissor rectangle is set.
+    pub(super) fn is_full(&self) -> bool {
+        self.viewport.is_none() && self.scissor_rect.is_none()
+    }
+}
+
+/// Location of indirect draw arguments inside a storage buffer.
+#[derive(Clone, Copy)]
+pub(super) struct IndirectArgs {
+    pub buffer: StorageBufferId,
+    pub offset: u64,
+    pub draw_count: u32,
+}
+
+impl IndirectArgs {
+    /// Returns the size in bytes of one tightly packed argument struct.
+    pub(super) fn stride(indexed: bool) -> u64 {
+        if indexed {
+            std::mem::size_of::<wgpu::util::DrawIndexedIndirectArgs>() as u64
+        } else {
+            std::mem::size_of::<wgpu::util::DrawIndirectArgs>() as u64
+        }
+    }
+
+    /// Validates the argument range against the storage buffer and returns the buffer to read
+    /// the arguments from.
+    pub(super) fn resolve<'a>(
+        &self,
+        renderer: &'a DrawListRenderer,
+        indexed: bool,
+    ) -> Option<&'a wgpu::Buffer> {
+        let Some(storage_buffer) = renderer.storage_buffers.get(self.buffer) else {
+            tracing::warn!("Invalid storage buffer id ({:?})", self.buffer);
+            return None;
+        };
+        if !storage_buffer.usage.contains(wgpu::BufferUsages::INDIRECT) {
+            tracing::warn!(
+                "Storage buffer {:?} was not created with indirect usage; create it with \
+                 `create_indirect_storage_buffer`.",
+                self.buffer
+            );
+            return None;
+        }
+        if !self.offset.is_multiple_of(4) {
+            tracing::warn!(
+                "Indirect draw offset {} into {:?} is not a multiple of 4.",
+                self.offset,
+                self.buffer
+            );
+            return None;
+        }
+
+        let end = u64::from(self.draw_count)
+            .checked_mul(Self::stride(indexed))
+            .and_then(|size| size.checked_add(self.offset));
+        if end.is_none_or(|end| end > storage_buffer.byte_len) {
+            tracing::warn!(
+                "Indirect draw of {} draws at offset {} overruns {:?} ({} bytes).",
+                self.draw_count,
+                self.offset,
+                self.buffer,
+                storage_buffer.byte_len
+            );
+            return None;
+        }
+
+        let Some(buffer) = renderer
+            .buffers
+            .get(storage_buffer.buffer)
+            .map(|record| &record.buffer)
+        else {
+            tracing::warn!(
+                "Invalid buffer id ({:?}) for storage buffer ({:?})",
+                storage_buffer.buffer,
+                self.buffer
+            );
+            return None;
+        };
+        Some(buffer)
+    }
+}
+
+pub(super) struct DrawIndirect {
+    pub render_target: RenderTarget,
+    pub material: MaterialId,
+    pub indirect: IndirectArgs,
+    pub sort_depth: f32,
+    pub region: PassRegion,
+    /// Name of the draw in graphics debuggers.
+    pub label: Option<String>,
+}
+
+impl DrawIndirect {
+    pub(super) fn execute(
+        &self,
+        renderer: &mut DrawListRenderer,
+        frame_context: FrameContext<'_>,
+        encoder: &mut wgpu::CommandEncoder,
+    ) {
+        renderer.ensure_render_target_ready(&frame_context, self.render_target);
+        let Some(prepared_draw) = PreparedDraw::try_new(
+            renderer,
+            frame_context.format,
             self.render_target,
-            prepared_draw.depth_state,
+            None,
+            self.material,
+            None,
         ) else {
             return;
         };
 
-        let Some(index_count) =
-            bind_draw_state(renderer, &mut render_pass, &prepared_draw, self.mesh)
-        else {
+        if let Some(depth_state) = prepared_draw.depth_state {
+            renderer.ensure_depth_buffer_ready(&frame_context, depth_state.depth_buffer);
+        }
+
+        let push_constants =
+            PushConstantBuffer::new(renderer, "draw_push_constants", [(&prepared_draw, None)]);
+
+        encode_draw(
+            renderer,
+            &frame_context,
+            encoder,
+            self.render_target,
+            self.region,
+            DrawCall {
+                prepared_draw: &prepared_draw,
+                material: self.material,
+                mesh: None,
+                vertex_count: 0,
+                instance_count: 0,
+                instance_buffer: None,
+                indirect: Some(self.indirect),
+                push_constants: DrawPushConstants::new(None, push_constants.as_ref()),
+                label: self.label.as_deref(),
+            },
+        );
+    }
+}
+
+pub(super) struct DrawMeshIndirect {
+    pub render_target: RenderTarget,
+    pub mesh: MeshId,
+    pub material: MaterialId,
+    pub indirect: IndirectArgs,
+    pub sort_depth: f32,
+    pub region: PassRegion,
+    /// Name of the draw in graphics debuggers.
+    pub label: Option<String>,
+}
+
+impl DrawMeshIndirect {
+    pub(super) fn execute(
+        &self,
+        renderer: &mut DrawListRenderer,
+        frame_context: FrameContext<'_>,
+        encoder: &mut wgpu::CommandEncoder,
+    ) {
+        renderer.ensure_render_target_ready(&frame_context, self.render_target);
+        let Some(prepared_draw) = PreparedDraw::try_new(
+            renderer,
+            frame_context.format,
+            self.render_target,
+            Some(self.mesh),
+            self.material,
+            None,
+        ) else {
             return;
         };
 
-        render_pass.set_vertex_buffer(1, instance_buffer.slice(..));
-        render_pass.draw_indexed(0..index_count, 0, 0..self.instance_count);
+        if let Some(depth_state) = prepared_draw.depth_state {
+            renderer.ensure_depth_buffer_ready(&frame_context, depth_state.depth_buffer);
+        }
+
+        let push_constants =
+            PushConstantBuffer::new(renderer, "draw_push_constants", [(&prepared_draw, None)]);
+
+        encode_draw(
+            renderer,
+            &frame_context,
+            encoder,
+            self.render_target,
+            self.region,
+            DrawCall {
+                prepared_draw: &prepared_draw,
+                material: self.material,
+                mesh: Some(self.mesh),
+                vertex_count: 0,
+                instance_count: 0,
+                instance_buffer: None,
+                indirect: Some(self.indirect),
+                push_constants: DrawPushConstants::new(None, push_constants.as_ref()),
+                label: self.label.as_deref(),
+            },
+        );
+    }
+}
+
+pub(super) struct ExecuteDrawBundle {
+    pub draw_bundle: DrawBundleId,
+    pub region: PassRegion,
+}
+
+impl ExecuteDrawBundle {
+    pub(super) fn execute(
+        &self,
+        renderer: &mut DrawListRenderer,
+        frame_context: FrameContext<'_>,
+        encoder: &mut wgpu::CommandEncoder,
+    ) {
+        let _ = renderer.encode_draw_bundle(&frame_context, encoder, self.draw_bundle, self.region);
+    }
+}
+
+pub(super) struct Dispatch {
+    pub compute_material: ComputeMaterialId,
+    pub workgroup_count: UVec3,
+    /// Name of the compute pass in graphics debuggers.
+    pub label: Option<String>,
+}
+
+impl Dispatch {
+    pub(super) fn execute(
+        &self,
+        renderer: &mut DrawListRenderer,
+        frame_context: FrameContext<'_>,
+        encoder: &mut wgpu::CommandEncoder,
+    ) {
+        let _ = renderer.encode_dispatch(
+            &frame_context,
+            encoder,
+            self.compute_material,
+            self.workgroup_count,
+            self.label.as_deref(),
+        );
     }
 }
 
@@ -189,8 +572,18 @@ pub(super) struct UpdateUniform {
 }
 
 impl UpdateUniform {
-    pub(super) fn execute(&self, renderer: &mut DrawListRenderer) {
-        let _ = renderer.write_uniform_bytes(self.uniform, self.data.as_slice());
+    pub(super) fn execute(
+        &self,
+        renderer: &mut DrawListRenderer,
+        encoder: &mut wgpu::CommandEncoder,
+    ) {
+        if renderer.write_uniform_bytes(
+            self.uniform,
+            self.data.as_slice(),
+            &mut WriteTarget::Encoder(encoder),
+        ) {
+            renderer.record_write(RecordedWriteTarget::Uniform(self.uniform));
+        }
     }
 }
 
@@ -200,8 +593,18 @@ pub(super) struct UpdateStorageBuffer {
 }
 
 impl UpdateStorageBuffer {
-    pub(super) fn execute(&self, renderer: &mut DrawListRenderer) {
-        let _ = renderer.write_storage_buffer_bytes(self.storage_buffer, self.data.as_slice());
+    pub(super) fn execute(
+        &self,
+        renderer: &mut DrawListRenderer,
+        encoder: &mut wgpu::CommandEncoder,
+    ) {
+        if renderer.write_storage_buffer_bytes_to(
+            self.storage_buffer,
+            self.data.as_slice(),
+            &mut WriteTarget::Encoder(encoder),
+        ) {
+            renderer.record_write(RecordedWriteTarget::StorageBuffer(self.storage_buffer));
+        }
     }
 }
 
@@ -213,13 +616,20 @@ pub(super) struct UpdateTextureRegion {
 }
 
 impl UpdateTextureRegion {
-    pub(super) fn execute(&self, renderer: &mut DrawListRenderer) {
-        let _ = renderer.write_texture_rgba8_region(
+    pub(super) fn execute(
+        &self,
+        renderer: &mut DrawListRenderer,
+        encoder: &mut wgpu::CommandEncoder,
+    ) {
+        if renderer.write_texture_rgba8_region(
             self.texture,
             self.origin,
             self.size,
             self.data.as_slice(),
-        );
+            &mut WriteTarget::Encoder(encoder),
+        ) {
+            renderer.record_write(RecordedWriteTarget::Texture(self.texture));
+        }
     }
 }
 
@@ -266,42 +676,32 @@ impl ResizeDepthBuffer {
     }
 }
 
-fn bind_draw_state(
-    renderer: &DrawListRenderer,
-    render_pass: &mut wgpu::RenderPass<'_>,
-    prepared_draw: &PreparedDraw,
-    mesh_id: MeshId,
-) -> Option<u32> {
-    if !bind_pipeline_and_groups(renderer, render_pass, prepared_draw) {
-        return None;
-    }
-
-    let Some(mesh) = renderer.meshes.get(mesh_id) else {
-        tracing::warn!("Invalid mesh id ({:?})", mesh_id);
-        return None;
+/// Encodes a single draw into a render pass of its own.
+fn encode_draw(
+    renderer: &mut DrawListRenderer,
+    frame_context: &FrameContext<'_>,
+    encoder: &mut wgpu::CommandEncoder,
+    render_target: RenderTarget,
+    region: PassRegion,
+    draw: DrawCall<'_>,
+) {
+    let Some(region) = renderer.resolve_pass_region(frame_context, render_target, region) else {
+        return;
     };
+    let Some(mut render_pass) = renderer.create_render_pass_for_draw(
+        encoder,
+        frame_context,
+        render_target,
+        draw.prepared_draw.depth_state,
+    ) else {
+        return;
+    };
+    region.apply(&mut render_pass);
 
-    render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
-    render_pass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-
-    Some(mesh.index_count)
-}
-
-fn bind_pipeline_and_groups(
-    renderer: &DrawListRenderer,
-    render_pass: &mut wgpu::RenderPass<'_>,
-    prepared_draw: &PreparedDraw,
-) -> bool {
-    let render_pipeline = &renderer.render_pipeline_cache[&prepared_draw.key];
-    render_pass.set_pipeline(render_pipeline);
-    for bind_group in prepared_draw.bind_groups_to_set.iter() {
-        let Some(bind_group_record) = renderer.bind_groups.get(bind_group.bind_group) else {
-            tracing::warn!("Invalid bind group id ({:?})", bind_group.bind_group);
-            return false;
-        };
-
-        render_pass.set_bind_group(bind_group.slot, &bind_group_record.bind_group, &[]);
-    }
+    let mut draw_encoder = DrawEncoder::new(renderer);
+    draw_encoder.encode(&mut render_pass, &draw);
+    let encoded = draw_encoder.finish();
+    drop(render_pass);
 
-    true
+    renderer.add_encoded_draws(encoded);
 }
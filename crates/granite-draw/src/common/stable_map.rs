@@ -57,6 +57,12 @@ where
         id
     }
 
+    #[inline]
+    pub fn clear(&mut self) {
+        self.data.clear();
+        self.lookup.clear();
+    }
+
     pub fn retain_keys<F>(&mut self, mut keep: F)
     where
         F: FnMut(&K) -> bool,
@@ -71,6 +77,20 @@ where
         });
     }
 
+    pub fn retain<F>(&mut self, mut keep: F)
+    where
+        F: FnMut(&K, Id) -> bool,
+    {
+        self.lookup.retain(|key, id| {
+            if keep(key, *id) {
+                true
+            } else {
+                self.data.remove(*id);
+                false
+            }
+        });
+    }
+
     #[inline]
     pub fn get_or_insert_with<F>(&mut self, key: K, create: F) -> Id
     where
@@ -61,6 +61,13 @@ where
     #[cfg(not(debug_assertions))]
     fn verify_id(_id: Id) {}
 
+    /// Removes every value. Handles to removed values stay invalid, even once their slots are
+    /// reused.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.data.clear();
+    }
+
     #[inline]
     pub fn iter(&self) -> impl Iterator<Item = (Id, &T)> {
         self.data.iter().map(|(index, value)| {
@@ -75,6 +82,20 @@ where
         })
     }
 
+    #[inline]
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Id, &mut T)> {
+        self.data.iter_mut().map(|(index, value)| {
+            (
+                Id(
+                    index,
+                    #[cfg(debug_assertions)]
+                    Self::type_id(),
+                ),
+                value,
+            )
+        })
+    }
+
     #[cfg(debug_assertions)]
     const fn type_id() -> std::any::TypeId {
         std::any::TypeId::of::<T>()
@@ -0,0 +1,455 @@
+//! Compute dispatches recorded into draw lists.
+//!
+//! A [`ComputeMaterial`] pairs a compute shader entry point with its bindings. Dispatching it
+//! with [`DrawList::dispatch`](crate::draw_list::DrawList::dispatch) encodes a compute pass in
+//! order with the other commands of the list, so a dispatch can fill a storage buffer that later
+//! draws in the same list read, for example culling that writes the arguments of
+//! [`DrawList::draw_indirect`](crate::draw_list::DrawList::draw_indirect):
+//!
+//! ```ignore
+//! let draw_args = renderer
+//!     .create_indirect_storage_buffer("draw_args", &[DrawIndirectArgs::default(); 256])
+//!     .unwrap();
+//! let cull = renderer.create_compute_material(
+//!     ComputeMaterial::new(renderer.create_compute_shader(shader, "cull"))
+//!         .name("cull")
+//!         .storage_buffer(0, 0, instances)
+//!         .storage_buffer_read_write(0, 1, draw_args),
+//! );
+//!
+//! draw_list.dispatch(cull, UVec3::new(4, 1, 1));
+//! draw_list.draw_indirect(RenderTarget::Surface, material, draw_args, 0, 256);
+//! ```
+
+use glam::UVec3;
+
+use crate::{
+    ComputeMaterialId, ComputeShaderId, DrawListRenderer, FrameContext, Id, RenderTargetId,
+    SamplerId, ShaderModuleId, ShaderVisibility, StorageBufferId, TextureId, UniformId,
+    bindings::{DrawBinding, DrawBindingResource},
+    draw_list::RenderTarget,
+    recording::RecordedDispatch,
+};
+
+pub(super) struct ComputeShader {
+    pub shader_module: ShaderModuleId,
+    pub entry_point: Option<String>,
+}
+
+impl ComputeShader {
+    pub(super) fn create(
+        shader_module: ShaderModuleId,
+        entry_point: Option<impl Into<String>>,
+    ) -> Self {
+        Self {
+            shader_module,
+            entry_point: entry_point.map(Into::into),
+        }
+    }
+}
+
+/// Describes a compute shader and the resources it binds.
+///
+/// Build with [`ComputeMaterial::new`] and fluent setters, then register via
+/// [`DrawListRenderer::create_compute_material`]. Every binding is visible to the compute stage;
+/// uniforms must be declared with [`ShaderVisibility::Compute`].
+#[must_use]
+pub struct ComputeMaterial {
+    pub(crate) compute_shader: ComputeShaderId,
+    pub(crate) bindings: Vec<DrawBinding>,
+    pub(crate) name: Option<String>,
+}
+
+impl ComputeMaterial {
+    /// Creates a compute material that runs `compute_shader`.
+    pub fn new(compute_shader: ComputeShaderId) -> Self {
+        Self {
+            compute_shader,
+            bindings: Vec::new(),
+            name: None,
+        }
+    }
+
+    /// Names the compute material, used to label the pipeline and bind groups created for it.
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    fn push_binding(mut self, binding: DrawBinding) -> Self {
+        self.bindings.push(binding);
+        self
+    }
+
+    /// Adds a uniform binding at `@group(group) @binding(binding)`.
+    pub fn uniform(self, group: u32, binding: u32, uniform: UniformId) -> Self {
+        self.push_binding(DrawBinding::uniform(group, binding, uniform))
+    }
+
+    /// Adds a read-only storage buffer binding at `@group(group) @binding(binding)`.
+    pub fn storage_buffer(self, group: u32, binding: u32, storage_buffer: StorageBufferId) -> Self {
+        self.push_binding(DrawBinding::storage_buffer(
+            group,
+            binding,
+            storage_buffer,
+            ShaderVisibility::Compute,
+        ))
+    }
+
+    /// Adds a read-write storage buffer binding at `@group(group) @binding(binding)`, declared
+    /// as `var<storage, read_write>` in the shader.
+    pub fn storage_buffer_read_write(
+        self,
+        group: u32,
+        binding: u32,
+        storage_buffer: StorageBufferId,
+    ) -> Self {
+        self.push_binding(DrawBinding::storage_buffer_read_write(
+            group,
+            binding,
+            storage_buffer,
+            ShaderVisibility::Compute,
+        ))
+    }
+
+    /// Adds a texture binding at `@group(group) @binding(binding)`.
+    pub fn texture(self, group: u32, binding: u32, texture: TextureId) -> Self {
+        self.push_binding(DrawBinding::texture_with_visibility(
+            group,
+            binding,
+            texture,
+            ShaderVisibility::Compute,
+        ))
+    }
+
+    /// Adds a render target as a texture binding at `@group(group) @binding(binding)`.
+    pub fn render_target_texture(
+        self,
+        group: u32,
+        binding: u32,
+        render_target: RenderTargetId,
+    ) -> Self {
+        self.push_binding(DrawBinding {
+            group,
+            binding,
+            resource: DrawBindingResource::RenderTarget {
+                render_target,
+                visibility: ShaderVisibility::Compute,
+            },
+        })
+    }
+
+    /// Adds a sampler binding at `@group(group) @binding(binding)`.
+    pub fn sampler(self, group: u32, binding: u32, sampler: SamplerId) -> Self {
+        self.push_binding(DrawBinding::sampler_with_visibility(
+            group,
+            binding,
+            sampler,
+            ShaderVisibility::Compute,
+        ))
+    }
+}
+
+pub(super) struct ComputeMaterialRecord {
+    pub name: Option<String>,
+    pub compute_shader: ComputeShaderId,
+    pub bindings: Vec<DrawBinding>,
+}
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub(super) struct ComputePipelineKey {
+    pub pipeline_layout: Id,
+    pub compute_shader: ComputeShaderId,
+}
+
+impl DrawListRenderer {
+    /// Creates a compute shader entry-point reference from a shader module.
+    pub fn create_compute_shader(
+        &mut self,
+        shader: ShaderModuleId,
+        entry_point: impl Into<String>,
+    ) -> ComputeShaderId {
+        self.compute_shaders
+            .push(ComputeShader::create(shader, Some(entry_point)))
+    }
+
+    /// Registers a compute material and returns its handle.
+    pub fn create_compute_material(
+        &mut self,
+        compute_material: ComputeMaterial,
+    ) -> ComputeMaterialId {
+        self.compute_materials.push(ComputeMaterialRecord {
+            name: compute_material.name,
+            compute_shader: compute_material.compute_shader,
+            bindings: compute_material.bindings,
+        })
+    }
+
+    /// Encodes a compute pass that dispatches `workgroup_count` workgroups of
+    /// `compute_material`.
+    ///
+    /// Returns `false` and logs a warning when the compute material or one of its bindings is
+    /// invalid, in which case nothing is dispatched.
+    pub(super) fn encode_dispatch(
+        &mut self,
+        frame_context: &FrameContext<'_>,
+        encoder: &mut wgpu::CommandEncoder,
+        compute_material: ComputeMaterialId,
+        workgroup_count: UVec3,
+        label: Option<&str>,
+    ) -> bool {
+        if workgroup_count.cmpeq(UVec3::ZERO).any() {
+            return true;
+        }
+        let max_workgroups = self.device.limits().max_compute_workgroups_per_dimension;
+        if workgroup_count.max_element() > max_workgroups {
+            tracing::warn!(
+                "Dispatch of {workgroup_count:?} workgroups exceeds the device limit of \
+                 {max_workgroups} per dimension."
+            );
+            return false;
+        }
+
+        let Some((bindings, compute_shader, name)) =
+            self.compute_materials.get(compute_material).map(|record| {
+                (
+                    record.bindings.clone(),
+                    record.compute_shader,
+                    record.name.clone(),
+                )
+            })
+        else {
+            tracing::warn!("Invalid compute material id ({:?})", compute_material);
+            return false;
+        };
+
+        for binding in bindings.iter() {
+            let visibility = match binding.resource {
+                DrawBindingResource::Uniform(uniform) => {
+                    self.uniforms.get(uniform).map(|record| record.visibility)
+                }
+                DrawBindingResource::StorageBuffer { visibility, .. }
+                | DrawBindingResource::Texture { visibility, .. }
+                | DrawBindingResource::RenderTarget { visibility, .. }
+                | DrawBindingResource::Sampler { visibility, .. } => Some(visibility),
+            };
+            if visibility.is_some_and(|visibility| visibility != ShaderVisibility::Compute) {
+                tracing::warn!(
+                    "Binding @group({}) @binding({}) of compute material {:?} is not visible to \
+                     compute shaders.",
+                    binding.group,
+                    binding.binding,
+                    compute_material
+                );
+                return false;
+            }
+            if let DrawBindingResource::RenderTarget { render_target, .. } = binding.resource {
+                self.ensure_render_target_ready(frame_context, RenderTarget::Custom(render_target));
+            }
+        }
+
+        let Some(resolved_bindings) =
+            self.resolve_draw_bindings(bindings.as_slice(), None, name.as_deref())
+        else {
+            return false;
+        };
+        let Some(pipeline_layout) =
+            self.get_or_create_pipeline_layout(resolved_bindings.pipeline_layout_key)
+        else {
+            tracing::warn!("Could not ensure a valid pipeline layout!");
+            return false;
+        };
+        let key = ComputePipelineKey {
+            pipeline_layout,
+            compute_shader,
+        };
+        if !self.ensure_compute_pipeline(key, name.as_deref()) {
+            tracing::warn!("Could not ensure a valid compute pipeline!");
+            return false;
+        }
+
+        self.submission_stats.passes += 1;
+        self.submission_stats.dispatches += 1;
+        self.submission_stats.pipeline_switches += 1;
+        self.submission_stats.bind_group_switches +=
+            resolved_bindings.bind_groups_to_set.len() as u32;
+        let first_query = self.allocate_pass_timestamps();
+
+        let pass_label = format!("{}_compute_pass", name.as_deref().unwrap_or("dispatch"));
+        self.record_pass(&pass_label, None, None, None);
+        self.record_dispatch(RecordedDispatch {
+            compute_material,
+            workgroup_count,
+            label: label.map(str::to_string),
+        });
+
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some(label.unwrap_or(&pass_label)),
+            timestamp_writes: self.compute_pass_timestamp_writes(first_query),
+        });
+        compute_pass.set_pipeline(&self.compute_pipeline_cache[&key]);
+        for bind_group in resolved_bindings.bind_groups_to_set.iter() {
+            let Some(bind_group_record) = self.bind_groups.get(bind_group.bind_group) else {
+                tracing::warn!("Invalid bind group id ({:?})", bind_group.bind_group);
+                return false;
+            };
+            compute_pass.set_bind_group(bind_group.slot, &bind_group_record.bind_group, &[]);
+        }
+        compute_pass.dispatch_workgroups(workgroup_count.x, workgroup_count.y, workgroup_count.z);
+
+        true
+    }
+
+    /// Creates the compute pipeline for `key` when it is not cached, labeled after `name`, the
+    /// compute material it is first created for.
+    fn ensure_compute_pipeline(&mut self, key: ComputePipelineKey, name: Option<&str>) -> bool {
+        if self.compute_pipeline_cache.contains_key(&key) {
+            self.submission_stats.pipeline_cache_hits += 1;
+        } else {
+            self.submission_stats.pipeline_cache_misses += 1;
+            let Some(compute_pipeline) = self.create_compute_pipeline(key, name) else {
+                return false;
+            };
+            self.compute_pipeline_cache.insert(key, compute_pipeline);
+        }
+        self.cache_usages
+            .pipeline_layouts
+            .touch(key.pipeline_layout, self.submission_index);
+        true
+    }
+
+    fn create_compute_pipeline(
+        &self,
+        key: ComputePipelineKey,
+        name: Option<&str>,
+    ) -> Option<wgpu::ComputePipeline> {
+        tracing::debug!("Creating compute pipeline for {key:?}");
+
+        let pipeline_layout = self.pipeline_layouts.get(key.pipeline_layout)?;
+        let compute_shader = self.compute_shaders.get(key.compute_shader)?;
+        let shader_module = self.shaders.get(compute_shader.shader_module)?;
+
+        let label = name.map(|name| format!("{name}_compute_pipeline"));
+        Some(
+            self.device
+                .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                    label: label.as_deref(),
+                    layout: Some(pipeline_layout),
+                    module: &shader_module.shader_module,
+                    entry_point: compute_shader.entry_point.as_deref(),
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    cache: self.pipeline_cache.as_ref(),
+                }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{draw_list::DrawList, test_support::Scene};
+
+    const CULL_SHADER: &str = "
+        @group(0) @binding(0) var<storage, read> counts: array<u32>;
+        @group(0) @binding(1) var<storage, read_write> draw_args: array<u32>;
+
+        @compute @workgroup_size(64)
+        fn cull(@builtin(global_invocation_id) id: vec3<u32>) {
+            draw_args[id.x] = counts[id.x];
+        }
+    ";
+
+    fn storage_buffer(renderer: &mut DrawListRenderer, name: &str) -> StorageBufferId {
+        renderer
+            .create_indirect_storage_buffer_bytes(
+                name,
+                wgpu::BufferSize::new(16).unwrap(),
+                &[0; 64],
+            )
+            .unwrap()
+    }
+
+    fn cull_material(renderer: &mut DrawListRenderer) -> (ComputeMaterialId, StorageBufferId) {
+        let shader = renderer.create_shader("cull", CULL_SHADER).unwrap();
+        let compute_shader = renderer.create_compute_shader(shader, "cull");
+        let counts = storage_buffer(renderer, "counts");
+        let draw_args = storage_buffer(renderer, "draw_args");
+        let compute_material = renderer.create_compute_material(
+            ComputeMaterial::new(compute_shader)
+                .name("cull")
+                .storage_buffer(0, 0, counts)
+                .storage_buffer_read_write(0, 1, draw_args),
+        );
+        (compute_material, draw_args)
+    }
+
+    #[test]
+    fn dispatch_is_recorded_as_a_compute_pass() {
+        let mut scene = Scene::new();
+        let (compute_material, draw_args) = cull_material(&mut scene.renderer);
+
+        let mut draw_list = DrawList::new();
+        draw_list.set_draw_label(Some("culling"));
+        draw_list.dispatch(compute_material, UVec3::new(2, 1, 1));
+        let submission = scene.submit(&draw_list);
+        assert_eq!(submission.passes.len(), 1);
+        assert_eq!(submission.passes[0].render_target, None);
+        assert_eq!(
+            submission.dispatches().collect::<Vec<_>>(),
+            [&RecordedDispatch {
+                compute_material,
+                workgroup_count: UVec3::new(2, 1, 1),
+                label: Some("culling".to_string()),
+            }]
+        );
+
+        let stats = scene.renderer.stats().last_submission;
+        assert_eq!((stats.passes, stats.dispatches, stats.draws), (1, 1, 0));
+        assert!(scene.renderer.storage_buffer_raw(draw_args).is_some());
+    }
+
+    #[test]
+    fn compute_pipelines_are_cached() {
+        let mut scene = Scene::new();
+        let (compute_material, _) = cull_material(&mut scene.renderer);
+
+        let mut draw_list = DrawList::new();
+        draw_list.dispatch(compute_material, UVec3::ONE);
+        draw_list.dispatch(compute_material, UVec3::ONE);
+        scene.submit(&draw_list);
+
+        let stats = scene.renderer.stats().last_submission;
+        assert_eq!(stats.dispatches, 2);
+        assert_eq!(
+            (stats.pipeline_cache_misses, stats.pipeline_cache_hits),
+            (1, 1)
+        );
+    }
+
+    #[test]
+    fn empty_and_invalid_dispatches_are_skipped() {
+        let mut scene = Scene::new();
+        let (compute_material, _) = cull_material(&mut scene.renderer);
+        let shader = scene.renderer.create_shader("cull", CULL_SHADER).unwrap();
+        let compute_shader = scene.renderer.create_compute_shader(shader, "cull");
+        let fragment_only = storage_buffer(&mut scene.renderer, "fragment_only");
+        let fragment_visible = scene.renderer.create_compute_material(ComputeMaterial {
+            compute_shader,
+            bindings: vec![DrawBinding::storage_buffer(
+                0,
+                0,
+                fragment_only,
+                ShaderVisibility::Fragment,
+            )],
+            name: None,
+        });
+
+        let mut draw_list = DrawList::new();
+        draw_list.dispatch(compute_material, UVec3::new(0, 1, 1));
+        draw_list.dispatch(fragment_visible, UVec3::ONE);
+        draw_list.dispatch(compute_material, UVec3::new(u32::MAX, 1, 1));
+        let submission = scene.submit(&draw_list);
+        assert!(submission.passes.is_empty());
+        assert_eq!(scene.renderer.stats().last_submission.dispatches, 0);
+    }
+}
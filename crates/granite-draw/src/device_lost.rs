@@ -0,0 +1,294 @@
+//! Recovering from a lost GPU device.
+//!
+//! A driver reset or a removed adapter loses the device, and every `wgpu` object created on it
+//! becomes unusable. The renderer keeps the data resources were created with alongside them, so
+//! [`DrawListRenderer::replace_device`] can recreate everything on a new device without
+//! invalidating any handles.
+//!
+//! The renderer does not watch for the loss itself: a device has a single device lost callback,
+//! which `granite`'s `Renderer` owns. Call [`DrawListRenderer::replace_device`] from
+//! `Scene::device_lost` once `Renderer` has recreated its device. Buffers other than uniforms do not keep a copy of each write unless
+//! [`DrawListRenderer::set_keep_buffer_contents`] asks for it.
+
+use wgpu::util::DeviceExt;
+
+use crate::DrawListRenderer;
+
+impl DrawListRenderer {
+    /// Sets whether buffers keep a CPU copy of every write, so
+    /// [`DrawListRenderer::replace_device`] and [captures](crate::capture) restore their latest
+    /// contents.
+    ///
+    /// Disabled by default, since large buffers written every frame would copy each write.
+    /// Without it, buffers other than uniforms only keep the data they were created with until
+    /// they are written again. Applies to writes made after the call.
+    pub fn set_keep_buffer_contents(&mut self, keep: bool) {
+        self.keep_buffer_contents = keep;
+    }
+
+    /// Recreates every resource on `device`, usually after the previous device was lost.
+    ///
+    /// All handles stay valid. Textures, meshes and uniforms are recreated from the data last
+    /// written from the CPU, and other buffers from the data they were created with. Those
+    /// written after creation are recreated zero-filled, with a warning, unless
+    /// [`DrawListRenderer::set_keep_buffer_contents`] is enabled. Contents produced on the GPU,
+    /// such as indirect arguments written by a compute pass, are not restored. Render targets
+    /// and depth buffers are reallocated on first use, and depth buffers must be cleared again
+    /// before they are loaded.
+    ///
+    /// Cached pipelines and bind groups, recorded draw bundles, and timings that were not taken
+    /// yet are dropped. A loaded pipeline cache starts over empty. Readbacks that did not
+    /// resolve yet fail with [`ReadbackError::DeviceLost`](crate::readback::ReadbackError).
+    pub fn replace_device(&mut self, device: wgpu::Device, queue: wgpu::Queue) {
+        self.device = device;
+        self.queue = queue;
+
+        self.render_pipeline_cache.clear();
+        self.compute_pipeline_cache.clear();
+        self.pipeline_layouts.clear();
+        self.bind_groups.clear();
+        self.bind_group_layouts.clear();
+        self.empty_bind_group_layout = None;
+        self.cache_usages = Default::default();
+        self.invalidate_draw_bundles();
+        self.transient_textures.clear();
+        self.uploads.reset();
+        self.push_constant_arena.reset();
+        self.fail_readbacks();
+
+        if self.pipeline_cache.is_some() {
+            self.pipeline_cache = Some(self.create_pipeline_cache(None));
+        }
+        if self.profiler.is_some() {
+            self.set_profiling(false);
+            self.set_profiling(true);
+        }
+
+        for (_, shader) in self.shaders.iter_mut() {
+            shader.recreate(&self.device);
+        }
+
+        for (_, record) in self.buffers.iter_mut() {
+            if record.contents.is_empty() && record.buffer.size() > 0 {
+                tracing::warn!(
+                    "Buffer `{}` was recreated zero-filled, since its contents were not kept",
+                    record.label
+                );
+            }
+            let contents = record.contents_or_zeroed();
+            self.submission_stats.bytes_uploaded += contents.len() as u64;
+            let buffer = self
+                .device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(&record.label),
+                    contents: &contents,
+                    usage: record.usage,
+                });
+            record.buffer = buffer;
+        }
+
+        for (_, mesh) in self.meshes.iter_mut() {
+            mesh.recreate(&self.device);
+            self.submission_stats.bytes_uploaded +=
+                mesh.vertex_buffer.size() + mesh.index_buffer.size();
+        }
+
+        for (_, texture) in self.textures.iter_mut() {
+            self.submission_stats.bytes_uploaded += texture.pixels.len() as u64;
+            texture.recreate(&self.device, &self.queue);
+        }
+
+        for (_, sampler) in self.samplers.iter_mut() {
+            sampler.recreate(&self.device);
+        }
+
+        for (_, render_target) in self.render_targets.iter_mut() {
+            render_target._texture = None;
+            render_target.view = None;
+            render_target.transient_texture = None;
+        }
+
+        for (_, depth_buffer) in self.depth_buffers.iter_mut() {
+            depth_buffer._texture = None;
+            depth_buffer.view = None;
+            depth_buffer.initialized = false;
+        }
+
+        tracing::info!("Recreated renderer resources on the new device.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{UVec2, Vec4};
+
+    use super::*;
+    use crate::{
+        AsUniformBuffer, ShaderVisibility, StorageBufferId, UniformId,
+        draw_list::{DrawList, RenderTarget},
+        recording::RecordingSurface,
+        test_support::fill_material,
+    };
+
+    #[derive(crate::encase::ShaderType)]
+    struct Color {
+        value: Vec4,
+    }
+
+    impl AsUniformBuffer for Color {
+        const VISIBILITY: ShaderVisibility = ShaderVisibility::Fragment;
+    }
+
+    fn color(value: Vec4) -> Color {
+        Color { value }
+    }
+
+    fn uniform_contents(renderer: &DrawListRenderer, uniform: UniformId) -> &[u8] {
+        let buffer = renderer.uniforms.get(uniform).unwrap().buffer;
+        &renderer.buffers.get(buffer).unwrap().contents
+    }
+
+    #[test]
+    fn device_loss_reaches_granite_when_both_layers_share_the_device() {
+        let recording = DrawListRenderer::new_recording().unwrap();
+        let (device, queue) = (recording.device.clone(), recording.queue.clone());
+        // The order every example uses: granite's `Renderer` watches the device it creates, then
+        // the scene creates a draw renderer on it.
+        let device_lost = granite::renderer::DeviceLostWatch::new(&device);
+        let mut renderer = DrawListRenderer::new(device.clone(), queue);
+        renderer.set_recording(true);
+        let material = fill_material(&mut renderer, "fill");
+        assert!(!device_lost.is_lost());
+
+        device.destroy();
+        let _ = device.poll(wgpu::PollType::Poll);
+        assert!(device_lost.is_lost());
+
+        // What `Scene::device_lost` does once `Renderer` recreated its device.
+        let replacement = DrawListRenderer::new_recording().unwrap();
+        let device_lost = granite::renderer::DeviceLostWatch::new(&replacement.device);
+        renderer.replace_device(replacement.device.clone(), replacement.queue.clone());
+        let surface = RecordingSurface::new(&renderer, UVec2::new(4, 4));
+        let mut draw_list = DrawList::new();
+        draw_list.draw(RenderTarget::Surface, material, 3);
+        renderer.submit_draw_list(surface.frame_context(), &draw_list);
+        assert_eq!(renderer.take_recorded_submissions().len(), 1);
+        assert!(!device_lost.is_lost());
+    }
+
+    #[test]
+    fn resources_are_recreated_on_the_new_device() {
+        let mut renderer = DrawListRenderer::new_recording().unwrap();
+        let surface = RecordingSurface::new(&renderer, UVec2::new(4, 4));
+        let material = renderer
+            .create_material_from_shader(
+                "fill",
+                "
+                @group(0) @binding(0) var<uniform> color: Color;
+
+                struct Color {
+                    value: vec4<f32>,
+                }
+
+                @vertex
+                fn vs_main() -> @builtin(position) vec4<f32> {
+                    return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+                }
+
+                @fragment
+                fn fs_main() -> @location(0) vec4<f32> {
+                    return color.value;
+                }
+                ",
+            )
+            .unwrap();
+        let uniform = renderer.create_uniform("color", &color(Vec4::ONE));
+        let material = renderer.create_material(material.uniform(0, 0, uniform));
+
+        let mut draw_list = DrawList::new();
+        draw_list.draw(RenderTarget::Surface, material, 3);
+        renderer.submit_draw_list(surface.frame_context(), &draw_list);
+
+        renderer.device.destroy();
+        let _ = renderer.device.poll(wgpu::PollType::Poll);
+
+        let replacement = DrawListRenderer::new_recording().unwrap();
+        renderer.replace_device(replacement.device.clone(), replacement.queue.clone());
+        assert!(renderer.render_pipeline_cache.is_empty());
+
+        let surface = RecordingSurface::new(&renderer, UVec2::new(4, 4));
+        renderer.submit_draw_list(surface.frame_context(), &draw_list);
+        let submission = renderer.take_recorded_submissions().pop().unwrap();
+        assert_eq!(submission.passes[0].draws.len(), 1);
+        assert_eq!(renderer.stats().last_submission.pipeline_cache_misses, 1);
+    }
+
+    #[test]
+    fn written_uniforms_always_keep_their_latest_contents() {
+        let mut renderer = DrawListRenderer::new_recording().unwrap();
+        let uniform = renderer.create_uniform("color", &color(Vec4::ONE));
+        assert!(renderer.write_uniform(uniform, &color(Vec4::X)));
+        assert_eq!(
+            uniform_contents(&renderer, uniform),
+            color_bytes(Vec4::X).as_slice()
+        );
+
+        let replacement = DrawListRenderer::new_recording().unwrap();
+        renderer.replace_device(replacement.device.clone(), replacement.queue.clone());
+        assert_eq!(
+            uniform_contents(&renderer, uniform),
+            color_bytes(Vec4::X).as_slice()
+        );
+    }
+
+    #[test]
+    fn written_storage_buffers_keep_their_contents_only_when_asked_to() {
+        let mut renderer = DrawListRenderer::new_recording().unwrap();
+        let storage_buffer = create_storage_buffer(&mut renderer);
+        assert_eq!(storage_buffer_contents(&renderer, storage_buffer), [1; 16]);
+
+        assert!(renderer.write_storage_buffer_bytes(storage_buffer, &[2; 16]));
+        assert!(storage_buffer_contents(&renderer, storage_buffer).is_empty());
+
+        renderer.set_keep_buffer_contents(true);
+        assert!(renderer.write_storage_buffer_bytes(storage_buffer, &[3; 16]));
+        assert_eq!(storage_buffer_contents(&renderer, storage_buffer), [3; 16]);
+    }
+
+    #[test]
+    fn dropped_contents_are_recreated_zero_filled() {
+        let mut renderer = DrawListRenderer::new_recording().unwrap();
+        let storage_buffer = create_storage_buffer(&mut renderer);
+        assert!(renderer.write_storage_buffer_bytes(storage_buffer, &[2; 16]));
+
+        let replacement = DrawListRenderer::new_recording().unwrap();
+        renderer.replace_device(replacement.device.clone(), replacement.queue.clone());
+
+        let buffer = renderer.storage_buffers.get(storage_buffer).unwrap().buffer;
+        let record = renderer.buffers.get(buffer).unwrap();
+        assert_eq!(record.buffer.size(), 16);
+        assert_eq!(*record.contents_or_zeroed(), [0; 16]);
+    }
+
+    fn create_storage_buffer(renderer: &mut DrawListRenderer) -> StorageBufferId {
+        renderer
+            .create_storage_buffer_bytes("values", wgpu::BufferSize::new(16).unwrap(), &[1; 16])
+            .unwrap()
+    }
+
+    fn storage_buffer_contents(
+        renderer: &DrawListRenderer,
+        storage_buffer: StorageBufferId,
+    ) -> &[u8] {
+        let buffer = renderer.storage_buffers.get(storage_buffer).unwrap().buffer;
+        &renderer.buffers.get(buffer).unwrap().contents
+    }
+
+    fn color_bytes(value: Vec4) -> Vec<u8> {
+        value
+            .to_array()
+            .iter()
+            .flat_map(|component| component.to_le_bytes())
+            .collect()
+    }
+}
@@ -0,0 +1,571 @@
+use std::collections::HashSet;
+
+use wgpu::util::DeviceExt;
+
+use crate::{
+    DepthBufferId, DrawBundleId, DrawListRenderer, FrameContext, MaterialDepthState, MeshId,
+    RenderPipelineKey, StorageBufferId,
+    commands::{FrameCommand, IndirectArgs, PassRegion},
+    common::Id,
+    draw_encoder::{DrawCall, DrawEncoder, EncodedDraws},
+    draw_list::{DrawList, RenderTarget},
+    prepared_draw::PreparedDraw,
+    push_constants::{DrawPushConstants, PushConstantBuffer},
+    recording::RecordedDraw,
+    stats::SubmissionStats,
+};
+
+pub(super) struct DrawBundleRecord {
+    pub name: String,
+    render_target: RenderTarget,
+    pub commands: Vec<FrameCommand>,
+    compiled: Option<CompiledDrawBundle>,
+}
+
+/// A recorded `wgpu::RenderBundle` along with everything it was built against.
+///
+/// The bundle is only valid while all of the cached objects it references still exist and the
+/// render target format is unchanged; see [`CompiledDrawBundle::is_valid`].
+struct CompiledDrawBundle {
+    bundle: wgpu::RenderBundle,
+    render_target_format: wgpu::TextureFormat,
+    depth_state: Option<MaterialDepthState>,
+    pipelines: HashSet<RenderPipelineKey>,
+    bind_groups: Vec<Id>,
+    meshes: Vec<MeshId>,
+    /// Indirect argument buffers and their byte length when recorded. A storage buffer write
+    /// with a different length replaces the backing buffer, which the bundle still references.
+    indirect_buffers: Vec<(StorageBufferId, u64)>,
+    _instance_buffers: Vec<wgpu::Buffer>,
+    _push_constants: Option<PushConstantBuffer>,
+    /// Draw and binding counters added to the submission stats each time the bundle executes.
+    draw_stats: SubmissionStats,
+    /// Draws added to the recorded submission each time the bundle executes.
+    recorded_draws: Vec<RecordedDraw>,
+}
+
+impl CompiledDrawBundle {
+    fn is_valid(
+        &self,
+        renderer: &DrawListRenderer,
+        render_target_format: wgpu::TextureFormat,
+    ) -> bool {
+        self.render_target_format == render_target_format
+            && self
+                .pipelines
+                .iter()
+                .all(|key| renderer.render_pipeline_cache.contains_key(key))
+            && self
+                .bind_groups
+                .iter()
+                .all(|bind_group| renderer.bind_groups.get(*bind_group).is_some())
+            && self
+                .meshes
+                .iter()
+                .all(|mesh| renderer.meshes.get(*mesh).is_some())
+            && self
+                .indirect_buffers
+                .iter()
+                .all(|(storage_buffer, byte_len)| {
+                    renderer
+                        .storage_buffers
+                        .get(*storage_buffer)
+                        .is_some_and(|record| record.byte_len == *byte_len)
+                })
+    }
+}
+
+/// A draw command after its pipeline and bind groups were resolved, ready to be encoded.
+struct PreparedBundleDraw {
+    prepared_draw: PreparedDraw,
+    mesh: Option<MeshId>,
+    vertex_count: u32,
+    instance_buffer: Option<usize>,
+    instance_count: u32,
+    indirect: Option<IndirectArgs>,
+    push_constants: Option<Vec<u8>>,
+}
+
+impl DrawListRenderer {
+    /// Compiles a draw list into a reusable draw bundle.
+    ///
+    /// Draw bundles are backed by a `wgpu::RenderBundle`, so pipelines and bind groups are
+    /// resolved once instead of on every submission. Only draw commands are allowed and all of
+    /// them must target the same render target; update, clear, and resize commands are rejected,
+    /// as are draws recorded with a viewport or scissor rectangle.
+    ///
+    /// The bundle is recorded lazily on first execution and recorded again automatically when a
+    /// referenced pipeline, bind group, or mesh is evicted (for example when a sampled render
+    /// target or storage buffer is reallocated), or when the render target format changes.
+    pub fn create_draw_bundle(&mut self, name: &str, draw_list: DrawList) -> Option<DrawBundleId> {
+        let DrawList { commands, .. } = draw_list;
+
+        let mut render_target = None;
+        for command in commands.iter() {
+            let Some(command_render_target) = command.render_target() else {
+                tracing::warn!(
+                    "Could not create draw bundle `{name}`: only draw commands can be bundled."
+                );
+                return None;
+            };
+
+            match render_target {
+                None => render_target = Some(command_render_target),
+                Some(render_target) if render_target != command_render_target => {
+                    tracing::warn!(
+                        "Could not create draw bundle `{name}`: all draws must target the same \
+                         render target."
+                    );
+                    return None;
+                }
+                Some(_) => {}
+            }
+        }
+
+        if commands
+            .iter()
+            .any(|command| command.region().is_some_and(|region| !region.is_full()))
+        {
+            tracing::warn!(
+                "Could not create draw bundle `{name}`: bundled draws cannot set a viewport or \
+                 scissor rectangle; set them on the draw list that executes the bundle instead."
+            );
+            return None;
+        }
+
+        let Some(render_target) = render_target else {
+            tracing::warn!("Could not create draw bundle `{name}` from an empty draw list.");
+            return None;
+        };
+
+        Some(self.draw_bundles.push(DrawBundleRecord {
+            name: name.to_string(),
+            render_target,
+            commands,
+            compiled: None,
+        }))
+    }
+
+    /// Returns the render target and recorded draw commands of a draw bundle.
+    pub(super) fn draw_bundle_commands(
+        &self,
+        draw_bundle: DrawBundleId,
+    ) -> Option<(RenderTarget, &[FrameCommand])> {
+        let record = self.draw_bundles.get(draw_bundle)?;
+        Some((record.render_target, record.commands.as_slice()))
+    }
+
+    /// Drops recorded bundles that use any of `pipelines`, so they are recorded again with the
+    /// pipelines that replace them.
+    pub(super) fn invalidate_draw_bundles_for_pipelines(
+        &mut self,
+        pipelines: &[RenderPipelineKey],
+    ) {
+        if pipelines.is_empty() {
+            return;
+        }
+        for (_, record) in self.draw_bundles.iter_mut() {
+            if record.compiled.as_ref().is_some_and(|compiled| {
+                compiled.pipelines.iter().any(|key| pipelines.contains(key))
+            }) {
+                record.compiled = None;
+            }
+        }
+    }
+
+    /// Drops every recorded bundle, so each is recorded again on its next execution.
+    pub(super) fn invalidate_draw_bundles(&mut self) {
+        for (_, record) in self.draw_bundles.iter_mut() {
+            record.compiled = None;
+        }
+    }
+
+    /// Executes a draw bundle into its render target, recording it first if needed.
+    pub(super) fn encode_draw_bundle(
+        &mut self,
+        frame_context: &FrameContext<'_>,
+        encoder: &mut wgpu::CommandEncoder,
+        draw_bundle: DrawBundleId,
+        region: PassRegion,
+    ) -> bool {
+        let Some(render_target) = self
+            .draw_bundles
+            .get(draw_bundle)
+            .map(|record| record.render_target)
+        else {
+            tracing::warn!("Invalid draw bundle id ({draw_bundle:?})");
+            return false;
+        };
+
+        self.ensure_render_target_ready(frame_context, render_target);
+        if !self.ensure_draw_bundle_compiled(frame_context, draw_bundle) {
+            return false;
+        }
+
+        let Some(depth_state) = self
+            .draw_bundles
+            .get(draw_bundle)
+            .and_then(|record| record.compiled.as_ref())
+            .map(|compiled| compiled.depth_state)
+        else {
+            return false;
+        };
+        if let Some(depth_state) = depth_state {
+            self.ensure_depth_buffer_ready(frame_context, depth_state.depth_buffer);
+        }
+
+        let Some(region) = self.resolve_pass_region(frame_context, render_target, region) else {
+            return false;
+        };
+        let Some(mut render_pass) =
+            self.create_render_pass_for_draw(encoder, frame_context, render_target, depth_state)
+        else {
+            return false;
+        };
+        region.apply(&mut render_pass);
+
+        let Some(compiled) = self
+            .draw_bundles
+            .get(draw_bundle)
+            .and_then(|record| record.compiled.as_ref())
+        else {
+            return false;
+        };
+        render_pass.execute_bundles(std::iter::once(&compiled.bundle));
+        let draw_stats = compiled.draw_stats;
+        let recorded_draws = self
+            .recording_enabled()
+            .then(|| compiled.recorded_draws.clone());
+        drop(render_pass);
+
+        self.submission_stats.add_draws(&draw_stats);
+        if let Some(recorded_draws) = recorded_draws {
+            self.record_draws(|| recorded_draws);
+        }
+
+        true
+    }
+
+    fn ensure_draw_bundle_compiled(
+        &mut self,
+        frame_context: &FrameContext<'_>,
+        draw_bundle: DrawBundleId,
+    ) -> bool {
+        let Some(record) = self.draw_bundles.get(draw_bundle) else {
+            return false;
+        };
+        let Some(render_target_format) =
+            self.render_target_format(frame_context.format, record.render_target)
+        else {
+            tracing::warn!(
+                "Invalid render target for draw bundle `{}` ({:?})",
+                record.name,
+                record.render_target
+            );
+            return false;
+        };
+
+        if let Some(compiled) = record.compiled.as_ref()
+            && compiled.is_valid(self, render_target_format)
+        {
+            // Replaying the bundle uses its cached objects without looking them up again.
+            let submission = self.submission_index;
+            for key in compiled.pipelines.iter() {
+                self.cache_usages.render_pipelines.touch(*key, submission);
+                self.cache_usages
+                    .pipeline_layouts
+                    .touch(key.pipeline_layout, submission);
+            }
+            for bind_group in compiled.bind_groups.iter() {
+                self.cache_usages.bind_groups.touch(*bind_group, submission);
+            }
+            return true;
+        }
+
+        // Take the commands out of the record while compiling, since preparing draws needs
+        // mutable access to the renderer's caches.
+        let Some(record) = self.draw_bundles.get_mut(draw_bundle) else {
+            return false;
+        };
+        record.compiled = None;
+        let name = record.name.clone();
+        let render_target = record.render_target;
+        let commands = std::mem::take(&mut record.commands);
+
+        let compiled = self.compile_draw_bundle(
+            draw_bundle,
+            &name,
+            frame_context,
+            render_target,
+            render_target_format,
+            &commands,
+        );
+
+        let Some(record) = self.draw_bundles.get_mut(draw_bundle) else {
+            return false;
+        };
+        record.commands = commands;
+        record.compiled = compiled;
+        record.compiled.is_some()
+    }
+
+    fn compile_draw_bundle(
+        &mut self,
+        draw_bundle: DrawBundleId,
+        name: &str,
+        frame_context: &FrameContext<'_>,
+        render_target: RenderTarget,
+        render_target_format: wgpu::TextureFormat,
+        commands: &[FrameCommand],
+    ) -> Option<CompiledDrawBundle> {
+        tracing::debug!("Recording draw bundle `{name}`");
+
+        let mut draws = Vec::with_capacity(commands.len());
+        let mut instance_buffers = Vec::new();
+        for command in commands.iter() {
+            let draw = match command {
+                FrameCommand::Draw(draw) => PreparedBundleDraw {
+                    prepared_draw: PreparedDraw::try_new(
+                        self,
+                        frame_context.format,
+                        render_target,
+                        None,
+                        draw.material,
+                        None,
+                    )?,
+                    mesh: None,
+                    vertex_count: draw.vertex_count,
+                    instance_buffer: None,
+                    instance_count: 1,
+                    indirect: None,
+                    push_constants: draw.push_constants.clone(),
+                },
+                FrameCommand::DrawMesh(draw) => PreparedBundleDraw {
+                    prepared_draw: PreparedDraw::try_new(
+                        self,
+                        frame_context.format,
+                        render_target,
+                        Some(draw.mesh),
+                        draw.material,
+                        None,
+                    )?,
+                    mesh: Some(draw.mesh),
+                    vertex_count: 0,
+                    instance_buffer: None,
+                    instance_count: 1,
+                    indirect: None,
+                    push_constants: draw.push_constants.clone(),
+                },
+                FrameCommand::DrawMeshInstanced(draw) => {
+                    let prepared_draw = PreparedDraw::try_new(
+                        self,
+                        frame_context.format,
+                        render_target,
+                        Some(draw.mesh),
+                        draw.material,
+                        Some(draw.instance_buffer_layout.clone()),
+                    )?;
+                    self.submission_stats.bytes_uploaded += draw.instance_data.len() as u64;
+                    instance_buffers.push(self.device.create_buffer_init(
+                        &wgpu::util::BufferInitDescriptor {
+                            label: Some(&format!("{name}_instance_buffer")),
+                            contents: draw.instance_data.as_slice(),
+                            usage: wgpu::BufferUsages::VERTEX,
+                        },
+                    ));
+                    PreparedBundleDraw {
+                        prepared_draw,
+                        mesh: Some(draw.mesh),
+                        vertex_count: 0,
+                        instance_buffer: Some(instance_buffers.len() - 1),
+                        instance_count: draw.instance_count,
+                        indirect: None,
+                        push_constants: None,
+                    }
+                }
+                FrameCommand::DrawIndirect(draw) => PreparedBundleDraw {
+                    prepared_draw: PreparedDraw::try_new(
+                        self,
+                        frame_context.format,
+                        render_target,
+                        None,
+                        draw.material,
+                        None,
+                    )?,
+                    mesh: None,
+                    vertex_count: 0,
+                    instance_buffer: None,
+                    instance_count: 0,
+                    indirect: Some(draw.indirect),
+                    push_constants: None,
+                },
+                FrameCommand::DrawMeshIndirect(draw) => PreparedBundleDraw {
+                    prepared_draw: PreparedDraw::try_new(
+                        self,
+                        frame_context.format,
+                        render_target,
+                        Some(draw.mesh),
+                        draw.material,
+                        None,
+                    )?,
+                    mesh: Some(draw.mesh),
+                    vertex_count: 0,
+                    instance_buffer: None,
+                    instance_count: 0,
+                    indirect: Some(draw.indirect),
+                    push_constants: None,
+                },
+                _ => return None,
+            };
+            draws.push(draw);
+        }
+
+        // A bundle executes inside a single render pass, so every draw has to agree on the
+        // attached depth buffer.
+        let depth_state = draws.first()?.prepared_draw.depth_state;
+        let depth_buffer: Option<DepthBufferId> = depth_state.map(|state| state.depth_buffer);
+        if draws.iter().any(|draw| {
+            draw.prepared_draw
+                .depth_state
+                .map(|state| state.depth_buffer)
+                != depth_buffer
+        }) {
+            tracing::warn!(
+                "Could not record draw bundle `{name}`: all materials must use the same depth \
+                 buffer."
+            );
+            return None;
+        }
+
+        let push_constants = PushConstantBuffer::new_owned(
+            self,
+            &format!("{name}_push_constants"),
+            draws
+                .iter()
+                .map(|draw| (&draw.prepared_draw, draw.push_constants.as_deref())),
+        );
+
+        let mut bundle_encoder =
+            self.device
+                .create_render_bundle_encoder(&wgpu::RenderBundleEncoderDescriptor {
+                    label: Some(name),
+                    color_formats: &[Some(render_target_format)],
+                    depth_stencil: depth_state.map(|_| wgpu::RenderBundleDepthStencil {
+                        format: crate::depth_buffer::DepthBufferRecord::FORMAT,
+                        depth_read_only: false,
+                        stencil_read_only: true,
+                    }),
+                    sample_count: 1,
+                    multiview: None,
+                });
+
+        let mut draw_encoder = DrawEncoder::recording(self);
+        for (index, (draw, command)) in draws.iter().zip(commands).enumerate() {
+            let encoded = draw_encoder.encode(
+                &mut bundle_encoder,
+                &DrawCall {
+                    prepared_draw: &draw.prepared_draw,
+                    material: command.material()?,
+                    mesh: draw.mesh,
+                    vertex_count: draw.vertex_count,
+                    instance_count: draw.instance_count,
+                    instance_buffer: draw
+                        .instance_buffer
+                        .map(|instance_buffer| &instance_buffers[instance_buffer]),
+                    indirect: draw.indirect,
+                    push_constants: DrawPushConstants {
+                        data: draw.push_constants.as_deref(),
+                        packed: push_constants.as_ref().map(|packed| (packed, index)),
+                    },
+                    label: command.label(),
+                },
+            );
+            if !encoded {
+                return None;
+            }
+        }
+        let EncodedDraws {
+            stats: draw_stats,
+            recorded_draws,
+        } = draw_encoder.finish();
+        let recorded_draws = recorded_draws
+            .unwrap_or_default()
+            .into_iter()
+            .map(|recorded_draw| RecordedDraw {
+                draw_bundle: Some(draw_bundle),
+                ..recorded_draw
+            })
+            .collect();
+
+        let bundle = bundle_encoder.finish(&wgpu::RenderBundleDescriptor { label: Some(name) });
+
+        let pipelines: HashSet<RenderPipelineKey> =
+            draws.iter().map(|draw| draw.prepared_draw.key).collect();
+        let mut bind_groups: Vec<Id> = draws
+            .iter()
+            .flat_map(|draw| {
+                draw.prepared_draw
+                    .bind_groups_to_set
+                    .iter()
+                    .map(|bind_group| bind_group.bind_group)
+            })
+            .collect();
+        bind_groups.sort();
+        bind_groups.dedup();
+        let mut meshes: Vec<MeshId> = draws.iter().filter_map(|draw| draw.mesh).collect();
+        meshes.sort();
+        meshes.dedup();
+
+        let mut indirect_buffers: Vec<(StorageBufferId, u64)> = Vec::new();
+        for indirect in draws.iter().filter_map(|draw| draw.indirect) {
+            if let Some(record) = self.storage_buffers.get(indirect.buffer) {
+                indirect_buffers.push((indirect.buffer, record.byte_len));
+            }
+        }
+        indirect_buffers.sort();
+        indirect_buffers.dedup();
+
+        Some(CompiledDrawBundle {
+            bundle,
+            render_target_format,
+            depth_state,
+            pipelines,
+            bind_groups,
+            meshes,
+            indirect_buffers,
+            _instance_buffers: instance_buffers,
+            _push_constants: push_constants,
+            draw_stats,
+            recorded_draws,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::Scene;
+
+    #[test]
+    fn bundles_track_each_pipeline_once() {
+        let mut scene = Scene::new();
+        let first = scene.fill_material("first");
+        let second = scene.fill_material("second");
+
+        let mut bundle_list = DrawList::new();
+        bundle_list.draw(RenderTarget::Surface, first, 3);
+        bundle_list.draw(RenderTarget::Surface, second, 3);
+        bundle_list.draw(RenderTarget::Surface, first, 3);
+        let bundle = scene
+            .renderer
+            .create_draw_bundle("bundle", bundle_list)
+            .unwrap();
+
+        let mut draw_list = DrawList::new();
+        draw_list.execute_draw_bundle(bundle);
+        assert_eq!(scene.submit(&draw_list).draws().count(), 3);
+
+        let record = scene.renderer.draw_bundles.get(bundle).unwrap();
+        assert_eq!(record.compiled.as_ref().unwrap().pipelines.len(), 2);
+    }
+}
@@ -0,0 +1,267 @@
+use wgpu::util::RenderEncoder;
+
+use crate::{
+    DrawListRenderer, MaterialId, MeshId, RenderPipelineKey,
+    commands::IndirectArgs,
+    common::Id,
+    prepared_draw::PreparedDraw,
+    push_constants::{DrawPushConstants, ResolvedPushConstants, set_push_constants},
+    recording::RecordedDraw,
+    stats::SubmissionStats,
+};
+
+/// A render pass or render bundle encoder that draws can be encoded into.
+///
+/// Render bundles have no multi-draw and no debug groups, so the defaults issue indirect draws
+/// one at a time and drop labels; render passes override both.
+pub(super) trait DrawTarget<'a>: RenderEncoder<'a> {
+    /// Issues every draw of `indirect` from `buffer`.
+    fn draw_indirect_args(
+        &mut self,
+        buffer: &'a wgpu::Buffer,
+        indirect: IndirectArgs,
+        indexed: bool,
+    ) {
+        let stride = IndirectArgs::stride(indexed);
+        for draw in 0..u64::from(indirect.draw_count) {
+            let offset = indirect.offset + draw * stride;
+            if indexed {
+                self.draw_indexed_indirect(buffer, offset);
+            } else {
+                self.draw_indirect(buffer, offset);
+            }
+        }
+    }
+
+    /// Opens a debug group named after a draw's label.
+    fn push_draw_label(&mut self, _label: &str) {}
+
+    /// Closes the debug group opened by [`DrawTarget::push_draw_label`].
+    fn pop_draw_label(&mut self) {}
+}
+
+impl<'a> DrawTarget<'a> for wgpu::RenderPass<'a> {
+    fn draw_indirect_args(
+        &mut self,
+        buffer: &'a wgpu::Buffer,
+        indirect: IndirectArgs,
+        indexed: bool,
+    ) {
+        match (indexed, indirect.draw_count) {
+            (false, 1) => self.draw_indirect(buffer, indirect.offset),
+            (true, 1) => self.draw_indexed_indirect(buffer, indirect.offset),
+            (false, count) => self.multi_draw_indirect(buffer, indirect.offset, count),
+            (true, count) => self.multi_draw_indexed_indirect(buffer, indirect.offset, count),
+        }
+    }
+
+    fn push_draw_label(&mut self, label: &str) {
+        self.push_debug_group(label);
+    }
+
+    fn pop_draw_label(&mut self) {
+        self.pop_debug_group();
+    }
+}
+
+impl<'a> DrawTarget<'a> for wgpu::RenderBundleEncoder<'a> {}
+
+/// One draw, after its pipeline and bind groups were resolved.
+pub(super) struct DrawCall<'a> {
+    pub prepared_draw: &'a PreparedDraw,
+    pub material: MaterialId,
+    pub mesh: Option<MeshId>,
+    /// Vertices drawn by non-indexed direct draws; indexed draws draw the whole mesh.
+    pub vertex_count: u32,
+    pub instance_count: u32,
+    /// Per-instance vertex buffer bound at slot 1.
+    pub instance_buffer: Option<&'a wgpu::Buffer>,
+    pub indirect: Option<IndirectArgs>,
+    pub push_constants: DrawPushConstants<'a>,
+    /// Name of the draw in graphics debuggers.
+    pub label: Option<&'a str>,
+}
+
+/// Draw counters and recorded draws gathered by a [`DrawEncoder`].
+#[derive(Default)]
+pub(super) struct EncodedDraws {
+    pub stats: SubmissionStats,
+    /// The encoded draws, when the renderer is recording submissions.
+    pub recorded_draws: Option<Vec<RecordedDraw>>,
+}
+
+/// Encodes draws into a render pass or render bundle.
+///
+/// Pipeline, bind group, and mesh buffer changes are only issued when they differ from the
+/// previous draw encoded by the same encoder, so one encoder is used per render pass or bundle.
+/// The encoder only borrows the renderer immutably, so the counters it gathers are added to the
+/// submission with [`DrawListRenderer::add_encoded_draws`] once the pass has ended.
+pub(super) struct DrawEncoder<'a> {
+    renderer: &'a DrawListRenderer,
+    pipeline: Option<RenderPipelineKey>,
+    bind_groups: Vec<Option<Id>>,
+    mesh: Option<MeshId>,
+    encoded: EncodedDraws,
+}
+
+impl<'a> DrawEncoder<'a> {
+    pub(super) fn new(renderer: &'a DrawListRenderer) -> Self {
+        Self {
+            renderer,
+            pipeline: None,
+            bind_groups: Vec::new(),
+            mesh: None,
+            encoded: EncodedDraws {
+                stats: SubmissionStats::default(),
+                recorded_draws: renderer.recording_enabled().then(Vec::new),
+            },
+        }
+    }
+
+    /// Creates an encoder that records its draws even while the renderer is not recording, for
+    /// draw bundles that keep their draws to record on every execution.
+    pub(super) fn recording(renderer: &'a DrawListRenderer) -> Self {
+        let mut draw_encoder = Self::new(renderer);
+        draw_encoder.encoded.recorded_draws = Some(Vec::new());
+        draw_encoder
+    }
+
+    pub(super) fn finish(self) -> EncodedDraws {
+        self.encoded
+    }
+
+    /// Binds the state of `draw` and issues it.
+    ///
+    /// Returns `false` and logs a warning when a resource of the draw is invalid, in which case
+    /// nothing is drawn.
+    pub(super) fn encode(&mut self, target: &mut impl DrawTarget<'a>, draw: &DrawCall<'a>) -> bool {
+        let renderer = self.renderer;
+
+        let indirect = match draw.indirect {
+            Some(indirect) => match indirect.resolve(renderer, draw.mesh.is_some()) {
+                Some(buffer) => Some((indirect, buffer)),
+                None => return false,
+            },
+            None => None,
+        };
+
+        let mesh = match draw.mesh {
+            Some(mesh_id) => match renderer.meshes.get(mesh_id) {
+                Some(mesh) => Some((mesh_id, mesh)),
+                None => {
+                    tracing::warn!("Invalid mesh id ({:?})", mesh_id);
+                    return false;
+                }
+            },
+            None => None,
+        };
+
+        let prepared_draw = draw.prepared_draw;
+        if self.pipeline != Some(prepared_draw.key) {
+            target.set_pipeline(&renderer.render_pipeline_cache[&prepared_draw.key]);
+            self.encoded.stats.pipeline_switches += 1;
+            self.pipeline = Some(prepared_draw.key);
+            // Bind groups stay bound across compatible pipeline changes, but a different pipeline
+            // layout may not be compatible, so bind them again.
+            self.bind_groups.clear();
+        }
+
+        for bind_group in prepared_draw.bind_groups_to_set.iter() {
+            let slot = bind_group.slot as usize;
+            if self.bind_groups.get(slot).copied().flatten() == Some(bind_group.bind_group) {
+                continue;
+            }
+
+            let Some(bind_group_record) = renderer.bind_groups.get(bind_group.bind_group) else {
+                tracing::warn!("Invalid bind group id ({:?})", bind_group.bind_group);
+                return false;
+            };
+            target.set_bind_group(bind_group.slot, Some(&bind_group_record.bind_group), &[]);
+            self.encoded.stats.bind_group_switches += 1;
+            if self.bind_groups.len() <= slot {
+                self.bind_groups.resize(slot + 1, None);
+            }
+            self.bind_groups[slot] = Some(bind_group.bind_group);
+        }
+
+        if !set_push_constants(target, prepared_draw, draw.push_constants) {
+            return false;
+        }
+        // Emulated push constants replace whatever was bound at their group.
+        if let Some(ResolvedPushConstants::Emulated { slot, .. }) = prepared_draw.push_constants {
+            self.encoded.stats.bind_group_switches += 1;
+            if let Some(bind_group) = self.bind_groups.get_mut(slot as usize) {
+                *bind_group = None;
+            }
+        }
+
+        if let Some((mesh_id, mesh)) = mesh
+            && self.mesh != Some(mesh_id)
+        {
+            target.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+            target.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            self.mesh = Some(mesh_id);
+        }
+        if let Some(instance_buffer) = draw.instance_buffer {
+            target.set_vertex_buffer(1, instance_buffer.slice(..));
+        }
+
+        if let Some(label) = draw.label {
+            target.push_draw_label(label);
+        }
+        let elements = match (indirect, mesh) {
+            (Some((indirect, buffer)), _) => {
+                target.draw_indirect_args(buffer, indirect, mesh.is_some());
+                None
+            }
+            (None, Some((_, mesh))) => {
+                target.draw_indexed(0..mesh.index_count, 0, 0..draw.instance_count);
+                Some(mesh.index_count)
+            }
+            (None, None) => {
+                target.draw(0..draw.vertex_count, 0..draw.instance_count);
+                Some(draw.vertex_count)
+            }
+        };
+        if draw.label.is_some() {
+            target.pop_draw_label();
+        }
+
+        match indirect {
+            Some((indirect, _)) => self
+                .encoded
+                .stats
+                .record_draws(indirect.draw_count, None, 0),
+            None => self
+                .encoded
+                .stats
+                .record_draws(1, elements, draw.instance_count),
+        }
+        if let Some(recorded_draws) = self.encoded.recorded_draws.as_mut() {
+            recorded_draws.push(match (indirect, elements) {
+                (Some((indirect, _)), _) => {
+                    RecordedDraw::indirect(draw.material, draw.mesh, indirect, draw.label)
+                }
+                (None, elements) => RecordedDraw::direct(
+                    draw.material,
+                    draw.mesh,
+                    elements.unwrap_or_default(),
+                    draw.instance_count,
+                    draw.label,
+                ),
+            });
+        }
+
+        true
+    }
+}
+
+impl DrawListRenderer {
+    /// Adds the counters and recorded draws of a finished [`DrawEncoder`] to the submission.
+    pub(super) fn add_encoded_draws(&mut self, encoded: EncodedDraws) {
+        self.submission_stats.add_draws(&encoded.stats);
+        if let Some(recorded_draws) = encoded.recorded_draws {
+            self.record_draws(|| recorded_draws);
+        }
+    }
+}
@@ -1,11 +1,13 @@
-use glam::UVec2;
+use glam::{UVec2, UVec3, Vec2};
 
 use crate::{
-    AsStorageBufferElement, AsUniformBuffer, DepthBufferId, MaterialId, MeshId, RenderTargetId,
-    StorageBufferId, TextureId, UniformId,
+    AsPushConstants, AsStorageBufferElement, AsUniformBuffer, ComputeMaterialId, DepthBufferId,
+    DrawBundleId, MaterialId, MeshId, RenderTargetId, StorageBufferId, TextureId, UniformId,
     commands::{
-        ClearDepthBuffer, Draw, DrawMesh, DrawMeshInstanced, FrameCommand, ResizeDepthBuffer,
-        ResizeRenderTarget, UpdateStorageBuffer, UpdateTextureRegion, UpdateUniform,
+        ClearDepthBuffer, Dispatch, Draw, DrawIndirect, DrawMesh, DrawMeshIndirect,
+        DrawMeshInstanced, ExecuteDrawBundle, FrameCommand, IndirectArgs, PassRegion,
+        ResizeDepthBuffer, ResizeRenderTarget, UpdateStorageBuffer, UpdateTextureRegion,
+        UpdateUniform,
     },
     encode_storage_buffer_elements,
     mesh::AsInstanceBufferLayout,
@@ -20,12 +22,89 @@ pub enum RenderTarget {
     Custom(RenderTargetId),
 }
 
+/// Region of a render target that draws are mapped into, in pixels.
+///
+/// See [`DrawList::set_viewport`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Viewport {
+    /// Top-left corner of the viewport.
+    pub origin: Vec2,
+    /// Width and height of the viewport.
+    pub size: Vec2,
+    /// Depth value that normalized device depth 0.0 maps to.
+    pub min_depth: f32,
+    /// Depth value that normalized device depth 1.0 maps to.
+    pub max_depth: f32,
+}
+
+impl Viewport {
+    /// Creates a viewport covering the full depth range.
+    pub fn new(origin: Vec2, size: Vec2) -> Self {
+        Self {
+            origin,
+            size,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        }
+    }
+}
+
+/// Region of a render target outside of which fragments are discarded, in pixels.
+///
+/// See [`DrawList::set_scissor_rect`].
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub struct ScissorRect {
+    /// Top-left corner of the rectangle.
+    pub origin: UVec2,
+    /// Width and height of the rectangle.
+    pub size: UVec2,
+}
+
+impl ScissorRect {
+    /// Creates a scissor rectangle.
+    pub fn new(origin: UVec2, size: UVec2) -> Self {
+        Self { origin, size }
+    }
+}
+
+/// Ordering applied to the draws inside a sorted range of a draw list.
+///
+/// See [`DrawList::begin_sorted`].
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub enum DrawSortMode {
+    /// Draws execute in the order they were recorded.
+    #[default]
+    None,
+    /// Nearest draws first by sort depth, then grouped by pipeline and bind groups. Intended for
+    /// opaque geometry, where drawing front-to-back lets the depth test reject hidden fragments
+    /// early.
+    FrontToBack,
+    /// Farthest draws first by sort depth, then grouped by pipeline and bind groups. Intended
+    /// for blended geometry that must be composited back-to-front.
+    BackToFront,
+    /// Grouped by pipeline, bind groups, and mesh to minimize state changes.
+    State,
+}
+
 /// Recorded draw and upload commands for a single submission.
+///
+/// Draw lists own all of their data and are `Send`, so separate lists can be recorded on worker
+/// threads and combined with [`DrawList::append`] or [`DrawList::merge_ordered`] before
+/// submission.
 #[derive(Default)]
 pub struct DrawList {
     pub(super) commands: Vec<FrameCommand>,
+    sort_depth: f32,
+    region: PassRegion,
+    draw_label: Option<String>,
 }
 
+// Draw lists are recorded on worker threads, so keep them `Send` and `Sync`.
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<DrawList>();
+};
+
 impl DrawList {
     /// Creates an empty draw list.
     ///
@@ -35,7 +114,92 @@ impl DrawList {
         Self::default()
     }
 
+    /// Merges sub-lists into a single draw list, ordered by their keys.
+    ///
+    /// Sub-lists are sorted by key with a stable sort and appended in that order, so the result
+    /// is deterministic no matter which order the sub-lists finished recording in. Sub-lists with
+    /// equal keys keep the order they were provided in.
+    ///
+    /// ```ignore
+    /// let sub_lists: Vec<(u32, DrawList)> = chunks
+    ///     .par_iter()
+    ///     .map(|chunk| (chunk.index, chunk.record_draw_list()))
+    ///     .collect();
+    /// let draw_list = DrawList::merge_ordered(sub_lists);
+    /// ```
+    pub fn merge_ordered<K: Ord>(sub_lists: impl IntoIterator<Item = (K, DrawList)>) -> Self {
+        let mut sub_lists: Vec<(K, DrawList)> = sub_lists.into_iter().collect();
+        sub_lists.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let mut draw_list = Self::new();
+        draw_list.extend(sub_lists.into_iter().map(|(_, sub_list)| sub_list));
+        draw_list
+    }
+
+    /// Returns `true` if no commands have been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Moves all commands from `other` to the end of this list, leaving `other` empty.
+    ///
+    /// Sorted ranges never span the boundary between the lists:
+    ///
+    /// - A sorted range open in this list is ended before the commands of `other`, so they keep
+    ///   the order `other` recorded them in, and begun again with the same mode after them, so
+    ///   draws recorded into this list afterwards are still sorted.
+    /// - A sorted range left open at the end of `other` is closed, so it does not extend over
+    ///   commands recorded into this list afterwards.
+    ///
+    /// The sort depth of this list is unchanged, and so is its profile label.
+    pub fn append(&mut self, other: &mut DrawList) {
+        let sets_profile_label = Self::last_profile_label(&other.commands).is_some();
+        let profile_label = Self::last_profile_label(&self.commands).flatten().cloned();
+
+        let open_sort_mode = Self::open_sort_mode(&self.commands);
+        if open_sort_mode.is_some() {
+            self.commands.push(FrameCommand::EndSort);
+        }
+
+        let other_sort_mode = Self::open_sort_mode(&other.commands);
+        self.commands.append(&mut other.commands);
+        if other_sort_mode.is_some() {
+            self.commands.push(FrameCommand::EndSort);
+        }
+        if sets_profile_label {
+            self.commands
+                .push(FrameCommand::SetProfileLabel(profile_label));
+        }
+        if let Some(mode) = open_sort_mode {
+            self.commands.push(FrameCommand::BeginSort(mode));
+        }
+    }
+
+    /// Returns the mode of the sorted range left open at the end of `commands`, if there is one.
+    fn open_sort_mode(commands: &[FrameCommand]) -> Option<DrawSortMode> {
+        commands
+            .iter()
+            .rev()
+            .find_map(|command| match command {
+                FrameCommand::BeginSort(mode) => Some(Some(*mode)),
+                FrameCommand::EndSort => Some(None),
+                _ => None,
+            })
+            .flatten()
+    }
+
+    /// Returns the label set by the last profile label command, if there is one.
+    fn last_profile_label(commands: &[FrameCommand]) -> Option<Option<&String>> {
+        commands.iter().rev().find_map(|command| match command {
+            FrameCommand::SetProfileLabel(label) => Some(label.as_ref()),
+            _ => None,
+        })
+    }
+
     /// Queues an update for a previously created uniform.
+    ///
+    /// Draws recorded after this call see the new value; draws recorded before it keep the
+    /// previous one.
     pub fn update_uniform<T: AsUniformBuffer>(&mut self, uniform: UniformId, data: &T) {
         let encoded = match data.encode_bytes() {
             Ok(encoded) => encoded,
@@ -90,6 +254,8 @@ impl DrawList {
     }
 
     /// Queues an update of a region of the specifed texture.
+    ///
+    /// Only the first mip level is updated, and block-compressed textures cannot be updated.
     pub fn update_texture_region(
         &mut self,
         texture: TextureId,
@@ -144,15 +310,81 @@ impl DrawList {
 
     /// Queues an indexed draw using the provided mesh and material.
     pub fn draw_mesh(&mut self, render_target: RenderTarget, mesh: MeshId, material: MaterialId) {
+        self.push_draw_mesh(render_target, mesh, material, None);
+    }
+
+    /// Queues an indexed draw, passing `push_constants` inline to the material's shaders.
+    ///
+    /// The material must declare push constants of type `T` with
+    /// [`Material::push_constants`](crate::Material::push_constants).
+    pub fn draw_mesh_with_push_constants<T: AsPushConstants>(
+        &mut self,
+        render_target: RenderTarget,
+        mesh: MeshId,
+        material: MaterialId,
+        push_constants: &T,
+    ) {
+        let encoded = match push_constants.encode_bytes() {
+            Ok(encoded) => encoded,
+            Err(error) => {
+                tracing::warn!("Could not encode push constants for draw on {mesh:?}: {error}");
+                return;
+            }
+        };
+        self.push_draw_mesh(render_target, mesh, material, Some(encoded));
+    }
+
+    fn push_draw_mesh(
+        &mut self,
+        render_target: RenderTarget,
+        mesh: MeshId,
+        material: MaterialId,
+        push_constants: Option<Vec<u8>>,
+    ) {
         self.commands.push(FrameCommand::DrawMesh(DrawMesh {
             render_target,
             mesh,
             material,
+            push_constants,
+            sort_depth: self.sort_depth,
+            region: self.region,
+            label: self.draw_label.clone(),
         }));
     }
 
     /// Queues a non-indexed draw using only the material pipeline.
     pub fn draw(&mut self, render_target: RenderTarget, material: MaterialId, vertex_count: u32) {
+        self.push_draw(render_target, material, vertex_count, None);
+    }
+
+    /// Queues a non-indexed draw, passing `push_constants` inline to the material's shaders.
+    ///
+    /// The material must declare push constants of type `T` with
+    /// [`Material::push_constants`](crate::Material::push_constants).
+    pub fn draw_with_push_constants<T: AsPushConstants>(
+        &mut self,
+        render_target: RenderTarget,
+        material: MaterialId,
+        vertex_count: u32,
+        push_constants: &T,
+    ) {
+        let encoded = match push_constants.encode_bytes() {
+            Ok(encoded) => encoded,
+            Err(error) => {
+                tracing::warn!("Could not encode push constants for draw of {material:?}: {error}");
+                return;
+            }
+        };
+        self.push_draw(render_target, material, vertex_count, Some(encoded));
+    }
+
+    fn push_draw(
+        &mut self,
+        render_target: RenderTarget,
+        material: MaterialId,
+        vertex_count: u32,
+        push_constants: Option<Vec<u8>>,
+    ) {
         if vertex_count == 0 {
             return;
         }
@@ -161,6 +393,10 @@ impl DrawList {
             render_target,
             material,
             vertex_count,
+            push_constants,
+            sort_depth: self.sort_depth,
+            region: self.region,
+            label: self.draw_label.clone(),
         }));
     }
 
@@ -192,6 +428,336 @@ impl DrawList {
                     }
                 },
                 instance_count: instances.len() as u32,
+                sort_depth: self.sort_depth,
+                region: self.region,
+                label: self.draw_label.clone(),
             }));
     }
+
+    /// Queues a non-indexed draw whose arguments are read from `indirect_buffer` at `offset`.
+    ///
+    /// The buffer must be created with
+    /// [`DrawListRenderer::create_indirect_storage_buffer`](crate::DrawListRenderer::create_indirect_storage_buffer)
+    /// and hold a `wgpu::util::DrawIndirectArgs` at `offset`, which must be a multiple of 4.
+    /// Because the arguments are read on the GPU, they can be written by a compute pass without a
+    /// CPU round-trip.
+    pub fn draw_indirect(
+        &mut self,
+        render_target: RenderTarget,
+        material: MaterialId,
+        indirect_buffer: StorageBufferId,
+        offset: u64,
+    ) {
+        self.multi_draw_indirect(render_target, material, indirect_buffer, offset, 1);
+    }
+
+    /// Queues `draw_count` non-indexed draws whose arguments are tightly packed
+    /// `wgpu::util::DrawIndirectArgs` in `indirect_buffer`, starting at `offset`.
+    pub fn multi_draw_indirect(
+        &mut self,
+        render_target: RenderTarget,
+        material: MaterialId,
+        indirect_buffer: StorageBufferId,
+        offset: u64,
+        draw_count: u32,
+    ) {
+        if draw_count == 0 {
+            return;
+        }
+
+        self.commands.push(FrameCommand::DrawIndirect(DrawIndirect {
+            render_target,
+            material,
+            indirect: IndirectArgs {
+                buffer: indirect_buffer,
+                offset,
+                draw_count,
+            },
+            sort_depth: self.sort_depth,
+            region: self.region,
+            label: self.draw_label.clone(),
+        }));
+    }
+
+    /// Queues an indexed draw of `mesh` whose arguments are read from `indirect_buffer` at
+    /// `offset`.
+    ///
+    /// The buffer must be created with
+    /// [`DrawListRenderer::create_indirect_storage_buffer`](crate::DrawListRenderer::create_indirect_storage_buffer)
+    /// and hold a `wgpu::util::DrawIndexedIndirectArgs` at `offset`, which must be a multiple
+    /// of 4.
+    pub fn draw_indexed_indirect(
+        &mut self,
+        render_target: RenderTarget,
+        mesh: MeshId,
+        material: MaterialId,
+        indirect_buffer: StorageBufferId,
+        offset: u64,
+    ) {
+        self.multi_draw_indexed_indirect(render_target, mesh, material, indirect_buffer, offset, 1);
+    }
+
+    /// Queues `draw_count` indexed draws of `mesh` whose arguments are tightly packed
+    /// `wgpu::util::DrawIndexedIndirectArgs` in `indirect_buffer`, starting at `offset`.
+    pub fn multi_draw_indexed_indirect(
+        &mut self,
+        render_target: RenderTarget,
+        mesh: MeshId,
+        material: MaterialId,
+        indirect_buffer: StorageBufferId,
+        offset: u64,
+        draw_count: u32,
+    ) {
+        if draw_count == 0 {
+            return;
+        }
+
+        self.commands
+            .push(FrameCommand::DrawMeshIndirect(DrawMeshIndirect {
+                render_target,
+                mesh,
+                material,
+                indirect: IndirectArgs {
+                    buffer: indirect_buffer,
+                    offset,
+                    draw_count,
+                },
+                sort_depth: self.sort_depth,
+                region: self.region,
+                label: self.draw_label.clone(),
+            }));
+    }
+
+    /// Queues execution of a draw bundle created with
+    /// [`DrawListRenderer::create_draw_bundle`](crate::DrawListRenderer::create_draw_bundle).
+    ///
+    /// The bundle draws into the render target it was recorded for, in order with the other
+    /// commands in this list, using the viewport and scissor rectangle set on this list.
+    pub fn execute_draw_bundle(&mut self, draw_bundle: DrawBundleId) {
+        self.commands
+            .push(FrameCommand::ExecuteDrawBundle(ExecuteDrawBundle {
+                draw_bundle,
+                region: self.region,
+            }));
+    }
+
+    /// Queues a compute pass that dispatches `workgroup_count` workgroups of a compute material
+    /// created with
+    /// [`DrawListRenderer::create_compute_material`](crate::DrawListRenderer::create_compute_material).
+    ///
+    /// The dispatch runs in order with the other commands in this list, so draws recorded after
+    /// it see what it wrote to read-write storage buffers, including the arguments of indirect
+    /// draws. The compute pass is labeled with the current draw label.
+    pub fn dispatch(&mut self, compute_material: ComputeMaterialId, workgroup_count: UVec3) {
+        self.commands.push(FrameCommand::Dispatch(Dispatch {
+            compute_material,
+            workgroup_count,
+            label: self.draw_label.clone(),
+        }));
+    }
+
+    /// Starts a sorted range of draws.
+    ///
+    /// Draws recorded until [`DrawList::end_sorted`] are reordered according to `mode` before
+    /// they execute. Only consecutive draws that share a render target and depth buffer are
+    /// reordered among each other; a draw into a different target starts a new run, so draws
+    /// never move past a change of target. Each run is encoded into a single render pass,
+    /// skipping redundant pipeline and bind group changes.
+    ///
+    /// Non-draw commands recorded inside the range (updates, clears, resizes, draw bundles,
+    /// dispatches) act as barriers: draws before them are sorted and executed first, and draws
+    /// after them form a new sorted group, so updates keep their position relative to the draws
+    /// around them.
+    pub fn begin_sorted(&mut self, mode: DrawSortMode) {
+        self.commands.push(FrameCommand::BeginSort(mode));
+    }
+
+    /// Ends a sorted range started with [`DrawList::begin_sorted`].
+    pub fn end_sorted(&mut self) {
+        self.commands.push(FrameCommand::EndSort);
+    }
+
+    /// Opens a debug group named `label`, which groups the commands recorded until the matching
+    /// [`DrawList::pop_debug_group`] in graphics debuggers such as RenderDoc.
+    ///
+    /// Groups still open when the list finishes executing are closed with a warning. Inside a
+    /// sorted range, debug group commands act as barriers like other non-draw commands.
+    pub fn push_debug_group(&mut self, label: &str) {
+        self.commands
+            .push(FrameCommand::PushDebugGroup(label.to_string()));
+    }
+
+    /// Closes the debug group opened by the last unmatched [`DrawList::push_debug_group`].
+    pub fn pop_debug_group(&mut self) {
+        self.commands.push(FrameCommand::PopDebugGroup);
+    }
+
+    /// Inserts a marker named `label` between the surrounding commands in graphics debuggers.
+    pub fn insert_debug_marker(&mut self, label: &str) {
+        self.commands
+            .push(FrameCommand::InsertDebugMarker(label.to_string()));
+    }
+
+    /// Sets the label of subsequently recorded draws, or `None` to stop labeling them.
+    ///
+    /// Each labeled draw is wrapped in a debug group with the label inside its render pass, so
+    /// it can be told apart in graphics debuggers. Render bundles cannot record debug groups, so
+    /// labels of draws compiled into a draw bundle are ignored; the bundle is labeled with its
+    /// name instead.
+    pub fn set_draw_label(&mut self, label: Option<&str>) {
+        self.draw_label = label.map(str::to_string);
+    }
+
+    /// Sets the label that subsequently recorded commands are reported under when profiling, or
+    /// `None` to stop labeling them.
+    ///
+    /// Changing the label inside a sorted range does not split it; the range is reported under
+    /// the label of its first draw. See [`crate::profiling`].
+    pub fn set_profile_label(&mut self, label: Option<&str>) {
+        self.commands
+            .push(FrameCommand::SetProfileLabel(label.map(str::to_string)));
+    }
+
+    /// Sets the viewport used by subsequently recorded draws, or `None` to cover the whole
+    /// render target.
+    ///
+    /// The viewport must lie within the render target when the draw executes; draws with a
+    /// viewport outside of their render target are skipped with a warning. This allows rendering
+    /// split-screen views or minimaps into the surface without extra render targets.
+    pub fn set_viewport(&mut self, viewport: Option<Viewport>) {
+        self.region.viewport = viewport;
+    }
+
+    /// Sets the scissor rectangle used by subsequently recorded draws, or `None` to disable
+    /// scissoring.
+    ///
+    /// The rectangle must lie within the render target when the draw executes; draws with an
+    /// empty scissor rectangle, or one outside of their render target, are skipped with a
+    /// warning.
+    pub fn set_scissor_rect(&mut self, scissor_rect: Option<ScissorRect>) {
+        self.region.scissor_rect = scissor_rect;
+    }
+
+    /// Sets the depth key attached to subsequently recorded draws.
+    ///
+    /// The key is only used by [`DrawSortMode::FrontToBack`] and [`DrawSortMode::BackToFront`];
+    /// smaller values are nearer to the viewer. Typically this is the view-space distance of the
+    /// object being drawn.
+    pub fn set_sort_depth(&mut self, depth: f32) {
+        self.sort_depth = depth;
+    }
+}
+
+impl Extend<DrawList> for DrawList {
+    /// Appends each draw list in order, as with [`DrawList::append`].
+    fn extend<T: IntoIterator<Item = DrawList>>(&mut self, iter: T) {
+        for mut draw_list in iter {
+            self.append(&mut draw_list);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Describes the sort and marker commands of a list, which is all these tests record.
+    fn describe(draw_list: &DrawList) -> Vec<String> {
+        draw_list
+            .commands
+            .iter()
+            .map(|command| match command {
+                FrameCommand::BeginSort(mode) => format!("begin {mode:?}"),
+                FrameCommand::EndSort => "end".to_string(),
+                FrameCommand::InsertDebugMarker(label) => label.clone(),
+                _ => "other".to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn append_ends_and_resumes_an_open_sorted_range() {
+        let mut draw_list = DrawList::new();
+        draw_list.begin_sorted(DrawSortMode::FrontToBack);
+        draw_list.insert_debug_marker("a");
+
+        let mut other = DrawList::new();
+        other.insert_debug_marker("b");
+
+        draw_list.append(&mut other);
+        draw_list.insert_debug_marker("c");
+        draw_list.end_sorted();
+
+        assert!(other.is_empty());
+        assert_eq!(
+            describe(&draw_list),
+            [
+                "begin FrontToBack",
+                "a",
+                "end",
+                "b",
+                "begin FrontToBack",
+                "c",
+                "end"
+            ]
+        );
+    }
+
+    #[test]
+    fn append_closes_a_range_left_open_in_other() {
+        let mut draw_list = DrawList::new();
+        draw_list.insert_debug_marker("a");
+
+        let mut other = DrawList::new();
+        other.begin_sorted(DrawSortMode::State);
+        other.insert_debug_marker("b");
+
+        draw_list.append(&mut other);
+        draw_list.insert_debug_marker("c");
+
+        assert_eq!(describe(&draw_list), ["a", "begin State", "b", "end", "c"]);
+    }
+
+    #[test]
+    fn append_keeps_closed_ranges_unchanged() {
+        let mut draw_list = DrawList::new();
+        draw_list.begin_sorted(DrawSortMode::State);
+        draw_list.end_sorted();
+
+        let mut other = DrawList::new();
+        other.begin_sorted(DrawSortMode::BackToFront);
+        other.end_sorted();
+
+        draw_list.append(&mut other);
+
+        assert_eq!(
+            describe(&draw_list),
+            ["begin State", "end", "begin BackToFront", "end"]
+        );
+    }
+
+    #[test]
+    fn extend_does_not_sort_across_appended_lists() {
+        let mut draw_list = DrawList::new();
+        draw_list.begin_sorted(DrawSortMode::BackToFront);
+
+        let mut first = DrawList::new();
+        first.insert_debug_marker("a");
+        let mut second = DrawList::new();
+        second.insert_debug_marker("b");
+        draw_list.extend([first, second]);
+
+        assert_eq!(
+            describe(&draw_list),
+            [
+                "begin BackToFront",
+                "end",
+                "a",
+                "begin BackToFront",
+                "end",
+                "b",
+                "begin BackToFront"
+            ]
+        );
+    }
 }
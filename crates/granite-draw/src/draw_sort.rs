@@ -0,0 +1,496 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use wgpu::util::DeviceExt;
+
+use crate::{
+    DepthBufferId, DrawListRenderer, FrameContext, MaterialId, MeshId, RenderPipelineKey,
+    commands::{FrameCommand, IndirectArgs, PassRegion},
+    common::Id,
+    draw_encoder::{DrawCall, DrawEncoder},
+    draw_list::{DrawSortMode, RenderTarget},
+    prepared_draw::PreparedDraw,
+    push_constants::{DrawPushConstants, PushConstantBuffer},
+};
+
+/// A draw from a sorted range, resolved and ready to be ordered and encoded.
+struct SortedDraw<'a> {
+    prepared_draw: PreparedDraw,
+    material: MaterialId,
+    mesh: Option<MeshId>,
+    vertex_count: u32,
+    instance_buffer: Option<usize>,
+    instance_count: u32,
+    indirect: Option<IndirectArgs>,
+    push_constants: Option<&'a [u8]>,
+    region: PassRegion,
+    label: Option<&'a str>,
+    key: DrawSortKey,
+}
+
+/// Ordering key for a draw inside a sorted range.
+///
+/// Keys only order draws within a run of consecutive draws that share a render target and depth
+/// buffer (see [`sort_pass_runs`]); the attachments are part of the key to find those runs.
+/// Pipeline and bind group components are derived from the [`RenderPipelineKey`] and bind group
+/// ids, so equal state sorts adjacently.
+#[derive(Clone, Debug)]
+struct DrawSortKey {
+    render_target: RenderTarget,
+    depth_buffer: Option<DepthBufferId>,
+    depth: f32,
+    pipeline: u64,
+    bind_groups: Vec<Id>,
+    mesh: Option<MeshId>,
+}
+
+impl DrawSortKey {
+    fn new(
+        render_target: RenderTarget,
+        prepared_draw: &PreparedDraw,
+        mesh: Option<MeshId>,
+        depth: f32,
+    ) -> Self {
+        Self {
+            render_target,
+            depth_buffer: prepared_draw
+                .depth_state
+                .map(|depth_state| depth_state.depth_buffer),
+            depth,
+            pipeline: pipeline_sort_key(&prepared_draw.key),
+            bind_groups: prepared_draw
+                .bind_groups_to_set
+                .iter()
+                .map(|bind_group| bind_group.bind_group)
+                .collect(),
+            mesh,
+        }
+    }
+
+    /// Returns `true` when both draws can be encoded into the same render pass.
+    fn same_pass(&self, other: &Self) -> bool {
+        self.render_target == other.render_target && self.depth_buffer == other.depth_buffer
+    }
+
+    /// Orders two draws of the same pass.
+    fn cmp(&self, other: &Self, mode: DrawSortMode) -> std::cmp::Ordering {
+        let state = || {
+            (self.pipeline, &self.bind_groups, self.mesh).cmp(&(
+                other.pipeline,
+                &other.bind_groups,
+                other.mesh,
+            ))
+        };
+
+        match mode {
+            DrawSortMode::None => std::cmp::Ordering::Equal,
+            DrawSortMode::FrontToBack => self.depth.total_cmp(&other.depth).then_with(state),
+            DrawSortMode::BackToFront => other.depth.total_cmp(&self.depth).then_with(state),
+            DrawSortMode::State => state(),
+        }
+    }
+}
+
+/// Sorts each run of consecutive draws that share a render target and depth buffer, keeping the
+/// runs in their recorded order.
+///
+/// Draws are never moved across a change of attachments: a draw into one target may sample
+/// another target drawn earlier in the range, so regrouping draws by target could read a target
+/// before it was drawn.
+fn sort_pass_runs<T>(draws: &mut [T], mode: DrawSortMode, key: impl Fn(&T) -> &DrawSortKey) {
+    for run in draws.chunk_by_mut(|a, b| key(a).same_pass(key(b))) {
+        run.sort_by(|a, b| key(a).cmp(key(b), mode));
+    }
+}
+
+/// Derives a stable ordering value for a pipeline, so draws sharing a pipeline sort together.
+fn pipeline_sort_key(key: &RenderPipelineKey) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl DrawListRenderer {
+    /// Sorts and encodes a range of draw commands.
+    ///
+    /// Draws are only reordered within runs of consecutive draws sharing a render target and depth
+    /// buffer. Each run is encoded into a single render pass, and pipeline, bind group, mesh
+    /// buffer, viewport, and scissor changes are only issued when they differ from the previous
+    /// draw.
+    pub(super) fn encode_sorted_draws(
+        &mut self,
+        frame_context: &FrameContext<'_>,
+        encoder: &mut wgpu::CommandEncoder,
+        mode: DrawSortMode,
+        commands: &[&FrameCommand],
+        frame_instance_buffers: &mut Vec<wgpu::Buffer>,
+    ) {
+        // Reallocating a render target evicts the bind groups that sample it, so make every
+        // target ready before any draw is resolved.
+        for command in commands.iter() {
+            if let Some(render_target) = command.render_target() {
+                self.ensure_render_target_ready(frame_context, render_target);
+            }
+        }
+
+        let mut draws = Vec::with_capacity(commands.len());
+        for command in commands.iter() {
+            if let Some(draw) =
+                self.prepare_sorted_draw(frame_context, command, frame_instance_buffers)
+            {
+                draws.push(draw);
+            }
+        }
+
+        sort_pass_runs(&mut draws, mode, |draw| &draw.key);
+
+        for draw in draws.iter() {
+            if let Some(depth_state) = draw.prepared_draw.depth_state {
+                self.ensure_depth_buffer_ready(frame_context, depth_state.depth_buffer);
+            }
+        }
+
+        let push_constants = PushConstantBuffer::new(
+            self,
+            "sorted_push_constants",
+            draws
+                .iter()
+                .map(|draw| (&draw.prepared_draw, draw.push_constants)),
+        );
+
+        let mut next_draw_index = 0;
+        for pass_draws in draws.chunk_by(|a, b| a.key.same_pass(&b.key)) {
+            let pass_start = next_draw_index;
+            next_draw_index += pass_draws.len();
+
+            // Draws with a rejected region are skipped, and the pass with them when none is left.
+            let regions: Vec<_> = pass_draws
+                .iter()
+                .map(|draw| {
+                    self.resolve_pass_region(frame_context, draw.key.render_target, draw.region)
+                })
+                .collect();
+            if regions.iter().all(Option::is_none) {
+                continue;
+            }
+
+            let first = &pass_draws[0];
+            let Some(mut render_pass) = self.create_render_pass_for_draw(
+                encoder,
+                frame_context,
+                first.key.render_target,
+                first.prepared_draw.depth_state,
+            ) else {
+                continue;
+            };
+
+            let mut draw_encoder = DrawEncoder::new(self);
+            let mut current_region = None;
+
+            for (pass_index, (draw, region)) in pass_draws.iter().zip(regions).enumerate() {
+                let Some(region) = region else {
+                    continue;
+                };
+                if current_region != Some(region) {
+                    region.apply(&mut render_pass);
+                    current_region = Some(region);
+                }
+
+                draw_encoder.encode(
+                    &mut render_pass,
+                    &DrawCall {
+                        prepared_draw: &draw.prepared_draw,
+                        material: draw.material,
+                        mesh: draw.mesh,
+                        vertex_count: draw.vertex_count,
+                        instance_count: draw.instance_count,
+                        instance_buffer: draw
+                            .instance_buffer
+                            .map(|instance_buffer| &frame_instance_buffers[instance_buffer]),
+                        indirect: draw.indirect,
+                        push_constants: DrawPushConstants {
+                            data: draw.push_constants,
+                            packed: push_constants
+                                .as_ref()
+                                .map(|packed| (packed, pass_start + pass_index)),
+                        },
+                        label: draw.label,
+                    },
+                );
+            }
+
+            let encoded = draw_encoder.finish();
+            drop(render_pass);
+            self.add_encoded_draws(encoded);
+        }
+    }
+
+    fn prepare_sorted_draw<'a>(
+        &mut self,
+        frame_context: &FrameContext<'_>,
+        command: &'a FrameCommand,
+        frame_instance_buffers: &mut Vec<wgpu::Buffer>,
+    ) -> Option<SortedDraw<'a>> {
+        let (render_target, mesh, vertex_count, instance_count, sort_depth, prepared_draw) =
+            match command {
+                FrameCommand::Draw(draw) => (
+                    draw.render_target,
+                    None,
+                    draw.vertex_count,
+                    1,
+                    draw.sort_depth,
+                    PreparedDraw::try_new(
+                        self,
+                        frame_context.format,
+                        draw.render_target,
+                        None,
+                        draw.material,
+                        None,
+                    )?,
+                ),
+                FrameCommand::DrawMesh(draw) => (
+                    draw.render_target,
+                    Some(draw.mesh),
+                    0,
+                    1,
+                    draw.sort_depth,
+                    PreparedDraw::try_new(
+                        self,
+                        frame_context.format,
+                        draw.render_target,
+                        Some(draw.mesh),
+                        draw.material,
+                        None,
+                    )?,
+                ),
+                FrameCommand::DrawMeshInstanced(draw) => (
+                    draw.render_target,
+                    Some(draw.mesh),
+                    0,
+                    draw.instance_count,
+                    draw.sort_depth,
+                    PreparedDraw::try_new(
+                        self,
+                        frame_context.format,
+                        draw.render_target,
+                        Some(draw.mesh),
+                        draw.material,
+                        Some(draw.instance_buffer_layout.clone()),
+                    )?,
+                ),
+                FrameCommand::DrawIndirect(draw) => (
+                    draw.render_target,
+                    None,
+                    0,
+                    0,
+                    draw.sort_depth,
+                    PreparedDraw::try_new(
+                        self,
+                        frame_context.format,
+                        draw.render_target,
+                        None,
+                        draw.material,
+                        None,
+                    )?,
+                ),
+                FrameCommand::DrawMeshIndirect(draw) => (
+                    draw.render_target,
+                    Some(draw.mesh),
+                    0,
+                    0,
+                    draw.sort_depth,
+                    PreparedDraw::try_new(
+                        self,
+                        frame_context.format,
+                        draw.render_target,
+                        Some(draw.mesh),
+                        draw.material,
+                        None,
+                    )?,
+                ),
+                _ => return None,
+            };
+
+        let push_constants = match command {
+            FrameCommand::Draw(draw) => draw.push_constants.as_deref(),
+            FrameCommand::DrawMesh(draw) => draw.push_constants.as_deref(),
+            _ => None,
+        };
+
+        let indirect = match command {
+            FrameCommand::DrawIndirect(draw) => Some(draw.indirect),
+            FrameCommand::DrawMeshIndirect(draw) => Some(draw.indirect),
+            _ => None,
+        };
+
+        let instance_buffer = if let FrameCommand::DrawMeshInstanced(draw) = command {
+            self.submission_stats.bytes_uploaded += draw.instance_data.len() as u64;
+            frame_instance_buffers.push(self.device.create_buffer_init(
+                &wgpu::util::BufferInitDescriptor {
+                    label: Some("frame_instance_buffer"),
+                    contents: draw.instance_data.as_slice(),
+                    usage: wgpu::BufferUsages::VERTEX,
+                },
+            ));
+            Some(frame_instance_buffers.len() - 1)
+        } else {
+            None
+        };
+
+        let key = DrawSortKey::new(render_target, &prepared_draw, mesh, sort_depth);
+        Some(SortedDraw {
+            prepared_draw,
+            material: command.material()?,
+            mesh,
+            vertex_count,
+            instance_buffer,
+            instance_count,
+            indirect,
+            push_constants,
+            region: command.region()?,
+            label: command.label(),
+            key,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::StableVec;
+
+    struct Ids(StableVec<()>);
+
+    impl Ids {
+        fn new() -> Self {
+            Self(StableVec::default())
+        }
+
+        fn next(&mut self) -> Id {
+            self.0.push(())
+        }
+    }
+
+    fn key(render_target: RenderTarget, depth: f32, pipeline: u64) -> DrawSortKey {
+        DrawSortKey {
+            render_target,
+            depth_buffer: None,
+            depth,
+            pipeline,
+            bind_groups: Vec::new(),
+            mesh: None,
+        }
+    }
+
+    fn depths(keys: &[DrawSortKey]) -> Vec<f32> {
+        keys.iter().map(|key| key.depth).collect()
+    }
+
+    #[test]
+    fn depth_modes_order_by_depth_then_state() {
+        let mut keys = vec![
+            key(RenderTarget::Surface, 2.0, 1),
+            key(RenderTarget::Surface, 1.0, 2),
+            key(RenderTarget::Surface, 1.0, 1),
+            key(RenderTarget::Surface, 3.0, 1),
+        ];
+
+        sort_pass_runs(&mut keys, DrawSortMode::FrontToBack, |key| key);
+        assert_eq!(depths(&keys), [1.0, 1.0, 2.0, 3.0]);
+        assert_eq!(keys[0].pipeline, 1);
+        assert_eq!(keys[1].pipeline, 2);
+
+        sort_pass_runs(&mut keys, DrawSortMode::BackToFront, |key| key);
+        assert_eq!(depths(&keys), [3.0, 2.0, 1.0, 1.0]);
+        assert_eq!(keys[2].pipeline, 1);
+    }
+
+    #[test]
+    fn state_mode_groups_pipeline_bind_groups_and_mesh() {
+        let mut ids = Ids::new();
+        let (bind_group_a, bind_group_b, mesh) = (ids.next(), ids.next(), ids.next());
+
+        let mut keys = vec![
+            DrawSortKey {
+                bind_groups: vec![bind_group_b],
+                ..key(RenderTarget::Surface, 0.0, 2)
+            },
+            DrawSortKey {
+                bind_groups: vec![bind_group_a],
+                mesh: Some(mesh),
+                ..key(RenderTarget::Surface, 1.0, 1)
+            },
+            DrawSortKey {
+                bind_groups: vec![bind_group_b],
+                ..key(RenderTarget::Surface, 2.0, 1)
+            },
+            DrawSortKey {
+                bind_groups: vec![bind_group_a],
+                ..key(RenderTarget::Surface, 3.0, 1)
+            },
+        ];
+
+        sort_pass_runs(&mut keys, DrawSortMode::State, |key| key);
+        assert_eq!(depths(&keys), [3.0, 1.0, 2.0, 0.0]);
+    }
+
+    #[test]
+    fn none_mode_keeps_recorded_order() {
+        let mut keys = vec![
+            key(RenderTarget::Surface, 3.0, 3),
+            key(RenderTarget::Surface, 1.0, 1),
+            key(RenderTarget::Surface, 2.0, 2),
+        ];
+
+        sort_pass_runs(&mut keys, DrawSortMode::None, |key| key);
+        assert_eq!(depths(&keys), [3.0, 1.0, 2.0]);
+    }
+
+    #[test]
+    fn draws_are_not_moved_across_a_change_of_target() {
+        let mut ids = Ids::new();
+        let offscreen = RenderTarget::Custom(ids.next());
+
+        // The surface draws in the middle sample the offscreen target drawn before them, so the
+        // offscreen draws after them must stay after them.
+        let mut keys = vec![
+            key(offscreen, 4.0, 1),
+            key(offscreen, 3.0, 1),
+            key(RenderTarget::Surface, 2.0, 1),
+            key(RenderTarget::Surface, 1.0, 1),
+            key(offscreen, 6.0, 1),
+            key(offscreen, 5.0, 1),
+        ];
+
+        sort_pass_runs(&mut keys, DrawSortMode::FrontToBack, |key| key);
+        assert_eq!(depths(&keys), [3.0, 4.0, 1.0, 2.0, 5.0, 6.0]);
+        assert_eq!(
+            keys.iter().map(|key| key.render_target).collect::<Vec<_>>(),
+            [
+                offscreen,
+                offscreen,
+                RenderTarget::Surface,
+                RenderTarget::Surface,
+                offscreen,
+                offscreen,
+            ]
+        );
+    }
+
+    #[test]
+    fn a_change_of_depth_buffer_splits_runs() {
+        let mut ids = Ids::new();
+        let depth_buffer = ids.next();
+
+        let mut keys = vec![
+            key(RenderTarget::Surface, 2.0, 1),
+            DrawSortKey {
+                depth_buffer: Some(depth_buffer),
+                ..key(RenderTarget::Surface, 1.0, 1)
+            },
+            key(RenderTarget::Surface, 0.0, 1),
+        ];
+
+        sort_pass_runs(&mut keys, DrawSortMode::FrontToBack, |key| key);
+        assert_eq!(depths(&keys), [2.0, 1.0, 0.0]);
+    }
+}
@@ -1,9 +1,16 @@
 use crate::FrameContext;
 
-use crate::draw_list::{DrawList, RenderTarget};
+use crate::draw_list::{DrawList, DrawSortMode, RenderTarget, ScissorRect, Viewport};
 
 use super::*;
 
+/// The bind group and layout keys of the draw bindings in one `@group`.
+type GroupedDrawBindings = (
+    u32,
+    Vec<BindGroupBindingKey>,
+    Vec<BindGroupLayoutBindingKey>,
+);
+
 impl DrawListRenderer {
     /// Begins recording a new higher-level draw list.
     ///
@@ -13,41 +20,173 @@ impl DrawListRenderer {
     }
 
     /// Executes all commands in a draw list into the provided frame.
+    ///
+    /// Commands execute in the order they were recorded: uniform, storage buffer, and texture
+    /// updates are encoded as staged copies between the surrounding draws, so each draw sees the
+    /// values written before it in the list.
     pub fn submit_draw_list(&mut self, frame_context: FrameContext<'_>, draw_list: &DrawList) {
-        let DrawList { commands } = draw_list;
+        self.capture_submitted_draw_list(&frame_context, draw_list);
+
         let mut frame_instance_buffers: Vec<wgpu::Buffer> = Vec::new();
         let mut encoder = self
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor {
                 label: Some("draw_list_encoder"),
             });
+        self.encode_uploads(&mut encoder, self.upload_budget());
+
+        self.encode_draw_list(
+            frame_context,
+            &mut encoder,
+            draw_list,
+            &mut frame_instance_buffers,
+        );
+        self.resolve_profiled_submission(&mut encoder);
+
+        self.finish_upload_buffers();
+        self.queue.submit(std::iter::once(encoder.finish()));
+        self.recall_upload_buffers();
+        self.push_constant_arena.recall();
+        self.poll_readbacks();
+        self.finish_profiled_submission();
+        self.finish_submission_stats();
+        self.finish_recorded_submission();
+        self.end_submission();
+    }
+
+    /// Encodes all commands in a draw list into `encoder`.
+    ///
+    /// Per-frame instance buffers are pushed into `frame_instance_buffers`, which must be kept
+    /// alive until the encoder is submitted. Updates are staged in the upload staging belt.
+    pub(super) fn encode_draw_list(
+        &mut self,
+        frame_context: FrameContext<'_>,
+        encoder: &mut wgpu::CommandEncoder,
+        draw_list: &DrawList,
+        frame_instance_buffers: &mut Vec<wgpu::Buffer>,
+    ) {
+        let DrawList { commands, .. } = draw_list;
+
+        let mut sort_mode = DrawSortMode::None;
+        let mut sorted_draws: Vec<&commands::FrameCommand> = Vec::new();
+        let mut profile_label: Option<&str> = None;
+        // Label of the first draw of the sorted range, which the whole range is reported under.
+        let mut sorted_profile_label: Option<&str> = None;
+        let mut debug_group_depth = 0_usize;
 
         for command in commands.iter() {
+            // Changing the label does not end a sorted range.
+            if let commands::FrameCommand::SetProfileLabel(label) = command {
+                profile_label = label.as_deref();
+                continue;
+            }
+
+            if sort_mode != DrawSortMode::None {
+                if command.render_target().is_some() {
+                    if sorted_draws.is_empty() {
+                        sorted_profile_label = profile_label;
+                    }
+                    sorted_draws.push(command);
+                    continue;
+                }
+
+                if !sorted_draws.is_empty() {
+                    self.begin_profiled_command("sorted_draws", sorted_profile_label);
+                    self.encode_sorted_draws(
+                        &frame_context,
+                        encoder,
+                        sort_mode,
+                        sorted_draws.as_slice(),
+                        frame_instance_buffers,
+                    );
+                    self.end_profiled_command();
+                    sorted_draws.clear();
+                }
+            }
+
+            if let Some(name) = command.profile_name() {
+                self.begin_profiled_command(name, profile_label);
+            }
+
             match command {
-                commands::FrameCommand::UpdateUniform(command) => command.execute(self),
-                commands::FrameCommand::UpdateStorageBuffer(command) => command.execute(self),
-                commands::FrameCommand::UpdateTextureRegion(command) => command.execute(self),
+                commands::FrameCommand::UpdateUniform(command) => command.execute(self, encoder),
+                commands::FrameCommand::UpdateStorageBuffer(command) => {
+                    command.execute(self, encoder)
+                }
+                commands::FrameCommand::UpdateTextureRegion(command) => {
+                    command.execute(self, encoder)
+                }
                 commands::FrameCommand::ClearDepthBuffer(command) => {
-                    command.execute(self, frame_context, &mut encoder)
+                    command.execute(self, frame_context, encoder)
                 }
                 commands::FrameCommand::ResizeDepthBuffer(command) => command.execute(self),
                 commands::FrameCommand::ResizeRenderTarget(command) => command.execute(self),
                 commands::FrameCommand::Draw(command) => {
-                    command.execute(self, frame_context, &mut encoder)
+                    command.execute(self, frame_context, encoder)
                 }
                 commands::FrameCommand::DrawMesh(command) => {
-                    command.execute(self, frame_context, &mut encoder)
+                    command.execute(self, frame_context, encoder)
+                }
+                commands::FrameCommand::DrawMeshInstanced(command) => {
+                    command.execute(self, frame_context, encoder, frame_instance_buffers)
+                }
+                commands::FrameCommand::DrawIndirect(command) => {
+                    command.execute(self, frame_context, encoder)
+                }
+                commands::FrameCommand::DrawMeshIndirect(command) => {
+                    command.execute(self, frame_context, encoder)
+                }
+                commands::FrameCommand::ExecuteDrawBundle(command) => {
+                    command.execute(self, frame_context, encoder)
+                }
+                commands::FrameCommand::Dispatch(command) => {
+                    command.execute(self, frame_context, encoder)
+                }
+                commands::FrameCommand::BeginSort(mode) => sort_mode = *mode,
+                commands::FrameCommand::EndSort => sort_mode = DrawSortMode::None,
+                commands::FrameCommand::SetProfileLabel(_) => {}
+                commands::FrameCommand::PushDebugGroup(label) => {
+                    encoder.push_debug_group(label);
+                    debug_group_depth += 1;
+                }
+                commands::FrameCommand::PopDebugGroup => {
+                    if debug_group_depth == 0 {
+                        tracing::warn!("Debug group popped without a matching push; ignoring it.");
+                    } else {
+                        encoder.pop_debug_group();
+                        debug_group_depth -= 1;
+                    }
+                }
+                commands::FrameCommand::InsertDebugMarker(label) => {
+                    encoder.insert_debug_marker(label)
                 }
-                commands::FrameCommand::DrawMeshInstanced(command) => command.execute(
-                    self,
-                    frame_context,
-                    &mut encoder,
-                    &mut frame_instance_buffers,
-                ),
+            }
+
+            if command.profile_name().is_some() {
+                self.end_profiled_command();
             }
         }
 
-        self.queue.submit(std::iter::once(encoder.finish()));
+        if !sorted_draws.is_empty() {
+            self.begin_profiled_command("sorted_draws", sorted_profile_label);
+            self.encode_sorted_draws(
+                &frame_context,
+                encoder,
+                sort_mode,
+                sorted_draws.as_slice(),
+                frame_instance_buffers,
+            );
+            self.end_profiled_command();
+        }
+
+        if debug_group_depth > 0 {
+            tracing::warn!(
+                "Draw list ended with {debug_group_depth} open debug group(s); closing them."
+            );
+            for _ in 0..debug_group_depth {
+                encoder.pop_debug_group();
+            }
+        }
     }
 
     pub(super) fn encode_clear_depth_buffer(
@@ -63,13 +202,25 @@ impl DrawListRenderer {
             tracing::warn!("Invalid depth buffer id ({depth_buffer:?})");
             return false;
         };
-        let Some(depth_view) = depth_record.view.as_ref() else {
+        if depth_record.view.is_none() {
             tracing::warn!("Depth buffer ({depth_buffer:?}) is not ready to clear");
             return false;
+        }
+
+        let label = format!("{}_clear_pass", depth_record.name);
+        self.submission_stats.passes += 1;
+        let first_query = self.allocate_pass_timestamps();
+        self.record_pass(&label, None, Some(depth_buffer), Some(value));
+        let Some(depth_view) = self
+            .depth_buffers
+            .get(depth_buffer)
+            .and_then(|depth_record| depth_record.view.as_ref())
+        else {
+            return false;
         };
 
         let _render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-            label: Some("clear_depth_buffer_pass"),
+            label: Some(&label),
             color_attachments: &[],
             depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
                 view: depth_view,
@@ -79,6 +230,7 @@ impl DrawListRenderer {
                 }),
                 stencil_ops: None,
             }),
+            timestamp_writes: self.pass_timestamp_writes(first_query),
             ..Default::default()
         });
 
@@ -90,33 +242,20 @@ impl DrawListRenderer {
     }
 
     pub(super) fn create_render_pass_for_draw<'encoder>(
-        &self,
+        &mut self,
         encoder: &'encoder mut wgpu::CommandEncoder,
         frame_context: &FrameContext<'_>,
         render_target: RenderTarget,
         depth_state: Option<MaterialDepthState>,
     ) -> Option<wgpu::RenderPass<'encoder>> {
-        let view = match render_target {
-            RenderTarget::Surface => frame_context.view,
-            RenderTarget::Custom(id) => {
-                let record = self.render_targets.get(id)?;
-                record.view.as_ref()?
-            }
-        };
-
-        let color_attachments = [Some(wgpu::RenderPassColorAttachment {
-            view,
-            depth_slice: None,
-            resolve_target: None,
-            ops: wgpu::Operations {
-                load: wgpu::LoadOp::Load,
-                store: wgpu::StoreOp::Store,
-            },
-        })];
+        // Validate before allocating timestamp queries, so skipped passes leave none unwritten.
+        if let RenderTarget::Custom(id) = render_target {
+            self.render_targets.get(id)?.view.as_ref()?;
+        }
 
         if let Some(depth_state) = depth_state {
             let depth_record = self.depth_buffers.get(depth_state.depth_buffer)?;
-            let depth_view = depth_record.view.as_ref()?;
+            depth_record.view.as_ref()?;
             let render_target_size = self.render_target_size(frame_context.size, render_target)?;
             if depth_record.size != render_target_size {
                 tracing::warn!(
@@ -136,29 +275,122 @@ impl DrawListRenderer {
                 );
                 return None;
             }
+        }
+
+        self.submission_stats.passes += 1;
+        let first_query = self.allocate_pass_timestamps();
+
+        let label = match render_target {
+            RenderTarget::Surface => "main_render_pass".to_string(),
+            RenderTarget::Custom(id) => format!("{}_pass", self.render_targets.get(id)?.name),
+        };
+        self.record_pass(
+            &label,
+            Some(render_target),
+            depth_state.map(|depth_state| depth_state.depth_buffer),
+            None,
+        );
+
+        let view = match render_target {
+            RenderTarget::Surface => frame_context.view,
+            RenderTarget::Custom(id) => self.render_targets.get(id)?.view.as_ref()?,
+        };
+
+        let color_attachments = [Some(wgpu::RenderPassColorAttachment {
+            view,
+            depth_slice: None,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Load,
+                store: wgpu::StoreOp::Store,
+            },
+        })];
 
-            return Some(encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("main_render_pass"),
-                color_attachments: &color_attachments,
-                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: depth_view,
+        let depth_stencil_attachment = match depth_state {
+            Some(depth_state) => {
+                let depth_record = self.depth_buffers.get(depth_state.depth_buffer)?;
+                Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: depth_record.view.as_ref()?,
                     depth_ops: Some(wgpu::Operations {
                         load: wgpu::LoadOp::Load,
                         store: wgpu::StoreOp::Store,
                     }),
                     stencil_ops: None,
-                }),
-                ..Default::default()
-            }));
-        }
+                })
+            }
+            None => None,
+        };
 
         Some(encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-            label: Some("main_render_pass"),
+            label: Some(&label),
             color_attachments: &color_attachments,
+            depth_stencil_attachment,
+            timestamp_writes: self.pass_timestamp_writes(first_query),
             ..Default::default()
         }))
     }
 
+    /// Checks a draw's viewport and scissor rectangle against its render target, before a pass
+    /// is created for it.
+    ///
+    /// Unset rectangles cover the whole render target, so a pass shared by several draws is reset
+    /// correctly when a later draw has no region. Returns `None` when a rectangle is empty or
+    /// does not fit inside the render target.
+    pub(super) fn resolve_pass_region(
+        &self,
+        frame_context: &FrameContext<'_>,
+        render_target: RenderTarget,
+        region: commands::PassRegion,
+    ) -> Option<ResolvedPassRegion> {
+        let render_target_size = self.render_target_size(frame_context.size, render_target)?;
+
+        let viewport = region.viewport.unwrap_or(Viewport::new(
+            glam::Vec2::ZERO,
+            render_target_size.as_vec2(),
+        ));
+        let viewport_end = viewport.origin + viewport.size;
+        if viewport.origin.min_element() < 0.0
+            || viewport.size.min_element() <= 0.0
+            || viewport_end.x > render_target_size.x as f32
+            || viewport_end.y > render_target_size.y as f32
+            || !(0.0..=1.0).contains(&viewport.min_depth)
+            || !(viewport.min_depth..=1.0).contains(&viewport.max_depth)
+        {
+            tracing::warn!(
+                "Viewport {viewport:?} does not fit inside {render_target:?} ({}x{}).",
+                render_target_size.x,
+                render_target_size.y
+            );
+            return None;
+        }
+
+        let scissor_rect = region
+            .scissor_rect
+            .unwrap_or(ScissorRect::new(UVec2::ZERO, render_target_size));
+        let scissor_rect_fits = scissor_rect.size.min_element() > 0
+            && scissor_rect
+                .origin
+                .x
+                .checked_add(scissor_rect.size.x)
+                .zip(scissor_rect.origin.y.checked_add(scissor_rect.size.y))
+                .is_some_and(|(end_x, end_y)| {
+                    end_x <= render_target_size.x && end_y <= render_target_size.y
+                });
+        if !scissor_rect_fits {
+            tracing::warn!(
+                "Scissor rectangle {scissor_rect:?} does not fit inside {render_target:?} ({}x{}).",
+                render_target_size.x,
+                render_target_size.y
+            );
+            return None;
+        }
+
+        Some(ResolvedPassRegion {
+            viewport,
+            scissor_rect,
+        })
+    }
+
     pub(super) fn get_or_create_vertex_buffer_layout(&mut self, layout: VertexBufferLayout) -> Id {
         self.vertex_buffer_layouts.get_or_insert(layout)
     }
@@ -173,16 +405,139 @@ impl DrawListRenderer {
     pub(super) fn resolve_draw_bindings(
         &mut self,
         draw_bindings: &[bindings::DrawBinding],
+        push_constants: Option<push_constants::PushConstantLayout>,
+        name: Option<&str>,
     ) -> Option<ResolvedDrawBindings> {
-        if draw_bindings.is_empty() {
-            return Some(ResolvedDrawBindings {
-                bind_groups_to_set: Vec::new(),
-                pipeline_layout_key: PipelineLayoutKey {
-                    bind_group_layouts: Vec::new(),
+        let grouped_bindings = self.group_draw_bindings(draw_bindings)?;
+        let push_constants = match push_constants {
+            Some(layout) => Some(self.resolve_push_constants(
+                layout,
+                grouped_bindings.iter().map(|(group, _, _)| *group),
+            )?),
+            None => None,
+        };
+
+        let mut bind_groups_to_set = Vec::with_capacity(grouped_bindings.len());
+        for (group, bindings, layout_bindings) in grouped_bindings.into_iter() {
+            let bind_group_layout =
+                self.get_or_create_bind_group_layout_for_key(BindGroupLayoutKey {
+                    bindings: layout_bindings,
+                })?;
+            let bind_group = self.get_or_create_bind_group_for_key(
+                BindGroupKey {
+                    bind_group_layout,
+                    bindings,
                 },
+                name,
+            )?;
+
+            bind_groups_to_set.push(ResolvedDrawBindGroup {
+                slot: group,
+                bind_group,
+                bind_group_layout,
             });
         }
 
+        let pipeline_layout_key = self.pipeline_layout_key(
+            bind_groups_to_set
+                .iter()
+                .map(|bind_group| (bind_group.slot, bind_group.bind_group_layout))
+                .collect(),
+            push_constants,
+        );
+
+        Some(ResolvedDrawBindings {
+            bind_groups_to_set,
+            push_constants,
+            pipeline_layout_key,
+        })
+    }
+
+    /// Resolves only the pipeline layout for a set of draw bindings.
+    ///
+    /// Unlike [`Self::resolve_draw_bindings`] no bind groups are created, so bound render targets
+    /// do not need to be allocated yet.
+    pub(super) fn resolve_pipeline_layout_key(
+        &mut self,
+        draw_bindings: &[bindings::DrawBinding],
+        push_constants: Option<push_constants::PushConstantLayout>,
+    ) -> Option<PipelineLayoutKey> {
+        let grouped_bindings = self.group_draw_bindings(draw_bindings)?;
+        let push_constants = match push_constants {
+            Some(layout) => Some(self.resolve_push_constants(
+                layout,
+                grouped_bindings.iter().map(|(group, _, _)| *group),
+            )?),
+            None => None,
+        };
+
+        let mut bind_group_layouts = Vec::with_capacity(grouped_bindings.len());
+        for (group, _, layout_bindings) in grouped_bindings.into_iter() {
+            let bind_group_layout =
+                self.get_or_create_bind_group_layout_for_key(BindGroupLayoutKey {
+                    bindings: layout_bindings,
+                })?;
+            bind_group_layouts.push((group, bind_group_layout));
+        }
+
+        Some(self.pipeline_layout_key(bind_group_layouts, push_constants))
+    }
+
+    /// Builds a pipeline layout key from `(group, bind group layout)` pairs, filling unused
+    /// groups below the highest one with an empty layout.
+    ///
+    /// Emulated push constants add their bind group layout at the fallback group; native ones
+    /// add a push constant range.
+    fn pipeline_layout_key(
+        &mut self,
+        mut bind_group_layouts: Vec<(u32, Id)>,
+        push_constants: Option<push_constants::ResolvedPushConstants>,
+    ) -> PipelineLayoutKey {
+        let native_push_constants = match push_constants {
+            Some(push_constants::ResolvedPushConstants::Native { stages, size }) => {
+                Some((stages, size))
+            }
+            Some(push_constants::ResolvedPushConstants::Emulated {
+                slot,
+                bind_group_layout,
+                ..
+            }) => {
+                bind_group_layouts.push((slot, bind_group_layout));
+                None
+            }
+            None => None,
+        };
+
+        if bind_group_layouts.is_empty() {
+            return PipelineLayoutKey {
+                bind_group_layouts: Vec::new(),
+                push_constants: native_push_constants,
+            };
+        }
+
+        let max_group = bind_group_layouts
+            .iter()
+            .map(|(group, _)| *group)
+            .max()
+            .unwrap_or(0);
+        let empty_bind_group_layout = self.get_or_create_empty_bind_group_layout();
+        let mut layouts = vec![empty_bind_group_layout; max_group as usize + 1];
+        for (group, bind_group_layout) in bind_group_layouts {
+            layouts[group as usize] = bind_group_layout;
+        }
+
+        PipelineLayoutKey {
+            bind_group_layouts: layouts,
+            push_constants: native_push_constants,
+        }
+    }
+
+    /// Validates draw bindings and groups their bind group and layout keys by `@group`, in
+    /// ascending order.
+    fn group_draw_bindings(
+        &self,
+        draw_bindings: &[bindings::DrawBinding],
+    ) -> Option<Vec<GroupedDrawBindings>> {
         let mut draw_bindings = draw_bindings.to_vec();
         draw_bindings.sort_by_key(|binding| (binding.group, binding.binding));
 
@@ -197,11 +552,7 @@ impl DrawListRenderer {
             }
         }
 
-        let mut grouped_bindings: Vec<(
-            u32,
-            Vec<BindGroupBindingKey>,
-            Vec<BindGroupLayoutBindingKey>,
-        )> = Vec::new();
+        let mut grouped_bindings: Vec<GroupedDrawBindings> = Vec::new();
 
         for draw_binding in draw_bindings.into_iter() {
             let (bind_group_binding_key, bind_group_layout_binding_key) = match draw_binding
@@ -229,11 +580,25 @@ impl DrawListRenderer {
                 bindings::DrawBindingResource::StorageBuffer {
                     storage_buffer: storage_buffer_id,
                     visibility,
+                    read_only,
                 } => {
                     let Some(storage_buffer) = self.storage_buffers.get(storage_buffer_id) else {
                         tracing::warn!("Invalid storage buffer id ({storage_buffer_id:?})");
                         return None;
                     };
+                    if !read_only
+                        && visibility.as_wgpu().contains(wgpu::ShaderStages::VERTEX)
+                        && !self
+                            .device
+                            .features()
+                            .contains(wgpu::Features::VERTEX_WRITABLE_STORAGE)
+                    {
+                        tracing::warn!(
+                            "Storage buffer {storage_buffer_id:?} is bound read-write to vertex \
+                             shaders, which the device does not support."
+                        );
+                        return None;
+                    }
 
                     (
                         BindGroupBindingKey {
@@ -243,7 +608,7 @@ impl DrawListRenderer {
                         BindGroupLayoutBindingKey {
                             binding: draw_binding.binding,
                             visibility,
-                            ty: BindGroupLayoutBindingTypeKey::StorageBuffer,
+                            ty: BindGroupLayoutBindingTypeKey::StorageBuffer { read_only },
                             min_binding_size: Some(storage_buffer.min_binding_size),
                         },
                     )
@@ -274,9 +639,17 @@ impl DrawListRenderer {
                     render_target,
                     visibility,
                 } => {
-                    if self.render_targets.get(render_target).is_none() {
+                    let Some(render_target_record) = self.render_targets.get(render_target) else {
                         tracing::warn!("Invalid render target id ({render_target:?})");
                         return None;
+                    };
+                    if !render_target_record.format.is_float() {
+                        tracing::warn!(
+                            "Render target ({render_target:?}) with format {:?} cannot be bound as \
+                             a texture",
+                            render_target_record.format
+                        );
+                        return None;
                     }
 
                     (
@@ -331,39 +704,7 @@ impl DrawListRenderer {
             ));
         }
 
-        let mut bind_groups_to_set = Vec::with_capacity(grouped_bindings.len());
-        for (group, bindings, layout_bindings) in grouped_bindings.into_iter() {
-            let bind_group_layout =
-                self.get_or_create_bind_group_layout_for_key(BindGroupLayoutKey {
-                    bindings: layout_bindings,
-                })?;
-            let bind_group = self.get_or_create_bind_group_for_key(BindGroupKey {
-                bind_group_layout,
-                bindings,
-            })?;
-
-            bind_groups_to_set.push(ResolvedDrawBindGroup {
-                slot: group,
-                bind_group,
-                bind_group_layout,
-            });
-        }
-
-        let max_group = bind_groups_to_set
-            .iter()
-            .map(|bind_group| bind_group.slot)
-            .max()
-            .unwrap_or(0);
-        let empty_bind_group_layout = self.get_or_create_empty_bind_group_layout();
-        let mut bind_group_layouts = vec![empty_bind_group_layout; max_group as usize + 1];
-        for bind_group in bind_groups_to_set.iter() {
-            bind_group_layouts[bind_group.slot as usize] = bind_group.bind_group_layout;
-        }
-
-        Some(ResolvedDrawBindings {
-            bind_groups_to_set,
-            pipeline_layout_key: PipelineLayoutKey { bind_group_layouts },
-        })
+        Some(grouped_bindings)
     }
 
     fn get_or_create_empty_bind_group_layout(&mut self) -> Id {
@@ -371,13 +712,20 @@ impl DrawListRenderer {
             return bind_group_layout;
         }
 
+        // Not keyed, so it is never evicted from the bounded cache.
         let bind_group_layout = self.create_bind_group_layout("empty_bind_group_layout", &[]);
         self.empty_bind_group_layout = Some(bind_group_layout);
         bind_group_layout
     }
 
-    fn get_or_create_bind_group_layout_for_key(&mut self, key: BindGroupLayoutKey) -> Option<Id> {
+    pub(super) fn get_or_create_bind_group_layout_for_key(
+        &mut self,
+        key: BindGroupLayoutKey,
+    ) -> Option<Id> {
         if let Some(bind_group_layout) = self.bind_group_layouts.get_id(&key) {
+            self.cache_usages
+                .bind_group_layouts
+                .touch(bind_group_layout, self.submission_index);
             return Some(bind_group_layout);
         }
 
@@ -389,11 +737,18 @@ impl DrawListRenderer {
                     has_dynamic_offset: false,
                     min_binding_size: binding.min_binding_size,
                 },
-                BindGroupLayoutBindingTypeKey::StorageBuffer => wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Storage { read_only: true },
-                    has_dynamic_offset: false,
+                BindGroupLayoutBindingTypeKey::DynamicUniform => wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
                     min_binding_size: binding.min_binding_size,
                 },
+                BindGroupLayoutBindingTypeKey::StorageBuffer { read_only } => {
+                    wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only },
+                        has_dynamic_offset: false,
+                        min_binding_size: binding.min_binding_size,
+                    }
+                }
                 BindGroupLayoutBindingTypeKey::Texture => wgpu::BindingType::Texture {
                     sample_type: wgpu::TextureSampleType::Float { filterable: true },
                     view_dimension: wgpu::TextureViewDimension::D2,
@@ -419,14 +774,28 @@ impl DrawListRenderer {
                     entries: entries.as_slice(),
                 });
         let bind_group_layout_id = self.bind_group_layouts.insert_keyed(key, bind_group_layout);
+        self.cache_usages
+            .bind_group_layouts
+            .touch(bind_group_layout_id, self.submission_index);
         Some(bind_group_layout_id)
     }
 
-    fn get_or_create_bind_group_for_key(&mut self, key: BindGroupKey) -> Option<Id> {
+    /// Returns the bind group for `key`, creating it when it is not cached. New bind groups are
+    /// labeled after `name`, the material they are first created for.
+    fn get_or_create_bind_group_for_key(
+        &mut self,
+        key: BindGroupKey,
+        name: Option<&str>,
+    ) -> Option<Id> {
         if let Some(bind_group) = self.bind_groups.get_id(&key) {
+            self.submission_stats.bind_group_cache_hits += 1;
+            self.cache_usages
+                .bind_groups
+                .touch(bind_group, self.submission_index);
             return Some(bind_group);
         }
 
+        self.submission_stats.bind_group_cache_misses += 1;
         let bind_group_layout = self.bind_group_layouts.get(key.bind_group_layout)?;
         let bind_group = {
             let mut entries = Vec::with_capacity(key.bindings.len());
@@ -435,12 +804,12 @@ impl DrawListRenderer {
                     BindGroupBindingResourceKey::Uniform(uniform_binding_id) => {
                         let uniform = self.uniforms.get(uniform_binding_id)?;
                         let buffer = self.buffers.get(uniform.buffer)?;
-                        buffer.as_entire_binding()
+                        buffer.buffer.as_entire_binding()
                     }
                     BindGroupBindingResourceKey::StorageBuffer(storage_buffer_id) => {
                         let storage_buffer = self.storage_buffers.get(storage_buffer_id)?;
                         let buffer = self.buffers.get(storage_buffer.buffer)?;
-                        buffer.as_entire_binding()
+                        buffer.buffer.as_entire_binding()
                     }
                     BindGroupBindingResourceKey::Texture(texture_id) => {
                         let texture = self.textures.get(texture_id)?;
@@ -452,7 +821,7 @@ impl DrawListRenderer {
                     }
                     BindGroupBindingResourceKey::Sampler(sampler_id) => {
                         let sampler = self.samplers.get(sampler_id)?;
-                        wgpu::BindingResource::Sampler(sampler)
+                        wgpu::BindingResource::Sampler(&sampler.sampler)
                     }
                 };
 
@@ -462,8 +831,9 @@ impl DrawListRenderer {
                 });
             }
 
+            let label = name.map(|name| format!("{name}_bind_group"));
             self.device.create_bind_group(&wgpu::BindGroupDescriptor {
-                label: Some("draw_bind_group"),
+                label: Some(label.as_deref().unwrap_or("draw_bind_group")),
                 layout: bind_group_layout,
                 entries: entries.as_slice(),
             })
@@ -472,11 +842,17 @@ impl DrawListRenderer {
         let bind_group_id = self
             .bind_groups
             .insert_keyed(key, BindGroupRecord { bind_group });
+        self.cache_usages
+            .bind_groups
+            .touch(bind_group_id, self.submission_index);
         Some(bind_group_id)
     }
 
     pub(super) fn get_or_create_pipeline_layout(&mut self, key: PipelineLayoutKey) -> Option<Id> {
         if let Some(pipeline_layout_id) = self.pipeline_layouts.get_id(&key) {
+            self.cache_usages
+                .pipeline_layouts
+                .touch(pipeline_layout_id, self.submission_index);
             return Some(pipeline_layout_id);
         }
 
@@ -486,29 +862,55 @@ impl DrawListRenderer {
             bind_group_layouts.push(bind_group_layout);
         }
 
+        let push_constant_ranges: Vec<wgpu::PushConstantRange> = key
+            .push_constants
+            .iter()
+            .map(|(stages, size)| wgpu::PushConstantRange {
+                stages: *stages,
+                range: 0..*size,
+            })
+            .collect();
+
         let pipeline_layout = self
             .device
             .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: None,
                 bind_group_layouts: bind_group_layouts.as_slice(),
-                push_constant_ranges: &[],
+                push_constant_ranges: push_constant_ranges.as_slice(),
             });
 
         let pipeline_layout_id = self.pipeline_layouts.insert_keyed(key, pipeline_layout);
+        self.cache_usages
+            .pipeline_layouts
+            .touch(pipeline_layout_id, self.submission_index);
         Some(pipeline_layout_id)
     }
 
-    pub(super) fn ensure_render_pipeline(&mut self, key: RenderPipelineKey) -> bool {
-        if !self.render_pipeline_cache.contains_key(&key) {
-            let Some(render_pipeline) = self.create_render_pipeline(key) else {
+    /// Creates the render pipeline for `key` when it is not cached, labeled after `name`, the
+    /// material it is first created for.
+    pub(super) fn ensure_render_pipeline(
+        &mut self,
+        key: RenderPipelineKey,
+        name: Option<&str>,
+    ) -> bool {
+        if self.render_pipeline_cache.contains_key(&key) {
+            self.submission_stats.pipeline_cache_hits += 1;
+        } else {
+            self.submission_stats.pipeline_cache_misses += 1;
+            let Some(render_pipeline) = self.create_render_pipeline(key, name) else {
                 return false;
             };
             self.render_pipeline_cache.insert(key, render_pipeline);
         }
+        self.touch_render_pipeline(key);
         true
     }
 
-    fn create_render_pipeline(&self, key: RenderPipelineKey) -> Option<wgpu::RenderPipeline> {
+    pub(super) fn create_render_pipeline(
+        &self,
+        key: RenderPipelineKey,
+        name: Option<&str>,
+    ) -> Option<wgpu::RenderPipeline> {
         tracing::debug!("Creating render pipeline for {key:?}");
 
         let device = &self.device;
@@ -551,6 +953,8 @@ impl DrawListRenderer {
         let fragment_shader_module = self.shaders.get(fragment_shader.shader_module)?;
 
         let blend = match key.blend_mode {
+            // Integer formats, such as picking ids, cannot be blended.
+            _ if !render_target::is_float_format(key.render_target_format) => None,
             BlendMode::Opaque => None,
             BlendMode::AlphaBlend => Some(wgpu::BlendState::ALPHA_BLENDING),
             BlendMode::Additive => Some(wgpu::BlendState {
@@ -602,9 +1006,10 @@ impl DrawListRenderer {
             });
         }
 
+        let label = name.map(|name| format!("{name}_pipeline"));
         Some(
             device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: None,
+                label: label.as_deref(),
                 layout: Some(pipeline_layout),
                 vertex: wgpu::VertexState {
                     module: &vertex_shader_module.shader_module,
@@ -622,8 +1027,104 @@ impl DrawListRenderer {
                     targets,
                 }),
                 multiview: None,
-                cache: None,
+                cache: self.pipeline_cache.as_ref(),
             }),
         )
     }
 }
+
+/// A viewport and scissor rectangle checked to fit inside their render target.
+#[derive(Clone, Copy, PartialEq)]
+pub(super) struct ResolvedPassRegion {
+    viewport: Viewport,
+    scissor_rect: ScissorRect,
+}
+
+impl ResolvedPassRegion {
+    /// Sets the viewport and scissor rectangle of `render_pass`.
+    pub(super) fn apply(&self, render_pass: &mut wgpu::RenderPass<'_>) {
+        let Self {
+            viewport,
+            scissor_rect,
+        } = self;
+        render_pass.set_viewport(
+            viewport.origin.x,
+            viewport.origin.y,
+            viewport.size.x,
+            viewport.size.y,
+            viewport.min_depth,
+            viewport.max_depth,
+        );
+        render_pass.set_scissor_rect(
+            scissor_rect.origin.x,
+            scissor_rect.origin.y,
+            scissor_rect.size.x,
+            scissor_rect.size.y,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::Scene;
+
+    /// Submits `draw_list` and returns the validation error it raised, if any.
+    fn submit_validated(scene: &mut Scene, draw_list: &DrawList) -> Option<wgpu::Error> {
+        scene
+            .renderer
+            .device
+            .push_error_scope(wgpu::ErrorFilter::Validation);
+        scene.submit(draw_list);
+        pollster::block_on(scene.renderer.device.pop_error_scope())
+    }
+
+    #[test]
+    fn balanced_debug_groups_pass_validation() {
+        let mut scene = Scene::new();
+        let material = scene.fill_material("fill");
+
+        let mut draw_list = DrawList::default();
+        draw_list.push_debug_group("frame");
+        draw_list.push_debug_group("opaque");
+        draw_list.draw(RenderTarget::Surface, material, 3);
+        draw_list.pop_debug_group();
+        draw_list.insert_debug_marker("overlay");
+        draw_list.pop_debug_group();
+
+        assert!(submit_validated(&mut scene, &draw_list).is_none());
+    }
+
+    #[test]
+    fn unmatched_debug_group_pops_are_ignored() {
+        let mut scene = Scene::new();
+        let material = scene.fill_material("fill");
+
+        let mut draw_list = DrawList::default();
+        draw_list.pop_debug_group();
+        draw_list.push_debug_group("opaque");
+        draw_list.draw(RenderTarget::Surface, material, 3);
+        draw_list.pop_debug_group();
+        draw_list.pop_debug_group();
+
+        assert!(submit_validated(&mut scene, &draw_list).is_none());
+    }
+
+    #[test]
+    fn open_debug_groups_are_closed_at_the_end_of_the_draw_list() {
+        let mut scene = Scene::new();
+        let material = scene.fill_material("fill");
+
+        let mut draw_list = DrawList::default();
+        draw_list.push_debug_group("frame");
+        draw_list.push_debug_group("opaque");
+        draw_list.draw(RenderTarget::Surface, material, 3);
+
+        assert!(submit_validated(&mut scene, &draw_list).is_none());
+
+        // The groups left open do not leak into the next submission.
+        let mut draw_list = DrawList::default();
+        draw_list.draw(RenderTarget::Surface, material, 3);
+        assert!(submit_validated(&mut scene, &draw_list).is_none());
+    }
+}
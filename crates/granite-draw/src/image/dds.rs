@@ -0,0 +1,196 @@
+//! DDS decoding, for block-compressed 2D textures with their mip levels, and the common
+//! uncompressed layouts.
+
+use ddsfile::{Caps2, Dds, DxgiFormat, FourCC, MiscFlag, PixelFormatFlags};
+use glam::UVec2;
+
+use super::{DecodedImage, ImageError, Pixels, f16_to_f32, mip_level_count_of};
+use crate::textures::TextureFormat;
+
+pub(super) const SIGNATURE: &[u8] = b"DDS ";
+
+/// The `BC5U` four character code, which `ddsfile` has no constant for.
+const BC5U: u32 = u32::from_le_bytes(*b"BC5U");
+
+/// How the pixels of a DDS file are laid out.
+enum Layout {
+    Compressed(TextureFormat),
+    Rgba8 {
+        srgb: Option<bool>,
+    },
+    Mono8,
+    Rgba16Float,
+    /// Uncompressed pixels of `bit_count` bits, with a mask per RGBA channel.
+    Masked {
+        bit_count: u32,
+        masks: [u32; 4],
+    },
+}
+
+pub(super) fn decode(bytes: &[u8], srgb: bool) -> Result<DecodedImage, ImageError> {
+    let dds = Dds::read(bytes)
+        .map_err(|error| ImageError::decode(format!("Invalid DDS file: {error}.")))?;
+    let header = &dds.header;
+    let size = UVec2::new(header.width, header.height);
+    let mip_level_count = header.mip_map_count.unwrap_or(1).max(1);
+
+    if size.x == 0 || size.y == 0 {
+        return Err(ImageError::decode("The DDS image has zero dimensions."));
+    }
+    if header.caps2.intersects(Caps2::CUBEMAP | Caps2::VOLUME) {
+        return Err(ImageError::unsupported(
+            "DDS cube maps and volume textures are not supported.",
+        ));
+    }
+    if mip_level_count > mip_level_count_of(size) {
+        return Err(ImageError::decode(format!(
+            "The DDS image has {mip_level_count} mip levels, more than its size allows."
+        )));
+    }
+
+    let pixel_format = &header.spf;
+    let compressed =
+        |linear, srgb_format| Layout::Compressed(if srgb { srgb_format } else { linear });
+    let layout = if let Some(header10) = &dds.header10 {
+        if header10.misc_flag.contains(MiscFlag::TEXTURECUBE) || header10.array_size > 1 {
+            return Err(ImageError::unsupported(
+                "DDS cube maps and texture arrays are not supported.",
+            ));
+        }
+        dx10_layout(header10.dxgi_format)?
+    } else if let Some(FourCC(four_cc)) = pixel_format.fourcc {
+        match four_cc {
+            FourCC::DXT1 => compressed(TextureFormat::Bc1, TextureFormat::Bc1Srgb),
+            FourCC::DXT2 | FourCC::DXT3 => compressed(TextureFormat::Bc2, TextureFormat::Bc2Srgb),
+            FourCC::DXT4 | FourCC::DXT5 => compressed(TextureFormat::Bc3, TextureFormat::Bc3Srgb),
+            FourCC::ATI1 | FourCC::BC4_UNORM => Layout::Compressed(TextureFormat::Bc4),
+            FourCC::ATI2 | BC5U => Layout::Compressed(TextureFormat::Bc5),
+            FourCC::A16B16G16R16F => Layout::Rgba16Float,
+            _ => {
+                return Err(ImageError::unsupported(format!(
+                    "DDS format `{}` is not supported.",
+                    String::from_utf8_lossy(&four_cc.to_le_bytes())
+                )));
+            }
+        }
+    } else if pixel_format
+        .flags
+        .intersects(PixelFormatFlags::RGB | PixelFormatFlags::LUMINANCE)
+    {
+        let bit_count = pixel_format.rgb_bit_count.unwrap_or_default();
+        let has_alpha = pixel_format.flags.contains(PixelFormatFlags::ALPHA_PIXELS);
+        let alpha_mask = if has_alpha {
+            pixel_format.a_bit_mask.unwrap_or_default()
+        } else {
+            0
+        };
+        let is_luminance = pixel_format.flags.contains(PixelFormatFlags::LUMINANCE);
+        let red_mask = pixel_format.r_bit_mask.unwrap_or_default();
+        // Luminance is stored in the red mask and spread to every color channel.
+        let masks = if is_luminance {
+            [red_mask, red_mask, red_mask, alpha_mask]
+        } else {
+            [
+                red_mask,
+                pixel_format.g_bit_mask.unwrap_or_default(),
+                pixel_format.b_bit_mask.unwrap_or_default(),
+                alpha_mask,
+            ]
+        };
+        if !matches!(bit_count, 8 | 16 | 24 | 32) {
+            return Err(ImageError::unsupported(format!(
+                "DDS files with {bit_count} bits per pixel are not supported."
+            )));
+        }
+        if is_luminance && !has_alpha && bit_count == 8 {
+            Layout::Mono8
+        } else {
+            Layout::Masked { bit_count, masks }
+        }
+    } else {
+        return Err(ImageError::unsupported(
+            "DDS pixel format is not supported.",
+        ));
+    };
+
+    let data = dds.data.as_slice();
+    let pixels = match layout {
+        Layout::Compressed(format) => {
+            let len = format.data_len(size, mip_level_count);
+            let Some(data) = data.get(..len) else {
+                return Err(ImageError::decode("The DDS image data is truncated."));
+            };
+            Pixels::Compressed {
+                format,
+                mip_level_count,
+                data: data.to_vec(),
+            }
+        }
+        // Uncompressed files load their first level, like other images.
+        Layout::Rgba8 { srgb } => Pixels::Rgba {
+            data: first_level(data, size, 4)?.to_vec(),
+            srgb,
+        },
+        Layout::Mono8 => Pixels::Mono(first_level(data, size, 1)?.to_vec()),
+        Layout::Rgba16Float => Pixels::RgbaF32(
+            first_level(data, size, 8)?
+                .chunks_exact(2)
+                .map(|half| f16_to_f32(u16::from_le_bytes([half[0], half[1]])))
+                .collect(),
+        ),
+        Layout::Masked { bit_count, masks } => {
+            let pixel_len = bit_count as usize / 8;
+            let data = first_level(data, size, pixel_len)?
+                .chunks_exact(pixel_len)
+                .flat_map(|pixel| {
+                    let mut value = [0; 4];
+                    value[..pixel_len].copy_from_slice(pixel);
+                    let value = u32::from_le_bytes(value);
+                    masks.map(|mask| {
+                        if mask == 0 {
+                            255
+                        } else {
+                            let max = mask >> mask.trailing_zeros();
+                            ((value & mask) >> mask.trailing_zeros()) as u64 * 255 / max as u64
+                        }
+                    })
+                })
+                .map(|channel| channel as u8)
+                .collect();
+            Pixels::Rgba { data, srgb: None }
+        }
+    };
+
+    Ok(DecodedImage { size, pixels })
+}
+
+fn dx10_layout(dxgi_format: DxgiFormat) -> Result<Layout, ImageError> {
+    Ok(match dxgi_format {
+        DxgiFormat::R16G16B16A16_Float => Layout::Rgba16Float,
+        DxgiFormat::R8G8B8A8_UNorm => Layout::Rgba8 { srgb: Some(false) },
+        DxgiFormat::R8G8B8A8_UNorm_sRGB => Layout::Rgba8 { srgb: Some(true) },
+        DxgiFormat::R8_UNorm => Layout::Mono8,
+        DxgiFormat::BC1_UNorm => Layout::Compressed(TextureFormat::Bc1),
+        DxgiFormat::BC1_UNorm_sRGB => Layout::Compressed(TextureFormat::Bc1Srgb),
+        DxgiFormat::BC2_UNorm => Layout::Compressed(TextureFormat::Bc2),
+        DxgiFormat::BC2_UNorm_sRGB => Layout::Compressed(TextureFormat::Bc2Srgb),
+        DxgiFormat::BC3_UNorm => Layout::Compressed(TextureFormat::Bc3),
+        DxgiFormat::BC3_UNorm_sRGB => Layout::Compressed(TextureFormat::Bc3Srgb),
+        DxgiFormat::BC4_UNorm => Layout::Compressed(TextureFormat::Bc4),
+        DxgiFormat::BC5_UNorm => Layout::Compressed(TextureFormat::Bc5),
+        DxgiFormat::BC6H_UF16 => Layout::Compressed(TextureFormat::Bc6h),
+        DxgiFormat::BC7_UNorm => Layout::Compressed(TextureFormat::Bc7),
+        DxgiFormat::BC7_UNorm_sRGB => Layout::Compressed(TextureFormat::Bc7Srgb),
+        _ => {
+            return Err(ImageError::unsupported(format!(
+                "DDS DXGI format {dxgi_format:?} is not supported."
+            )));
+        }
+    })
+}
+
+/// Returns the bytes of the first level of an uncompressed image.
+fn first_level(data: &[u8], size: UVec2, pixel_len: usize) -> Result<&[u8], ImageError> {
+    data.get(..size.x as usize * size.y as usize * pixel_len)
+        .ok_or_else(|| ImageError::decode("The DDS image data is truncated."))
+}
@@ -0,0 +1,131 @@
+//! KTX2 decoding, for 2D textures without supercompression, with their mip levels.
+
+use glam::UVec2;
+use ktx2::{Format, Reader};
+
+use super::{DecodedImage, ImageError, Pixels, f16_to_f32, mip_level_count_of};
+use crate::textures::TextureFormat;
+
+pub(super) const SIGNATURE: &[u8] = b"\xabKTX 20\xbb\r\n\x1a\n";
+
+/// How the pixels of a KTX2 file are laid out, from its `VkFormat`.
+enum Layout {
+    Compressed(TextureFormat),
+    Rgba8 { srgb: bool },
+    Mono8,
+    Rgba16Float,
+    Rgba32Float,
+}
+
+pub(super) fn decode(bytes: &[u8]) -> Result<DecodedImage, ImageError> {
+    let reader = Reader::new(bytes)
+        .map_err(|error| ImageError::decode(format!("Invalid KTX2 file: {error}.")))?;
+    let header = reader.header();
+    let size = UVec2::new(header.pixel_width, header.pixel_height);
+    // A level count of 0 asks for mips to be generated, which only the first level is needed
+    // for.
+    let mip_level_count = header.level_count.max(1);
+
+    if size.y == 0 {
+        return Err(ImageError::unsupported(
+            "1D KTX2 textures are not supported.",
+        ));
+    }
+    if header.pixel_depth > 0 || header.layer_count > 0 || header.face_count != 1 {
+        return Err(ImageError::unsupported(
+            "KTX2 volume textures, texture arrays and cube maps are not supported.",
+        ));
+    }
+    if let Some(scheme) = header.supercompression_scheme {
+        return Err(ImageError::unsupported(format!(
+            "KTX2 supercompression scheme {scheme:?} is not supported."
+        )));
+    }
+    if mip_level_count > mip_level_count_of(size) {
+        return Err(ImageError::decode(format!(
+            "The KTX2 image has {mip_level_count} mip levels, more than its size allows."
+        )));
+    }
+
+    let Some(format) = header.format else {
+        return Err(ImageError::unsupported(
+            "KTX2 files without a VkFormat are not supported.",
+        ));
+    };
+    let layout = match format {
+        Format::R8_UNORM => Layout::Mono8,
+        Format::R8G8B8A8_UNORM => Layout::Rgba8 { srgb: false },
+        Format::R8G8B8A8_SRGB => Layout::Rgba8 { srgb: true },
+        Format::R16G16B16A16_SFLOAT => Layout::Rgba16Float,
+        Format::R32G32B32A32_SFLOAT => Layout::Rgba32Float,
+        Format::BC1_RGB_UNORM_BLOCK | Format::BC1_RGBA_UNORM_BLOCK => {
+            Layout::Compressed(TextureFormat::Bc1)
+        }
+        Format::BC1_RGB_SRGB_BLOCK | Format::BC1_RGBA_SRGB_BLOCK => {
+            Layout::Compressed(TextureFormat::Bc1Srgb)
+        }
+        Format::BC2_UNORM_BLOCK => Layout::Compressed(TextureFormat::Bc2),
+        Format::BC2_SRGB_BLOCK => Layout::Compressed(TextureFormat::Bc2Srgb),
+        Format::BC3_UNORM_BLOCK => Layout::Compressed(TextureFormat::Bc3),
+        Format::BC3_SRGB_BLOCK => Layout::Compressed(TextureFormat::Bc3Srgb),
+        Format::BC4_UNORM_BLOCK => Layout::Compressed(TextureFormat::Bc4),
+        Format::BC5_UNORM_BLOCK => Layout::Compressed(TextureFormat::Bc5),
+        Format::BC6H_UFLOAT_BLOCK => Layout::Compressed(TextureFormat::Bc6h),
+        Format::BC7_UNORM_BLOCK => Layout::Compressed(TextureFormat::Bc7),
+        Format::BC7_SRGB_BLOCK => Layout::Compressed(TextureFormat::Bc7Srgb),
+        _ => {
+            return Err(ImageError::unsupported(format!(
+                "KTX2 format {format:?} is not supported."
+            )));
+        }
+    };
+
+    // Levels are indexed from the largest; the reader has checked that each one is in bounds.
+    let first = || reader.levels().next().map_or(&[][..], |level| level.data);
+
+    let pixels = match layout {
+        Layout::Compressed(format) => {
+            let data: Vec<u8> = reader
+                .levels()
+                .take(mip_level_count as usize)
+                .flat_map(|level| level.data.iter().copied())
+                .collect();
+            if data.len() != format.data_len(size, mip_level_count) {
+                return Err(ImageError::decode(
+                    "The KTX2 levels don't match the size of the image.",
+                ));
+            }
+            Pixels::Compressed {
+                format,
+                mip_level_count,
+                data,
+            }
+        }
+        // Uncompressed files load their first level, like other images.
+        Layout::Rgba8 { srgb } => Pixels::Rgba {
+            data: first_level(first(), size, 4)?.to_vec(),
+            srgb: Some(srgb),
+        },
+        Layout::Mono8 => Pixels::Mono(first_level(first(), size, 1)?.to_vec()),
+        Layout::Rgba16Float => Pixels::RgbaF32(
+            first_level(first(), size, 8)?
+                .chunks_exact(2)
+                .map(|half| f16_to_f32(u16::from_le_bytes([half[0], half[1]])))
+                .collect(),
+        ),
+        Layout::Rgba32Float => Pixels::RgbaF32(
+            first_level(first(), size, 16)?
+                .chunks_exact(4)
+                .map(|float| f32::from_le_bytes([float[0], float[1], float[2], float[3]]))
+                .collect(),
+        ),
+    };
+
+    Ok(DecodedImage { size, pixels })
+}
+
+/// Returns the bytes of the first level of an uncompressed image.
+fn first_level(data: &[u8], size: UVec2, pixel_len: usize) -> Result<&[u8], ImageError> {
+    data.get(..size.x as usize * size.y as usize * pixel_len)
+        .ok_or_else(|| ImageError::decode("The KTX2 image data is truncated."))
+}
@@ -0,0 +1,642 @@
+//! Decoding image files into textures.
+//!
+//! [`DrawListRenderer::create_texture_from_path`] and
+//! [`DrawListRenderer::create_texture_from_image_bytes`] recognize PNG, JPEG, TGA, Radiance HDR,
+//! DDS and KTX2 files by their contents, and pick the [`TextureFormat`] that matches:
+//!
+//! - Grayscale images without alpha become [`TextureFormat::Mono`], which is sampled from the
+//!   red channel.
+//! - Other images become [`TextureFormat::RgbaSrgb`], or [`TextureFormat::Rgba`] for data such
+//!   as normal maps, as [`ImageOptions::srgb`] asks.
+//! - HDR images become [`TextureFormat::Rgba16Float`].
+//! - DDS and KTX2 files keep their block-compressed format and mip levels. Uncompressed DDS and
+//!   KTX2 files load their first level like other images.
+//!
+//! ```ignore
+//! let albedo = renderer.create_texture_from_path("assets/albedo.png", &ImageOptions {
+//!     generate_mips: true,
+//!     ..Default::default()
+//! })?;
+//! ```
+
+mod dds;
+mod ktx2;
+
+use std::{fmt, path::Path};
+
+use ::image::{DynamicImage, ImageFormat};
+
+use glam::UVec2;
+
+use crate::{
+    DrawListRenderer, TextureId,
+    textures::{TextureFormat, mip_level_size},
+};
+
+/// Why an image could not be turned into a texture.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageErrorKind {
+    /// The image file could not be read from disk.
+    Io,
+    /// The image uses a file format or feature that isn't supported.
+    Unsupported,
+    /// The image data is invalid or truncated.
+    Decode,
+}
+
+/// An image that could not be turned into a texture.
+#[derive(Clone, Debug)]
+pub struct ImageError {
+    /// Name or file path the texture was created with.
+    pub name: String,
+    pub kind: ImageErrorKind,
+    pub message: String,
+}
+
+impl ImageError {
+    fn new(name: &str, kind: ImageErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            kind,
+            message: message.into(),
+        }
+    }
+
+    /// An error of a decoder, which is named once it reaches the renderer.
+    fn decode(message: impl Into<String>) -> Self {
+        Self::new("", ImageErrorKind::Decode, message)
+    }
+
+    fn unsupported(message: impl Into<String>) -> Self {
+        Self::new("", ImageErrorKind::Unsupported, message)
+    }
+
+    fn with_name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.name, self.message)
+    }
+}
+
+impl std::error::Error for ImageError {}
+
+/// How an image is turned into a texture.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ImageOptions {
+    /// Whether 8-bit color images hold sRGB colors, which are converted to linear when sampled.
+    /// Turn this off for data such as normal maps. Files that state their color space, and
+    /// grayscale and HDR images, ignore it.
+    pub srgb: bool,
+    /// Whether to generate a full mip chain by averaging each level into the next. Files that
+    /// store their own mip levels keep them instead.
+    pub generate_mips: bool,
+    /// Whether to multiply color by alpha, for materials with
+    /// [`BlendMode::Premultiplied`](crate::BlendMode::Premultiplied). Not
+    /// supported for block-compressed files.
+    pub premultiply_alpha: bool,
+}
+
+impl Default for ImageOptions {
+    fn default() -> Self {
+        Self {
+            srgb: true,
+            generate_mips: false,
+            premultiply_alpha: false,
+        }
+    }
+}
+
+/// An image as it was decoded from a file, before it is converted into texture data.
+struct DecodedImage {
+    size: UVec2,
+    pixels: Pixels,
+}
+
+enum Pixels {
+    /// One 8-bit channel.
+    Mono(Vec<u8>),
+    /// 8-bit RGBA, in sRGB when `srgb` is `true`, or as the options ask when it is `None`.
+    Rgba { data: Vec<u8>, srgb: Option<bool> },
+    /// Linear floating point RGBA.
+    RgbaF32(Vec<f32>),
+    /// Texture data that is used as it is, with every mip level.
+    Compressed {
+        format: TextureFormat,
+        mip_level_count: u32,
+        data: Vec<u8>,
+    },
+}
+
+impl DrawListRenderer {
+    /// Creates a texture from an image file, named after its path.
+    ///
+    /// See [`DrawListRenderer::create_texture_from_image_bytes`].
+    pub fn create_texture_from_path(
+        &mut self,
+        path: impl AsRef<Path>,
+        options: &ImageOptions,
+    ) -> Result<TextureId, ImageError> {
+        let path = path.as_ref();
+        let name = path.display().to_string();
+        let bytes = std::fs::read(path)
+            .map_err(|error| ImageError::new(&name, ImageErrorKind::Io, error.to_string()))?;
+        self.create_texture_from_image_bytes(&name, &bytes, options)
+    }
+
+    /// Creates a texture from the contents of an image file.
+    ///
+    /// The file format is recognized from `bytes`, and the texture format is picked as the
+    /// [module documentation](crate::image) describes. An [`ImageError`] is returned when the
+    /// image cannot be decoded, or the device cannot create a texture of its format.
+    pub fn create_texture_from_image_bytes(
+        &mut self,
+        name: &str,
+        bytes: &[u8],
+        options: &ImageOptions,
+    ) -> Result<TextureId, ImageError> {
+        let image = decode(bytes, options).map_err(|error| error.with_name(name))?;
+        let size = image.size;
+        let (format, mip_level_count, data) =
+            texture_data(image, options).map_err(|error| error.with_name(name))?;
+
+        // Only the block-compressed formats need a device feature.
+        let required_features = format.to_wgpu().required_features();
+        if !self.device.features().contains(required_features) {
+            return Err(ImageError::new(
+                name,
+                ImageErrorKind::Unsupported,
+                format!("{format:?} textures need a device with `TEXTURE_COMPRESSION_BC`."),
+            ));
+        }
+
+        self.create_texture_with_mips(name, size, format, mip_level_count, &data)
+            .ok_or_else(|| {
+                ImageError::new(
+                    name,
+                    ImageErrorKind::Unsupported,
+                    format!(
+                        "Could not create a {}x{} {format:?} texture.",
+                        size.x, size.y
+                    ),
+                )
+            })
+    }
+}
+
+/// Decodes an image in whichever format its first bytes identify. TGA files have no signature,
+/// so they are recognized by their header.
+fn decode(bytes: &[u8], options: &ImageOptions) -> Result<DecodedImage, ImageError> {
+    if bytes.starts_with(dds::SIGNATURE) {
+        return dds::decode(bytes, options.srgb);
+    }
+    if bytes.starts_with(ktx2::SIGNATURE) {
+        return ktx2::decode(bytes);
+    }
+
+    let format = match ::image::guess_format(bytes) {
+        Ok(format @ (ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::Hdr)) => format,
+        _ if has_tga_header(bytes) => ImageFormat::Tga,
+        _ => return Err(ImageError::unsupported("Unknown image file format.")),
+    };
+    let image = ::image::load_from_memory_with_format(bytes, format).map_err(|error| {
+        let kind = match error {
+            ::image::ImageError::Unsupported(_) | ::image::ImageError::Limits(_) => {
+                ImageErrorKind::Unsupported
+            }
+            _ => ImageErrorKind::Decode,
+        };
+        ImageError::new("", kind, error.to_string())
+    })?;
+
+    let size = UVec2::new(image.width(), image.height());
+    if size.x == 0 || size.y == 0 {
+        return Err(ImageError::decode("The image has zero dimensions."));
+    }
+    let pixels = match image {
+        // Grayscale without alpha is sampled from the red channel.
+        DynamicImage::ImageLuma8(_) | DynamicImage::ImageLuma16(_) => {
+            Pixels::Mono(image.into_luma8().into_raw())
+        }
+        DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_) => {
+            Pixels::RgbaF32(image.into_rgba32f().into_raw())
+        }
+        _ => Pixels::Rgba {
+            data: image.into_rgba8().into_raw(),
+            srgb: None,
+        },
+    };
+
+    Ok(DecodedImage { size, pixels })
+}
+
+/// Returns `true` when `bytes` start with a plausible TGA header: a color map flag, a color
+/// mapped, true color or grayscale image type, and a pixel depth TGA allows.
+fn has_tga_header(bytes: &[u8]) -> bool {
+    bytes.len() >= 18
+        && bytes[1] <= 1
+        && matches!(bytes[2], 1 | 2 | 3 | 9 | 10 | 11)
+        && matches!(bytes[16], 8 | 15 | 16 | 24 | 32)
+}
+
+/// Converts a decoded image into the format, mip level count and data of a texture.
+fn texture_data(
+    image: DecodedImage,
+    options: &ImageOptions,
+) -> Result<(TextureFormat, u32, Vec<u8>), ImageError> {
+    let DecodedImage { size, pixels } = image;
+    let mip_level_count = if options.generate_mips {
+        mip_level_count_of(size)
+    } else {
+        1
+    };
+
+    Ok(match pixels {
+        Pixels::Compressed {
+            format,
+            mip_level_count,
+            data,
+        } => {
+            if options.premultiply_alpha {
+                return Err(ImageError::unsupported(
+                    "Block-compressed images cannot be premultiplied.",
+                ));
+            }
+            (format, mip_level_count, data)
+        }
+
+        Pixels::Mono(data) => {
+            if !options.generate_mips {
+                return Ok((TextureFormat::Mono, 1, data));
+            }
+            let level = data.iter().map(|&value| f32::from(value) / 255.0).collect();
+            let data = mip_chain(size, 1, level)
+                .iter()
+                .flatten()
+                .map(|&value| unorm_to_u8(value))
+                .collect();
+            (TextureFormat::Mono, mip_level_count, data)
+        }
+
+        Pixels::Rgba { data, srgb } => {
+            let srgb = srgb.unwrap_or(options.srgb);
+            let format = if srgb {
+                TextureFormat::RgbaSrgb
+            } else {
+                TextureFormat::Rgba
+            };
+            if !options.generate_mips && !options.premultiply_alpha {
+                return Ok((format, 1, data));
+            }
+
+            // Filtering and premultiplying happen on linear colors.
+            let to_linear: [f32; 256] = std::array::from_fn(|value| {
+                let value = value as f32 / 255.0;
+                if srgb { srgb_to_linear(value) } else { value }
+            });
+            let mut level: Vec<f32> = data
+                .chunks_exact(4)
+                .flat_map(|pixel| {
+                    [
+                        to_linear[pixel[0] as usize],
+                        to_linear[pixel[1] as usize],
+                        to_linear[pixel[2] as usize],
+                        f32::from(pixel[3]) / 255.0,
+                    ]
+                })
+                .collect();
+            if options.premultiply_alpha {
+                premultiply(&mut level);
+            }
+            let levels = if options.generate_mips {
+                mip_chain(size, 4, level)
+            } else {
+                vec![level]
+            };
+
+            let data = levels
+                .iter()
+                .flat_map(|level| level.chunks_exact(4))
+                .flat_map(|pixel| {
+                    let color =
+                        |value: f32| unorm_to_u8(if srgb { linear_to_srgb(value) } else { value });
+                    [
+                        color(pixel[0]),
+                        color(pixel[1]),
+                        color(pixel[2]),
+                        unorm_to_u8(pixel[3]),
+                    ]
+                })
+                .collect();
+            (format, mip_level_count, data)
+        }
+
+        Pixels::RgbaF32(mut level) => {
+            if options.premultiply_alpha {
+                premultiply(&mut level);
+            }
+            let levels = if options.generate_mips {
+                mip_chain(size, 4, level)
+            } else {
+                vec![level]
+            };
+            let data = levels
+                .iter()
+                .flatten()
+                .flat_map(|&value| f32_to_f16(value).to_le_bytes())
+                .collect();
+            (TextureFormat::Rgba16Float, mip_level_count, data)
+        }
+    })
+}
+
+/// Multiplies the color of linear RGBA pixels by their alpha.
+fn premultiply(pixels: &mut [f32]) {
+    for pixel in pixels.chunks_exact_mut(4) {
+        let alpha = pixel[3];
+        pixel[..3].iter_mut().for_each(|value| *value *= alpha);
+    }
+}
+
+/// Returns every mip level of an image of `size`, starting with `level`, by averaging 2x2
+/// pixels of each level into the next.
+fn mip_chain(size: UVec2, channels: usize, level: Vec<f32>) -> Vec<Vec<f32>> {
+    let mut levels = vec![level];
+    for index in 1..mip_level_count_of(size) {
+        let source_size = mip_level_size(size, index - 1);
+        let target_size = mip_level_size(size, index);
+        let source = &levels[levels.len() - 1];
+        let source_width = source_size.x as usize;
+
+        let mut target = Vec::with_capacity(target_size.x as usize * target_size.y as usize);
+        for y in 0..target_size.y as usize {
+            // Levels of width or height 1 average a pixel with itself.
+            let rows = [y * 2, (y * 2 + 1).min(source_size.y as usize - 1)];
+            for x in 0..target_size.x as usize {
+                let columns = [x * 2, (x * 2 + 1).min(source_width - 1)];
+                for channel in 0..channels {
+                    let sum: f32 = rows
+                        .iter()
+                        .flat_map(|row| columns.map(|column| row * source_width + column))
+                        .map(|pixel| source[pixel * channels + channel])
+                        .sum();
+                    target.push(sum / 4.0);
+                }
+            }
+        }
+        levels.push(target);
+    }
+    levels
+}
+
+/// Returns the number of levels of a full mip chain of `size`.
+fn mip_level_count_of(size: UVec2) -> u32 {
+    32 - size.max_element().leading_zeros()
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn unorm_to_u8(value: f32) -> u8 {
+    (value * 255.0).round().clamp(0.0, 255.0) as u8
+}
+
+/// Converts the bits of a half precision float.
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = i32::from((bits >> 10) & 0x1f);
+    let mantissa = f32::from(bits & 0x3ff);
+    sign * match exponent {
+        0 => mantissa * 2.0_f32.powi(-24),
+        0x1f if mantissa == 0.0 => f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => (1.0 + mantissa / 1024.0) * 2.0_f32.powi(exponent - 15),
+    }
+}
+
+/// Converts a float to the bits of the nearest half precision float. Values too large for a
+/// half become infinite.
+pub(crate) fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    if exponent == 0xff {
+        let nan = if mantissa != 0 { 0x200 } else { 0 };
+        return sign | 0x7c00 | nan;
+    }
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+    if exponent <= 0 {
+        // Subnormal halves, or zero.
+        if exponent < -10 {
+            return sign;
+        }
+        let mantissa = mantissa | 0x80_0000;
+        let shift = (14 - exponent) as u32;
+        return sign | ((mantissa + (1 << (shift - 1))) >> shift) as u16;
+    }
+
+    // Round to nearest even; a carry into the exponent rounds up to the next power of two.
+    let half = ((exponent as u32) << 10) | (mantissa >> 13);
+    let remainder = mantissa & 0x1fff;
+    let round_up = remainder > 0x1000 || (remainder == 0x1000 && half & 1 == 1);
+    sign | (half + u32::from(round_up)) as u16
+}
+
+pub(crate) fn read_u32_le(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        bytes.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use ::image::{GrayImage, RgbaImage};
+    use ddsfile::{AlphaMode, D3D10ResourceDimension, D3DFormat, Dds, DxgiFormat};
+
+    use super::*;
+
+    fn encode(image: DynamicImage, format: ImageFormat) -> Vec<u8> {
+        let mut bytes = Cursor::new(Vec::new());
+        image.write_to(&mut bytes, format).unwrap();
+        bytes.into_inner()
+    }
+
+    fn load(bytes: &[u8], options: &ImageOptions) -> (TextureFormat, u32, Vec<u8>) {
+        texture_data(decode(bytes, options).unwrap(), options).unwrap()
+    }
+
+    fn rgba_image() -> DynamicImage {
+        let pixels = [[255, 0, 0, 255], [0, 255, 0, 128], [0, 0, 255, 0], [255; 4]];
+        DynamicImage::ImageRgba8(RgbaImage::from_raw(2, 2, pixels.concat()).unwrap())
+    }
+
+    fn dds_bytes(dds: &Dds) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        dds.write(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn grayscale_images_become_mono_textures() {
+        let image = DynamicImage::ImageLuma8(GrayImage::from_raw(2, 1, vec![10, 200]).unwrap());
+        let bytes = encode(image, ImageFormat::Png);
+        assert_eq!(
+            load(&bytes, &ImageOptions::default()),
+            (TextureFormat::Mono, 1, vec![10, 200])
+        );
+    }
+
+    #[test]
+    fn color_images_follow_the_srgb_option() {
+        let bytes = encode(rgba_image(), ImageFormat::Png);
+        let (format, _, data) = load(&bytes, &ImageOptions::default());
+        assert_eq!(format, TextureFormat::RgbaSrgb);
+        assert_eq!(data, rgba_image().into_bytes());
+
+        let options = ImageOptions {
+            srgb: false,
+            ..Default::default()
+        };
+        assert_eq!(load(&bytes, &options).0, TextureFormat::Rgba);
+    }
+
+    #[test]
+    fn tga_files_are_recognized_by_their_header() {
+        let bytes = encode(rgba_image(), ImageFormat::Tga);
+        assert!(has_tga_header(&bytes));
+        assert_eq!(
+            load(&bytes, &ImageOptions::default()).2,
+            rgba_image().into_bytes()
+        );
+    }
+
+    #[test]
+    fn premultiplying_scales_color_by_alpha() {
+        let bytes = encode(rgba_image(), ImageFormat::Png);
+        let options = ImageOptions {
+            srgb: false,
+            premultiply_alpha: true,
+            ..Default::default()
+        };
+        let (_, _, data) = load(&bytes, &options);
+        assert_eq!(
+            data,
+            [[255, 0, 0, 255], [0, 128, 0, 128], [0, 0, 0, 0], [255; 4]].concat()
+        );
+    }
+
+    #[test]
+    fn generated_mips_average_each_level_into_the_next() {
+        let bytes = encode(rgba_image(), ImageFormat::Png);
+        let options = ImageOptions {
+            srgb: false,
+            generate_mips: true,
+            ..Default::default()
+        };
+        let (_, mip_level_count, data) = load(&bytes, &options);
+        assert_eq!(mip_level_count, 2);
+        assert_eq!(data.len(), (4 + 1) * 4);
+        // (255 + 0 + 0 + 255) / 4 for each color, and (255 + 128 + 0 + 255) / 4 for alpha.
+        assert_eq!(data[16..], [128, 128, 128, 160]);
+    }
+
+    #[test]
+    fn compressed_dds_files_keep_their_format_and_mips() {
+        let dds = Dds::new_dxgi(ddsfile::NewDxgiParams {
+            height: 8,
+            width: 8,
+            depth: None,
+            format: DxgiFormat::BC7_UNorm_sRGB,
+            mipmap_levels: Some(4),
+            array_layers: None,
+            caps2: None,
+            is_cubemap: false,
+            resource_dimension: D3D10ResourceDimension::Texture2D,
+            alpha_mode: AlphaMode::Unknown,
+        })
+        .unwrap();
+        let (format, mip_level_count, data) = load(&dds_bytes(&dds), &ImageOptions::default());
+        assert_eq!(format, TextureFormat::Bc7Srgb);
+        assert_eq!(mip_level_count, 4);
+        assert_eq!(
+            data.len(),
+            TextureFormat::Bc7Srgb.data_len(UVec2::splat(8), 4)
+        );
+    }
+
+    #[test]
+    fn legacy_dds_files_follow_the_srgb_option() {
+        let dds = Dds::new_d3d(ddsfile::NewD3dParams {
+            height: 4,
+            width: 4,
+            depth: None,
+            format: D3DFormat::DXT1,
+            mipmap_levels: None,
+            caps2: None,
+        })
+        .unwrap();
+        let bytes = dds_bytes(&dds);
+        assert_eq!(
+            load(&bytes, &ImageOptions::default()).0,
+            TextureFormat::Bc1Srgb
+        );
+
+        let options = ImageOptions {
+            srgb: false,
+            ..Default::default()
+        };
+        assert_eq!(load(&bytes, &options).0, TextureFormat::Bc1);
+    }
+
+    #[test]
+    fn compressed_images_cannot_be_premultiplied() {
+        let dds = Dds::new_d3d(ddsfile::NewD3dParams {
+            height: 4,
+            width: 4,
+            depth: None,
+            format: D3DFormat::DXT5,
+            mipmap_levels: None,
+            caps2: None,
+        })
+        .unwrap();
+        let options = ImageOptions {
+            premultiply_alpha: true,
+            ..Default::default()
+        };
+        let image = decode(&dds_bytes(&dds), &options).unwrap();
+        let error = texture_data(image, &options).unwrap_err();
+        assert_eq!(error.kind, ImageErrorKind::Unsupported);
+    }
+
+    #[test]
+    fn unknown_files_are_unsupported() {
+        let error = decode(b"not an image", &ImageOptions::default())
+            .err()
+            .unwrap();
+        assert_eq!(error.kind, ImageErrorKind::Unsupported);
+    }
+}
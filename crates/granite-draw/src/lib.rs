@@ -1,7 +1,8 @@
 //! Higher-level draw-list renderer built on top of owned `wgpu` device and queue handles.
 //!
 //! This crate provides stable resource handles, materials, meshes, render targets, and draw-list
-//! submission into a user-provided [`FrameContext`].
+//! submission into a user-provided [`FrameContext`], optionally ordered by a
+//! [`render_graph::RenderGraph`].
 
 use std::collections::HashMap;
 
@@ -15,17 +16,41 @@ use crate::{
 };
 
 mod bindings;
+pub mod cache_budget;
+pub mod capture;
 mod commands;
 mod common;
+pub mod compute;
 pub mod depth_buffer;
+mod device_lost;
+mod draw_bundle;
+mod draw_encoder;
 pub mod draw_list;
+mod draw_sort;
 mod execution;
+pub mod image;
 pub mod mesh;
+pub mod model;
+pub mod picking;
+pub mod pipeline_cache;
+pub mod post_process;
 mod prepared_draw;
+pub mod profiling;
+mod push_constants;
+pub mod readback;
+pub mod recording;
+pub mod render_graph;
 pub mod render_target;
 mod resources;
 pub mod sampler;
+pub mod shader_error;
+pub mod shader_preprocessor;
+mod shader_reload;
+pub mod stats;
+#[cfg(test)]
+mod test_support;
 pub mod textures;
+pub mod uploads;
 
 /// Handle to a uniform resource.
 pub type UniformId = Id;
@@ -49,6 +74,14 @@ pub type ShaderModuleId = Id;
 pub type VertexShaderId = Id;
 /// Handle to a fragment shader entry-point resource.
 pub type FragmentShaderId = Id;
+/// Handle to a compiled draw bundle.
+pub type DrawBundleId = Id;
+/// Handle to a shader template compiled per define set.
+pub type ShaderTemplateId = Id;
+/// Handle to a compute shader entry-point resource.
+pub type ComputeShaderId = Id;
+/// Handle to a compute material resource.
+pub type ComputeMaterialId = Id;
 
 /// Trait implemented by types that can be uploaded as uniforms.
 pub trait AsUniformBuffer: crate::encase::ShaderType + crate::encase::internal::WriteInto {
@@ -74,6 +107,22 @@ fn encode_uniform_bytes<T: AsUniformBuffer + ?Sized>(
     Ok(buffer.into_inner())
 }
 
+/// Trait implemented by types that can be passed inline with a draw as push constants.
+///
+/// See [`Material::push_constants`].
+pub trait AsPushConstants: crate::encase::ShaderType + crate::encase::internal::WriteInto {
+    /// Shader stage visibility of the push constants.
+    const VISIBILITY: ShaderVisibility;
+
+    /// Encodes the push constants into GPU-ready bytes.
+    fn encode_bytes(&self) -> crate::encase::internal::Result<Vec<u8>> {
+        // Push constants and uniforms share the same host-shareable layout rules.
+        let mut buffer = crate::encase::UniformBuffer::new(Vec::new());
+        buffer.write(self)?;
+        Ok(buffer.into_inner())
+    }
+}
+
 /// Trait implemented by element types that can be uploaded into storage-buffer arrays.
 pub trait AsStorageBufferElement:
     crate::encase::ShaderType + crate::encase::ShaderSize + crate::encase::internal::WriteInto
@@ -125,6 +174,8 @@ impl ShaderVisibility {
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
 struct PipelineLayoutKey {
     bind_group_layouts: Vec<Id>,
+    /// Native push constant range, starting at offset 0.
+    push_constants: Option<(wgpu::ShaderStages, u32)>,
 }
 
 #[must_use]
@@ -138,12 +189,18 @@ pub struct Material {
     pub(crate) bindings: Vec<bindings::DrawBinding>,
     pub(crate) blend_mode: BlendMode,
     pub(crate) depth_state: Option<MaterialDepthState>,
+    pub(crate) push_constants: Option<push_constants::PushConstantLayout>,
+    pub(crate) name: Option<String>,
 }
 
 #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
 enum BindGroupLayoutBindingTypeKey {
     Uniform,
-    StorageBuffer,
+    /// Uniform bound with a dynamic offset, used to emulate push constants.
+    DynamicUniform,
+    StorageBuffer {
+        read_only: bool,
+    },
     Texture,
     Sampler,
 }
@@ -254,6 +311,44 @@ struct BindGroupRecord {
     bind_group: wgpu::BindGroup,
 }
 
+struct BufferRecord {
+    buffer: wgpu::Buffer,
+    label: String,
+    usage: wgpu::BufferUsages,
+    /// A copy of the data the buffer was created with, used to recreate it on a new device and
+    /// to capture it. Uniform buffers keep their latest contents. Other buffers drop the copy
+    /// once they are written again, unless [`DrawListRenderer::set_keep_buffer_contents`] is
+    /// enabled.
+    contents: Vec<u8>,
+}
+
+impl BufferRecord {
+    /// Whether writes to the buffer are copied into its contents. Uniform buffers are small, so
+    /// they always are.
+    fn keeps_contents(&self, keep_buffer_contents: bool) -> bool {
+        keep_buffer_contents || self.usage.contains(wgpu::BufferUsages::UNIFORM)
+    }
+
+    /// Keeps `data` as the contents of the buffer when `keep` is set, or drops the copy.
+    fn set_contents(&mut self, data: &[u8], keep: bool) {
+        if self.keeps_contents(keep) {
+            self.contents.clear();
+            self.contents.extend_from_slice(data);
+        } else {
+            self.contents = Vec::new();
+        }
+    }
+
+    /// The kept contents, or zeros the size of the buffer when they were dropped.
+    fn contents_or_zeroed(&self) -> std::borrow::Cow<'_, [u8]> {
+        if self.contents.is_empty() {
+            std::borrow::Cow::Owned(vec![0; self.buffer.size() as usize])
+        } else {
+            std::borrow::Cow::Borrowed(&self.contents)
+        }
+    }
+}
+
 struct UniformRecord {
     buffer: Id,
     visibility: ShaderVisibility,
@@ -264,14 +359,18 @@ struct StorageBufferRecord {
     buffer: Id,
     min_binding_size: wgpu::BufferSize,
     byte_len: u64,
+    usage: wgpu::BufferUsages,
 }
 
 struct MaterialRecord {
+    /// Used to label the pipelines and bind groups created for the material.
+    name: Option<String>,
     vertex_shader: VertexShaderId,
     fragment_shader: FragmentShaderId,
     bindings: Vec<bindings::DrawBinding>,
     blend_mode: BlendMode,
     depth_state: Option<MaterialDepthState>,
+    push_constants: Option<push_constants::PushConstantLayout>,
 }
 
 struct ResolvedDrawBindGroup {
@@ -282,6 +381,7 @@ struct ResolvedDrawBindGroup {
 
 struct ResolvedDrawBindings {
     bind_groups_to_set: Vec<ResolvedDrawBindGroup>,
+    push_constants: Option<push_constants::ResolvedPushConstants>,
     pipeline_layout_key: PipelineLayoutKey,
 }
 
@@ -289,6 +389,9 @@ struct ResolvedDrawBindings {
 pub struct DrawListRenderer {
     device: wgpu::Device,
     queue: wgpu::Queue,
+    /// Whether buffers keep a copy of data written after creation; see
+    /// [`DrawListRenderer::set_keep_buffer_contents`].
+    keep_buffer_contents: bool,
 
     depth_buffers: StableVec<depth_buffer::DepthBufferRecord>,
     render_targets: StableVec<render_target::RenderTargetRecord>,
@@ -296,20 +399,47 @@ pub struct DrawListRenderer {
     instance_buffer_layouts: StableSet<VertexBufferLayout>,
     bind_group_layouts: StableMap<BindGroupLayoutKey, wgpu::BindGroupLayout>,
     bind_groups: StableMap<BindGroupKey, BindGroupRecord>,
-    buffers: StableVec<wgpu::Buffer>,
+    buffers: StableVec<BufferRecord>,
     uniforms: StableVec<UniformRecord>,
     storage_buffers: StableVec<StorageBufferRecord>,
     textures: StableVec<textures::TextureRecord>,
-    samplers: StableVec<wgpu::Sampler>,
+    samplers: StableVec<sampler::SamplerRecord>,
     materials: StableVec<MaterialRecord>,
     pipeline_layouts: StableMap<PipelineLayoutKey, wgpu::PipelineLayout>,
     meshes: StableVec<mesh::Mesh>,
     shaders: StableVec<resources::ShaderModule>,
     vertex_shaders: StableVec<resources::VertexShader>,
     fragment_shaders: StableVec<resources::FragmentShader>,
+    compute_shaders: StableVec<compute::ComputeShader>,
+    compute_materials: StableVec<compute::ComputeMaterialRecord>,
+    shader_includes: HashMap<String, String>,
+    shader_templates: StableVec<shader_preprocessor::ShaderTemplate>,
+    shader_permutations: HashMap<
+        (ShaderTemplateId, shader_preprocessor::ShaderDefines),
+        shader_preprocessor::ShaderPermutation,
+    >,
+    shader_files: HashMap<ShaderModuleId, shader_reload::WatchedFile>,
+    shader_include_files: HashMap<String, shader_reload::WatchedFile>,
+    draw_bundles: StableVec<draw_bundle::DrawBundleRecord>,
+    transient_textures: StableVec<render_graph::TransientTexture>,
 
     empty_bind_group_layout: Option<Id>,
     render_pipeline_cache: HashMap<RenderPipelineKey, wgpu::RenderPipeline>,
+    compute_pipeline_cache: HashMap<compute::ComputePipelineKey, wgpu::ComputePipeline>,
+    pipeline_cache: Option<wgpu::PipelineCache>,
+    cache_budgets: cache_budget::CacheBudgets,
+    cache_usages: cache_budget::CacheUsages,
+    /// Incremented after every submission; used to find least recently used cache entries.
+    submission_index: u64,
+    profiler: Option<profiling::Profiler>,
+    recorder: Option<recording::Recorder>,
+    capture: capture::CaptureState,
+    uploads: uploads::UploadQueue,
+    push_constant_arena: push_constants::PushConstantArena,
+    readbacks: Vec<readback::PendingReadback>,
+    /// Counters of the submission being recorded.
+    submission_stats: stats::SubmissionStats,
+    last_submission_stats: stats::SubmissionStats,
 }
 
 /// Borrowed surface submission data for executing a draw list.
@@ -331,6 +461,7 @@ impl DrawListRenderer {
     /// Creates a higher-level draw-list renderer from owned `wgpu` handles.
     pub fn new(device: wgpu::Device, queue: wgpu::Queue) -> Self {
         Self {
+            keep_buffer_contents: false,
             device,
             queue,
             depth_buffers: StableVec::default(),
@@ -350,8 +481,30 @@ impl DrawListRenderer {
             shaders: StableVec::default(),
             vertex_shaders: StableVec::default(),
             fragment_shaders: StableVec::default(),
+            compute_shaders: StableVec::default(),
+            compute_materials: StableVec::default(),
+            shader_includes: HashMap::default(),
+            shader_templates: StableVec::default(),
+            shader_permutations: HashMap::default(),
+            shader_files: HashMap::default(),
+            shader_include_files: HashMap::default(),
+            draw_bundles: StableVec::default(),
+            transient_textures: StableVec::default(),
             empty_bind_group_layout: None,
             render_pipeline_cache: HashMap::default(),
+            compute_pipeline_cache: HashMap::default(),
+            pipeline_cache: None,
+            cache_budgets: cache_budget::CacheBudgets::default(),
+            cache_usages: cache_budget::CacheUsages::default(),
+            submission_index: 0,
+            profiler: None,
+            recorder: None,
+            capture: capture::CaptureState::default(),
+            uploads: uploads::UploadQueue::default(),
+            push_constant_arena: push_constants::PushConstantArena::default(),
+            readbacks: Vec::new(),
+            submission_stats: stats::SubmissionStats::default(),
+            last_submission_stats: stats::SubmissionStats::default(),
         }
     }
 }
@@ -16,6 +16,9 @@ pub trait AsVertexBufferLayout:
     const STEP_MODE: VertexStepMode = VertexStepMode::Vertex;
     /// Static list of vertex attributes for this type.
     const ATTRIBUTES: &'static [VertexAttribute];
+    /// What each entry of [`Self::ATTRIBUTES`] holds, used to fill vertices from imported
+    /// [models](crate::model). Empty when the type doesn't describe its attributes.
+    const SEMANTICS: &'static [Option<VertexSemantic>] = &[];
 
     /// Returns the vertex buffer layout metadata for this type.
     fn layout() -> VertexBufferLayout {
@@ -67,6 +70,27 @@ pub struct VertexAttribute {
     pub offset: u64,
 }
 
+/// The meaning of a vertex attribute, which imported [models](crate::model) are mapped onto.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum VertexSemantic {
+    /// Object space position.
+    Position,
+    /// Unit length normal.
+    Normal,
+    /// Unit length tangent, with the handedness of the bitangent in `w`.
+    Tangent,
+    /// First set of texture coordinates.
+    TexCoord0,
+    /// Second set of texture coordinates.
+    TexCoord1,
+    /// Linear RGBA vertex color.
+    Color,
+    /// Indices of the joints that influence the vertex.
+    Joints,
+    /// Weights of the joints that influence the vertex.
+    Weights,
+}
+
 #[derive(Clone, Hash, PartialEq, Eq)]
 /// Compact description of a buffer layout used by higher-level pipeline caches.
 pub struct VertexBufferLayout {
@@ -83,6 +107,10 @@ pub(super) struct Mesh {
     pub vertex_buffer: wgpu::Buffer,
     pub index_buffer: wgpu::Buffer,
     pub index_count: u32,
+    pub name: String,
+    /// Encoded vertices and indices, used to recreate the buffers on a new device.
+    pub vertex_bytes: Vec<u8>,
+    pub index_bytes: Vec<u8>,
 }
 
 impl Mesh {
@@ -96,26 +124,104 @@ impl Mesh {
         let vertex_bytes = V::encode_slice(vertices)
             .unwrap_or_else(|error| panic!("Could not encode vertex buffer `{name}`: {error}"));
         let index_bytes = encode_index_bytes(indices);
+        Self::from_bytes(
+            device,
+            name,
+            vertex_buffer_layout_id,
+            vertex_bytes,
+            index_bytes,
+        )
+    }
 
-        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some(&format!("{name}_vertices")),
-            contents: vertex_bytes.as_slice(),
-            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-        });
-
-        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some(&format!("{name}_indices")),
-            contents: index_bytes.as_slice(),
-            usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
-        });
+    /// Creates a mesh from encoded vertices and little-endian `u32` indices.
+    pub(super) fn from_bytes(
+        device: &wgpu::Device,
+        name: &str,
+        vertex_buffer_layout_id: Id,
+        vertex_bytes: Vec<u8>,
+        index_bytes: Vec<u8>,
+    ) -> Self {
+        let (vertex_buffer, index_buffer) =
+            create_mesh_buffers(device, name, &vertex_bytes, &index_bytes);
 
         Self {
             vertex_buffer_layout_id,
             vertex_buffer,
             index_buffer,
+            index_count: (index_bytes.len() / std::mem::size_of::<u32>()) as u32,
+            name: name.to_string(),
+            vertex_bytes,
+            index_bytes,
+        }
+    }
+
+    /// Creates a mesh whose buffers are allocated but not filled, leaving the encoded vertices
+    /// and indices to be uploaded later.
+    pub(super) fn create_uninitialized<V: AsVertexBufferLayout>(
+        device: &wgpu::Device,
+        name: &str,
+        vertex_buffer_layout_id: Id,
+        vertices: &[V],
+        indices: &[u32],
+    ) -> Self {
+        let vertex_bytes = V::encode_slice(vertices)
+            .unwrap_or_else(|error| panic!("Could not encode vertex buffer `{name}`: {error}"));
+        let index_bytes = encode_index_bytes(indices);
+
+        let create_buffer = |label: String, len: usize, usage: wgpu::BufferUsages| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(&label),
+                size: (len as u64).next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT),
+                usage: usage | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        };
+
+        Self {
+            vertex_buffer_layout_id,
+            vertex_buffer: create_buffer(
+                format!("{name}_vertices"),
+                vertex_bytes.len(),
+                wgpu::BufferUsages::VERTEX,
+            ),
+            index_buffer: create_buffer(
+                format!("{name}_indices"),
+                index_bytes.len(),
+                wgpu::BufferUsages::INDEX,
+            ),
             index_count: indices.len() as u32,
+            name: name.to_string(),
+            vertex_bytes,
+            index_bytes,
         }
     }
+
+    /// Recreates the vertex and index buffers on `device`.
+    pub(super) fn recreate(&mut self, device: &wgpu::Device) {
+        (self.vertex_buffer, self.index_buffer) =
+            create_mesh_buffers(device, &self.name, &self.vertex_bytes, &self.index_bytes);
+    }
+}
+
+fn create_mesh_buffers(
+    device: &wgpu::Device,
+    name: &str,
+    vertex_bytes: &[u8],
+    index_bytes: &[u8],
+) -> (wgpu::Buffer, wgpu::Buffer) {
+    let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some(&format!("{name}_vertices")),
+        contents: vertex_bytes,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+    });
+
+    let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some(&format!("{name}_indices")),
+        contents: index_bytes,
+        usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
+    });
+
+    (vertex_buffer, index_buffer)
 }
 
 pub(super) fn vertex_attributes(
@@ -0,0 +1,757 @@
+//! glTF 2.0 importing, for `.gltf` files with embedded or external buffers, and `.glb` files.
+
+use std::collections::HashMap;
+
+use glam::{Mat4, Quat, Vec3, Vec4};
+
+use super::{
+    AlphaMode, ImportedMesh, ImportedModel, ModelError, ModelImage, ModelInstance, ModelMaterial,
+    Primitive, Resources, TextureReference,
+    json::{self, Value, ValueExt},
+};
+use crate::{image::read_u32_le, mesh::VertexSemantic};
+
+pub(super) const GLB_MAGIC: &[u8] = b"glTF";
+
+const GLB_HEADER_LEN: usize = 12;
+const GLB_CHUNK_JSON: u32 = 0x4e4f_534a;
+const GLB_CHUNK_BIN: u32 = 0x004e_4942;
+
+/// Extensions that don't change how a model must be read, or that are read.
+const SUPPORTED_EXTENSIONS: &[&str] = &["KHR_mesh_quantization", "KHR_materials_emissive_strength"];
+
+/// The vertex attributes that are imported, by their glTF name.
+const ATTRIBUTES: &[(&str, VertexSemantic)] = &[
+    ("POSITION", VertexSemantic::Position),
+    ("NORMAL", VertexSemantic::Normal),
+    ("TANGENT", VertexSemantic::Tangent),
+    ("TEXCOORD_0", VertexSemantic::TexCoord0),
+    ("TEXCOORD_1", VertexSemantic::TexCoord1),
+    ("COLOR_0", VertexSemantic::Color),
+    ("JOINTS_0", VertexSemantic::Joints),
+    ("WEIGHTS_0", VertexSemantic::Weights),
+];
+
+const MODE_TRIANGLES: usize = 4;
+const MODE_TRIANGLE_STRIP: usize = 5;
+const MODE_TRIANGLE_FAN: usize = 6;
+
+const COMPONENT_BYTE: usize = 5120;
+const COMPONENT_UNSIGNED_BYTE: usize = 5121;
+const COMPONENT_SHORT: usize = 5122;
+const COMPONENT_UNSIGNED_SHORT: usize = 5123;
+const COMPONENT_UNSIGNED_INT: usize = 5125;
+const COMPONENT_FLOAT: usize = 5126;
+
+pub(super) fn import_glb(
+    bytes: &[u8],
+    resources: &Resources<'_>,
+) -> Result<ImportedModel, ModelError> {
+    let (Some(version), Some(len)) = (read_u32_le(bytes, 4), read_u32_le(bytes, 8)) else {
+        return Err(ModelError::decode("The GLB header is truncated."));
+    };
+    if version != 2 {
+        return Err(ModelError::unsupported(format!(
+            "GLB version {version} is not supported."
+        )));
+    }
+    let Some(bytes) = bytes.get(..len as usize) else {
+        return Err(ModelError::decode("The GLB file is truncated."));
+    };
+
+    // A JSON chunk comes first, optionally followed by a binary chunk. Other chunks are skipped.
+    let mut chunks = HashMap::new();
+    let mut position = GLB_HEADER_LEN;
+    while position < bytes.len() {
+        let (Some(chunk_len), Some(chunk_type)) = (
+            read_u32_le(bytes, position),
+            read_u32_le(bytes, position + 4),
+        ) else {
+            return Err(ModelError::decode("The GLB chunk header is truncated."));
+        };
+        let start = position + 8;
+        let Some(data) = bytes.get(start..start + chunk_len as usize) else {
+            return Err(ModelError::decode("The GLB chunk is truncated."));
+        };
+        if position == GLB_HEADER_LEN && chunk_type != GLB_CHUNK_JSON {
+            return Err(ModelError::decode("The first GLB chunk is not JSON."));
+        }
+        chunks.entry(chunk_type).or_insert(data);
+        position = start + chunk_len as usize;
+    }
+    let Some(text) = chunks.get(&GLB_CHUNK_JSON) else {
+        return Err(ModelError::decode("The GLB file has no JSON chunk."));
+    };
+    let text = std::str::from_utf8(text)
+        .map_err(|_| ModelError::decode("The GLB JSON chunk is not valid UTF-8."))?;
+
+    import_document(
+        &json::parse(text)?,
+        chunks.get(&GLB_CHUNK_BIN).copied(),
+        resources,
+    )
+}
+
+pub(super) fn import(bytes: &[u8], resources: &Resources<'_>) -> Result<ImportedModel, ModelError> {
+    let text = std::str::from_utf8(bytes)
+        .map_err(|_| ModelError::decode("The glTF file is not valid UTF-8."))?;
+    import_document(&json::parse(text)?, None, resources)
+}
+
+fn import_document(
+    document: &Value,
+    binary_chunk: Option<&[u8]>,
+    resources: &Resources<'_>,
+) -> Result<ImportedModel, ModelError> {
+    let version = document["asset"]["version"].as_str();
+    if !version.is_some_and(|version| version.starts_with("2.")) {
+        return Err(ModelError::unsupported(format!(
+            "glTF version {} is not supported.",
+            version.unwrap_or("unknown")
+        )));
+    }
+    if let Some(extension) = document["extensionsRequired"]
+        .elements()
+        .iter()
+        .filter_map(|extension| extension.as_str())
+        .find(|extension| !SUPPORTED_EXTENSIONS.contains(extension))
+    {
+        return Err(ModelError::unsupported(format!(
+            "The glTF extension `{extension}` is not supported."
+        )));
+    }
+
+    let buffers = document["buffers"]
+        .elements()
+        .iter()
+        .enumerate()
+        .map(|(index, buffer)| load_buffer(index, buffer, binary_chunk, resources))
+        .collect::<Result<Vec<_>, _>>()?;
+    let document = Document { document, buffers };
+
+    let images = document
+        .list("images")
+        .iter()
+        .enumerate()
+        .map(|(index, image)| document.image(index, image, resources))
+        .collect::<Result<Vec<_>, _>>()?;
+    let materials = document
+        .list("materials")
+        .iter()
+        .map(|material| document.material(material))
+        .collect::<Result<Vec<_>, _>>()?;
+    let meshes = document
+        .list("meshes")
+        .iter()
+        .enumerate()
+        .map(|(index, mesh)| document.mesh(index, mesh))
+        .collect::<Result<Vec<_>, _>>()?;
+    let instances = document.instances()?;
+
+    Ok(ImportedModel {
+        meshes,
+        materials,
+        images,
+        instances,
+    })
+}
+
+/// Returns the contents of a buffer, which is either embedded in its URI, an external file,
+/// or the binary chunk of a GLB file.
+fn load_buffer(
+    index: usize,
+    buffer: &Value,
+    binary_chunk: Option<&[u8]>,
+    resources: &Resources<'_>,
+) -> Result<Vec<u8>, ModelError> {
+    let data = match buffer["uri"].as_str() {
+        Some(uri) if uri.starts_with("data:") => decode_data_uri(uri)?,
+        Some(uri) => resources.read(decode_uri(uri))?,
+        None => match binary_chunk {
+            Some(chunk) if index == 0 => chunk.to_vec(),
+            _ => {
+                return Err(ModelError::decode(format!(
+                    "Buffer {index} has no URI and is not the GLB binary chunk."
+                )));
+            }
+        },
+    };
+    let len = buffer["byteLength"].as_usize().unwrap_or(data.len());
+    if data.len() < len {
+        return Err(ModelError::decode(format!(
+            "Buffer {index} is shorter than its byte length."
+        )));
+    }
+    Ok(data)
+}
+
+/// A glTF document with its buffers loaded.
+struct Document<'a> {
+    document: &'a Value,
+    buffers: Vec<Vec<u8>>,
+}
+
+/// Where the elements of an accessor are stored.
+struct Accessor<'a> {
+    /// The bytes from the first element on, or `None` when every element is zero.
+    data: Option<&'a [u8]>,
+    stride: usize,
+    component_type: usize,
+    normalized: bool,
+    components: usize,
+    count: usize,
+}
+
+impl<'a> Document<'a> {
+    /// Returns the elements of a top-level array.
+    fn list(&self, key: &str) -> &'a [Value] {
+        self.document[key].elements()
+    }
+
+    fn item(&self, key: &str, index: usize) -> Result<&'a Value, ModelError> {
+        self.list(key)
+            .get(index)
+            .ok_or_else(|| ModelError::decode(format!("Reference to missing {key} {index}.")))
+    }
+
+    /// Returns the bytes of a buffer view, from `offset` on, and its stride.
+    fn buffer_view(
+        &self,
+        index: usize,
+        offset: usize,
+    ) -> Result<(&[u8], Option<usize>), ModelError> {
+        let view = self.item("bufferViews", index)?;
+        let buffer = view["buffer"]
+            .as_usize()
+            .and_then(|buffer| self.buffers.get(buffer))
+            .ok_or_else(|| ModelError::decode(format!("Buffer view {index} has no buffer.")))?;
+        let start = view["byteOffset"].as_usize().unwrap_or(0);
+        let len = view["byteLength"].as_usize().unwrap_or(0);
+        let data = buffer
+            .get(start..start.saturating_add(len))
+            .and_then(|data| data.get(offset..))
+            .ok_or_else(|| ModelError::decode(format!("Buffer view {index} is out of range.")))?;
+        Ok((data, view["byteStride"].as_usize()))
+    }
+
+    fn accessor(&self, index: usize) -> Result<(Accessor<'_>, &'a Value), ModelError> {
+        let accessor = self.item("accessors", index)?;
+        let component_type = accessor["componentType"].as_usize().unwrap_or(0);
+        let component_len = component_len(component_type).ok_or_else(|| {
+            ModelError::decode(format!(
+                "Accessor {index} has an invalid component type {component_type}."
+            ))
+        })?;
+        let components = match accessor["type"].as_str() {
+            Some("SCALAR") => 1,
+            Some("VEC2") => 2,
+            Some("VEC3") => 3,
+            Some("VEC4") => 4,
+            element_type => {
+                return Err(ModelError::unsupported(format!(
+                    "Accessor {index} has elements of type {}, which are not supported.",
+                    element_type.unwrap_or("unknown")
+                )));
+            }
+        };
+        let count = accessor["count"].as_usize().unwrap_or(0);
+        let element_len = component_len * components;
+
+        let (data, stride) = match accessor["bufferView"].as_usize() {
+            Some(view) => {
+                let offset = accessor["byteOffset"].as_usize().unwrap_or(0);
+                let (data, stride) = self.buffer_view(view, offset)?;
+                let stride = stride.unwrap_or(element_len);
+                let len = match count {
+                    0 => Some(0),
+                    count => stride
+                        .checked_mul(count - 1)
+                        .and_then(|len| len.checked_add(element_len)),
+                };
+                if len.is_none_or(|len| data.len() < len) {
+                    return Err(ModelError::decode(format!(
+                        "Accessor {index} reads past the end of its buffer view."
+                    )));
+                }
+                (Some(data), stride)
+            }
+            None => (None, element_len),
+        };
+
+        Ok((
+            Accessor {
+                data,
+                stride,
+                component_type,
+                normalized: accessor["normalized"].as_bool().unwrap_or(false),
+                components,
+                count,
+            },
+            accessor,
+        ))
+    }
+
+    /// Reads every component of an accessor, applying its sparse substitutions.
+    fn read<T: Copy + Default>(
+        &self,
+        index: usize,
+        read_component: impl Fn(&[u8], usize, bool) -> T,
+    ) -> Result<(Vec<T>, usize), ModelError> {
+        let (accessor, value) = self.accessor(index)?;
+        let component_size = component_len(accessor.component_type).unwrap_or(1);
+        let read_element = |element: &[u8], values: &mut Vec<T>| {
+            for component in element
+                .chunks_exact(component_size)
+                .take(accessor.components)
+            {
+                values.push(read_component(
+                    component,
+                    accessor.component_type,
+                    accessor.normalized,
+                ));
+            }
+        };
+
+        let mut values = Vec::with_capacity(accessor.count * accessor.components);
+        match accessor.data {
+            Some(data) => {
+                for element in 0..accessor.count {
+                    read_element(&data[element * accessor.stride..], &mut values);
+                }
+            }
+            None => values.resize(accessor.count * accessor.components, T::default()),
+        }
+
+        let sparse = &value["sparse"];
+        if !sparse.is_null() {
+            let count = sparse["count"].as_usize().unwrap_or(0);
+            let indices = &sparse["indices"];
+            let index_type = indices["componentType"].as_usize().unwrap_or(0);
+            let index_len = match index_type {
+                COMPONENT_UNSIGNED_BYTE | COMPONENT_UNSIGNED_SHORT | COMPONENT_UNSIGNED_INT => {
+                    component_len(index_type).unwrap_or(1)
+                }
+                _ => {
+                    return Err(ModelError::decode(format!(
+                        "Accessor {index} has invalid sparse indices."
+                    )));
+                }
+            };
+            let sparse_view = |value: &Value| {
+                let view = value["bufferView"].as_usize().ok_or_else(|| {
+                    ModelError::decode(format!("Accessor {index} has invalid sparse data."))
+                })?;
+                let offset = value["byteOffset"].as_usize().unwrap_or(0);
+                self.buffer_view(view, offset).map(|(data, _)| data)
+            };
+            let sparse_indices = sparse_view(indices)?;
+            let sparse_values = sparse_view(&sparse["values"])?;
+            let element_len = component_size * accessor.components;
+            if sparse_indices.len() < count * index_len || sparse_values.len() < count * element_len
+            {
+                return Err(ModelError::decode(format!(
+                    "Accessor {index} reads past the end of its sparse data."
+                )));
+            }
+
+            let mut element = Vec::with_capacity(accessor.components);
+            for (target, replacement) in sparse_indices
+                .chunks_exact(index_len)
+                .zip(sparse_values.chunks_exact(element_len))
+                .take(count)
+            {
+                let target = read_index(target, index_type) as usize;
+                if target >= accessor.count {
+                    return Err(ModelError::decode(format!(
+                        "Accessor {index} has a sparse index out of range."
+                    )));
+                }
+                element.clear();
+                read_element(replacement, &mut element);
+                let start = target * accessor.components;
+                values[start..start + accessor.components].copy_from_slice(&element);
+            }
+        }
+
+        Ok((values, accessor.components))
+    }
+
+    /// Reads the elements of an accessor as floats, padded to four components with zeros, and
+    /// a fourth component of `w`.
+    fn read_vectors(&self, index: usize, w: f32) -> Result<Vec<[f32; 4]>, ModelError> {
+        let (values, components) = self.read(index, read_float)?;
+        Ok(values
+            .chunks_exact(components)
+            .map(|element| {
+                let mut vector = [0.0, 0.0, 0.0, w];
+                vector[..components].copy_from_slice(element);
+                vector
+            })
+            .collect())
+    }
+
+    fn read_indices(&self, index: usize) -> Result<Vec<u32>, ModelError> {
+        let (accessor, _) = self.accessor(index)?;
+        if accessor.components != 1
+            || !matches!(
+                accessor.component_type,
+                COMPONENT_UNSIGNED_BYTE | COMPONENT_UNSIGNED_SHORT | COMPONENT_UNSIGNED_INT
+            )
+        {
+            return Err(ModelError::decode(format!(
+                "Accessor {index} can't hold vertex indices."
+            )));
+        }
+        self.read(index, |bytes, component_type, _| {
+            read_index(bytes, component_type)
+        })
+        .map(|(values, _)| values)
+    }
+
+    fn image(
+        &self,
+        index: usize,
+        image: &Value,
+        resources: &Resources<'_>,
+    ) -> Result<ModelImage, ModelError> {
+        let name = || {
+            image["name"]
+                .as_str()
+                .map_or_else(|| resources.embedded_image_name(index), str::to_string)
+        };
+        if let Some(uri) = image["uri"].as_str() {
+            return Ok(if uri.starts_with("data:") {
+                ModelImage::Embedded {
+                    name: name(),
+                    data: decode_data_uri(uri)?,
+                }
+            } else {
+                ModelImage::Path(resources.path(decode_uri(uri)))
+            });
+        }
+        let Some(view) = image["bufferView"].as_usize() else {
+            return Err(ModelError::decode(format!(
+                "Image {index} has no URI or buffer view."
+            )));
+        };
+        Ok(ModelImage::Embedded {
+            name: name(),
+            data: self.buffer_view(view, 0)?.0.to_vec(),
+        })
+    }
+
+    /// Resolves a texture info object into the image of its texture.
+    fn texture(&self, info: &Value) -> Result<Option<TextureReference>, ModelError> {
+        let Some(texture) = info["index"].as_usize() else {
+            return Ok(None);
+        };
+        let Some(image) = self.item("textures", texture)?["source"].as_usize() else {
+            tracing::warn!("glTF texture {texture} has no image that can be loaded");
+            return Ok(None);
+        };
+        if image >= self.list("images").len() {
+            return Err(ModelError::decode(format!(
+                "Texture {texture} references missing image {image}."
+            )));
+        }
+        Ok(Some(TextureReference {
+            image,
+            tex_coord: info["texCoord"].as_usize().unwrap_or(0) as u32,
+        }))
+    }
+
+    fn material(&self, material: &Value) -> Result<ModelMaterial, ModelError> {
+        let defaults = ModelMaterial::default();
+        let pbr = &material["pbrMetallicRoughness"];
+        let normal = &material["normalTexture"];
+        let occlusion = &material["occlusionTexture"];
+        let emissive_strength =
+            material["extensions"]["KHR_materials_emissive_strength"]["emissiveStrength"]
+                .as_f32()
+                .unwrap_or(1.0);
+        let alpha_mode = match material["alphaMode"].as_str() {
+            Some("MASK") => AlphaMode::Mask {
+                cutoff: material["alphaCutoff"].as_f32().unwrap_or(0.5),
+            },
+            Some("BLEND") => AlphaMode::Blend,
+            _ => AlphaMode::Opaque,
+        };
+
+        Ok(ModelMaterial {
+            name: material["name"].as_str().unwrap_or_default().to_string(),
+            base_color_factor: pbr["baseColorFactor"]
+                .as_f32_array()
+                .map_or(defaults.base_color_factor, Vec4::from),
+            base_color_texture: self.texture(&pbr["baseColorTexture"])?,
+            metallic_factor: pbr["metallicFactor"]
+                .as_f32()
+                .unwrap_or(defaults.metallic_factor),
+            roughness_factor: pbr["roughnessFactor"]
+                .as_f32()
+                .unwrap_or(defaults.roughness_factor),
+            metallic_roughness_texture: self.texture(&pbr["metallicRoughnessTexture"])?,
+            normal_texture: self.texture(normal)?,
+            normal_scale: normal["scale"].as_f32().unwrap_or(defaults.normal_scale),
+            occlusion_texture: self.texture(occlusion)?,
+            occlusion_strength: occlusion["strength"]
+                .as_f32()
+                .unwrap_or(defaults.occlusion_strength),
+            emissive_factor: material["emissiveFactor"]
+                .as_f32_array()
+                .map_or(defaults.emissive_factor, Vec3::from)
+                * emissive_strength,
+            emissive_texture: self.texture(&material["emissiveTexture"])?,
+            alpha_mode,
+            double_sided: material["doubleSided"].as_bool().unwrap_or(false),
+        })
+    }
+
+    fn mesh(&self, index: usize, mesh: &Value) -> Result<ImportedMesh, ModelError> {
+        let name = mesh["name"]
+            .as_str()
+            .map_or_else(|| format!("mesh{index}"), str::to_string);
+
+        let mut primitives = Vec::new();
+        for (primitive_index, primitive) in mesh["primitives"].elements().iter().enumerate() {
+            let mode = primitive["mode"].as_usize().unwrap_or(MODE_TRIANGLES);
+            if !matches!(
+                mode,
+                MODE_TRIANGLES | MODE_TRIANGLE_STRIP | MODE_TRIANGLE_FAN
+            ) {
+                tracing::warn!(
+                    "Skipping primitive {primitive_index} of glTF mesh `{name}`: only triangles \
+                     are supported"
+                );
+                continue;
+            }
+
+            let mut attributes = HashMap::new();
+            for &(attribute, semantic) in ATTRIBUTES {
+                let Some(accessor) = primitive["attributes"][attribute].as_usize() else {
+                    continue;
+                };
+                // A missing fourth component is opaque alpha for colors, and zero otherwise.
+                let w = if semantic == VertexSemantic::Color {
+                    1.0
+                } else {
+                    0.0
+                };
+                attributes.insert(semantic, self.read_vectors(accessor, w)?);
+            }
+            let Some(positions) = attributes.get(&VertexSemantic::Position) else {
+                return Err(ModelError::decode(format!(
+                    "Primitive {primitive_index} of mesh `{name}` has no positions."
+                )));
+            };
+
+            let indices = match primitive["indices"].as_usize() {
+                Some(accessor) => self.read_indices(accessor)?,
+                None => (0..positions.len() as u32).collect(),
+            };
+            let material = primitive["material"].as_usize();
+            if material.is_some_and(|material| material >= self.list("materials").len()) {
+                return Err(ModelError::decode(format!(
+                    "Primitive {primitive_index} of mesh `{name}` references a missing material."
+                )));
+            }
+
+            primitives.push(Primitive {
+                attributes,
+                indices: triangle_list(mode, &indices),
+                material,
+            });
+        }
+
+        Ok(ImportedMesh { name, primitives })
+    }
+
+    /// Places the meshes of the nodes of the default scene, or of every root node when the
+    /// document has no scenes.
+    fn instances(&self) -> Result<Vec<ModelInstance>, ModelError> {
+        let nodes = self.list("nodes");
+        let scenes = self.list("scenes");
+        let roots: Vec<usize> = if scenes.is_empty() {
+            let mut is_child = vec![false; nodes.len()];
+            for child in nodes
+                .iter()
+                .flat_map(|node| node["children"].elements())
+                .filter_map(ValueExt::as_usize)
+            {
+                if let Some(is_child) = is_child.get_mut(child) {
+                    *is_child = true;
+                }
+            }
+            (0..nodes.len()).filter(|&node| !is_child[node]).collect()
+        } else {
+            let scene = self.document["scene"].as_usize().unwrap_or(0);
+            self.item("scenes", scene)?["nodes"]
+                .elements()
+                .iter()
+                .filter_map(ValueExt::as_usize)
+                .collect()
+        };
+
+        let mut instances = Vec::new();
+        let mut stack = roots
+            .into_iter()
+            .map(|node| (node, Mat4::IDENTITY))
+            .collect::<Vec<_>>();
+        // Nodes have at most one parent, so a valid scene visits every node at most once.
+        let mut visits = 0;
+        while let Some((index, parent)) = stack.pop() {
+            visits += 1;
+            if visits > nodes.len() {
+                return Err(ModelError::decode("The glTF node hierarchy has a cycle."));
+            }
+            let node = self.item("nodes", index)?;
+            let transform = parent * node_transform(node);
+            if let Some(mesh) = node["mesh"].as_usize() {
+                if mesh >= self.list("meshes").len() {
+                    return Err(ModelError::decode(format!(
+                        "Node {index} references missing mesh {mesh}."
+                    )));
+                }
+                instances.push(ModelInstance { mesh, transform });
+            }
+            stack.extend(
+                node["children"]
+                    .elements()
+                    .iter()
+                    .filter_map(ValueExt::as_usize)
+                    .map(|child| (child, transform)),
+            );
+        }
+        Ok(instances)
+    }
+}
+
+/// Returns the transform of a node, from its matrix or its translation, rotation and scale.
+fn node_transform(node: &Value) -> Mat4 {
+    if let Some(matrix) = node["matrix"].as_f32_array::<16>() {
+        return Mat4::from_cols_array(&matrix);
+    }
+    let translation = node["translation"]
+        .as_f32_array()
+        .map_or(Vec3::ZERO, Vec3::from);
+    let rotation = node["rotation"]
+        .as_f32_array()
+        .map_or(Quat::IDENTITY, Quat::from_array);
+    let scale = node["scale"].as_f32_array().map_or(Vec3::ONE, Vec3::from);
+    Mat4::from_scale_rotation_translation(scale, rotation, translation)
+}
+
+/// Converts the indices of a triangle list, strip or fan into a triangle list.
+fn triangle_list(mode: usize, indices: &[u32]) -> Vec<u32> {
+    let triangle_count = indices.len().saturating_sub(2);
+    match mode {
+        MODE_TRIANGLE_STRIP => (0..triangle_count)
+            .flat_map(|i| {
+                // Every other triangle is flipped to keep the winding order.
+                if i.is_multiple_of(2) {
+                    [indices[i], indices[i + 1], indices[i + 2]]
+                } else {
+                    [indices[i], indices[i + 2], indices[i + 1]]
+                }
+            })
+            .collect(),
+        MODE_TRIANGLE_FAN => (0..triangle_count)
+            .flat_map(|i| [indices[i + 1], indices[i + 2], indices[0]])
+            .collect(),
+        _ => indices[..indices.len() - indices.len() % 3].to_vec(),
+    }
+}
+
+fn component_len(component_type: usize) -> Option<usize> {
+    match component_type {
+        COMPONENT_BYTE | COMPONENT_UNSIGNED_BYTE => Some(1),
+        COMPONENT_SHORT | COMPONENT_UNSIGNED_SHORT => Some(2),
+        COMPONENT_UNSIGNED_INT | COMPONENT_FLOAT => Some(4),
+        _ => None,
+    }
+}
+
+/// Reads a component as a float, mapping normalized integers onto `0..=1` or `-1..=1`.
+fn read_float(bytes: &[u8], component_type: usize, normalized: bool) -> f32 {
+    let value = match component_type {
+        COMPONENT_BYTE => f32::from(bytes[0] as i8),
+        COMPONENT_UNSIGNED_BYTE => f32::from(bytes[0]),
+        COMPONENT_SHORT => f32::from(i16::from_le_bytes([bytes[0], bytes[1]])),
+        COMPONENT_UNSIGNED_SHORT => f32::from(u16::from_le_bytes([bytes[0], bytes[1]])),
+        COMPONENT_UNSIGNED_INT => read_u32_le(bytes, 0).unwrap_or_default() as f32,
+        _ => f32::from_bits(read_u32_le(bytes, 0).unwrap_or_default()),
+    };
+    if !normalized {
+        return value;
+    }
+    match component_type {
+        COMPONENT_BYTE => (value / 127.0).max(-1.0),
+        COMPONENT_UNSIGNED_BYTE => value / 255.0,
+        COMPONENT_SHORT => (value / 32767.0).max(-1.0),
+        COMPONENT_UNSIGNED_SHORT => value / 65535.0,
+        _ => value,
+    }
+}
+
+fn read_index(bytes: &[u8], component_type: usize) -> u32 {
+    match component_type {
+        COMPONENT_UNSIGNED_BYTE => u32::from(bytes[0]),
+        COMPONENT_UNSIGNED_SHORT => u32::from(u16::from_le_bytes([bytes[0], bytes[1]])),
+        _ => read_u32_le(bytes, 0).unwrap_or_default(),
+    }
+}
+
+/// Decodes the contents of a base64 `data:` URI.
+fn decode_data_uri(uri: &str) -> Result<Vec<u8>, ModelError> {
+    let Some((_, data)) = uri
+        .split_once(',')
+        .filter(|(header, _)| header.ends_with(";base64"))
+    else {
+        return Err(ModelError::unsupported(
+            "Only base64 data URIs are supported.",
+        ));
+    };
+
+    let mut bytes = Vec::with_capacity(data.len() / 4 * 3);
+    let mut bits = 0_u32;
+    let mut bit_count = 0;
+    for byte in data.bytes() {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            b'=' => break,
+            _ => return Err(ModelError::decode("Invalid base64 data URI.")),
+        };
+        bits = bits << 6 | u32::from(value);
+        bit_count += 6;
+        if bit_count >= 8 {
+            bit_count -= 8;
+            bytes.push((bits >> bit_count) as u8);
+        }
+    }
+    Ok(bytes)
+}
+
+/// Decodes the `%XX` escapes of a URI.
+fn decode_uri(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut position = 0;
+    while position < bytes.len() {
+        let escaped = (bytes[position] == b'%')
+            .then(|| bytes.get(position + 1..position + 3))
+            .flatten()
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u8::from_str_radix(digits, 16).ok());
+        if let Some(byte) = escaped {
+            decoded.push(byte);
+            position += 3;
+        } else {
+            decoded.push(bytes[position]);
+            position += 1;
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
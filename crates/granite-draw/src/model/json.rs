@@ -0,0 +1,50 @@
+//! Reading the JSON values of glTF documents.
+
+pub(super) use serde_json::Value;
+
+use super::ModelError;
+
+pub(super) fn parse(text: &str) -> Result<Value, ModelError> {
+    serde_json::from_str(text)
+        .map_err(|error| ModelError::decode(format!("Invalid glTF JSON: {error}.")))
+}
+
+/// Conversions of JSON values into the types glTF stores in them.
+pub(super) trait ValueExt {
+    fn as_f32(&self) -> Option<f32>;
+
+    /// Returns the value as an index or count, when it is a non-negative integer.
+    fn as_usize(&self) -> Option<usize>;
+
+    /// Returns the elements of an array, or nothing when this isn't an array.
+    fn elements(&self) -> &[Value];
+
+    /// Returns an array of `N` numbers.
+    fn as_f32_array<const N: usize>(&self) -> Option<[f32; N]>;
+}
+
+impl ValueExt for Value {
+    fn as_f32(&self) -> Option<f32> {
+        self.as_f64().map(|value| value as f32)
+    }
+
+    fn as_usize(&self) -> Option<usize> {
+        self.as_u64().and_then(|value| usize::try_from(value).ok())
+    }
+
+    fn elements(&self) -> &[Value] {
+        self.as_array().map_or(&[], Vec::as_slice)
+    }
+
+    fn as_f32_array<const N: usize>(&self) -> Option<[f32; N]> {
+        let elements = self.elements();
+        if elements.len() != N {
+            return None;
+        }
+        let mut array = [0.0; N];
+        for (value, element) in array.iter_mut().zip(elements) {
+            *value = element.as_f32()?;
+        }
+        Some(array)
+    }
+}
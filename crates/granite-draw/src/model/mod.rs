@@ -0,0 +1,867 @@
+//! Importing glTF 2.0 and OBJ models into meshes.
+//!
+//! [`DrawListRenderer::create_model_from_path`] and
+//! [`DrawListRenderer::create_model_from_bytes`] recognize `.glb`, `.gltf` and `.obj` files by
+//! their contents, and create a mesh for every primitive of the model, so a mesh with several
+//! materials is split into one [`SubMesh`] per material.
+//!
+//! Vertices are written into the layout of a user vertex type, by the [`VertexSemantic`] of
+//! each of its attributes. Attributes without a semantic, and semantics the model doesn't have,
+//! are filled with defaults: white for colors, and zero otherwise. Missing normals are
+//! generated flat, and missing tangents are generated from the first texture coordinates.
+//!
+//! ```ignore
+//! #[vertex_buffer]
+//! struct Vertex {
+//!     #[layout(semantic = Position)]
+//!     position: Vec3,
+//!     #[layout(semantic = Normal)]
+//!     normal: Vec3,
+//!     #[layout(semantic = TexCoord0)]
+//!     uv: Vec2,
+//! }
+//!
+//! let model = renderer.create_model_from_path::<Vertex>("assets/helmet.glb")?;
+//! ```
+//!
+//! Materials are returned as parameters with references into [`Model::images`], which can be
+//! turned into textures with [`DrawListRenderer::create_texture_from_model_image`].
+
+mod gltf;
+mod json;
+mod obj;
+
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use glam::{Mat4, Vec2, Vec3, Vec4};
+
+use crate::{
+    DrawListRenderer, MeshId, TextureId,
+    image::{ImageError, ImageOptions, f32_to_f16},
+    mesh::{AsVertexBufferLayout, Mesh, VertexAttribute, VertexFormat, VertexSemantic},
+};
+
+/// Why a model could not be imported.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ModelErrorKind {
+    /// The model, or a file it references, could not be read from disk.
+    Io,
+    /// The model uses a file format or feature that isn't supported.
+    Unsupported,
+    /// The model data is invalid or truncated.
+    Decode,
+    /// The vertex type has no position semantic, or an attribute format that semantics can't
+    /// be written into.
+    Layout,
+}
+
+/// A model that could not be imported.
+#[derive(Clone, Debug)]
+pub struct ModelError {
+    /// Name or file path the model was imported with.
+    pub name: String,
+    pub kind: ModelErrorKind,
+    pub message: String,
+}
+
+impl ModelError {
+    fn new(name: &str, kind: ModelErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            kind,
+            message: message.into(),
+        }
+    }
+
+    /// An error of a parser, which is named once it reaches the renderer.
+    fn decode(message: impl Into<String>) -> Self {
+        Self::new("", ModelErrorKind::Decode, message)
+    }
+
+    fn unsupported(message: impl Into<String>) -> Self {
+        Self::new("", ModelErrorKind::Unsupported, message)
+    }
+
+    fn with_name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+}
+
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.name, self.message)
+    }
+}
+
+impl std::error::Error for ModelError {}
+
+/// The meshes, materials and images of an imported model.
+#[derive(Clone, Debug)]
+pub struct Model {
+    pub meshes: Vec<ModelMesh>,
+    pub materials: Vec<ModelMaterial>,
+    /// Images referenced by the materials, which are not loaded until they are asked for.
+    pub images: Vec<ModelImage>,
+    /// Where the meshes are placed in the model. glTF nodes can place a mesh several times, or
+    /// not at all; OBJ meshes are placed once, as they are.
+    pub instances: Vec<ModelInstance>,
+}
+
+/// A named mesh of a model, split into one sub-mesh per primitive.
+#[derive(Clone, Debug)]
+pub struct ModelMesh {
+    pub name: String,
+    pub sub_meshes: Vec<SubMesh>,
+}
+
+/// A part of a model mesh that is drawn with one material.
+#[derive(Clone, Copy, Debug)]
+pub struct SubMesh {
+    pub mesh: MeshId,
+    /// Index into [`Model::materials`], or `None` for the default material.
+    pub material: Option<usize>,
+}
+
+/// A model mesh placed with a transform.
+#[derive(Clone, Copy, Debug)]
+pub struct ModelInstance {
+    /// Index into [`Model::meshes`].
+    pub mesh: usize,
+    /// Transform from mesh space into model space.
+    pub transform: Mat4,
+}
+
+/// How the alpha of a material's base color is used.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum AlphaMode {
+    /// Alpha is ignored.
+    #[default]
+    Opaque,
+    /// Fragments with an alpha below `cutoff` are discarded, the rest are opaque.
+    Mask { cutoff: f32 },
+    /// Alpha blends with what is behind.
+    Blend,
+}
+
+/// Metallic-roughness material parameters. Factors multiply the matching texture when it is
+/// present.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModelMaterial {
+    pub name: String,
+    /// Linear RGBA base color.
+    pub base_color_factor: Vec4,
+    /// sRGB base color texture.
+    pub base_color_texture: Option<TextureReference>,
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    /// Texture with roughness in its green channel and metalness in its blue channel.
+    pub metallic_roughness_texture: Option<TextureReference>,
+    /// Tangent space normal map.
+    pub normal_texture: Option<TextureReference>,
+    /// Scale of the normal map's X and Y.
+    pub normal_scale: f32,
+    /// Texture with ambient occlusion in its red channel.
+    pub occlusion_texture: Option<TextureReference>,
+    pub occlusion_strength: f32,
+    /// Linear RGB emitted light.
+    pub emissive_factor: Vec3,
+    /// sRGB emissive texture.
+    pub emissive_texture: Option<TextureReference>,
+    pub alpha_mode: AlphaMode,
+    /// Whether back faces are drawn.
+    pub double_sided: bool,
+}
+
+impl Default for ModelMaterial {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            base_color_factor: Vec4::ONE,
+            base_color_texture: None,
+            metallic_factor: 1.0,
+            roughness_factor: 1.0,
+            metallic_roughness_texture: None,
+            normal_texture: None,
+            normal_scale: 1.0,
+            occlusion_texture: None,
+            occlusion_strength: 1.0,
+            emissive_factor: Vec3::ZERO,
+            emissive_texture: None,
+            alpha_mode: AlphaMode::Opaque,
+            double_sided: false,
+        }
+    }
+}
+
+/// A texture of a material.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TextureReference {
+    /// Index into [`Model::images`].
+    pub image: usize,
+    /// Which set of texture coordinates the texture is sampled with.
+    pub tex_coord: u32,
+}
+
+/// An image file referenced by a model.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ModelImage {
+    /// An image file referenced by the model. The path is relative to the model when the model
+    /// was not loaded from a path.
+    Path(PathBuf),
+    /// An image stored in the model itself.
+    Embedded { name: String, data: Vec<u8> },
+}
+
+/// A model as it was parsed from a file, before its meshes are created.
+struct ImportedModel {
+    meshes: Vec<ImportedMesh>,
+    materials: Vec<ModelMaterial>,
+    images: Vec<ModelImage>,
+    instances: Vec<ModelInstance>,
+}
+
+struct ImportedMesh {
+    name: String,
+    primitives: Vec<Primitive>,
+}
+
+/// Triangles with their vertex attributes, every one of which has a value per position.
+struct Primitive {
+    attributes: HashMap<VertexSemantic, Vec<[f32; 4]>>,
+    indices: Vec<u32>,
+    material: Option<usize>,
+}
+
+impl DrawListRenderer {
+    /// Imports a model file, with meshes named after its path.
+    ///
+    /// Images and buffers the model references are looked up next to it. See
+    /// [`DrawListRenderer::create_model_from_bytes`].
+    pub fn create_model_from_path<V: AsVertexBufferLayout>(
+        &mut self,
+        path: impl AsRef<Path>,
+    ) -> Result<Model, ModelError> {
+        let path = path.as_ref();
+        let name = path.display().to_string();
+        let bytes = std::fs::read(path)
+            .map_err(|error| ModelError::new(&name, ModelErrorKind::Io, error.to_string()))?;
+        let directory = path.parent().unwrap_or(Path::new(""));
+        self.create_model::<V>(&name, &bytes, Some(directory))
+    }
+
+    /// Imports a model from the contents of a file, creating a mesh with vertices of type `V`
+    /// for every primitive.
+    ///
+    /// The file format is recognized from `bytes`. Models are self-contained here: glTF files
+    /// that reference external buffers are rejected, OBJ material libraries are skipped, and
+    /// external images keep their relative paths.
+    /// A [`ModelError`] is returned when the model cannot be parsed, or `V` has no attribute
+    /// with a [`VertexSemantic::Position`].
+    pub fn create_model_from_bytes<V: AsVertexBufferLayout>(
+        &mut self,
+        name: &str,
+        bytes: &[u8],
+    ) -> Result<Model, ModelError> {
+        self.create_model::<V>(name, bytes, None)
+    }
+
+    /// Creates a texture from an image of an imported model.
+    pub fn create_texture_from_model_image(
+        &mut self,
+        image: &ModelImage,
+        options: &ImageOptions,
+    ) -> Result<TextureId, ImageError> {
+        match image {
+            ModelImage::Path(path) => self.create_texture_from_path(path, options),
+            ModelImage::Embedded { name, data } => {
+                self.create_texture_from_image_bytes(name, data, options)
+            }
+        }
+    }
+
+    fn create_model<V: AsVertexBufferLayout>(
+        &mut self,
+        name: &str,
+        bytes: &[u8],
+        directory: Option<&Path>,
+    ) -> Result<Model, ModelError> {
+        let attributes = semantic_attributes::<V>().map_err(|error| error.with_name(name))?;
+        let semantics = attributes
+            .iter()
+            .map(|(_, semantic, _)| *semantic)
+            .collect::<Vec<_>>();
+
+        let imported = import(name, bytes, directory).map_err(|error| error.with_name(name))?;
+
+        // Prepare every primitive before creating any mesh, so that no meshes are left behind
+        // when the model turns out to be invalid.
+        let mut encoded = Vec::with_capacity(imported.meshes.len());
+        for mesh in imported.meshes {
+            let mut primitives = Vec::with_capacity(mesh.primitives.len());
+            for mut primitive in mesh.primitives {
+                primitive
+                    .validate()
+                    .map_err(|error| error.with_name(name))?;
+                primitive.complete(&semantics);
+                let vertex_bytes = primitive.encode(V::STRIDE as usize, &attributes);
+                let index_bytes = primitive
+                    .indices
+                    .iter()
+                    .flat_map(|index| index.to_le_bytes())
+                    .collect::<Vec<_>>();
+                primitives.push((vertex_bytes, index_bytes, primitive.material));
+            }
+            encoded.push((mesh.name, primitives));
+        }
+
+        let vertex_buffer_layout_id = self.get_or_create_vertex_buffer_layout(V::layout());
+        let meshes = encoded
+            .into_iter()
+            .map(|(mesh_name, primitives)| {
+                let sub_meshes = primitives
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, (vertex_bytes, index_bytes, material))| {
+                        self.submission_stats.bytes_uploaded +=
+                            (vertex_bytes.len() + index_bytes.len()) as u64;
+                        let mesh = self.meshes.push(Mesh::from_bytes(
+                            &self.device,
+                            &format!("{name}/{mesh_name}/{index}"),
+                            vertex_buffer_layout_id,
+                            vertex_bytes,
+                            index_bytes,
+                        ));
+                        SubMesh { mesh, material }
+                    })
+                    .collect();
+                ModelMesh {
+                    name: mesh_name,
+                    sub_meshes,
+                }
+            })
+            .collect();
+
+        Ok(Model {
+            meshes,
+            materials: imported.materials,
+            images: imported.images,
+            instances: imported.instances,
+        })
+    }
+}
+
+/// Parses a model in whichever format its first bytes identify.
+fn import(name: &str, bytes: &[u8], directory: Option<&Path>) -> Result<ImportedModel, ModelError> {
+    let resources = Resources { name, directory };
+    if bytes.starts_with(gltf::GLB_MAGIC) {
+        gltf::import_glb(bytes, &resources)
+    } else if bytes.trim_ascii_start().starts_with(b"{") {
+        gltf::import(bytes, &resources)
+    } else if let Ok(text) = std::str::from_utf8(bytes) {
+        obj::import(text, &resources)
+    } else {
+        Err(ModelError::unsupported("Unknown model file format."))
+    }
+}
+
+/// Finds the files a model references.
+struct Resources<'a> {
+    name: &'a str,
+    directory: Option<&'a Path>,
+}
+
+impl Resources<'_> {
+    /// Returns the path of a file referenced relative to the model. The path stays relative
+    /// when the model was not loaded from a file.
+    fn path(&self, relative: impl AsRef<Path>) -> PathBuf {
+        match self.directory {
+            Some(directory) => directory.join(relative),
+            None => relative.as_ref().to_path_buf(),
+        }
+    }
+
+    fn read(&self, relative: impl AsRef<Path>) -> Result<Vec<u8>, ModelError> {
+        if self.directory.is_none() {
+            return Err(ModelError::unsupported(format!(
+                "`{}` is an external file, which can only be read for models loaded from a \
+                 path.",
+                relative.as_ref().display()
+            )));
+        }
+        let path = self.path(relative);
+        std::fs::read(&path).map_err(|error| {
+            ModelError::new(
+                "",
+                ModelErrorKind::Io,
+                format!("{}: {error}", path.display()),
+            )
+        })
+    }
+
+    /// Names an image that is stored in the model.
+    fn embedded_image_name(&self, index: usize) -> String {
+        format!("{}/image{index}", self.name)
+    }
+}
+
+impl Primitive {
+    fn vertex_count(&self) -> usize {
+        self.attributes
+            .get(&VertexSemantic::Position)
+            .map_or(0, Vec::len)
+    }
+
+    fn validate(&self) -> Result<(), ModelError> {
+        let vertex_count = self.vertex_count();
+        if let Some((semantic, _)) = self
+            .attributes
+            .iter()
+            .find(|(_, values)| values.len() != vertex_count)
+        {
+            return Err(ModelError::decode(format!(
+                "The {semantic:?} attribute has a different number of values than the \
+                 positions."
+            )));
+        }
+        if self
+            .indices
+            .iter()
+            .any(|&index| index as usize >= vertex_count)
+        {
+            return Err(ModelError::decode("A vertex index is out of range."));
+        }
+        if !self.indices.len().is_multiple_of(3) {
+            return Err(ModelError::decode(
+                "The number of indices is not a multiple of 3.",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Generates the normals and tangents that `semantics` ask for and the primitive lacks.
+    fn complete(&mut self, semantics: &[Option<VertexSemantic>]) {
+        let wants = |semantic| semantics.contains(&Some(semantic));
+        let wants_tangents = wants(VertexSemantic::Tangent)
+            && !self.attributes.contains_key(&VertexSemantic::Tangent)
+            && self.attributes.contains_key(&VertexSemantic::TexCoord0);
+        if (wants(VertexSemantic::Normal) || wants_tangents)
+            && !self.attributes.contains_key(&VertexSemantic::Normal)
+        {
+            self.generate_flat_normals();
+        }
+        if wants_tangents {
+            self.generate_tangents();
+        }
+    }
+
+    /// Gives every triangle its own vertices, with the normal of the triangle.
+    fn generate_flat_normals(&mut self) {
+        let indices = std::mem::take(&mut self.indices);
+        for values in self.attributes.values_mut() {
+            *values = indices
+                .iter()
+                .map(|&index| values[index as usize])
+                .collect();
+        }
+        self.indices = (0..indices.len() as u32).collect();
+
+        let positions = &self.attributes[&VertexSemantic::Position];
+        let normals = positions
+            .chunks_exact(3)
+            .flat_map(|triangle| {
+                let [a, b, c] = [0, 1, 2].map(|corner| Vec4::from(triangle[corner]).truncate());
+                let normal = (b - a).cross(c - a).normalize_or_zero().extend(0.0);
+                [normal.to_array(); 3]
+            })
+            .collect();
+        self.attributes.insert(VertexSemantic::Normal, normals);
+    }
+
+    /// Generates tangents along the direction the first texture coordinates increase in `u`,
+    /// with the sign of the bitangent in `w`.
+    fn generate_tangents(&mut self) {
+        let position = |index: u32| {
+            Vec4::from(self.attributes[&VertexSemantic::Position][index as usize]).truncate()
+        };
+        let uv = |index: u32| {
+            let uv = self.attributes[&VertexSemantic::TexCoord0][index as usize];
+            Vec2::new(uv[0], uv[1])
+        };
+
+        let vertex_count = self.vertex_count();
+        let mut tangents = vec![Vec3::ZERO; vertex_count];
+        let mut bitangents = vec![Vec3::ZERO; vertex_count];
+        for triangle in self.indices.chunks_exact(3) {
+            let edges =
+                [position(triangle[1]), position(triangle[2])].map(|p| p - position(triangle[0]));
+            let uv_edges = [uv(triangle[1]), uv(triangle[2])].map(|p| p - uv(triangle[0]));
+            let determinant = uv_edges[0].perp_dot(uv_edges[1]);
+            if determinant.abs() <= f32::EPSILON {
+                continue;
+            }
+            let tangent = (edges[0] * uv_edges[1].y - edges[1] * uv_edges[0].y) / determinant;
+            let bitangent = (edges[1] * uv_edges[0].x - edges[0] * uv_edges[1].x) / determinant;
+            for &index in triangle {
+                tangents[index as usize] += tangent;
+                bitangents[index as usize] += bitangent;
+            }
+        }
+
+        let normals = &self.attributes[&VertexSemantic::Normal];
+        let tangents = tangents
+            .into_iter()
+            .zip(bitangents)
+            .zip(normals)
+            .map(|((tangent, bitangent), normal)| {
+                let normal = Vec4::from(*normal).truncate();
+                // Make the tangent perpendicular to the normal.
+                let tangent = (tangent - normal * normal.dot(tangent))
+                    .try_normalize()
+                    .unwrap_or_else(|| normal.any_orthonormal_vector());
+                let handedness = if normal.cross(tangent).dot(bitangent) < 0.0 {
+                    -1.0
+                } else {
+                    1.0
+                };
+                tangent.extend(handedness).to_array()
+            })
+            .collect();
+        self.attributes.insert(VertexSemantic::Tangent, tangents);
+    }
+
+    /// Writes the vertices into elements of `stride` bytes, by the semantic of each attribute.
+    fn encode(
+        &self,
+        stride: usize,
+        attributes: &[(VertexAttribute, Option<VertexSemantic>, Encoding)],
+    ) -> Vec<u8> {
+        let vertex_count = self.vertex_count();
+        let mut bytes = vec![0; stride * vertex_count];
+        for (attribute, semantic, encoding) in attributes {
+            let values = semantic.and_then(|semantic| self.attributes.get(&semantic));
+            let default = match semantic {
+                Some(VertexSemantic::Color) => [1.0; 4],
+                Some(VertexSemantic::Tangent) => [1.0, 0.0, 0.0, 1.0],
+                _ => [0.0; 4],
+            };
+            for (vertex, element) in bytes.chunks_exact_mut(stride).enumerate() {
+                let value = values.map_or(default, |values| values[vertex]);
+                encoding.write(&mut element[attribute.offset as usize..], value);
+            }
+        }
+        bytes
+    }
+}
+
+/// How the components of an attribute are stored.
+#[derive(Clone, Copy)]
+enum Component {
+    Float32,
+    Float16,
+    Unorm8,
+    Snorm8,
+    Unorm16,
+    Snorm16,
+    Uint8,
+    Sint8,
+    Uint16,
+    Sint16,
+    Uint32,
+    Sint32,
+}
+
+#[derive(Clone, Copy)]
+struct Encoding {
+    component: Component,
+    count: usize,
+}
+
+impl Encoding {
+    fn of(format: VertexFormat) -> Option<Self> {
+        use VertexFormat as F;
+        let (component, count) = match format {
+            F::Float32 => (Component::Float32, 1),
+            F::Float32x2 => (Component::Float32, 2),
+            F::Float32x3 => (Component::Float32, 3),
+            F::Float32x4 => (Component::Float32, 4),
+            F::Float16 => (Component::Float16, 1),
+            F::Float16x2 => (Component::Float16, 2),
+            F::Float16x4 => (Component::Float16, 4),
+            F::Unorm8 => (Component::Unorm8, 1),
+            F::Unorm8x2 => (Component::Unorm8, 2),
+            F::Unorm8x4 => (Component::Unorm8, 4),
+            F::Snorm8 => (Component::Snorm8, 1),
+            F::Snorm8x2 => (Component::Snorm8, 2),
+            F::Snorm8x4 => (Component::Snorm8, 4),
+            F::Unorm16 => (Component::Unorm16, 1),
+            F::Unorm16x2 => (Component::Unorm16, 2),
+            F::Unorm16x4 => (Component::Unorm16, 4),
+            F::Snorm16 => (Component::Snorm16, 1),
+            F::Snorm16x2 => (Component::Snorm16, 2),
+            F::Snorm16x4 => (Component::Snorm16, 4),
+            F::Uint8 => (Component::Uint8, 1),
+            F::Uint8x2 => (Component::Uint8, 2),
+            F::Uint8x4 => (Component::Uint8, 4),
+            F::Sint8 => (Component::Sint8, 1),
+            F::Sint8x2 => (Component::Sint8, 2),
+            F::Sint8x4 => (Component::Sint8, 4),
+            F::Uint16 => (Component::Uint16, 1),
+            F::Uint16x2 => (Component::Uint16, 2),
+            F::Uint16x4 => (Component::Uint16, 4),
+            F::Sint16 => (Component::Sint16, 1),
+            F::Sint16x2 => (Component::Sint16, 2),
+            F::Sint16x4 => (Component::Sint16, 4),
+            F::Uint32 => (Component::Uint32, 1),
+            F::Uint32x2 => (Component::Uint32, 2),
+            F::Uint32x3 => (Component::Uint32, 3),
+            F::Uint32x4 => (Component::Uint32, 4),
+            F::Sint32 => (Component::Sint32, 1),
+            F::Sint32x2 => (Component::Sint32, 2),
+            F::Sint32x3 => (Component::Sint32, 3),
+            F::Sint32x4 => (Component::Sint32, 4),
+            _ => return None,
+        };
+        Some(Self { component, count })
+    }
+
+    /// Writes the first components of `value` to the start of `bytes`.
+    fn write(self, bytes: &mut [u8], value: [f32; 4]) {
+        let mut position = 0;
+        let mut put = |component: &[u8]| {
+            bytes[position..position + component.len()].copy_from_slice(component);
+            position += component.len();
+        };
+        for value in &value[..self.count] {
+            // Float to integer casts saturate, so out of range values are clamped.
+            match self.component {
+                Component::Float32 => put(&value.to_le_bytes()),
+                Component::Float16 => put(&f32_to_f16(*value).to_le_bytes()),
+                Component::Unorm8 => put(&[(value.clamp(0.0, 1.0) * 255.0).round() as u8]),
+                Component::Snorm8 => {
+                    put(&((value.clamp(-1.0, 1.0) * 127.0).round() as i8).to_le_bytes())
+                }
+                Component::Unorm16 => {
+                    put(&((value.clamp(0.0, 1.0) * 65535.0).round() as u16).to_le_bytes())
+                }
+                Component::Snorm16 => {
+                    put(&((value.clamp(-1.0, 1.0) * 32767.0).round() as i16).to_le_bytes())
+                }
+                Component::Uint8 => put(&[value.round() as u8]),
+                Component::Sint8 => put(&(value.round() as i8).to_le_bytes()),
+                Component::Uint16 => put(&(value.round() as u16).to_le_bytes()),
+                Component::Sint16 => put(&(value.round() as i16).to_le_bytes()),
+                Component::Uint32 => put(&(value.round() as u32).to_le_bytes()),
+                Component::Sint32 => put(&(value.round() as i32).to_le_bytes()),
+            }
+        }
+    }
+}
+
+/// Pairs the attributes of `V` with their semantics and encodings.
+fn semantic_attributes<V: AsVertexBufferLayout>()
+-> Result<Vec<(VertexAttribute, Option<VertexSemantic>, Encoding)>, ModelError> {
+    let layout_error = |message: String| ModelError::new("", ModelErrorKind::Layout, message);
+    let type_name = std::any::type_name::<V>();
+    if !V::SEMANTICS.contains(&Some(VertexSemantic::Position)) {
+        return Err(layout_error(format!(
+            "`{type_name}` has no attribute with a `Position` semantic; mark one with \
+             #[layout(semantic = Position)]."
+        )));
+    }
+    if V::SEMANTICS.len() != V::ATTRIBUTES.len() {
+        return Err(layout_error(format!(
+            "`{type_name}` has {} attributes but {} semantics.",
+            V::ATTRIBUTES.len(),
+            V::SEMANTICS.len()
+        )));
+    }
+
+    V::ATTRIBUTES
+        .iter()
+        .zip(V::SEMANTICS)
+        .map(|(attribute, semantic)| {
+            let encoding = match Encoding::of(attribute.format) {
+                Some(encoding) => encoding,
+                // Attributes without a semantic are left zeroed, whatever their format.
+                None if semantic.is_none() => Encoding {
+                    component: Component::Uint8,
+                    count: 0,
+                },
+                None => {
+                    return Err(layout_error(format!(
+                        "`{type_name}` stores {semantic:?} as {:?}, which model vertices \
+                         can't be written into.",
+                        attribute.format
+                    )));
+                }
+            };
+            Ok((*attribute, *semantic, encoding))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions(primitive: &Primitive) -> Vec<[f32; 3]> {
+        primitive.attributes[&VertexSemantic::Position]
+            .iter()
+            .map(|&[x, y, z, _]| [x, y, z])
+            .collect()
+    }
+
+    /// Packs a glTF document and its binary chunk into a GLB file.
+    fn glb(json: &str, binary: &[u8]) -> Vec<u8> {
+        let pad = |data: &[u8], fill: u8| {
+            let mut data = data.to_vec();
+            data.resize(data.len().next_multiple_of(4), fill);
+            data
+        };
+        let json = pad(json.as_bytes(), b' ');
+        let binary = pad(binary, 0);
+        let len = 12 + 8 + json.len() + 8 + binary.len();
+
+        let mut bytes = Vec::with_capacity(len);
+        bytes.extend_from_slice(b"glTF");
+        bytes.extend_from_slice(&2_u32.to_le_bytes());
+        bytes.extend_from_slice(&(len as u32).to_le_bytes());
+        for (chunk_type, data) in [(b"JSON", json), (b"BIN\0", binary)] {
+            bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(chunk_type);
+            bytes.extend_from_slice(&data);
+        }
+        bytes
+    }
+
+    #[test]
+    fn gltf_strips_become_triangle_lists() {
+        let positions_data: Vec<u8> = [
+            [0.0_f32, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [1.0, 1.0, 0.0],
+        ]
+        .iter()
+        .flatten()
+        .flat_map(|value| value.to_le_bytes())
+        .collect();
+        let json = r#"{
+            "asset": { "version": "2.0" },
+            "buffers": [{ "byteLength": 48 }],
+            "bufferViews": [{ "buffer": 0, "byteLength": 48 }],
+            "accessors": [{ "bufferView": 0, "componentType": 5126, "count": 4, "type": "VEC3" }],
+            "meshes": [{
+                "name": "quad",
+                "primitives": [{ "attributes": { "POSITION": 0 }, "mode": 5, "material": 0 }]
+            }],
+            "materials": [{
+                "emissiveFactor": [1.0, 0.5, 0.0],
+                "extensions": { "KHR_materials_emissive_strength": { "emissiveStrength": 2.0 } },
+                "alphaMode": "MASK"
+            }],
+            "nodes": [{ "mesh": 0, "translation": [0.0, 0.0, 5.0] }],
+            "scenes": [{ "nodes": [0] }]
+        }"#;
+
+        let model = import("quad.glb", &glb(json, &positions_data), None).unwrap();
+        let primitive = &model.meshes[0].primitives[0];
+        assert_eq!(model.meshes[0].name, "quad");
+        assert_eq!(positions(primitive)[3], [1.0, 1.0, 0.0]);
+        // The second triangle of the strip is flipped to keep the winding order.
+        assert_eq!(primitive.indices, [0, 1, 2, 1, 3, 2]);
+        assert_eq!(primitive.material, Some(0));
+
+        let material = &model.materials[0];
+        assert_eq!(material.emissive_factor, Vec3::new(2.0, 1.0, 0.0));
+        assert_eq!(material.alpha_mode, AlphaMode::Mask { cutoff: 0.5 });
+        assert_eq!(model.instances.len(), 1);
+        assert_eq!(
+            model.instances[0].transform,
+            Mat4::from_translation(Vec3::new(0.0, 0.0, 5.0))
+        );
+    }
+
+    #[test]
+    fn gltf_files_with_unknown_required_extensions_are_unsupported() {
+        let json = r#"{
+            "asset": { "version": "2.0" },
+            "extensionsRequired": ["KHR_draco_mesh_compression"]
+        }"#;
+        let error = import("model.gltf", json.as_bytes(), None).err().unwrap();
+        assert_eq!(error.kind, ModelErrorKind::Unsupported);
+    }
+
+    #[test]
+    fn invalid_gltf_json_is_a_decode_error() {
+        let error = import("model.gltf", b"{ \"asset\": ", None).err().unwrap();
+        assert_eq!(error.kind, ModelErrorKind::Decode);
+    }
+
+    #[test]
+    fn obj_faces_are_split_by_material() {
+        let obj = "\
+            v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+            vt 0 0\nvt 1 1\n\
+            o square\n\
+            usemtl red\nf 1/1 2/1 3/2 4/2\n\
+            usemtl blue\nf 1 3 4\n";
+
+        let model = import("square.obj", obj.as_bytes(), None).unwrap();
+        assert_eq!(model.meshes.len(), 1);
+        assert_eq!(model.meshes[0].name, "square");
+        let [red, blue] = &model.meshes[0].primitives[..] else {
+            panic!("expected a primitive per material");
+        };
+        // The quad is split into a fan of two triangles.
+        assert_eq!(red.indices, [0, 1, 2, 0, 2, 3]);
+        // Texture coordinates are flipped to put their origin at the top left.
+        assert_eq!(
+            red.attributes[&VertexSemantic::TexCoord0][2],
+            [1.0, 0.0, 0.0, 0.0]
+        );
+        assert_eq!(blue.indices.len(), 3);
+        assert_eq!(
+            [red.material, blue.material]
+                .map(|material| model.materials[material.unwrap()].name.clone()),
+            ["red", "blue"]
+        );
+    }
+
+    #[test]
+    fn missing_normals_are_generated_flat() {
+        let obj = "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 0 0 1\nf 1 2 3\nf 1 4 2\n";
+        let mut model = import("corner.obj", obj.as_bytes(), None).unwrap();
+        let primitive = &mut model.meshes[0].primitives[0];
+        primitive.complete(&[Some(VertexSemantic::Position), Some(VertexSemantic::Normal)]);
+
+        // Every triangle gets its own vertices, facing the way it winds.
+        assert_eq!(primitive.indices, [0, 1, 2, 3, 4, 5]);
+        let normals = &primitive.attributes[&VertexSemantic::Normal];
+        assert_eq!(normals[0], [0.0, 0.0, 1.0, 0.0]);
+        assert_eq!(normals[3], [0.0, 1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn out_of_range_values_are_clamped_to_their_format() {
+        let mut bytes = [0; 4];
+        Encoding::of(VertexFormat::Unorm8x4)
+            .unwrap()
+            .write(&mut bytes, [-1.0, 0.5, 1.0, 2.0]);
+        assert_eq!(bytes, [0, 128, 255, 255]);
+
+        let mut bytes = [0; 4];
+        Encoding::of(VertexFormat::Snorm16x2)
+            .unwrap()
+            .write(&mut bytes, [-2.0, 1.0, 0.0, 0.0]);
+        assert_eq!(
+            bytes,
+            [(-32767_i16).to_le_bytes(), 32767_i16.to_le_bytes()].concat()[..]
+        );
+    }
+}
@@ -0,0 +1,441 @@
+//! Wavefront OBJ importing, with materials from MTL libraries.
+//!
+//! Every object or group becomes a mesh, with a sub-mesh per material. Polygons are split into
+//! triangle fans, and texture coordinates are flipped to put their origin at the top left, like
+//! glTF's.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use glam::{Mat4, Vec3};
+
+use super::{
+    AlphaMode, ImportedMesh, ImportedModel, ModelError, ModelImage, ModelInstance, ModelMaterial,
+    Primitive, Resources, TextureReference,
+};
+use crate::mesh::VertexSemantic;
+
+pub(super) fn import(text: &str, resources: &Resources<'_>) -> Result<ImportedModel, ModelError> {
+    let mut importer = Importer {
+        resources,
+        positions: Vec::new(),
+        colors: Vec::new(),
+        tex_coords: Vec::new(),
+        normals: Vec::new(),
+        materials: Vec::new(),
+        images: Vec::new(),
+        image_paths: HashMap::new(),
+        meshes: Vec::new(),
+        mesh: MeshBuilder::new("default"),
+        material: None,
+    };
+
+    for (line_index, line) in logical_lines(text) {
+        let error = |message: &str| ModelError::decode(format!("Line {line_index}: {message}"));
+        let line = line.split('#').next().unwrap_or_default();
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        let rest = line.trim_start()[keyword.len()..].trim();
+
+        match keyword {
+            "v" => {
+                let values = parse_floats(tokens).ok_or_else(|| error("Invalid position."))?;
+                let (position, color) = match values[..] {
+                    [x, y, z] | [x, y, z, _] => ([x, y, z], None),
+                    // Some exporters append a vertex color.
+                    [x, y, z, r, g, b] => ([x, y, z], Some([r, g, b])),
+                    _ => return Err(error("Invalid position.")),
+                };
+                importer.positions.push(position);
+                importer.colors.push(color);
+            }
+            "vt" => {
+                let values =
+                    parse_floats(tokens).ok_or_else(|| error("Invalid texture coordinate."))?;
+                let [u, v] = match values[..] {
+                    [u] => [u, 0.0],
+                    [u, v, ..] => [u, v],
+                    _ => return Err(error("Invalid texture coordinate.")),
+                };
+                importer.tex_coords.push([u, 1.0 - v]);
+            }
+            "vn" => {
+                let values = parse_floats(tokens).ok_or_else(|| error("Invalid normal."))?;
+                let [x, y, z] = values[..] else {
+                    return Err(error("Invalid normal."));
+                };
+                importer
+                    .normals
+                    .push(Vec3::new(x, y, z).normalize_or_zero().to_array());
+            }
+            "f" => {
+                let corners = tokens
+                    .map(|corner| importer.corner(corner))
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(|| error("Invalid face."))?;
+                if corners.len() < 3 {
+                    return Err(error("A face has fewer than 3 vertices."));
+                }
+                importer.face(&corners);
+            }
+            "o" | "g" => importer.start_mesh(rest),
+            "usemtl" => importer.material = Some(importer.material_index(rest)),
+            "mtllib" => {
+                for library in rest.split_whitespace() {
+                    if let Err(error) = importer.load_library(library) {
+                        tracing::warn!("Skipping OBJ material library `{library}`: {error}");
+                    }
+                }
+            }
+            // Lines, points, smoothing groups and curves are not imported.
+            _ => {}
+        }
+    }
+    importer.finish_mesh();
+
+    let instances = (0..importer.meshes.len())
+        .map(|mesh| ModelInstance {
+            mesh,
+            transform: Mat4::IDENTITY,
+        })
+        .collect();
+    Ok(ImportedModel {
+        meshes: importer.meshes,
+        materials: importer.materials,
+        images: importer.images,
+        instances,
+    })
+}
+
+/// Returns the non-empty lines with their line number, joining lines that end with a
+/// backslash to the next.
+fn logical_lines(text: &str) -> impl Iterator<Item = (usize, String)> {
+    let mut lines = text.lines().enumerate();
+    std::iter::from_fn(move || {
+        let (index, line) = lines.next()?;
+        let mut line = line.to_string();
+        while line.ends_with('\\') {
+            line.pop();
+            let Some((_, next)) = lines.next() else {
+                break;
+            };
+            line.push(' ');
+            line.push_str(next);
+        }
+        Some((index + 1, line))
+    })
+}
+
+fn parse_floats<'a>(tokens: impl Iterator<Item = &'a str>) -> Option<Vec<f32>> {
+    tokens.map(|token| token.parse().ok()).collect()
+}
+
+/// The position, texture coordinate and normal indices of a face corner.
+type Corner = (usize, Option<usize>, Option<usize>);
+
+struct Importer<'a> {
+    resources: &'a Resources<'a>,
+    positions: Vec<[f32; 3]>,
+    colors: Vec<Option<[f32; 3]>>,
+    tex_coords: Vec<[f32; 2]>,
+    normals: Vec<[f32; 3]>,
+    materials: Vec<ModelMaterial>,
+    images: Vec<ModelImage>,
+    image_paths: HashMap<PathBuf, usize>,
+    meshes: Vec<ImportedMesh>,
+    mesh: MeshBuilder,
+    material: Option<usize>,
+}
+
+impl Importer<'_> {
+    /// Parses a face corner such as `1`, `1/2`, `1//3` or `1/2/3`. Negative indices count back
+    /// from the last element.
+    fn corner(&self, corner: &str) -> Option<Corner> {
+        let resolve = |index: &str, len: usize| -> Option<usize> {
+            let index = index.parse::<i64>().ok()?;
+            let index = if index < 0 {
+                len as i64 + index
+            } else {
+                index - 1
+            };
+            (0..len as i64).contains(&index).then_some(index as usize)
+        };
+        let mut parts = corner.split('/');
+        let position = resolve(parts.next()?, self.positions.len())?;
+        let tex_coord = match parts.next() {
+            None | Some("") => None,
+            Some(index) => Some(resolve(index, self.tex_coords.len())?),
+        };
+        let normal = match parts.next() {
+            None | Some("") => None,
+            Some(index) => Some(resolve(index, self.normals.len())?),
+        };
+        Some((position, tex_coord, normal))
+    }
+
+    fn face(&mut self, corners: &[Corner]) {
+        let sub_mesh = self.mesh.sub_mesh(self.material);
+        let indices = corners
+            .iter()
+            .map(|&corner| {
+                *sub_mesh.vertices.entry(corner).or_insert_with(|| {
+                    sub_mesh.corners.push(corner);
+                    sub_mesh.corners.len() as u32 - 1
+                })
+            })
+            .collect::<Vec<_>>();
+        for i in 1..indices.len() - 1 {
+            sub_mesh
+                .indices
+                .extend([indices[0], indices[i], indices[i + 1]]);
+        }
+    }
+
+    /// Starts a new mesh, unless the current one has no faces yet, in which case it is renamed.
+    fn start_mesh(&mut self, name: &str) {
+        let name = if name.is_empty() { "default" } else { name };
+        if self.mesh.sub_meshes.is_empty() {
+            self.mesh.name = name.to_string();
+            return;
+        }
+        self.finish_mesh();
+        self.mesh = MeshBuilder::new(name);
+    }
+
+    /// Turns the faces of the current mesh into primitives.
+    fn finish_mesh(&mut self) {
+        let mesh = std::mem::replace(&mut self.mesh, MeshBuilder::new(""));
+        if mesh.sub_meshes.is_empty() {
+            return;
+        }
+        let primitives = mesh
+            .sub_meshes
+            .into_iter()
+            .map(|sub_mesh| self.primitive(sub_mesh))
+            .collect();
+        self.meshes.push(ImportedMesh {
+            name: mesh.name,
+            primitives,
+        });
+    }
+
+    fn primitive(&self, sub_mesh: SubMeshBuilder) -> Primitive {
+        let corners = &sub_mesh.corners;
+        let mut attributes = HashMap::new();
+        attributes.insert(
+            VertexSemantic::Position,
+            corners
+                .iter()
+                .map(|&(position, _, _)| {
+                    let [x, y, z] = self.positions[position];
+                    [x, y, z, 0.0]
+                })
+                .collect(),
+        );
+        if corners
+            .iter()
+            .any(|&(position, _, _)| self.colors[position].is_some())
+        {
+            let colors = corners
+                .iter()
+                .map(|&(position, _, _)| {
+                    let [r, g, b] = self.colors[position].unwrap_or([1.0; 3]);
+                    [r, g, b, 1.0]
+                })
+                .collect();
+            attributes.insert(VertexSemantic::Color, colors);
+        }
+        if corners.iter().any(|&(_, tex_coord, _)| tex_coord.is_some()) {
+            let tex_coords = corners
+                .iter()
+                .map(|&(_, tex_coord, _)| {
+                    let [u, v] = tex_coord.map_or([0.0; 2], |index| self.tex_coords[index]);
+                    [u, v, 0.0, 0.0]
+                })
+                .collect();
+            attributes.insert(VertexSemantic::TexCoord0, tex_coords);
+        }
+        // Normals are kept only when every corner has one, so that the rest are generated flat.
+        if corners.iter().all(|&(_, _, normal)| normal.is_some()) {
+            let normals = corners
+                .iter()
+                .map(|&(_, _, normal)| {
+                    let [x, y, z] = normal.map_or([0.0; 3], |index| self.normals[index]);
+                    [x, y, z, 0.0]
+                })
+                .collect();
+            attributes.insert(VertexSemantic::Normal, normals);
+        }
+
+        Primitive {
+            attributes,
+            indices: sub_mesh.indices,
+            material: sub_mesh.material,
+        }
+    }
+
+    /// Returns the index of the material named `name`, adding a default material when no
+    /// library defines it.
+    fn material_index(&mut self, name: &str) -> usize {
+        if let Some(index) = self
+            .materials
+            .iter()
+            .position(|material| material.name == name)
+        {
+            return index;
+        }
+        self.materials.push(obj_material(name));
+        self.materials.len() - 1
+    }
+
+    fn load_library(&mut self, library: &str) -> Result<(), ModelError> {
+        let bytes = self.resources.read(library)?;
+        let text = String::from_utf8_lossy(&bytes);
+        // Textures are found relative to the library.
+        let directory = Path::new(library).parent().unwrap_or(Path::new(""));
+
+        let mut material: Option<ModelMaterial> = None;
+        let mut has_roughness = false;
+        for (_, line) in logical_lines(&text) {
+            let line = line.split('#').next().unwrap_or_default();
+            let mut tokens = line.split_whitespace();
+            let Some(keyword) = tokens.next() else {
+                continue;
+            };
+            let rest = line.trim_start()[keyword.len()..].trim();
+            let values = parse_floats(rest.split_whitespace()).unwrap_or_default();
+
+            if keyword == "newmtl" {
+                if let Some(material) = material.take() {
+                    self.add_material(material);
+                }
+                material = Some(obj_material(rest));
+                has_roughness = false;
+                continue;
+            }
+            let Some(material) = material.as_mut() else {
+                continue;
+            };
+            match (keyword, &values[..]) {
+                ("Kd", &[r, g, b, ..]) => {
+                    material.base_color_factor =
+                        Vec3::new(r, g, b).extend(material.base_color_factor.w);
+                }
+                ("d", &[alpha, ..]) => material.base_color_factor.w = alpha,
+                ("Tr", &[transparency, ..]) => material.base_color_factor.w = 1.0 - transparency,
+                ("Ke", &[r, g, b, ..]) => material.emissive_factor = Vec3::new(r, g, b),
+                ("Pr", &[roughness, ..]) => {
+                    material.roughness_factor = roughness;
+                    has_roughness = true;
+                }
+                ("Pm", &[metallic, ..]) => material.metallic_factor = metallic,
+                // Blinn-Phong shininess, when the library has no PBR roughness.
+                ("Ns", &[shininess, ..]) if !has_roughness => {
+                    material.roughness_factor = (2.0 / (shininess.max(0.0) + 2.0)).sqrt();
+                }
+                ("map_Kd", _) => {
+                    material.base_color_texture = self.texture(directory, rest);
+                }
+                ("map_Ke", _) => material.emissive_texture = self.texture(directory, rest),
+                ("norm" | "map_Bump" | "map_bump" | "bump", _) => {
+                    material.normal_texture = self.texture(directory, rest);
+                    if let Some(scale) = map_option(rest, "-bm") {
+                        material.normal_scale = scale;
+                    }
+                }
+                _ => {}
+            }
+        }
+        if let Some(material) = material {
+            self.add_material(material);
+        }
+        Ok(())
+    }
+
+    /// Adds a material from a library, replacing an earlier one with the same name.
+    fn add_material(&mut self, mut material: ModelMaterial) {
+        if material.base_color_factor.w < 1.0 {
+            material.alpha_mode = AlphaMode::Blend;
+        }
+        let index = self.material_index(&material.name);
+        self.materials[index] = material;
+    }
+
+    /// Returns a reference to the image of a texture map statement, whose file name comes
+    /// after its options.
+    fn texture(&mut self, directory: &Path, statement: &str) -> Option<TextureReference> {
+        let file_name = statement.split_whitespace().last()?;
+        let path = self.resources.path(directory.join(file_name));
+        let image = *self.image_paths.entry(path.clone()).or_insert_with(|| {
+            self.images.push(ModelImage::Path(path));
+            self.images.len() - 1
+        });
+        Some(TextureReference {
+            image,
+            tex_coord: 0,
+        })
+    }
+}
+
+/// Returns the value of a numeric texture map option such as `-bm 0.5`.
+fn map_option(statement: &str, option: &str) -> Option<f32> {
+    let mut tokens = statement.split_whitespace();
+    tokens.find(|&token| token == option)?;
+    tokens.next()?.parse().ok()
+}
+
+/// The defaults of OBJ materials, which are not metallic.
+fn obj_material(name: &str) -> ModelMaterial {
+    ModelMaterial {
+        name: name.to_string(),
+        metallic_factor: 0.0,
+        ..Default::default()
+    }
+}
+
+struct MeshBuilder {
+    name: String,
+    sub_meshes: Vec<SubMeshBuilder>,
+}
+
+impl MeshBuilder {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            sub_meshes: Vec::new(),
+        }
+    }
+
+    /// Returns the sub-mesh with `material`, which is created on first use.
+    fn sub_mesh(&mut self, material: Option<usize>) -> &mut SubMeshBuilder {
+        let index = match self
+            .sub_meshes
+            .iter()
+            .position(|sub_mesh| sub_mesh.material == material)
+        {
+            Some(index) => index,
+            None => {
+                self.sub_meshes.push(SubMeshBuilder {
+                    material,
+                    corners: Vec::new(),
+                    vertices: HashMap::new(),
+                    indices: Vec::new(),
+                });
+                self.sub_meshes.len() - 1
+            }
+        };
+        &mut self.sub_meshes[index]
+    }
+}
+
+/// The faces of a mesh that use one material, with a vertex per distinct corner.
+struct SubMeshBuilder {
+    material: Option<usize>,
+    corners: Vec<Corner>,
+    vertices: HashMap<Corner, u32>,
+    indices: Vec<u32>,
+}
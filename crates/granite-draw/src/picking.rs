@@ -0,0 +1,260 @@
+//! Object picking through an id render target.
+//!
+//! A [`PickingPass`] owns a surface-sized [`RenderTargetFormat::R32Uint`] render target. Scene
+//! objects are drawn into it a second time, with materials whose fragment shaders write an id
+//! instead of a color. Ids can come from a uniform or push constant per draw, or from an
+//! instance attribute for instanced draws, which must be interpolated flat:
+//!
+//! ```ignore
+//! @fragment fn pick(@location(3) @interpolate(flat) id: u32) -> @location(0) u32 {
+//!     return id;
+//! }
+//! ```
+//!
+//! The id `0` is reserved for pixels no object was drawn into. After the frame is submitted,
+//! [`PickingPass::pick`] reads back the id under the cursor, which arrives with a later
+//! submission:
+//!
+//! ```ignore
+//! picking.record_clear(&mut draw_list);
+//! draw_list.draw_mesh_instanced(
+//!     RenderTarget::Custom(picking.render_target()),
+//!     picking_material,
+//!     mesh,
+//!     &instances,
+//! );
+//! renderer.submit_draw_list(frame_context, &draw_list);
+//!
+//! if let Some(position) = input.mouse_position()
+//!     && input.mouse_just_pressed(MouseButton::Left)
+//! {
+//!     picking.pick(&mut renderer, IVec2::new(position.x, position.y));
+//! }
+//! if let Some(pick) = picking.take_pick() {
+//!     selection = pick.id;
+//! }
+//! ```
+
+use glam::{IVec2, UVec2};
+
+use crate::{
+    BlendMode, DrawListRenderer, MaterialId, RenderTargetId,
+    draw_list::{DrawList, RenderTarget},
+    readback::Readback,
+    render_target::{RenderTargetFormat, RenderTargetSize},
+};
+
+const PICKING_CLEAR_SHADER: &str = r"
+@vertex fn fullscreen_vertex(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment fn clear_ids() -> @location(0) u32 {
+    return 0u;
+}
+";
+
+/// The id read back under a position of a [`PickingPass`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Pick {
+    /// The position that was picked, in pixels.
+    pub position: UVec2,
+    /// The id drawn at the position, or `None` when no object was drawn there.
+    pub id: Option<u32>,
+}
+
+/// An id render target with readbacks of the ids under the cursor.
+///
+/// Created by [`DrawListRenderer::create_picking_pass`].
+pub struct PickingPass {
+    render_target: RenderTargetId,
+    clear_material: MaterialId,
+    pending: Option<(UVec2, Readback<Vec<u8>>)>,
+}
+
+impl PickingPass {
+    /// Returns the render target that picking materials draw ids into.
+    pub fn render_target(&self) -> RenderTargetId {
+        self.render_target
+    }
+
+    /// Appends a draw to `draw_list` that resets every id to `0`.
+    ///
+    /// Record it before the draws of every frame that is picked from.
+    pub fn record_clear(&self, draw_list: &mut DrawList) {
+        draw_list.draw(
+            RenderTarget::Custom(self.render_target),
+            self.clear_material,
+            3,
+        );
+    }
+
+    /// Reads back the id at `position`, as drawn by the submissions so far.
+    ///
+    /// Replaces a pick that did not arrive yet. Returns `false` when `position` lies outside
+    /// the render target, such as when the cursor left the window.
+    pub fn pick(&mut self, renderer: &mut DrawListRenderer, position: IVec2) -> bool {
+        let Some(record) = renderer.render_targets.get(self.render_target) else {
+            tracing::warn!("Invalid picking render target ({:?})", self.render_target);
+            return false;
+        };
+        let size = record.size;
+        if position.min_element() < 0 || position.x >= size.x as i32 || position.y >= size.y as i32
+        {
+            return false;
+        }
+
+        let position = position.as_uvec2();
+        let Some(readback) = renderer.read_render_target(self.render_target, position, UVec2::ONE)
+        else {
+            return false;
+        };
+        self.pending = Some((position, readback));
+        true
+    }
+
+    /// Returns the pick once its id arrived from the GPU.
+    ///
+    /// Submissions poll for the readback, so a pick usually arrives with the next frame.
+    pub fn take_pick(&mut self) -> Option<Pick> {
+        let (position, readback) = self.pending.as_mut()?;
+        let position = *position;
+        let result = readback.try_take()?;
+        self.pending = None;
+
+        match result {
+            Ok(bytes) => {
+                let id = u32::from_le_bytes(bytes.try_into().ok()?);
+                Some(Pick {
+                    position,
+                    id: (id != 0).then_some(id),
+                })
+            }
+            Err(error) => {
+                tracing::warn!("Could not read back the picked id: {error}");
+                None
+            }
+        }
+    }
+}
+
+impl DrawListRenderer {
+    /// Creates the surface-sized id render target and the clear material of a picking pass.
+    pub fn create_picking_pass(&mut self, name: &str) -> PickingPass {
+        let render_target = self.create_render_target(
+            &format!("{name}_picking"),
+            RenderTargetSize::SurfaceSize,
+            RenderTargetFormat::R32Uint,
+        );
+        let clear_material = self
+            .create_material_from_shader(&format!("{name}_picking_clear"), PICKING_CLEAR_SHADER)
+            .unwrap_or_else(|error| panic!("Invalid built-in picking shader: {error}"))
+            .blend_mode(BlendMode::Opaque);
+        let clear_material = self.create_material(clear_material);
+
+        PickingPass {
+            render_target,
+            clear_material,
+            pending: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        recording::RecordingSurface,
+        test_support::{SURFACE_SIZE, Scene, gpu_renderer},
+    };
+
+    /// Writes the id `42` into the top left corner of the render target, above the diagonal
+    /// from its top right to its bottom left corner.
+    const CORNER_ID_SHADER: &str = "
+        @vertex
+        fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+            let corner = vec2<f32>(f32(index & 1u), f32(index >> 1u));
+            return vec4<f32>(-1.0 + corner.x * 2.0, 1.0 - corner.y * 2.0, 0.0, 1.0);
+        }
+
+        @fragment
+        fn fs_main() -> @location(0) u32 {
+            return 42u;
+        }
+    ";
+
+    #[test]
+    fn picks_resolve_after_the_frame_that_drew_ids() {
+        let mut scene = Scene::new();
+        let mut picking = scene.renderer.create_picking_pass("scene");
+
+        let mut draw_list = DrawList::default();
+        picking.record_clear(&mut draw_list);
+        let submission = scene.submit(&draw_list);
+        assert_eq!(
+            submission
+                .draws_into(RenderTarget::Custom(picking.render_target()))
+                .count(),
+            1
+        );
+
+        assert!(picking.pick(&mut scene.renderer, IVec2::new(2, 3)));
+        scene.renderer.poll_readbacks();
+        // Only the clear was drawn, so no object is under the position.
+        assert_eq!(
+            picking.take_pick(),
+            Some(Pick {
+                position: UVec2::new(2, 3),
+                id: None,
+            })
+        );
+        assert_eq!(picking.take_pick(), None);
+    }
+
+    #[test]
+    fn picks_return_the_id_drawn_under_the_position() {
+        // The no-op backend of the recording renderer keeps no texture contents to read ids from.
+        let Some(mut renderer) = gpu_renderer() else {
+            eprintln!("Skipped: no graphics adapter to draw ids with.");
+            return;
+        };
+        let surface = RecordingSurface::new(&renderer, SURFACE_SIZE);
+        let mut picking = renderer.create_picking_pass("scene");
+        let material = renderer
+            .create_material_from_shader("corner_id", CORNER_ID_SHADER)
+            .unwrap()
+            .blend_mode(BlendMode::Opaque);
+        let material = renderer.create_material(material);
+
+        let mut draw_list = DrawList::default();
+        picking.record_clear(&mut draw_list);
+        draw_list.draw(RenderTarget::Custom(picking.render_target()), material, 3);
+        renderer.submit_draw_list(surface.frame_context(), &draw_list);
+
+        let mut pick = |position: IVec2| {
+            assert!(picking.pick(&mut renderer, position));
+            renderer
+                .device
+                .poll(wgpu::PollType::wait_indefinitely())
+                .unwrap();
+            renderer.poll_readbacks();
+            picking.take_pick().unwrap().id
+        };
+        assert_eq!(pick(IVec2::new(1, 2)), Some(42));
+        assert_eq!(pick(IVec2::new(6, 5)), None);
+    }
+
+    #[test]
+    fn positions_outside_the_render_target_are_not_picked() {
+        let mut scene = Scene::new();
+        let mut picking = scene.renderer.create_picking_pass("scene");
+        let mut draw_list = DrawList::default();
+        picking.record_clear(&mut draw_list);
+        scene.submit(&draw_list);
+
+        assert!(!picking.pick(&mut scene.renderer, IVec2::new(-1, 0)));
+        assert!(!picking.pick(&mut scene.renderer, IVec2::new(0, 8)));
+        assert_eq!(picking.take_pick(), None);
+    }
+}
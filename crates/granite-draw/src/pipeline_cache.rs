@@ -0,0 +1,314 @@
+//! Avoiding pipeline creation hitches on first draw.
+//!
+//! Render pipelines are created lazily the first time a material is drawn with a particular
+//! mesh layout and target format. Two tools move that cost out of the frame loop:
+//!
+//! - [`DrawListRenderer::warm_up_pipelines`] builds pipelines for known combinations at load
+//!   time.
+//! - [`DrawListRenderer::load_pipeline_cache`] and [`DrawListRenderer::save_pipeline_cache`]
+//!   persist the driver's compiled pipeline data between runs, so pipelines created in later
+//!   launches are cheaper. This needs [`wgpu::Features::PIPELINE_CACHE`] on the device.
+//!
+//! Cache files are named with [`wgpu::util::pipeline_cache_key`], so each adapter and driver
+//! keeps its own file in the cache directory.
+
+use std::path::{Path, PathBuf};
+
+use crate::{
+    DrawListRenderer, MaterialId, RenderPipelineKey,
+    mesh::{AsInstanceBufferLayout, AsVertexBufferLayout, VertexBufferLayout},
+};
+
+/// A combination of material, vertex layouts, and target format to build a pipeline for ahead of
+/// the first draw.
+#[derive(Clone)]
+#[must_use]
+pub struct PipelineWarmUp {
+    material: MaterialId,
+    render_target_format: wgpu::TextureFormat,
+    vertex_buffer_layout: Option<VertexBufferLayout>,
+    instance_buffer_layout: Option<VertexBufferLayout>,
+}
+
+impl PipelineWarmUp {
+    /// Warms up `material` drawn without a mesh into a target of `render_target_format`.
+    ///
+    /// Use [`crate::render_target::RenderTargetFormat`]'s matching `wgpu` format for custom
+    /// render targets, or the surface format for [`crate::draw_list::RenderTarget::Surface`].
+    pub fn new(material: MaterialId, render_target_format: wgpu::TextureFormat) -> Self {
+        Self {
+            material,
+            render_target_format,
+            vertex_buffer_layout: None,
+            instance_buffer_layout: None,
+        }
+    }
+
+    /// Draws meshes with vertices of type `V`.
+    pub fn mesh<V: AsVertexBufferLayout>(mut self) -> Self {
+        self.vertex_buffer_layout = Some(V::layout());
+        self
+    }
+
+    /// Draws instanced with per-instance data of type `I`.
+    pub fn instances<I: AsInstanceBufferLayout>(mut self) -> Self {
+        self.instance_buffer_layout = Some(I::layout());
+        self
+    }
+}
+
+impl DrawListRenderer {
+    /// Creates render pipelines for each combination up front, so the first draw that uses
+    /// them does not stall on pipeline creation.
+    ///
+    /// Combinations that are already cached are skipped. Invalid combinations log a warning.
+    /// Returns the number of pipelines that were created. Warmed-up pipelines are not counted
+    /// in [`crate::stats::SubmissionStats::pipeline_cache_misses`].
+    pub fn warm_up_pipelines(&mut self, warm_ups: &[PipelineWarmUp]) -> usize {
+        warm_ups
+            .iter()
+            .filter(|warm_up| self.warm_up_pipeline(warm_up))
+            .count()
+    }
+
+    fn warm_up_pipeline(&mut self, warm_up: &PipelineWarmUp) -> bool {
+        let Some((draw_bindings, push_constants)) = self
+            .materials
+            .get(warm_up.material)
+            .map(|material| (material.bindings.clone(), material.push_constants))
+        else {
+            tracing::warn!("Invalid material id ({:?})", warm_up.material);
+            return false;
+        };
+
+        let vertex_buffer_layout = warm_up
+            .vertex_buffer_layout
+            .clone()
+            .map(|layout| self.get_or_create_vertex_buffer_layout(layout));
+        let instance_buffer_layout = warm_up
+            .instance_buffer_layout
+            .clone()
+            .map(|layout| self.get_or_create_instance_buffer_layout(layout));
+
+        let Some(pipeline_layout) = self
+            .resolve_pipeline_layout_key(&draw_bindings, push_constants)
+            .and_then(|key| self.get_or_create_pipeline_layout(key))
+        else {
+            tracing::warn!(
+                "Could not warm up a pipeline layout for {:?}",
+                warm_up.material
+            );
+            return false;
+        };
+
+        let Some(material) = self.materials.get(warm_up.material) else {
+            return false;
+        };
+        let key = RenderPipelineKey::for_material(
+            material,
+            warm_up.render_target_format,
+            vertex_buffer_layout,
+            instance_buffer_layout,
+            pipeline_layout,
+        );
+
+        if self.render_pipeline_cache.contains_key(&key) {
+            return false;
+        }
+        // Not created through `ensure_render_pipeline`, so warm-ups between frames are not
+        // counted as pipeline cache misses of the next submission.
+        let Some(render_pipeline) = self.create_render_pipeline(key, material.name.as_deref())
+        else {
+            tracing::warn!(
+                "Could not warm up a render pipeline for {:?}",
+                warm_up.material
+            );
+            return false;
+        };
+        self.render_pipeline_cache.insert(key, render_pipeline);
+        self.touch_render_pipeline(key);
+        true
+    }
+
+    /// Loads pipeline cache data saved by [`DrawListRenderer::save_pipeline_cache`] for
+    /// `adapter_info` from `directory`, and uses it for every pipeline created afterwards.
+    ///
+    /// Pass the info of the adapter the device was requested from. Call this before creating
+    /// pipelines. A missing file starts an empty cache that can be saved later. Returns `false`
+    /// when the device or backend does not support [`wgpu::Features::PIPELINE_CACHE`] or the
+    /// file cannot be read.
+    pub fn load_pipeline_cache(
+        &mut self,
+        directory: impl AsRef<Path>,
+        adapter_info: &wgpu::AdapterInfo,
+    ) -> bool {
+        if !self
+            .device
+            .features()
+            .contains(wgpu::Features::PIPELINE_CACHE)
+        {
+            tracing::debug!("Pipeline caches are not supported by this device");
+            return false;
+        }
+        let Some(path) = pipeline_cache_file(directory.as_ref(), adapter_info) else {
+            tracing::debug!(
+                "Pipeline caches are not supported by the {} backend",
+                adapter_info.backend
+            );
+            return false;
+        };
+
+        let data = match std::fs::read(&path) {
+            Ok(data) => Some(data),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => None,
+            Err(error) => {
+                tracing::warn!(
+                    "Could not read pipeline cache `{}`: {error}",
+                    path.display()
+                );
+                return false;
+            }
+        };
+
+        self.pipeline_cache = Some(self.create_pipeline_cache(data.as_deref()));
+        true
+    }
+
+    /// Creates a pipeline cache from saved `data`, or an empty one.
+    pub(super) fn create_pipeline_cache(&self, data: Option<&[u8]>) -> wgpu::PipelineCache {
+        // SAFETY: `data` must have been returned by `PipelineCache::get_data`. It is only ever
+        // read from a file written by `save_pipeline_cache`, named with the
+        // `pipeline_cache_key` of the adapter, so it came from `get_data` on a compatible
+        // adapter unless the file was modified outside this renderer, which cannot be proven.
+        // wgpu ignores data from another driver version, and `fallback` starts an empty cache
+        // instead of failing in that case.
+        unsafe {
+            self.device
+                .create_pipeline_cache(&wgpu::PipelineCacheDescriptor {
+                    label: Some("pipeline_cache"),
+                    data,
+                    fallback: true,
+                })
+        }
+    }
+
+    /// Writes the pipeline cache loaded with [`DrawListRenderer::load_pipeline_cache`] to the
+    /// file for `adapter_info` in `directory`, including pipelines created since it was loaded.
+    ///
+    /// The file is written next to its final path and then renamed over it, so an interrupted
+    /// save does not leave a truncated cache behind. Returns `false` when no cache is loaded or
+    /// the file cannot be written.
+    pub fn save_pipeline_cache(
+        &self,
+        directory: impl AsRef<Path>,
+        adapter_info: &wgpu::AdapterInfo,
+    ) -> bool {
+        let Some(data) = self
+            .pipeline_cache
+            .as_ref()
+            .and_then(|pipeline_cache| pipeline_cache.get_data())
+        else {
+            return false;
+        };
+        let Some(path) = pipeline_cache_file(directory.as_ref(), adapter_info) else {
+            return false;
+        };
+
+        let temp_path = path.with_extension("tmp");
+        if let Err(error) =
+            std::fs::write(&temp_path, data).and_then(|_| std::fs::rename(&temp_path, &path))
+        {
+            tracing::warn!(
+                "Could not write pipeline cache `{}`: {error}",
+                path.display()
+            );
+            return false;
+        }
+        true
+    }
+}
+
+/// The cache file for `adapter_info` in `directory`, or `None` when its backend has no pipeline
+/// caches.
+fn pipeline_cache_file(directory: &Path, adapter_info: &wgpu::AdapterInfo) -> Option<PathBuf> {
+    wgpu::util::pipeline_cache_key(adapter_info).map(|key| directory.join(key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        draw_list::{DrawList, RenderTarget},
+        test_support::Scene,
+    };
+
+    fn adapter_info(backend: wgpu::Backend, vendor: u32, device: u32) -> wgpu::AdapterInfo {
+        wgpu::AdapterInfo {
+            name: String::from("adapter"),
+            vendor,
+            device,
+            device_type: wgpu::DeviceType::DiscreteGpu,
+            driver: String::new(),
+            driver_info: String::new(),
+            backend,
+        }
+    }
+
+    /// Draws a fill material into the surface, after warming up its pipeline when `warm_up`
+    /// is set, and returns the pipeline cache misses of that first submission.
+    fn first_draw_misses(warm_up: bool) -> u32 {
+        let mut scene = Scene::new();
+        let fill = scene.fill_material("fill");
+        if warm_up {
+            let format = scene.surface.frame_context().format;
+            let warm_ups = [PipelineWarmUp::new(fill, format)];
+            assert_eq!(scene.renderer.warm_up_pipelines(&warm_ups), 1);
+            assert_eq!(scene.renderer.warm_up_pipelines(&warm_ups), 0);
+        }
+
+        let mut draw_list = DrawList::new();
+        draw_list.draw(RenderTarget::Surface, fill, 3);
+        assert_eq!(scene.submit(&draw_list).draws().count(), 1);
+        scene.renderer.stats().last_submission.pipeline_cache_misses
+    }
+
+    #[test]
+    fn warmed_up_pipelines_are_not_missed_on_first_draw() {
+        assert_eq!(first_draw_misses(true), 0);
+    }
+
+    #[test]
+    fn pipelines_are_missed_on_first_draw_without_warm_up() {
+        assert_eq!(first_draw_misses(false), 1);
+    }
+
+    #[test]
+    fn cache_files_are_named_per_adapter() {
+        let directory = Path::new("cache");
+        let first = pipeline_cache_file(directory, &adapter_info(wgpu::Backend::Vulkan, 1, 2));
+        let second = pipeline_cache_file(directory, &adapter_info(wgpu::Backend::Vulkan, 1, 3));
+
+        let first = first.unwrap();
+        assert_eq!(first.parent(), Some(directory));
+        assert_ne!(Some(first), second);
+    }
+
+    #[test]
+    fn backends_without_pipeline_caches_have_no_cache_file() {
+        assert_eq!(
+            pipeline_cache_file(Path::new("cache"), &adapter_info(wgpu::Backend::Noop, 1, 2)),
+            None
+        );
+    }
+
+    #[test]
+    fn unsupported_devices_do_not_load_or_save_caches() {
+        let mut renderer = DrawListRenderer::new_recording().unwrap();
+        let adapter_info = adapter_info(wgpu::Backend::Vulkan, 1, 2);
+        let directory = std::env::temp_dir();
+
+        assert!(!renderer.load_pipeline_cache(&directory, &adapter_info));
+        assert!(renderer.pipeline_cache.is_none());
+        assert!(!renderer.save_pipeline_cache(&directory, &adapter_info));
+    }
+}
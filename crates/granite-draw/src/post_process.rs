@@ -0,0 +1,871 @@
+use std::collections::HashMap;
+
+use glam::Vec4;
+
+use crate::{
+    AsUniformBuffer, BlendMode, DrawListRenderer, FragmentShaderId, Material, MaterialId,
+    RenderTargetId, SamplerId, ShaderModuleId, ShaderVisibility, StorageBufferId, TextureId,
+    UniformId, VertexShaderId,
+    bindings::DrawBinding,
+    draw_list::{DrawList, RenderTarget},
+    render_target::{RenderTargetFormat, RenderTargetSize},
+    resources::FragmentShader,
+    sampler::{SamplerAddressing, SamplerFiltering},
+};
+
+/// Shared WGSL declarations prepended to every post-processing shader.
+///
+/// Custom effects can use `t_input`/`s_input` to sample the previous step, and receive a
+/// `FullscreenVertex` with texture coordinates in their fragment entry point.
+pub const POST_PROCESS_PRELUDE: &str = r"
+@group(0) @binding(0) var t_input: texture_2d<f32>;
+@group(0) @binding(1) var s_input: sampler;
+
+struct FullscreenVertex {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// Generate a fullscreen triangle from vertex index, no vertex buffer needed.
+@vertex fn fullscreen_vertex(@builtin(vertex_index) index: u32) -> FullscreenVertex {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    // Flip V: NDC y-up vs texture y-down.
+    return FullscreenVertex(vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0), vec2<f32>(uv.x, 1.0 - uv.y));
+}
+";
+
+const BUILTIN_EFFECTS_SHADER: &str = r"
+struct EffectParams {
+    params: vec4<f32>,
+}
+
+@group(0) @binding(2) var<uniform> effect: EffectParams;
+@group(0) @binding(3) var t_secondary: texture_2d<f32>;
+
+const LUMA: vec3<f32> = vec3<f32>(0.299, 0.587, 0.114);
+
+fn sample_input(uv: vec2<f32>) -> vec4<f32> {
+    return textureSampleLevel(t_input, s_input, uv, 0.0);
+}
+
+@fragment fn blit(v: FullscreenVertex) -> @location(0) vec4<f32> {
+    return sample_input(v.uv);
+}
+
+fn tonemap_aces(x: vec3<f32>) -> vec3<f32> {
+    let a = 2.51;
+    let b = 0.03;
+    let c = 2.43;
+    let d = 0.59;
+    let e = 0.14;
+    return clamp((x * (a * x + b)) / (x * (c * x + d) + e), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+// params: x = exposure, y = operator (0 = Reinhard, 1 = ACES).
+@fragment fn tonemap(v: FullscreenVertex) -> @location(0) vec4<f32> {
+    let color = sample_input(v.uv);
+    let hdr = max(color.rgb * effect.params.x, vec3<f32>(0.0));
+    let reinhard = hdr / (hdr + vec3<f32>(1.0));
+    return vec4<f32>(select(reinhard, tonemap_aces(hdr), effect.params.y > 0.5), color.a);
+}
+
+// params: x = gamma.
+@fragment fn gamma(v: FullscreenVertex) -> @location(0) vec4<f32> {
+    let color = sample_input(v.uv);
+    let corrected = pow(max(color.rgb, vec3<f32>(0.0)), vec3<f32>(1.0 / effect.params.x));
+    return vec4<f32>(corrected, color.a);
+}
+
+@fragment fn fxaa(v: FullscreenVertex) -> @location(0) vec4<f32> {
+    let span_max = 8.0;
+    let reduce_mul = 1.0 / 8.0;
+    let reduce_min = 1.0 / 128.0;
+    let texel = 1.0 / vec2<f32>(textureDimensions(t_input));
+
+    let color = sample_input(v.uv);
+    let luma_nw = dot(sample_input(v.uv + vec2<f32>(-1.0, -1.0) * texel).rgb, LUMA);
+    let luma_ne = dot(sample_input(v.uv + vec2<f32>(1.0, -1.0) * texel).rgb, LUMA);
+    let luma_sw = dot(sample_input(v.uv + vec2<f32>(-1.0, 1.0) * texel).rgb, LUMA);
+    let luma_se = dot(sample_input(v.uv + vec2<f32>(1.0, 1.0) * texel).rgb, LUMA);
+    let luma_m = dot(color.rgb, LUMA);
+    let luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    let luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+    var direction = vec2<f32>(
+        -((luma_nw + luma_ne) - (luma_sw + luma_se)),
+        (luma_nw + luma_sw) - (luma_ne + luma_se),
+    );
+    let luma_sum = luma_nw + luma_ne + luma_sw + luma_se;
+    let direction_reduce = max(luma_sum * 0.25 * reduce_mul, reduce_min);
+    let inverse_direction_min = 1.0 / (min(abs(direction.x), abs(direction.y)) + direction_reduce);
+    direction = clamp(
+        direction * inverse_direction_min,
+        vec2<f32>(-span_max),
+        vec2<f32>(span_max),
+    ) * texel;
+
+    let rgb_a = 0.5 * (sample_input(v.uv + direction * (1.0 / 3.0 - 0.5)).rgb
+        + sample_input(v.uv + direction * (2.0 / 3.0 - 0.5)).rgb);
+    let rgb_b = rgb_a * 0.5 + 0.25 * (sample_input(v.uv + direction * -0.5).rgb
+        + sample_input(v.uv + direction * 0.5).rgb);
+    let luma_b = dot(rgb_b, LUMA);
+    return vec4<f32>(select(rgb_b, rgb_a, luma_b < luma_min || luma_b > luma_max), color.a);
+}
+
+// params: xy = direction in texels, z = sigma.
+@fragment fn gaussian_blur(v: FullscreenVertex) -> @location(0) vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(t_input));
+    let sigma = max(effect.params.z, 0.0001);
+    let radius = min(i32(ceil(sigma * 3.0)), 32);
+    var sum = vec4<f32>(0.0);
+    var weight_sum = 0.0;
+    for (var i = -radius; i <= radius; i++) {
+        let weight = exp(-f32(i * i) / (2.0 * sigma * sigma));
+        sum += sample_input(v.uv + effect.params.xy * texel * f32(i)) * weight;
+        weight_sum += weight;
+    }
+    return sum / weight_sum;
+}
+
+// params: x = threshold.
+@fragment fn bloom_threshold(v: FullscreenVertex) -> @location(0) vec4<f32> {
+    let color = sample_input(v.uv);
+    let brightness = max(color.r, max(color.g, color.b));
+    let contribution = max(brightness - effect.params.x, 0.0) / max(brightness, 0.0001);
+    return vec4<f32>(color.rgb * contribution, 1.0);
+}
+
+// params: x = intensity. The blurred bright pass is bound as the secondary texture.
+@fragment fn bloom_combine(v: FullscreenVertex) -> @location(0) vec4<f32> {
+    let color = sample_input(v.uv);
+    let bloom = textureSampleLevel(t_secondary, s_input, v.uv, 0.0);
+    return vec4<f32>(color.rgb + bloom.rgb * effect.params.x, color.a);
+}
+
+// params: x = intensity, y = radius, z = smoothness.
+@fragment fn vignette(v: FullscreenVertex) -> @location(0) vec4<f32> {
+    let color = sample_input(v.uv);
+    let distance_from_center = distance(v.uv, vec2<f32>(0.5)) * 1.41421356;
+    let falloff_end = effect.params.y + effect.params.z;
+    let falloff = smoothstep(effect.params.y, falloff_end, distance_from_center);
+    return vec4<f32>(color.rgb * (1.0 - effect.params.x * falloff), color.a);
+}
+
+// params: x = LUT size, y = strength. The LUT is bound as the secondary texture, laid out as a
+// horizontal strip of `size` blue slices, each `size` x `size` texels.
+@fragment fn color_grading(v: FullscreenVertex) -> @location(0) vec4<f32> {
+    let color = sample_input(v.uv);
+    let size = effect.params.x;
+    let graded_input = clamp(color.rgb, vec3<f32>(0.0), vec3<f32>(1.0));
+    let blue = graded_input.b * (size - 1.0);
+    let slice_0 = floor(blue);
+    let slice_1 = min(slice_0 + 1.0, size - 1.0);
+    let red = graded_input.r * (size - 1.0) + 0.5;
+    let green = (graded_input.g * (size - 1.0) + 0.5) / size;
+    let uv_0 = vec2<f32>((slice_0 * size + red) / (size * size), green);
+    let uv_1 = vec2<f32>((slice_1 * size + red) / (size * size), green);
+    let graded_0 = textureSampleLevel(t_secondary, s_input, uv_0, 0.0).rgb;
+    let graded_1 = textureSampleLevel(t_secondary, s_input, uv_1, 0.0).rgb;
+    let graded = mix(graded_0, graded_1, blue - slice_0);
+    return vec4<f32>(mix(color.rgb, graded, effect.params.y), color.a);
+}
+";
+
+/// Tonemapping curve applied by [`PostEffect::Tonemap`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TonemapOperator {
+    /// `color / (color + 1)`.
+    #[default]
+    Reinhard,
+    /// Fitted ACES filmic curve.
+    Aces,
+}
+
+/// A full-screen effect in a post-processing chain.
+///
+/// Built-in effects sample the previous step with a linear, clamped sampler. HDR effects such as
+/// tonemapping and bloom expect the chain to use [`RenderTargetFormat::Rgba16Float`].
+pub enum PostEffect {
+    /// Maps HDR colors into the displayable range after scaling them by `exposure`.
+    Tonemap {
+        operator: TonemapOperator,
+        exposure: f32,
+    },
+    /// Applies `color^(1 / gamma)`.
+    Gamma { gamma: f32 },
+    /// Fast approximate anti-aliasing.
+    Fxaa,
+    /// Separable gaussian blur with standard deviation `sigma` in pixels.
+    GaussianBlur { sigma: f32 },
+    /// Adds a blurred copy of the pixels brighter than `threshold`, scaled by `intensity`.
+    Bloom {
+        threshold: f32,
+        intensity: f32,
+        sigma: f32,
+    },
+    /// Darkens pixels further than `radius` from the center over `smoothness`, where 1.0 is the
+    /// distance to a corner.
+    Vignette {
+        intensity: f32,
+        radius: f32,
+        smoothness: f32,
+    },
+    /// Remaps colors through a lookup table texture, blended with the input by `strength`.
+    ///
+    /// The LUT is a strip of `lut_size` blue slices laid out horizontally, each `lut_size` by
+    /// `lut_size` texels, so the texture is `lut_size * lut_size` wide and `lut_size` tall.
+    ColorGrading {
+        lut: TextureId,
+        lut_size: u32,
+        strength: f32,
+    },
+    /// A user-provided full-screen WGSL effect.
+    ///
+    /// When the source fails to compile, the error is logged and the step passes its input
+    /// through unchanged.
+    Custom(CustomEffect),
+}
+
+impl PostEffect {
+    /// Returns the packed parameters for each uniform this effect owns, in step order.
+    fn params(&self) -> Vec<Vec4> {
+        match *self {
+            PostEffect::Tonemap { operator, exposure } => {
+                let operator = match operator {
+                    TonemapOperator::Reinhard => 0.0,
+                    TonemapOperator::Aces => 1.0,
+                };
+                vec![Vec4::new(exposure, operator, 0.0, 0.0)]
+            }
+            PostEffect::Gamma { gamma } => vec![Vec4::new(gamma, 0.0, 0.0, 0.0)],
+            PostEffect::Fxaa | PostEffect::Custom(_) => Vec::new(),
+            PostEffect::GaussianBlur { sigma } => vec![
+                Vec4::new(1.0, 0.0, sigma, 0.0),
+                Vec4::new(0.0, 1.0, sigma, 0.0),
+            ],
+            PostEffect::Bloom {
+                threshold,
+                intensity,
+                sigma,
+            } => vec![
+                Vec4::new(threshold, 0.0, 0.0, 0.0),
+                Vec4::new(1.0, 0.0, sigma, 0.0),
+                Vec4::new(0.0, 1.0, sigma, 0.0),
+                Vec4::new(intensity, 0.0, 0.0, 0.0),
+            ],
+            PostEffect::Vignette {
+                intensity,
+                radius,
+                smoothness,
+            } => vec![Vec4::new(intensity, radius, smoothness, 0.0)],
+            PostEffect::ColorGrading {
+                lut_size, strength, ..
+            } => vec![Vec4::new(lut_size as f32, strength, 0.0, 0.0)],
+        }
+    }
+}
+
+/// A full-screen WGSL effect inserted into a post-processing chain.
+///
+/// The source is appended to [`POST_PROCESS_PRELUDE`] and must contain exactly one `@fragment`
+/// entry point taking a `FullscreenVertex`. Group 0 is reserved for the chain's input; extra
+/// resources are bound at `@group(1)`.
+///
+/// ```ignore
+/// let invert = CustomEffect::new(
+///     "invert",
+///     "@fragment fn invert(v: FullscreenVertex) -> @location(0) vec4<f32> {
+///         let color = textureSample(t_input, s_input, v.uv);
+///         return vec4<f32>(1.0 - color.rgb, color.a);
+///     }",
+/// );
+/// ```
+#[must_use]
+pub struct CustomEffect {
+    name: String,
+    source: String,
+    bindings: Vec<DrawBinding>,
+}
+
+impl CustomEffect {
+    /// Creates a custom effect from WGSL source.
+    pub fn new(name: &str, source: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            source: source.to_string(),
+            bindings: Vec::new(),
+        }
+    }
+
+    /// Adds a uniform binding at `@group(1) @binding(binding)`.
+    pub fn uniform(mut self, binding: u32, uniform: UniformId) -> Self {
+        self.bindings
+            .push(DrawBinding::uniform(1, binding, uniform));
+        self
+    }
+
+    /// Adds a storage buffer binding at `@group(1) @binding(binding)`.
+    pub fn storage_buffer(mut self, binding: u32, storage_buffer: StorageBufferId) -> Self {
+        self.bindings.push(DrawBinding::storage_buffer(
+            1,
+            binding,
+            storage_buffer,
+            ShaderVisibility::Fragment,
+        ));
+        self
+    }
+
+    /// Adds a texture binding at `@group(1) @binding(binding)`.
+    pub fn texture(mut self, binding: u32, texture: TextureId) -> Self {
+        self.bindings
+            .push(DrawBinding::texture(1, binding, texture));
+        self
+    }
+
+    /// Adds a render target texture binding at `@group(1) @binding(binding)`.
+    pub fn render_target_texture(mut self, binding: u32, render_target: RenderTargetId) -> Self {
+        self.bindings
+            .push(DrawBinding::render_target(1, binding, render_target));
+        self
+    }
+
+    /// Adds a sampler binding at `@group(1) @binding(binding)`.
+    pub fn sampler(mut self, binding: u32, sampler: SamplerId) -> Self {
+        self.bindings
+            .push(DrawBinding::sampler(1, binding, sampler));
+        self
+    }
+}
+
+#[must_use]
+/// Describes a post-processing chain: its working format and ordered effects.
+///
+/// Build with [`PostProcess::new`] and [`PostProcess::effect`], then create the chain via
+/// [`DrawListRenderer::create_post_process_chain`].
+pub struct PostProcess {
+    format: RenderTargetFormat,
+    effects: Vec<PostEffect>,
+}
+
+impl PostProcess {
+    /// Creates an empty chain whose input and intermediate targets use `format`.
+    pub fn new(format: RenderTargetFormat) -> Self {
+        Self {
+            format,
+            effects: Vec::new(),
+        }
+    }
+
+    /// Appends an effect; effects run in the order they are added.
+    pub fn effect(mut self, effect: PostEffect) -> Self {
+        self.effects.push(effect);
+        self
+    }
+}
+
+/// Handle to an effect in a [`PostProcessChain`], in the order effects were added.
+pub type PostEffectIndex = usize;
+
+/// A single full-screen draw of a chain.
+struct PostProcessStep {
+    material: MaterialId,
+    /// Intermediate target this step draws into, or `None` for the chain's destination.
+    output: Option<RenderTargetId>,
+}
+
+struct PostEffectRecord {
+    kind: std::mem::Discriminant<PostEffect>,
+    uniforms: Vec<UniformId>,
+    lut: Option<TextureId>,
+}
+
+/// Packed parameters of a built-in effect step, bound at `@group(0) @binding(2)`.
+#[derive(crate::encase::ShaderType)]
+struct EffectParams {
+    params: Vec4,
+}
+
+impl AsUniformBuffer for EffectParams {
+    const VISIBILITY: ShaderVisibility = ShaderVisibility::Fragment;
+}
+
+/// A post-processing chain created by [`DrawListRenderer::create_post_process_chain`].
+///
+/// Draw the scene into [`PostProcessChain::input`], then call [`PostProcessChain::record`] to
+/// append the effects to a draw list. Effects alternate between two surface-sized ping-pong
+/// targets owned by the chain, and the last one draws into the requested destination.
+pub struct PostProcessChain {
+    input: RenderTargetId,
+    steps: Vec<PostProcessStep>,
+    effects: Vec<PostEffectRecord>,
+}
+
+impl PostProcessChain {
+    /// Returns the surface-sized render target the scene should be drawn into.
+    pub fn input(&self) -> RenderTargetId {
+        self.input
+    }
+
+    /// Appends every effect of the chain to `draw_list`, writing the final result into
+    /// `destination`.
+    ///
+    /// A chain without effects copies its input to `destination`.
+    pub fn record(&self, draw_list: &mut DrawList, destination: RenderTarget) {
+        if destination == RenderTarget::Custom(self.input) {
+            tracing::warn!(
+                "Post-processing chain cannot write into its own input render target ({:?}).",
+                self.input
+            );
+            return;
+        }
+
+        for step in self.steps.iter() {
+            let render_target = step.output.map_or(destination, RenderTarget::Custom);
+            draw_list.draw(render_target, step.material, 3);
+        }
+    }
+
+    /// Records new settings for a built-in effect into `draw_list`.
+    ///
+    /// `effect` must be the same kind of effect that was added at `index`; the lookup table of
+    /// [`PostEffect::ColorGrading`] cannot be changed. Custom effects update their own bindings
+    /// instead. Returns `false` and logs a warning when the update does not apply.
+    pub fn update_effect(
+        &self,
+        draw_list: &mut DrawList,
+        index: PostEffectIndex,
+        effect: &PostEffect,
+    ) -> bool {
+        let Some(record) = self.effects.get(index) else {
+            tracing::warn!("Invalid post-processing effect index ({index})");
+            return false;
+        };
+        if record.kind != std::mem::discriminant(effect) {
+            tracing::warn!("Post-processing effect {index} is a different kind of effect.");
+            return false;
+        }
+        if let PostEffect::ColorGrading { lut, .. } = *effect
+            && record.lut != Some(lut)
+        {
+            tracing::warn!("The lookup table of post-processing effect {index} cannot change.");
+            return false;
+        }
+        if let PostEffect::Custom(_) = effect {
+            tracing::warn!("Custom post-processing effect {index} has no built-in settings.");
+            return false;
+        }
+
+        for (&uniform, params) in record.uniforms.iter().zip(effect.params()) {
+            draw_list.update_uniform(uniform, &EffectParams { params });
+        }
+
+        true
+    }
+}
+
+/// Accumulates steps while a chain is being created.
+struct PostProcessChainBuilder {
+    ping_pong: [RenderTargetId; 2],
+    sampler: SamplerId,
+    builtin_shader: ShaderModuleId,
+    vertex_shader: VertexShaderId,
+    fragment_shaders: HashMap<&'static str, FragmentShaderId>,
+    steps: Vec<PostProcessStep>,
+    /// The render target the next step samples.
+    current: RenderTargetId,
+}
+
+impl PostProcessChainBuilder {
+    /// Returns the ping-pong target that is not currently being sampled.
+    fn next_ping_pong(&self) -> RenderTargetId {
+        if self.current == self.ping_pong[0] {
+            self.ping_pong[1]
+        } else {
+            self.ping_pong[0]
+        }
+    }
+}
+
+impl DrawListRenderer {
+    /// Creates the render targets, materials, and uniforms of a post-processing chain.
+    ///
+    /// The chain owns a surface-sized input target, two surface-sized ping-pong targets, and two
+    /// extra targets when it contains a bloom effect, all using the chain's format.
+    pub fn create_post_process_chain(
+        &mut self,
+        name: &str,
+        post_process: PostProcess,
+    ) -> PostProcessChain {
+        let PostProcess { format, effects } = post_process;
+
+        let input = self.create_render_target(
+            &format!("{name}_input"),
+            RenderTargetSize::SurfaceSize,
+            format,
+        );
+        let ping_pong = [0, 1].map(|index| {
+            self.create_render_target(
+                &format!("{name}_ping_pong_{index}"),
+                RenderTargetSize::SurfaceSize,
+                format,
+            )
+        });
+        let sampler = self.create_sampler(
+            &format!("{name}_post_process"),
+            SamplerAddressing::ClampToEdge,
+            SamplerFiltering::Linear,
+        );
+        let builtin_shader = self
+            .create_shader(
+                &format!("{name}_post_process"),
+                &format!("{POST_PROCESS_PRELUDE}{BUILTIN_EFFECTS_SHADER}"),
+            )
+            .unwrap_or_else(|error| panic!("Invalid built-in post-process shader: {error}"));
+        let vertex_shader = self.create_vertex_shader(builtin_shader, "fullscreen_vertex");
+
+        let mut builder = PostProcessChainBuilder {
+            ping_pong,
+            sampler,
+            builtin_shader,
+            vertex_shader,
+            fragment_shaders: HashMap::default(),
+            steps: Vec::new(),
+            current: input,
+        };
+        let mut bloom_targets: Option<[RenderTargetId; 2]> = None;
+        let mut effect_records = Vec::with_capacity(effects.len());
+
+        for effect in effects.iter() {
+            let uniforms: Vec<UniformId> = effect
+                .params()
+                .into_iter()
+                .map(|params| {
+                    self.create_uniform(&format!("{name}_effect"), &EffectParams { params })
+                })
+                .collect();
+
+            match effect {
+                PostEffect::Tonemap { .. } => {
+                    self.push_post_process_step(&mut builder, "tonemap", Some(uniforms[0]), None);
+                }
+                PostEffect::Gamma { .. } => {
+                    self.push_post_process_step(&mut builder, "gamma", Some(uniforms[0]), None);
+                }
+                PostEffect::Fxaa => {
+                    self.push_post_process_step(&mut builder, "fxaa", None, None);
+                }
+                PostEffect::GaussianBlur { .. } => {
+                    self.push_post_process_step(
+                        &mut builder,
+                        "gaussian_blur",
+                        Some(uniforms[0]),
+                        None,
+                    );
+                    self.push_post_process_step(
+                        &mut builder,
+                        "gaussian_blur",
+                        Some(uniforms[1]),
+                        None,
+                    );
+                }
+                PostEffect::Bloom { .. } => {
+                    let [bright, blurred] = *bloom_targets.get_or_insert_with(|| {
+                        [0, 1].map(|index| {
+                            self.create_render_target(
+                                &format!("{name}_bloom_{index}"),
+                                RenderTargetSize::SurfaceSize,
+                                format,
+                            )
+                        })
+                    });
+
+                    // The bright pass and its blur draw into the bloom targets, so the effect's
+                    // input stays intact for the final combine.
+                    let effect_input = builder.current;
+                    for (entry_point, uniform, output) in [
+                        ("bloom_threshold", uniforms[0], bright),
+                        ("gaussian_blur", uniforms[1], blurred),
+                        ("gaussian_blur", uniforms[2], bright),
+                    ] {
+                        let material = self
+                            .post_process_material(&mut builder, entry_point)
+                            .uniform(0, 2, uniform);
+                        let material = self.create_material(material);
+                        builder.steps.push(PostProcessStep {
+                            material,
+                            output: Some(output),
+                        });
+                        builder.current = output;
+                    }
+
+                    builder.current = effect_input;
+                    self.push_post_process_step(
+                        &mut builder,
+                        "bloom_combine",
+                        Some(uniforms[3]),
+                        Some(bright),
+                    );
+                }
+                PostEffect::Vignette { .. } => {
+                    self.push_post_process_step(&mut builder, "vignette", Some(uniforms[0]), None);
+                }
+                PostEffect::ColorGrading { lut, .. } => {
+                    let material = self
+                        .post_process_material(&mut builder, "color_grading")
+                        .uniform(0, 2, uniforms[0])
+                        .texture(0, 3, *lut);
+                    self.push_post_process_material(&mut builder, material);
+                }
+                PostEffect::Custom(custom) => match self.create_shader(
+                    &format!("{name}_{}", custom.name),
+                    &format!("{POST_PROCESS_PRELUDE}{}", custom.source),
+                ) {
+                    Ok(shader) => {
+                        let vertex_shader = self.create_vertex_shader(shader, "fullscreen_vertex");
+                        let fragment_shader = self
+                            .fragment_shaders
+                            .push(FragmentShader::create(shader, Option::<String>::None));
+                        let mut material = Material::new(vertex_shader, fragment_shader)
+                            .render_target_texture(0, 0, builder.current)
+                            .sampler(0, 1, builder.sampler)
+                            .blend_mode(BlendMode::Opaque);
+                        material.bindings.extend(custom.bindings.iter().copied());
+                        self.push_post_process_material(&mut builder, material);
+                    }
+                    Err(error) => {
+                        // Keep the chain intact by passing the image through unchanged.
+                        tracing::warn!("Custom post effect replaced with a blit: {error}");
+                        self.push_post_process_step(&mut builder, "blit", None, None);
+                    }
+                },
+            }
+
+            effect_records.push(PostEffectRecord {
+                kind: std::mem::discriminant(effect),
+                uniforms,
+                lut: match *effect {
+                    PostEffect::ColorGrading { lut, .. } => Some(lut),
+                    _ => None,
+                },
+            });
+        }
+
+        if builder.steps.is_empty() {
+            self.push_post_process_step(&mut builder, "blit", None, None);
+        }
+
+        // The last step draws into the destination passed to `record`.
+        if let Some(last) = builder.steps.last_mut() {
+            last.output = None;
+        }
+
+        PostProcessChain {
+            input,
+            steps: builder.steps,
+            effects: effect_records,
+        }
+    }
+
+    /// Creates a material for a built-in effect step that samples the builder's current target.
+    fn post_process_material(
+        &mut self,
+        builder: &mut PostProcessChainBuilder,
+        entry_point: &'static str,
+    ) -> Material {
+        let builtin_shader = builder.builtin_shader;
+        let fragment_shader = *builder
+            .fragment_shaders
+            .entry(entry_point)
+            .or_insert_with(|| self.create_fragment_shader(builtin_shader, entry_point));
+
+        Material::new(builder.vertex_shader, fragment_shader)
+            .render_target_texture(0, 0, builder.current)
+            .sampler(0, 1, builder.sampler)
+            .blend_mode(BlendMode::Opaque)
+    }
+
+    /// Appends a built-in effect step that draws into the next ping-pong target.
+    fn push_post_process_step(
+        &mut self,
+        builder: &mut PostProcessChainBuilder,
+        entry_point: &'static str,
+        uniform: Option<UniformId>,
+        secondary: Option<RenderTargetId>,
+    ) {
+        let mut material = self.post_process_material(builder, entry_point);
+        if let Some(uniform) = uniform {
+            material = material.uniform(0, 2, uniform);
+        }
+        if let Some(secondary) = secondary {
+            material = material.render_target_texture(0, 3, secondary);
+        }
+        self.push_post_process_material(builder, material);
+    }
+
+    /// Registers `material` as a step that draws into the next ping-pong target.
+    fn push_post_process_material(
+        &mut self,
+        builder: &mut PostProcessChainBuilder,
+        material: Material,
+    ) {
+        let output = builder.next_ping_pong();
+        let material = self.create_material(material);
+        builder.steps.push(PostProcessStep {
+            material,
+            output: Some(output),
+        });
+        builder.current = output;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bindings::DrawBindingResource, test_support::Scene};
+
+    /// A recorded draw of a chain step.
+    #[derive(Debug, PartialEq)]
+    struct RecordedStep {
+        output: RenderTarget,
+        /// The render target sampled as the step's input.
+        input: Option<RenderTargetId>,
+        /// The render target sampled as the step's secondary texture.
+        secondary: Option<RenderTargetId>,
+    }
+
+    /// Draws the scene into the chain's input, records the chain, and returns its steps.
+    fn record_chain(
+        scene: &mut Scene,
+        chain: &PostProcessChain,
+        destination: RenderTarget,
+    ) -> Vec<RecordedStep> {
+        let fill = scene.fill_material("scene");
+        let mut draw_list = DrawList::default();
+        draw_list.draw(RenderTarget::Custom(chain.input()), fill, 3);
+        chain.record(&mut draw_list, destination);
+
+        let submission = scene.submit(&draw_list);
+        let materials = &scene.renderer.materials;
+        let sampled = |material: MaterialId, binding: u32| {
+            let material = materials.get(material).unwrap();
+            material
+                .bindings
+                .iter()
+                .find_map(|draw_binding| match draw_binding.resource {
+                    DrawBindingResource::RenderTarget { render_target, .. }
+                        if draw_binding.group == 0 && draw_binding.binding == binding =>
+                    {
+                        Some(render_target)
+                    }
+                    _ => None,
+                })
+        };
+        submission
+            .passes
+            .iter()
+            .flat_map(|pass| pass.draws.iter().map(move |draw| (pass, draw)))
+            .filter(|(_, draw)| draw.material != fill)
+            .map(|(pass, draw)| RecordedStep {
+                output: pass.render_target.unwrap(),
+                input: sampled(draw.material, 0),
+                secondary: sampled(draw.material, 3),
+            })
+            .collect()
+    }
+
+    /// Returns the intermediate render target a step draws into.
+    fn intermediate_output(step: &RecordedStep) -> RenderTargetId {
+        let RenderTarget::Custom(output) = step.output else {
+            panic!("Intermediate steps draw into render targets owned by the chain.");
+        };
+        output
+    }
+
+    #[test]
+    fn steps_sample_the_previous_step_and_the_last_writes_the_destination() {
+        let mut scene = Scene::new();
+        let chain = scene.renderer.create_post_process_chain(
+            "chain",
+            PostProcess::new(RenderTargetFormat::Rgba16Float)
+                .effect(PostEffect::Tonemap {
+                    operator: TonemapOperator::Aces,
+                    exposure: 1.0,
+                })
+                .effect(PostEffect::GaussianBlur { sigma: 2.0 })
+                .effect(PostEffect::Fxaa),
+        );
+        let destination = scene.renderer.create_render_target(
+            "destination",
+            RenderTargetSize::SurfaceSize,
+            RenderTargetFormat::Rgba16Float,
+        );
+
+        let steps = record_chain(&mut scene, &chain, RenderTarget::Custom(destination));
+
+        // Tonemap, both blur directions, and FXAA.
+        assert_eq!(steps.len(), 4);
+        assert_eq!(steps[0].input, Some(chain.input()));
+        for pair in steps.windows(2) {
+            let previous_output = intermediate_output(&pair[0]);
+            assert_ne!(previous_output, chain.input());
+            assert_eq!(pair[1].input, Some(previous_output));
+        }
+        assert_eq!(steps[3].output, RenderTarget::Custom(destination));
+        // Consecutive intermediate steps alternate between the two ping-pong targets.
+        assert_ne!(steps[0].output, steps[1].output);
+        assert_eq!(steps[0].output, steps[2].output);
+    }
+
+    #[test]
+    fn chains_write_the_surface_when_it_is_the_destination() {
+        let mut scene = Scene::new();
+        let chain = scene.renderer.create_post_process_chain(
+            "chain",
+            PostProcess::new(RenderTargetFormat::RgbaSrgb).effect(PostEffect::Gamma { gamma: 2.2 }),
+        );
+
+        let steps = record_chain(&mut scene, &chain, RenderTarget::Surface);
+
+        assert_eq!(
+            steps,
+            vec![RecordedStep {
+                output: RenderTarget::Surface,
+                input: Some(chain.input()),
+                secondary: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn bloom_combines_its_input_with_the_blurred_bright_pass() {
+        let mut scene = Scene::new();
+        let chain = scene.renderer.create_post_process_chain(
+            "chain",
+            PostProcess::new(RenderTargetFormat::Rgba16Float)
+                .effect(PostEffect::Gamma { gamma: 2.2 })
+                .effect(PostEffect::Bloom {
+                    threshold: 1.0,
+                    intensity: 0.5,
+                    sigma: 2.0,
+                }),
+        );
+
+        let steps = record_chain(&mut scene, &chain, RenderTarget::Surface);
+
+        // Gamma, the bright pass, both blur directions, and the combine.
+        assert_eq!(steps.len(), 5);
+        // The bright pass and its blur each read the step before them.
+        for pair in steps[..4].windows(2) {
+            assert_eq!(pair[1].input, Some(intermediate_output(&pair[0])));
+        }
+        // The combine reads the effect's input and the blurred bright pass.
+        assert_eq!(
+            steps[4],
+            RecordedStep {
+                output: RenderTarget::Surface,
+                input: Some(intermediate_output(&steps[0])),
+                secondary: Some(intermediate_output(&steps[3])),
+            }
+        );
+    }
+}
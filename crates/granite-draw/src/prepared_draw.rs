@@ -6,6 +6,7 @@ pub struct PreparedDraw {
     pub key: RenderPipelineKey,
     pub bind_groups_to_set: Vec<ResolvedDrawBindGroup>,
     pub depth_state: Option<MaterialDepthState>,
+    pub push_constants: Option<push_constants::ResolvedPushConstants>,
 }
 
 impl PreparedDraw {
@@ -17,18 +18,17 @@ impl PreparedDraw {
         material: MaterialId,
         instance_buffer_layout: Option<VertexBufferLayout>,
     ) -> Option<PreparedDraw> {
-        let (vertex_shader, fragment_shader, draw_bindings, blend_mode, depth_state) = {
-            let Some(material) = renderer.materials.get(material) else {
-                tracing::warn!("Invalid material id ({:?})", material);
-                return None;
-            };
-            (
-                material.vertex_shader,
-                material.fragment_shader,
-                material.bindings.clone(),
-                material.blend_mode,
-                material.depth_state,
-            )
+        let Some((draw_bindings, push_constants, name)) =
+            renderer.materials.get(material).map(|material| {
+                (
+                    material.bindings.clone(),
+                    material.push_constants,
+                    material.name.clone(),
+                )
+            })
+        else {
+            tracing::warn!("Invalid material id ({:?})", material);
+            return None;
         };
 
         let vertex_buffer_layout = if let Some(mesh_id) = mesh {
@@ -48,7 +48,11 @@ impl PreparedDraw {
         let instance_buffer_layout = instance_buffer_layout
             .map(|layout| renderer.get_or_create_instance_buffer_layout(layout));
 
-        let resolved_bindings = renderer.resolve_draw_bindings(draw_bindings.as_slice())?;
+        let resolved_bindings = renderer.resolve_draw_bindings(
+            draw_bindings.as_slice(),
+            push_constants,
+            name.as_deref(),
+        )?;
         let Some(pipeline_layout_id) =
             renderer.get_or_create_pipeline_layout(resolved_bindings.pipeline_layout_key)
         else {
@@ -56,24 +60,17 @@ impl PreparedDraw {
             return None;
         };
 
-        let depth_stencil = depth_state.map(|depth_state| RenderPipelineDepthKey {
-            format: DepthBufferRecord::FORMAT,
-            compare: depth_state.compare,
-            write_enabled: depth_state.write_enabled,
-        });
-
-        let key = RenderPipelineKey {
-            render_target_format: renderer.render_target_format(surface_format, render_target)?,
-            depth_stencil,
+        let material = renderer.materials.get(material)?;
+        let depth_state = material.depth_state;
+        let key = RenderPipelineKey::for_material(
+            material,
+            renderer.render_target_format(surface_format, render_target)?,
             vertex_buffer_layout,
             instance_buffer_layout,
-            pipeline_layout: pipeline_layout_id,
-            vertex_shader,
-            fragment_shader,
-            blend_mode,
-        };
+            pipeline_layout_id,
+        );
 
-        if !renderer.ensure_render_pipeline(key) {
+        if !renderer.ensure_render_pipeline(key, name.as_deref()) {
             tracing::warn!("Could not ensure a valid render pipeline!");
             return None;
         }
@@ -82,6 +79,37 @@ impl PreparedDraw {
             key,
             bind_groups_to_set: resolved_bindings.bind_groups_to_set,
             depth_state,
+            push_constants: resolved_bindings.push_constants,
         })
     }
 }
+
+impl RenderPipelineKey {
+    /// Builds the key of the pipeline that draws `material` with the given layouts.
+    pub(super) fn for_material(
+        material: &MaterialRecord,
+        render_target_format: wgpu::TextureFormat,
+        vertex_buffer_layout: Option<Id>,
+        instance_buffer_layout: Option<Id>,
+        pipeline_layout: Id,
+    ) -> Self {
+        let depth_stencil = material
+            .depth_state
+            .map(|depth_state| RenderPipelineDepthKey {
+                format: DepthBufferRecord::FORMAT,
+                compare: depth_state.compare,
+                write_enabled: depth_state.write_enabled,
+            });
+
+        Self {
+            render_target_format,
+            depth_stencil,
+            vertex_buffer_layout,
+            instance_buffer_layout,
+            pipeline_layout,
+            vertex_shader: material.vertex_shader,
+            fragment_shader: material.fragment_shader,
+            blend_mode: material.blend_mode,
+        }
+    }
+}
@@ -0,0 +1,533 @@
+//! Timing of submitted draw list commands.
+//!
+//! When profiling is enabled with [`DrawListRenderer::set_profiling`], every command of a
+//! submitted draw list is timed. On devices with [`wgpu::Features::TIMESTAMP_QUERY`],
+//! timestamps are written at the start and end of each render pass and read back
+//! asynchronously, so GPU timings become available a few frames after their submission. Without
+//! the feature only the CPU time spent encoding each command is reported.
+//!
+//! Commands can be grouped for reporting with
+//! [`DrawList::set_profile_label`](crate::draw_list::DrawList::set_profile_label).
+
+use std::{
+    collections::VecDeque,
+    ops::Range,
+    sync::{Arc, OnceLock},
+    time::{Duration, Instant},
+};
+
+use crate::DrawListRenderer;
+
+/// Number of finished submissions kept when [`DrawListRenderer::take_timings`] is not called.
+const MAX_COMPLETED_SUBMISSIONS: usize = 64;
+
+/// Timing of one draw list command, or of one sorted range of draws.
+#[derive(Clone, Debug)]
+pub struct CommandTiming {
+    /// Kind of command, such as `draw_mesh`, or `sorted_draws` for a sorted range.
+    pub command: &'static str,
+    /// Profile label active when the command was recorded.
+    pub label: Option<String>,
+    /// Time the GPU spent in the render passes encoded for the command. `None` when the command
+    /// encoded no render pass or timestamps are not supported.
+    pub gpu_time: Option<Duration>,
+    /// Time spent encoding the command on the CPU.
+    pub cpu_time: Duration,
+}
+
+/// Combined timing of the commands that share a profile label.
+#[derive(Clone, Debug)]
+pub struct LabelTiming {
+    pub label: String,
+    /// Number of timed commands with the label.
+    pub commands: usize,
+    /// Sum of the GPU times of the commands, or `None` when none of them has one.
+    pub gpu_time: Option<Duration>,
+    pub cpu_time: Duration,
+}
+
+/// Timings of the commands of one submission, in the order they executed.
+#[derive(Clone, Debug)]
+pub struct SubmissionTimings {
+    /// Index of the submission, counted from the creation of the renderer.
+    pub submission: u64,
+    pub commands: Vec<CommandTiming>,
+}
+
+impl SubmissionTimings {
+    /// Returns the sum of the GPU times of all commands, or `None` when no command has one.
+    pub fn gpu_time(&self) -> Option<Duration> {
+        self.commands
+            .iter()
+            .map(|command| command.gpu_time)
+            .fold(None, add_gpu_times)
+    }
+
+    /// Returns the time spent encoding all commands on the CPU.
+    pub fn cpu_time(&self) -> Duration {
+        self.commands.iter().map(|command| command.cpu_time).sum()
+    }
+
+    /// Returns the combined timings of labeled commands, in the order each label first
+    /// appeared.
+    pub fn labels(&self) -> Vec<LabelTiming> {
+        let mut labels: Vec<LabelTiming> = Vec::new();
+        for command in self.commands.iter() {
+            let Some(label) = command.label.as_deref() else {
+                continue;
+            };
+
+            let index = match labels.iter().position(|timing| timing.label == label) {
+                Some(index) => index,
+                None => {
+                    labels.push(LabelTiming {
+                        label: label.to_string(),
+                        commands: 0,
+                        gpu_time: None,
+                        cpu_time: Duration::ZERO,
+                    });
+                    labels.len() - 1
+                }
+            };
+
+            let timing = &mut labels[index];
+            timing.commands += 1;
+            timing.gpu_time = add_gpu_times(timing.gpu_time, command.gpu_time);
+            timing.cpu_time += command.cpu_time;
+        }
+        labels
+    }
+}
+
+/// Adds two optional GPU times, treating a missing time as zero unless both are missing.
+fn add_gpu_times(a: Option<Duration>, b: Option<Duration>) -> Option<Duration> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a + b),
+        (a, b) => a.or(b),
+    }
+}
+
+/// A command whose timing is being recorded.
+struct RecordedCommand {
+    command: &'static str,
+    label: Option<String>,
+    /// Timestamp queries written by the command's render passes.
+    queries: Option<Range<u32>>,
+    cpu_time: Duration,
+}
+
+/// Timestamp queries and the buffers they are read back through.
+struct TimestampQueries {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    /// Readback buffers that are not in use by a pending submission.
+    free_readback_buffers: Vec<wgpu::Buffer>,
+    /// Nanoseconds per timestamp tick.
+    period: f32,
+}
+
+/// A submitted frame whose timestamps are being read back.
+struct PendingTimings {
+    submission: u64,
+    commands: Vec<RecordedCommand>,
+    readback_buffer: wgpu::Buffer,
+    /// Set by the map callback to whether mapping succeeded.
+    mapped: Arc<OnceLock<bool>>,
+}
+
+pub(super) struct Profiler {
+    timestamps: Option<TimestampQueries>,
+    /// Commands of the submission being encoded.
+    commands: Vec<RecordedCommand>,
+    current: Option<(RecordedCommand, Instant)>,
+    next_query: u32,
+    /// Readback buffer the current submission's timestamps are copied into.
+    resolved: Option<wgpu::Buffer>,
+    pending: VecDeque<PendingTimings>,
+    completed: VecDeque<SubmissionTimings>,
+}
+
+impl Profiler {
+    fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let timestamps = device
+            .features()
+            .contains(wgpu::Features::TIMESTAMP_QUERY)
+            .then(|| TimestampQueries {
+                query_set: device.create_query_set(&wgpu::QuerySetDescriptor {
+                    label: Some("profiler_query_set"),
+                    ty: wgpu::QueryType::Timestamp,
+                    count: wgpu::QUERY_SET_MAX_QUERIES,
+                }),
+                resolve_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("profiler_resolve_buffer"),
+                    size: u64::from(wgpu::QUERY_SET_MAX_QUERIES) * wgpu::QUERY_SIZE as u64,
+                    usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                    mapped_at_creation: false,
+                }),
+                free_readback_buffers: Vec::new(),
+                period: queue.get_timestamp_period(),
+            });
+
+        Self {
+            timestamps,
+            commands: Vec::new(),
+            current: None,
+            next_query: 0,
+            resolved: None,
+            pending: VecDeque::new(),
+            completed: VecDeque::new(),
+        }
+    }
+
+    /// Allocates a pair of timestamp queries for a render pass of the current command.
+    fn allocate_pass_queries(&mut self) -> Option<u32> {
+        self.timestamps.as_ref()?;
+        let (command, _) = self.current.as_mut()?;
+
+        if self.next_query + 2 > wgpu::QUERY_SET_MAX_QUERIES {
+            if self.next_query == wgpu::QUERY_SET_MAX_QUERIES {
+                tracing::warn!(
+                    "Submission encodes more than {} render passes; the remaining passes are not \
+                     timed.",
+                    wgpu::QUERY_SET_MAX_QUERIES / 2
+                );
+                // Only warn once per submission.
+                self.next_query += 1;
+            }
+            return None;
+        }
+
+        let first_query = self.next_query;
+        self.next_query += 2;
+        command.queries = Some(match command.queries.take() {
+            Some(queries) => queries.start..self.next_query,
+            None => first_query..self.next_query,
+        });
+        Some(first_query)
+    }
+
+    fn resolve(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder) {
+        let query_count = self.next_query.min(wgpu::QUERY_SET_MAX_QUERIES);
+        let Some(timestamps) = self.timestamps.as_mut() else {
+            return;
+        };
+        if query_count == 0 {
+            return;
+        }
+
+        let readback_buffer = timestamps.free_readback_buffers.pop().unwrap_or_else(|| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("profiler_readback_buffer"),
+                size: timestamps.resolve_buffer.size(),
+                usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        });
+
+        let size = u64::from(query_count) * wgpu::QUERY_SIZE as u64;
+        encoder.resolve_query_set(
+            &timestamps.query_set,
+            0..query_count,
+            &timestamps.resolve_buffer,
+            0,
+        );
+        encoder.copy_buffer_to_buffer(&timestamps.resolve_buffer, 0, &readback_buffer, 0, size);
+        self.resolved = Some(readback_buffer);
+    }
+
+    fn finish_submission(&mut self, submission: u64) {
+        let commands = std::mem::take(&mut self.commands);
+        self.current = None;
+        self.next_query = 0;
+
+        let Some(readback_buffer) = self.resolved.take() else {
+            self.push_completed(SubmissionTimings {
+                submission,
+                commands: commands
+                    .into_iter()
+                    .map(|command| command_timing(command, None))
+                    .collect(),
+            });
+            return;
+        };
+
+        let mapped = Arc::new(OnceLock::new());
+        readback_buffer.map_async(wgpu::MapMode::Read, .., {
+            let mapped = Arc::clone(&mapped);
+            move |result| {
+                let _ = mapped.set(result.is_ok());
+            }
+        });
+        self.pending.push_back(PendingTimings {
+            submission,
+            commands,
+            readback_buffer,
+            mapped,
+        });
+    }
+
+    /// Moves submissions whose timestamps have been read back to the completed queue.
+    fn collect_pending(&mut self, device: &wgpu::Device) {
+        if self.pending.is_empty() {
+            return;
+        }
+        let _ = device.poll(wgpu::PollType::Poll);
+
+        while let Some(pending) = self.pending.front() {
+            let Some(&mapped) = pending.mapped.get() else {
+                break;
+            };
+            let Some(pending) = self.pending.pop_front() else {
+                break;
+            };
+
+            let timestamps: Option<Vec<u64>> = mapped.then(|| {
+                let data = pending.readback_buffer.slice(..).get_mapped_range();
+                data.chunks_exact(wgpu::QUERY_SIZE as usize)
+                    .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap_or_default()))
+                    .collect()
+            });
+            let period = self
+                .timestamps
+                .as_ref()
+                .map_or(1.0, |timestamps| timestamps.period);
+
+            let commands = pending
+                .commands
+                .into_iter()
+                .map(|command| {
+                    let gpu_time = timestamps.as_ref().and_then(|timestamps| {
+                        let queries = command.queries.as_ref()?;
+                        let begin = timestamps.get(queries.start as usize)?;
+                        let end = timestamps.get(queries.end as usize - 1)?;
+                        Some(Duration::from_nanos(
+                            (end.saturating_sub(*begin) as f64 * f64::from(period)) as u64,
+                        ))
+                    });
+                    command_timing(command, gpu_time)
+                })
+                .collect();
+
+            if mapped {
+                pending.readback_buffer.unmap();
+                if let Some(timestamps) = self.timestamps.as_mut() {
+                    timestamps
+                        .free_readback_buffers
+                        .push(pending.readback_buffer);
+                }
+            }
+
+            self.push_completed(SubmissionTimings {
+                submission: pending.submission,
+                commands,
+            });
+        }
+    }
+
+    fn push_completed(&mut self, timings: SubmissionTimings) {
+        if self.completed.len() == MAX_COMPLETED_SUBMISSIONS {
+            self.completed.pop_front();
+        }
+        self.completed.push_back(timings);
+    }
+}
+
+fn command_timing(command: RecordedCommand, gpu_time: Option<Duration>) -> CommandTiming {
+    CommandTiming {
+        command: command.command,
+        label: command.label,
+        gpu_time,
+        cpu_time: command.cpu_time,
+    }
+}
+
+impl DrawListRenderer {
+    /// Enables or disables timing of the commands of submitted draw lists and render graphs.
+    ///
+    /// Profiling adds a timestamp query pair to every render pass, so leave it disabled when
+    /// the timings are not needed. Disabling it drops timings that were not taken yet.
+    pub fn set_profiling(&mut self, enabled: bool) {
+        match (enabled, self.profiler.is_some()) {
+            (true, false) => self.profiler = Some(Profiler::new(&self.device, &self.queue)),
+            (false, true) => self.profiler = None,
+            _ => {}
+        }
+    }
+
+    /// Returns `true` when profiling is enabled.
+    pub fn profiling_enabled(&self) -> bool {
+        self.profiler.is_some()
+    }
+
+    /// Returns `true` when GPU times are measured, which requires
+    /// [`wgpu::Features::TIMESTAMP_QUERY`]. Otherwise only CPU encoding times are reported.
+    pub fn gpu_timestamps_supported(&self) -> bool {
+        self.device
+            .features()
+            .contains(wgpu::Features::TIMESTAMP_QUERY)
+    }
+
+    /// Returns the timings of submissions that finished since the last call, oldest first.
+    ///
+    /// GPU timings are read back without blocking, so a submission is usually reported a few
+    /// frames after it was submitted. Only the most recent 64 finished submissions are kept.
+    pub fn take_timings(&mut self) -> Vec<SubmissionTimings> {
+        let Some(profiler) = self.profiler.as_mut() else {
+            return Vec::new();
+        };
+        profiler.collect_pending(&self.device);
+        profiler.completed.drain(..).collect()
+    }
+
+    /// Starts timing a command. Render passes created until
+    /// [`Self::end_profiled_command`] are attributed to it.
+    pub(super) fn begin_profiled_command(&mut self, command: &'static str, label: Option<&str>) {
+        let Some(profiler) = self.profiler.as_mut() else {
+            return;
+        };
+        profiler.current = Some((
+            RecordedCommand {
+                command,
+                label: label.map(str::to_string),
+                queries: None,
+                cpu_time: Duration::ZERO,
+            },
+            Instant::now(),
+        ));
+    }
+
+    pub(super) fn end_profiled_command(&mut self) {
+        let Some(profiler) = self.profiler.as_mut() else {
+            return;
+        };
+        if let Some((mut command, start)) = profiler.current.take() {
+            command.cpu_time = start.elapsed();
+            profiler.commands.push(command);
+        }
+    }
+
+    /// Allocates the timestamp queries of a render or compute pass of the command being timed.
+    /// Pass the result to [`Self::pass_timestamp_writes`] or
+    /// [`Self::compute_pass_timestamp_writes`] when beginning the pass.
+    pub(super) fn allocate_pass_timestamps(&mut self) -> Option<u32> {
+        self.profiler.as_mut()?.allocate_pass_queries()
+    }
+
+    pub(super) fn pass_timestamp_writes(
+        &self,
+        first_query: Option<u32>,
+    ) -> Option<wgpu::RenderPassTimestampWrites<'_>> {
+        let first_query = first_query?;
+        let timestamps = self.profiler.as_ref()?.timestamps.as_ref()?;
+        Some(wgpu::RenderPassTimestampWrites {
+            query_set: &timestamps.query_set,
+            beginning_of_pass_write_index: Some(first_query),
+            end_of_pass_write_index: Some(first_query + 1),
+        })
+    }
+
+    pub(super) fn compute_pass_timestamp_writes(
+        &self,
+        first_query: Option<u32>,
+    ) -> Option<wgpu::ComputePassTimestampWrites<'_>> {
+        let first_query = first_query?;
+        let timestamps = self.profiler.as_ref()?.timestamps.as_ref()?;
+        Some(wgpu::ComputePassTimestampWrites {
+            query_set: &timestamps.query_set,
+            beginning_of_pass_write_index: Some(first_query),
+            end_of_pass_write_index: Some(first_query + 1),
+        })
+    }
+
+    /// Resolves the timestamps written by the submission encoded into `encoder`.
+    pub(super) fn resolve_profiled_submission(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.resolve(&self.device, encoder);
+        }
+    }
+
+    /// Starts reading back the timings of the submission that was just submitted.
+    pub(super) fn finish_profiled_submission(&mut self) {
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.finish_submission(self.submission_index);
+            profiler.collect_pending(&self.device);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        draw_list::{DrawList, RenderTarget},
+        test_support::Scene,
+    };
+
+    #[test]
+    fn timings_fall_back_to_cpu_time_per_label_without_timestamps() {
+        let mut scene = Scene::new();
+        // The no-op device has no optional features.
+        assert!(!scene.renderer.gpu_timestamps_supported());
+        scene.renderer.set_profiling(true);
+        let material = scene.fill_material("fill");
+
+        let mut draw_list = DrawList::default();
+        draw_list.set_profile_label(Some("opaque"));
+        draw_list.draw(RenderTarget::Surface, material, 3);
+        draw_list.draw(RenderTarget::Surface, material, 3);
+        draw_list.set_profile_label(Some("overlay"));
+        draw_list.draw(RenderTarget::Surface, material, 3);
+        draw_list.set_profile_label(None);
+        draw_list.draw(RenderTarget::Surface, material, 3);
+        scene.submit(&draw_list);
+
+        // Without timestamps the submission is reported as soon as it is submitted.
+        let timings = scene.renderer.take_timings();
+        assert_eq!(timings.len(), 1);
+        let timings = &timings[0];
+        assert_eq!(timings.commands.len(), 4);
+        assert!(
+            timings
+                .commands
+                .iter()
+                .all(|command| command.command == "draw")
+        );
+        assert!(
+            timings
+                .commands
+                .iter()
+                .all(|command| command.gpu_time.is_none())
+        );
+        assert_eq!(timings.gpu_time(), None);
+
+        let labels = timings.labels();
+        let summary: Vec<(&str, usize)> = labels
+            .iter()
+            .map(|label| (label.label.as_str(), label.commands))
+            .collect();
+        assert_eq!(summary, [("opaque", 2), ("overlay", 1)]);
+        assert!(labels.iter().all(|label| label.gpu_time.is_none()));
+        assert_eq!(
+            labels[0].cpu_time,
+            timings.commands[0].cpu_time + timings.commands[1].cpu_time
+        );
+        assert_eq!(labels[1].cpu_time, timings.commands[2].cpu_time);
+        assert!(labels.iter().map(|label| label.cpu_time).sum::<Duration>() <= timings.cpu_time());
+
+        assert!(scene.renderer.take_timings().is_empty());
+    }
+
+    #[test]
+    fn disabling_profiling_drops_untaken_timings() {
+        let mut scene = Scene::new();
+        scene.renderer.set_profiling(true);
+        let material = scene.fill_material("fill");
+
+        let mut draw_list = DrawList::default();
+        draw_list.draw(RenderTarget::Surface, material, 3);
+        scene.submit(&draw_list);
+
+        scene.renderer.set_profiling(false);
+        assert!(scene.renderer.take_timings().is_empty());
+    }
+}
@@ -0,0 +1,718 @@
+//! Push constants: small per-draw values passed inline with draw commands.
+//!
+//! Materials declare their push constants with [`Material::push_constants`]. On devices with
+//! [`wgpu::Features::PUSH_CONSTANTS`] the values are set directly on the render pass. Elsewhere
+//! they are emulated: the values of every draw in a submission are packed into a uniform buffer,
+//! reused by later submissions, that is bound with a dynamic offset at the material's fallback
+//! `@group`.
+//!
+//! Shaders can support both paths with the `PUSH_CONSTANTS` preprocessor define, which is set
+//! when push constants are native:
+//!
+//! ```wgsl
+//! #ifdef PUSH_CONSTANTS
+//! var<push_constant> object: Object;
+//! #else
+//! @group(1) @binding(0) var<uniform> object: Object;
+//! #endif
+//! ```
+
+use std::collections::HashMap;
+
+use wgpu::util::{DeviceExt, RenderEncoder};
+
+use crate::{
+    AsPushConstants, BindGroupLayoutBindingKey, BindGroupLayoutBindingTypeKey, BindGroupLayoutKey,
+    DrawListRenderer, Material, ShaderVisibility, common::Id, prepared_draw::PreparedDraw,
+};
+
+/// Push constants declared on a material.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub(super) struct PushConstantLayout {
+    pub size: u32,
+    pub visibility: ShaderVisibility,
+    /// `@group` the values are bound at when push constants are emulated.
+    pub fallback_group: u32,
+}
+
+/// How the push constants of a draw are set.
+#[derive(Clone, Copy, Debug)]
+pub(super) enum ResolvedPushConstants {
+    /// Set with `set_push_constants`.
+    Native {
+        stages: wgpu::ShaderStages,
+        size: u32,
+    },
+    /// Bound as a dynamic-offset uniform at `@group(slot) @binding(0)`.
+    Emulated {
+        slot: u32,
+        bind_group_layout: Id,
+        size: u32,
+    },
+}
+
+impl ResolvedPushConstants {
+    fn size(&self) -> u32 {
+        match self {
+            Self::Native { size, .. } | Self::Emulated { size, .. } => *size,
+        }
+    }
+}
+
+/// Size of the uniform buffers the emulated push constants of a submission are packed into.
+const PUSH_CONSTANT_CHUNK_SIZE: u64 = 64 << 10;
+
+/// Uniform buffers the emulated push constants of a submission are packed into, reused by the
+/// following submissions.
+#[derive(Default)]
+pub(super) struct PushConstantArena {
+    chunks: Vec<wgpu::Buffer>,
+    /// The chunk being packed into.
+    current: usize,
+    /// The first free byte of the current chunk.
+    offset: u64,
+    /// Bind groups over each chunk, by chunk index and the bind group layout they were created
+    /// for. Dropped with each submission, since bind group layouts can be evicted.
+    bind_groups: HashMap<(usize, Id), wgpu::BindGroup>,
+}
+
+impl PushConstantArena {
+    /// Starts packing into the first chunk again, once the submission that used them was
+    /// submitted. Writes to the chunks are ordered after it by the queue.
+    pub(super) fn recall(&mut self) {
+        self.current = 0;
+        self.offset = 0;
+        self.bind_groups.clear();
+    }
+
+    /// Drops the chunks, which belong to a lost device.
+    pub(super) fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Emulated push constants of several draws, packed into one uniform buffer.
+pub(super) struct PushConstantBuffer {
+    /// Offset of each draw's values, in the order the draws were packed.
+    offsets: Vec<Option<u32>>,
+    /// Bind groups over the buffer, by the bind group layout they were created for.
+    bind_groups: HashMap<Id, wgpu::BindGroup>,
+    /// The buffer, when it is not shared with the rest of the submission.
+    _buffer: Option<wgpu::Buffer>,
+}
+
+/// Values packed at offsets aligned for dynamic uniform bindings, before they are written.
+struct PackedPushConstants {
+    contents: Vec<u8>,
+    offsets: Vec<Option<u32>>,
+    layouts: Vec<(Id, u32)>,
+}
+
+impl PushConstantBuffer {
+    /// Packs the values of the given draws that emulate push constants into the uniform buffers
+    /// shared by the current submission. Draws recorded without values get zeroes.
+    ///
+    /// Returns `None` when none of the draws emulate push constants.
+    pub(super) fn new<'a>(
+        renderer: &mut DrawListRenderer,
+        label: &str,
+        draws: impl IntoIterator<Item = (&'a PreparedDraw, Option<&'a [u8]>)>,
+    ) -> Option<Self> {
+        let packed = pack(renderer, draws)?;
+        renderer.submission_stats.bytes_uploaded += packed.contents.len() as u64;
+
+        let (chunk, base) = renderer.allocate_push_constants(packed.contents.len() as u64);
+        renderer.queue.write_buffer(
+            &renderer.push_constant_arena.chunks[chunk],
+            base,
+            &packed.contents,
+        );
+
+        let mut bind_groups = HashMap::new();
+        for (bind_group_layout, size) in packed.layouts {
+            let bind_group = match renderer
+                .push_constant_arena
+                .bind_groups
+                .get(&(chunk, bind_group_layout))
+            {
+                Some(bind_group) => bind_group.clone(),
+                None => {
+                    let buffer = &renderer.push_constant_arena.chunks[chunk];
+                    let Some(bind_group) =
+                        create_bind_group(renderer, label, buffer, bind_group_layout, size)
+                    else {
+                        continue;
+                    };
+                    renderer
+                        .push_constant_arena
+                        .bind_groups
+                        .insert((chunk, bind_group_layout), bind_group.clone());
+                    bind_group
+                }
+            };
+            bind_groups.insert(bind_group_layout, bind_group);
+        }
+
+        Some(Self {
+            offsets: packed
+                .offsets
+                .into_iter()
+                .map(|offset| offset.map(|offset| offset + base as u32))
+                .collect(),
+            bind_groups,
+            _buffer: None,
+        })
+    }
+
+    /// Packs the values of the given draws into a uniform buffer of their own, for draws that
+    /// are encoded again by later submissions, such as draw bundles.
+    ///
+    /// Returns `None` when none of the draws emulate push constants.
+    pub(super) fn new_owned<'a>(
+        renderer: &mut DrawListRenderer,
+        label: &str,
+        draws: impl IntoIterator<Item = (&'a PreparedDraw, Option<&'a [u8]>)>,
+    ) -> Option<Self> {
+        let packed = pack(renderer, draws)?;
+        renderer.submission_stats.bytes_uploaded += packed.contents.len() as u64;
+
+        let buffer = renderer
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents: packed.contents.as_slice(),
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+
+        let mut bind_groups = HashMap::new();
+        for (bind_group_layout, size) in packed.layouts {
+            if bind_groups.contains_key(&bind_group_layout) {
+                continue;
+            }
+            if let Some(bind_group) =
+                create_bind_group(renderer, label, &buffer, bind_group_layout, size)
+            {
+                bind_groups.insert(bind_group_layout, bind_group);
+            }
+        }
+
+        Some(Self {
+            offsets: packed.offsets,
+            bind_groups,
+            _buffer: Some(buffer),
+        })
+    }
+}
+
+/// Packs the values of the draws that emulate push constants, starting at offset 0.
+fn pack<'a>(
+    renderer: &DrawListRenderer,
+    draws: impl IntoIterator<Item = (&'a PreparedDraw, Option<&'a [u8]>)>,
+) -> Option<PackedPushConstants> {
+    let alignment = renderer.device.limits().min_uniform_buffer_offset_alignment as usize;
+
+    let mut contents = Vec::new();
+    let mut offsets = Vec::new();
+    let mut layouts: Vec<(Id, u32)> = Vec::new();
+    for (prepared_draw, data) in draws {
+        let Some(ResolvedPushConstants::Emulated {
+            bind_group_layout,
+            size,
+            ..
+        }) = prepared_draw.push_constants
+        else {
+            offsets.push(None);
+            continue;
+        };
+
+        let offset = contents.len().next_multiple_of(alignment);
+        contents.resize(offset + size as usize, 0);
+        if let Some(data) = data {
+            let len = data.len().min(size as usize);
+            contents[offset..offset + len].copy_from_slice(&data[..len]);
+        }
+        offsets.push(Some(offset as u32));
+        if !layouts
+            .iter()
+            .any(|(layout, _)| *layout == bind_group_layout)
+        {
+            layouts.push((bind_group_layout, size));
+        }
+    }
+
+    if layouts.is_empty() {
+        return None;
+    }
+
+    // Buffer writes must be a multiple of four bytes long.
+    contents.resize(
+        contents
+            .len()
+            .next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT as usize),
+        0,
+    );
+    Some(PackedPushConstants {
+        contents,
+        offsets,
+        layouts,
+    })
+}
+
+/// Creates a bind group over `buffer` for `bind_group_layout`, whose dynamic binding covers
+/// `size` bytes.
+fn create_bind_group(
+    renderer: &DrawListRenderer,
+    label: &str,
+    buffer: &wgpu::Buffer,
+    bind_group_layout: Id,
+    size: u32,
+) -> Option<wgpu::BindGroup> {
+    let Some(layout) = renderer.bind_group_layouts.get(bind_group_layout) else {
+        tracing::warn!("Invalid bind group layout id ({bind_group_layout:?})");
+        return None;
+    };
+    Some(
+        renderer
+            .device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(label),
+                layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer,
+                        offset: 0,
+                        size: wgpu::BufferSize::new(u64::from(size)),
+                    }),
+                }],
+            }),
+    )
+}
+
+/// The push constant values of one draw, and where they were packed when emulated.
+#[derive(Clone, Copy, Default)]
+pub(super) struct DrawPushConstants<'a> {
+    pub data: Option<&'a [u8]>,
+    /// The buffer the draw was packed into, and the draw's index in it.
+    pub packed: Option<(&'a PushConstantBuffer, usize)>,
+}
+
+impl<'a> DrawPushConstants<'a> {
+    pub(super) fn new(data: Option<&'a [u8]>, packed: Option<&'a PushConstantBuffer>) -> Self {
+        Self {
+            data,
+            packed: packed.map(|packed| (packed, 0)),
+        }
+    }
+}
+
+/// Sets the push constants of a draw. Draws without values set zeroes.
+///
+/// Returns `false` and logs a warning when the values do not match the material.
+pub(super) fn set_push_constants<'a>(
+    render_pass: &mut impl RenderEncoder<'a>,
+    prepared_draw: &PreparedDraw,
+    push_constants: DrawPushConstants<'a>,
+) -> bool {
+    let Some(resolved) = prepared_draw.push_constants else {
+        if push_constants.data.is_some() {
+            tracing::warn!("Push constants passed to a draw whose material declares none");
+            return false;
+        }
+        return true;
+    };
+
+    if let Some(data) = push_constants.data
+        && data.len() != resolved.size() as usize
+    {
+        tracing::warn!(
+            "Push constants of {} bytes passed to a material that declares {} bytes",
+            data.len(),
+            resolved.size()
+        );
+        return false;
+    }
+
+    match resolved {
+        ResolvedPushConstants::Native { stages, size } => match push_constants.data {
+            Some(data) => render_pass.set_push_constants(stages, 0, data),
+            None => render_pass.set_push_constants(stages, 0, &vec![0; size as usize]),
+        },
+        ResolvedPushConstants::Emulated {
+            slot,
+            bind_group_layout,
+            ..
+        } => {
+            let Some((packed, index)) = push_constants.packed else {
+                tracing::warn!("Emulated push constants were not packed for the draw");
+                return false;
+            };
+            let (Some(Some(offset)), Some(bind_group)) = (
+                packed.offsets.get(index),
+                packed.bind_groups.get(&bind_group_layout),
+            ) else {
+                tracing::warn!("Emulated push constants were not packed for the draw");
+                return false;
+            };
+            render_pass.set_bind_group(slot, Some(bind_group), &[*offset]);
+        }
+    }
+
+    true
+}
+
+impl DrawListRenderer {
+    /// Returns `true` when the device sets push constants natively instead of emulating them.
+    pub fn push_constants_supported(&self) -> bool {
+        self.device
+            .features()
+            .contains(wgpu::Features::PUSH_CONSTANTS)
+    }
+
+    /// Returns whether push constants of `layout` fit within the device's limit.
+    fn push_constants_fit(&self, layout: &PushConstantLayout) -> bool {
+        layout.size <= self.device.limits().max_push_constant_size
+    }
+
+    /// Finds room for `len` bytes of emulated push constants in the current submission's
+    /// chunks, creating a chunk when none has room left. Returns the chunk and the offset into
+    /// it, aligned for dynamic uniform bindings.
+    fn allocate_push_constants(&mut self, len: u64) -> (usize, u64) {
+        let alignment = u64::from(self.device.limits().min_uniform_buffer_offset_alignment);
+        let arena = &mut self.push_constant_arena;
+        while let Some(chunk) = arena.chunks.get(arena.current) {
+            let offset = arena.offset.next_multiple_of(alignment);
+            if offset + len <= chunk.size() {
+                arena.offset = offset + len;
+                return (arena.current, offset);
+            }
+            arena.current += 1;
+            arena.offset = 0;
+        }
+
+        arena
+            .chunks
+            .push(self.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("push_constants"),
+                size: len.max(PUSH_CONSTANT_CHUNK_SIZE),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }));
+        arena.current = arena.chunks.len() - 1;
+        arena.offset = len;
+        (arena.current, 0)
+    }
+
+    /// Resolves how the push constants of a material are set, creating the bind group layout
+    /// used to emulate them.
+    ///
+    /// `groups` are the `@group`s used by the material's bindings, which must not include the
+    /// fallback group. Returns `None` for layouts over the device's limit, which
+    /// [`Self::check_push_constant_layout`] rejected.
+    pub(super) fn resolve_push_constants(
+        &mut self,
+        layout: PushConstantLayout,
+        mut groups: impl Iterator<Item = u32>,
+    ) -> Option<ResolvedPushConstants> {
+        if self.push_constants_supported() {
+            return self
+                .push_constants_fit(&layout)
+                .then(|| ResolvedPushConstants::Native {
+                    stages: layout.visibility.as_wgpu(),
+                    size: layout.size,
+                });
+        }
+
+        if groups.any(|group| group == layout.fallback_group) {
+            tracing::warn!(
+                "Push constant fallback @group({}) is also used by the material's bindings",
+                layout.fallback_group
+            );
+            return None;
+        }
+
+        let bind_group_layout =
+            self.get_or_create_bind_group_layout_for_key(BindGroupLayoutKey {
+                bindings: vec![BindGroupLayoutBindingKey {
+                    binding: 0,
+                    visibility: layout.visibility,
+                    ty: BindGroupLayoutBindingTypeKey::DynamicUniform,
+                    min_binding_size: wgpu::BufferSize::new(u64::from(layout.size)),
+                }],
+            })?;
+
+        Some(ResolvedPushConstants::Emulated {
+            slot: layout.fallback_group,
+            bind_group_layout,
+            size: layout.size,
+        })
+    }
+
+    /// Rejects push constants over the limit of a device that supports them natively. Shaders
+    /// see the `PUSH_CONSTANTS` define on such devices, so they cannot fall back to a uniform;
+    /// the draws of the material are skipped instead.
+    pub(super) fn check_push_constant_layout(&self, layout: &PushConstantLayout) {
+        if self.push_constants_supported() && !self.push_constants_fit(layout) {
+            tracing::warn!(
+                "Push constants of {} bytes exceed the device limit of {} bytes; draws of the \
+                 material are skipped",
+                layout.size,
+                self.device.limits().max_push_constant_size
+            );
+        }
+    }
+}
+
+impl Material {
+    /// Declares push constants of type `T`, passed with each draw through
+    /// [`DrawList::draw_with_push_constants`](crate::draw_list::DrawList::draw_with_push_constants)
+    /// and
+    /// [`DrawList::draw_mesh_with_push_constants`](crate::draw_list::DrawList::draw_mesh_with_push_constants).
+    ///
+    /// Devices without [`wgpu::Features::PUSH_CONSTANTS`] bind the values as a uniform at
+    /// `@group(fallback_group) @binding(0)` instead, so no other binding may use that group.
+    /// Draws of this material that pass no values see zeroes.
+    ///
+    /// On devices with the feature, `T` must fit within
+    /// [`wgpu::Limits::max_push_constant_size`]; draws of materials whose push constants exceed
+    /// it are skipped with a warning.
+    pub fn push_constants<T: AsPushConstants>(mut self, fallback_group: u32) -> Self {
+        self.push_constants = Some(PushConstantLayout {
+            size: <T as crate::encase::ShaderType>::min_size().get() as u32,
+            visibility: T::VISIBILITY,
+            fallback_group,
+        });
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{UVec2, Vec4};
+
+    use super::*;
+    use crate::{
+        MaterialId,
+        draw_list::{DrawList, RenderTarget},
+        recording::RecordingSurface,
+        test_support::{Scene, fill_material},
+    };
+
+    const OBJECT_SHADER: &str = "
+        @group(0) @binding(0) var<uniform> object: Object;
+
+        struct Object {
+            color: vec4<f32>,
+        }
+
+        @vertex
+        fn vs_main() -> @builtin(position) vec4<f32> {
+            return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+        }
+
+        @fragment
+        fn fs_main() -> @location(0) vec4<f32> {
+            return object.color;
+        }
+    ";
+
+    #[derive(crate::encase::ShaderType)]
+    struct Object {
+        color: Vec4,
+    }
+
+    impl AsPushConstants for Object {
+        const VISIBILITY: ShaderVisibility = ShaderVisibility::Fragment;
+    }
+
+    /// Large enough for [`Object`] but not for [`Outline`].
+    const PUSH_CONSTANT_LIMIT: u32 = 16;
+
+    #[derive(crate::encase::ShaderType)]
+    struct Outline {
+        color: Vec4,
+        width: Vec4,
+    }
+
+    impl AsPushConstants for Outline {
+        const VISIBILITY: ShaderVisibility = ShaderVisibility::Fragment;
+    }
+
+    /// Declares `Object` with `fields` as push constants where they are native, as a uniform
+    /// otherwise.
+    fn native_shader(fields: &str) -> String {
+        format!(
+            "
+            struct Object {{
+                {fields}
+            }}
+
+            #ifdef PUSH_CONSTANTS
+            var<push_constant> object: Object;
+            #else
+            @group(0) @binding(0) var<uniform> object: Object;
+            #endif
+
+            @vertex
+            fn vs_main() -> @builtin(position) vec4<f32> {{
+                return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+            }}
+
+            @fragment
+            fn fs_main() -> @location(0) vec4<f32> {{
+                return object.color;
+            }}
+            "
+        )
+    }
+
+    /// Creates a recording renderer whose device supports push constants of up to
+    /// [`PUSH_CONSTANT_LIMIT`] bytes.
+    fn native_renderer() -> DrawListRenderer {
+        DrawListRenderer::new_recording_with_device(&wgpu::DeviceDescriptor {
+            required_features: wgpu::Features::PUSH_CONSTANTS,
+            required_limits: wgpu::Limits {
+                max_push_constant_size: PUSH_CONSTANT_LIMIT,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    fn object_material(renderer: &mut DrawListRenderer) -> MaterialId {
+        let material = renderer
+            .create_material_from_shader("object", OBJECT_SHADER)
+            .unwrap();
+        renderer.create_material(material.push_constants::<Object>(0))
+    }
+
+    fn prepare(renderer: &mut DrawListRenderer, material: MaterialId) -> PreparedDraw {
+        let surface = RecordingSurface::new(renderer, UVec2::new(4, 4));
+        let format = surface.frame_context().format;
+        PreparedDraw::try_new(
+            renderer,
+            format,
+            RenderTarget::Surface,
+            None,
+            material,
+            None,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn values_are_packed_at_aligned_offsets_and_zero_filled() {
+        let mut renderer = DrawListRenderer::new_recording().unwrap();
+        assert!(!renderer.push_constants_supported());
+        let material = object_material(&mut renderer);
+        let emulated = prepare(&mut renderer, material);
+        let fill = fill_material(&mut renderer, "fill");
+        let without_push_constants = prepare(&mut renderer, fill);
+
+        let values = [7; 16];
+        let packed = pack(
+            &renderer,
+            [
+                (&emulated, Some(&values[..])),
+                (&without_push_constants, None),
+                (&emulated, None),
+            ],
+        )
+        .unwrap();
+
+        let alignment = renderer.device.limits().min_uniform_buffer_offset_alignment;
+        assert_eq!(packed.offsets, [Some(0), None, Some(alignment)]);
+        assert_eq!(packed.contents.len(), alignment as usize + 16);
+        assert_eq!(packed.contents[..16], values);
+        assert!(packed.contents[16..].iter().all(|byte| *byte == 0));
+        assert_eq!(packed.layouts.len(), 1);
+
+        assert!(pack(&renderer, [(&without_push_constants, None)]).is_none());
+    }
+
+    #[test]
+    fn draws_of_each_submission_share_one_buffer() {
+        let mut scene = Scene::new();
+        let material = object_material(&mut scene.renderer);
+
+        let mut draw_list = DrawList::new();
+        for color in [Vec4::X, Vec4::Y, Vec4::Z] {
+            draw_list.draw_with_push_constants(
+                RenderTarget::Surface,
+                material,
+                3,
+                &Object { color },
+            );
+        }
+
+        for _ in 0..2 {
+            let submission = scene.submit(&draw_list);
+            assert_eq!(submission.draws().count(), 3);
+            assert_eq!(scene.renderer.push_constant_arena.chunks.len(), 1);
+        }
+    }
+
+    #[test]
+    fn native_push_constants_are_set_on_the_pipeline() {
+        let mut renderer = native_renderer();
+        let surface = RecordingSurface::new(&renderer, UVec2::new(4, 4));
+        let material = renderer
+            .create_material_from_shader("object", &native_shader("color: vec4<f32>,"))
+            .unwrap();
+        let material = renderer.create_material(material.push_constants::<Object>(0));
+
+        let mut draw_list = DrawList::new();
+        draw_list.draw_with_push_constants(
+            RenderTarget::Surface,
+            material,
+            3,
+            &Object { color: Vec4::ONE },
+        );
+        renderer
+            .device
+            .push_error_scope(wgpu::ErrorFilter::Validation);
+        renderer.submit_draw_list(surface.frame_context(), &draw_list);
+        assert!(pollster::block_on(renderer.device.pop_error_scope()).is_none());
+
+        let submission = renderer.take_recorded_submissions().pop().unwrap();
+        assert_eq!(submission.draws().count(), 1);
+        assert_eq!(renderer.stats().last_submission.pipeline_cache_misses, 1);
+        assert_eq!(renderer.render_pipeline_cache.len(), 1);
+        assert!(renderer.push_constant_arena.chunks.is_empty());
+    }
+
+    #[test]
+    fn push_constants_over_the_device_limit_are_rejected() {
+        let mut renderer = native_renderer();
+        let surface = RecordingSurface::new(&renderer, UVec2::new(4, 4));
+        let material = renderer
+            .create_material_from_shader(
+                "outline",
+                &native_shader("color: vec4<f32>, width: vec4<f32>,"),
+            )
+            .unwrap();
+        let material = renderer.create_material(material.push_constants::<Outline>(0));
+
+        let mut draw_list = DrawList::new();
+        draw_list.draw_with_push_constants(
+            RenderTarget::Surface,
+            material,
+            3,
+            &Outline {
+                color: Vec4::ONE,
+                width: Vec4::ONE,
+            },
+        );
+        renderer
+            .device
+            .push_error_scope(wgpu::ErrorFilter::Validation);
+        renderer.submit_draw_list(surface.frame_context(), &draw_list);
+        assert!(pollster::block_on(renderer.device.pop_error_scope()).is_none());
+
+        // The draw is skipped before a pipeline the shader cannot match is created.
+        let submission = renderer.take_recorded_submissions().pop().unwrap();
+        assert_eq!(submission.draws().count(), 0);
+        assert!(renderer.render_pipeline_cache.is_empty());
+    }
+}
@@ -0,0 +1,379 @@
+//! Reading storage buffers and render targets back to the CPU without blocking the frame.
+//!
+//! [`DrawListRenderer::read_storage_buffer`] copies a storage buffer into a mappable buffer and
+//! returns a [`Readback`] right away. The copy is mapped while the device is polled, which
+//! every submission does, or [`DrawListRenderer::poll_readbacks`] does between frames. The
+//! readback then resolves, either by checking it from the frame loop:
+//!
+//! ```ignore
+//! let mut histogram = renderer.read_storage_buffer::<u32>(histogram_buffer).unwrap();
+//! // ... a few frames later ...
+//! if let Some(Ok(bins)) = histogram.try_take() {
+//!     println!("{bins:?}");
+//! }
+//! ```
+//!
+//! or by awaiting it, as [`Readback`] is a [`Future`].
+
+use std::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+use glam::UVec2;
+
+use crate::{AsStorageBufferElement, DrawListRenderer, RenderTargetId, StorageBufferId};
+
+/// Why a readback did not resolve to data.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ReadbackError {
+    /// The copy of the buffer could not be mapped.
+    Map(wgpu::BufferAsyncError),
+    /// The device was lost, or replaced, before the copy was mapped.
+    DeviceLost,
+    /// The bytes could not be decoded into the requested elements.
+    Decode(String),
+}
+
+impl fmt::Display for ReadbackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReadbackError::Map(error) => write!(f, "Could not map the readback buffer: {error}"),
+            ReadbackError::DeviceLost => write!(f, "The device was lost before the readback."),
+            ReadbackError::Decode(error) => write!(f, "Could not decode the readback: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for ReadbackError {}
+
+#[derive(Default)]
+struct ReadbackState {
+    /// Set by the map callback.
+    mapped: Option<Result<(), wgpu::BufferAsyncError>>,
+    result: Option<Result<Vec<u8>, ReadbackError>>,
+    waker: Option<Waker>,
+}
+
+impl ReadbackState {
+    fn resolve(&mut self, result: Result<Vec<u8>, ReadbackError>) {
+        self.result = Some(result);
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// The contents of a storage buffer, once they arrive from the GPU.
+///
+/// Resolves once: after [`Readback::try_take`] returned a result, or the future completed, the
+/// readback stays pending.
+pub struct Readback<T> {
+    state: Arc<Mutex<ReadbackState>>,
+    decode: fn(Vec<u8>) -> Result<T, ReadbackError>,
+}
+
+impl<T> Readback<T> {
+    /// Returns `true` when the result can be taken.
+    pub fn is_ready(&self) -> bool {
+        self.state.lock().unwrap().result.is_some()
+    }
+
+    /// Returns the result if it arrived, without waiting.
+    pub fn try_take(&mut self) -> Option<Result<T, ReadbackError>> {
+        let result = self.state.lock().unwrap().result.take()?;
+        Some(result.and_then(self.decode))
+    }
+}
+
+impl<T> Future for Readback<T> {
+    type Output = Result<T, ReadbackError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.state.lock().unwrap();
+        match state.result.take() {
+            Some(result) => Poll::Ready(result.and_then(self.decode)),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+pub(super) struct PendingReadback {
+    buffer: wgpu::Buffer,
+    row_len: usize,
+    padded_row_len: usize,
+    state: Arc<Mutex<ReadbackState>>,
+}
+
+impl DrawListRenderer {
+    /// Reads the elements of a storage buffer back, as they are after all work submitted so
+    /// far.
+    pub fn read_storage_buffer<T>(
+        &mut self,
+        storage_buffer: StorageBufferId,
+    ) -> Option<Readback<Vec<T>>>
+    where
+        T: AsStorageBufferElement + crate::encase::internal::CreateFrom,
+    {
+        let state = self.queue_storage_buffer_readback(storage_buffer)?;
+        Some(Readback {
+            state,
+            decode: |bytes| {
+                crate::encase::StorageBuffer::new(bytes)
+                    .create()
+                    .map_err(|error| ReadbackError::Decode(error.to_string()))
+            },
+        })
+    }
+
+    /// Reads the raw bytes of a storage buffer back, as they are after all work submitted so
+    /// far.
+    pub fn read_storage_buffer_bytes(
+        &mut self,
+        storage_buffer: StorageBufferId,
+    ) -> Option<Readback<Vec<u8>>> {
+        let state = self.queue_storage_buffer_readback(storage_buffer)?;
+        Some(Readback { state, decode: Ok })
+    }
+
+    /// Polls the device without blocking and resolves the readbacks that were mapped.
+    ///
+    /// Submissions do this as well, so this is only needed while no frames are submitted.
+    pub fn poll_readbacks(&mut self) {
+        if self.readbacks.is_empty() {
+            return;
+        }
+
+        if let Err(error) = self.device.poll(wgpu::PollType::Poll) {
+            tracing::warn!("Could not poll the device for readbacks: {error}");
+        }
+
+        self.readbacks.retain(|readback| {
+            let mut state = readback.state.lock().unwrap();
+            match state.mapped.take() {
+                None => true,
+                Some(Ok(())) => {
+                    let bytes = readback
+                        .buffer
+                        .slice(..)
+                        .get_mapped_range()
+                        .chunks_exact(readback.padded_row_len)
+                        .flat_map(|row| &row[..readback.row_len])
+                        .copied()
+                        .collect();
+                    readback.buffer.unmap();
+                    state.resolve(Ok(bytes));
+                    false
+                }
+                Some(Err(error)) => {
+                    state.resolve(Err(ReadbackError::Map(error)));
+                    false
+                }
+            }
+        });
+    }
+
+    /// Fails every readback that was not resolved yet, as their buffers belong to a lost
+    /// device.
+    pub(super) fn fail_readbacks(&mut self) {
+        for readback in self.readbacks.drain(..) {
+            readback
+                .state
+                .lock()
+                .unwrap()
+                .resolve(Err(ReadbackError::DeviceLost));
+        }
+    }
+
+    /// Reads a region of a render target back as tightly packed rows of texels, as they are
+    /// after all work submitted so far.
+    ///
+    /// Transient render targets do not keep their contents between submissions and cannot be
+    /// read back.
+    pub fn read_render_target(
+        &mut self,
+        render_target: RenderTargetId,
+        origin: UVec2,
+        size: UVec2,
+    ) -> Option<Readback<Vec<u8>>> {
+        let Some(record) = self.render_targets.get(render_target) else {
+            tracing::warn!("Invalid render target id ({render_target:?})");
+            return None;
+        };
+        let Some(texture) = record._texture.as_ref() else {
+            tracing::warn!(
+                "Render target ({render_target:?}) has no contents to read back; it was not drawn \
+                 into yet or is transient."
+            );
+            return None;
+        };
+        let end = origin + size;
+        if size.x == 0 || size.y == 0 || end.x > record.size.x || end.y > record.size.y {
+            tracing::warn!(
+                "Readback region {origin}..{end} does not fit inside render target \
+                 ({render_target:?}) of size {}.",
+                record.size
+            );
+            return None;
+        }
+
+        let texel_size = record.format.to_wgpu().block_copy_size(None)?;
+        let row_len = size.x * texel_size;
+        let padded_row_len = row_len.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(&format!("{}_readback", record.name)),
+            size: u64::from(padded_row_len) * u64::from(size.y),
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("readback_encoder"),
+            });
+        encoder.copy_texture_to_buffer(
+            wgpu::TexelCopyTextureInfo {
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: origin.x,
+                    y: origin.y,
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row_len),
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width: size.x,
+                height: size.y,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        let state =
+            self.submit_readback(encoder, buffer, row_len as usize, padded_row_len as usize);
+        Some(Readback { state, decode: Ok })
+    }
+
+    /// Submits a copy of a storage buffer into a mappable buffer.
+    fn queue_storage_buffer_readback(
+        &mut self,
+        storage_buffer_id: StorageBufferId,
+    ) -> Option<Arc<Mutex<ReadbackState>>> {
+        let Some(storage_buffer) = self.storage_buffers.get(storage_buffer_id) else {
+            tracing::warn!("Invalid storage buffer id ({storage_buffer_id:?})");
+            return None;
+        };
+        let byte_len = storage_buffer.byte_len;
+        let Some(record) = self.buffers.get(storage_buffer.buffer) else {
+            tracing::warn!(
+                "Invalid buffer id ({:?}) for storage buffer ({storage_buffer_id:?})",
+                storage_buffer.buffer
+            );
+            return None;
+        };
+
+        let copy_size = byte_len.next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT);
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(&format!("{}_readback", record.label)),
+            size: copy_size,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("readback_encoder"),
+            });
+        encoder.copy_buffer_to_buffer(&record.buffer, 0, &buffer, 0, copy_size);
+
+        // The whole buffer is a single row.
+        Some(self.submit_readback(encoder, buffer, byte_len as usize, copy_size as usize))
+    }
+
+    /// Submits `encoder`, which copies into `buffer`, and maps the buffer once the copy is done.
+    ///
+    /// The buffer holds rows of `padded_row_len` bytes, of which the first `row_len` are read.
+    fn submit_readback(
+        &mut self,
+        encoder: wgpu::CommandEncoder,
+        buffer: wgpu::Buffer,
+        row_len: usize,
+        padded_row_len: usize,
+    ) -> Arc<Mutex<ReadbackState>> {
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        let state = Arc::new(Mutex::new(ReadbackState::default()));
+        let mapped = Arc::clone(&state);
+        buffer.map_async(wgpu::MapMode::Read, .., move |result| {
+            mapped.lock().unwrap().mapped = Some(result);
+        });
+
+        self.readbacks.push(PendingReadback {
+            buffer,
+            row_len,
+            padded_row_len,
+            state: Arc::clone(&state),
+        });
+        state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn storage_buffer_readbacks_resolve_once_polled() {
+        let mut renderer = DrawListRenderer::new_recording().unwrap();
+        let buffer = renderer
+            .create_storage_buffer("values", &[1_u32, 2, 3])
+            .unwrap();
+        let mut readback = renderer.read_storage_buffer::<u32>(buffer).unwrap();
+
+        renderer.poll_readbacks();
+        assert!(readback.is_ready());
+        assert_eq!(readback.try_take(), Some(Ok(vec![1, 2, 3])));
+        // The result is taken only once.
+        assert_eq!(readback.try_take(), None);
+        assert!(renderer.readbacks.is_empty());
+    }
+
+    #[test]
+    fn readbacks_can_be_awaited() {
+        let mut renderer = DrawListRenderer::new_recording().unwrap();
+        let buffer = renderer.create_storage_buffer("values", &[8_u32]).unwrap();
+        let readback = renderer.read_storage_buffer_bytes(buffer).unwrap();
+        renderer.poll_readbacks();
+        assert_eq!(
+            pollster::block_on(readback),
+            Ok(8_u32.to_le_bytes().to_vec())
+        );
+    }
+
+    #[test]
+    fn lost_devices_fail_pending_readbacks() {
+        let mut renderer = DrawListRenderer::new_recording().unwrap();
+        let buffer = renderer.create_storage_buffer("values", &[7_u32]).unwrap();
+        let mut readback = renderer.read_storage_buffer_bytes(buffer).unwrap();
+
+        renderer.fail_readbacks();
+        assert_eq!(readback.try_take(), Some(Err(ReadbackError::DeviceLost)));
+        assert!(renderer.readbacks.is_empty());
+    }
+}
@@ -0,0 +1,687 @@
+//! Recording what submissions draw, for testing scene code without a GPU.
+//!
+//! [`DrawListRenderer::new_recording`], available with the `recording` feature, creates a
+//! renderer on the no-op backend of `wgpu`. Every call still goes through `wgpu` and is
+//! validated by it as on a real device, along with the renderer's own checks of ids, bindings,
+//! sizes, and depth buffer initialization; only the backend does no GPU work. While recording is
+//! enabled, on any device, every submission leaves a [`RecordedSubmission`] describing the render
+//! and compute passes it began, the draws and dispatches encoded into them, and the updates
+//! copied between them, so tests can assert what was drawn:
+//!
+//! ```ignore
+//! let mut renderer = DrawListRenderer::new_recording().unwrap();
+//! let surface = RecordingSurface::new(&renderer, UVec2::new(640, 480));
+//! // ... create resources and build `draw_list` with the scene code under test ...
+//! renderer.submit_draw_list(surface.frame_context(), &draw_list);
+//!
+//! let submission = renderer.take_recorded_submissions().pop().unwrap();
+//! assert!(submission.draws_into(RenderTarget::Surface).any(|draw| {
+//!     draw.mesh == Some(player_mesh) && draw.material == player_material
+//! }));
+//! ```
+
+use std::collections::VecDeque;
+
+use glam::{UVec2, UVec3};
+
+use crate::{
+    ComputeMaterialId, DepthBufferId, DrawBundleId, DrawListRenderer, FrameContext, MaterialId,
+    MeshId, StorageBufferId, TextureId, UniformId, commands::IndirectArgs, draw_list::RenderTarget,
+};
+
+/// Recorded submissions that were not taken yet are dropped beyond this many.
+const MAX_RECORDED_SUBMISSIONS: usize = 64;
+
+/// A draw encoded into a render pass.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecordedDraw {
+    pub material: MaterialId,
+    pub mesh: Option<MeshId>,
+    /// Vertices, or indices of the mesh, drawn per instance. `None` for indirect draws.
+    pub elements: Option<u32>,
+    /// Instances drawn. Zero for indirect draws, as their counts are only known on the GPU.
+    pub instances: u32,
+    /// The storage buffer holding the arguments of an indirect draw.
+    pub indirect_buffer: Option<StorageBufferId>,
+    /// Draws issued by the command, which is more than one for indirect multi-draws.
+    pub draw_count: u32,
+    /// The draw bundle the draw was executed from.
+    pub draw_bundle: Option<DrawBundleId>,
+    pub label: Option<String>,
+}
+
+impl RecordedDraw {
+    pub(super) fn direct(
+        material: MaterialId,
+        mesh: Option<MeshId>,
+        elements: u32,
+        instances: u32,
+        label: Option<&str>,
+    ) -> Self {
+        Self {
+            material,
+            mesh,
+            elements: Some(elements),
+            instances,
+            indirect_buffer: None,
+            draw_count: 1,
+            draw_bundle: None,
+            label: label.map(str::to_string),
+        }
+    }
+
+    pub(super) fn indirect(
+        material: MaterialId,
+        mesh: Option<MeshId>,
+        indirect: IndirectArgs,
+        label: Option<&str>,
+    ) -> Self {
+        Self {
+            material,
+            mesh,
+            elements: None,
+            instances: 0,
+            indirect_buffer: Some(indirect.buffer),
+            draw_count: indirect.draw_count,
+            draw_bundle: None,
+            label: label.map(str::to_string),
+        }
+    }
+}
+
+/// A compute dispatch encoded into a compute pass.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecordedDispatch {
+    pub compute_material: ComputeMaterialId,
+    pub workgroup_count: UVec3,
+    pub label: Option<String>,
+}
+
+/// A render or compute pass begun by a submission.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordedPass {
+    /// The label the pass was given in graphics debuggers.
+    pub label: String,
+    /// The render target drawn into, or `None` for passes that only clear a depth buffer and for
+    /// compute passes.
+    pub render_target: Option<RenderTarget>,
+    pub depth_buffer: Option<DepthBufferId>,
+    /// The value the depth buffer was cleared to, for depth buffer clears.
+    pub depth_clear_value: Option<f32>,
+    pub draws: Vec<RecordedDraw>,
+    /// The dispatch of a compute pass.
+    pub dispatch: Option<RecordedDispatch>,
+}
+
+/// The resource an update recorded in a draw list wrote to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecordedWriteTarget {
+    Uniform(UniformId),
+    StorageBuffer(StorageBufferId),
+    Texture(TextureId),
+}
+
+/// An update recorded in a draw list, encoded as a copy between the passes around it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RecordedWrite {
+    pub target: RecordedWriteTarget,
+    /// The number of passes of the submission encoded before the copy.
+    pub passes_before: usize,
+}
+
+/// The render passes of one submission, in encoding order.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RecordedSubmission {
+    /// Index of the submission, counted from the creation of the renderer.
+    pub submission: u64,
+    pub passes: Vec<RecordedPass>,
+    /// Updates encoded between the passes, in encoding order.
+    pub writes: Vec<RecordedWrite>,
+}
+
+impl RecordedSubmission {
+    /// Returns every draw of the submission, in encoding order.
+    pub fn draws(&self) -> impl Iterator<Item = &RecordedDraw> {
+        self.passes.iter().flat_map(|pass| pass.draws.iter())
+    }
+
+    /// Returns every dispatch of the submission, in encoding order.
+    pub fn dispatches(&self) -> impl Iterator<Item = &RecordedDispatch> {
+        self.passes.iter().filter_map(|pass| pass.dispatch.as_ref())
+    }
+
+    /// Returns the draws into `render_target`, in encoding order.
+    pub fn draws_into(&self, render_target: RenderTarget) -> impl Iterator<Item = &RecordedDraw> {
+        self.passes
+            .iter()
+            .filter(move |pass| pass.render_target == Some(render_target))
+            .flat_map(|pass| pass.draws.iter())
+    }
+}
+
+/// An offscreen texture standing in for the window surface of a recording renderer.
+pub struct RecordingSurface {
+    view: wgpu::TextureView,
+    size: UVec2,
+    format: wgpu::TextureFormat,
+}
+
+impl RecordingSurface {
+    /// Creates a surface of `size` with an sRGB RGBA format.
+    pub fn new(renderer: &DrawListRenderer, size: UVec2) -> Self {
+        Self::with_format(renderer, size, wgpu::TextureFormat::Rgba8UnormSrgb)
+    }
+
+    /// Creates a surface of `size` and `format`.
+    pub fn with_format(
+        renderer: &DrawListRenderer,
+        size: UVec2,
+        format: wgpu::TextureFormat,
+    ) -> Self {
+        let texture = renderer.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("recording_surface"),
+            size: wgpu::Extent3d {
+                width: size.x,
+                height: size.y,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+
+        Self {
+            view: texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            size,
+            format,
+        }
+    }
+
+    /// Returns the context to submit draw lists and render graphs with.
+    pub fn frame_context(&self) -> FrameContext<'_> {
+        FrameContext::new(&self.view, self.size, self.format)
+    }
+}
+
+#[derive(Default)]
+pub(super) struct Recorder {
+    /// Passes of the submission being encoded.
+    passes: Vec<RecordedPass>,
+    /// Writes of the submission being encoded.
+    writes: Vec<RecordedWrite>,
+    completed: VecDeque<RecordedSubmission>,
+}
+
+impl DrawListRenderer {
+    /// Creates a renderer on the no-op backend of `wgpu`, with recording enabled.
+    ///
+    /// Commands are encoded and submitted through `wgpu` as usual, but the backend does no GPU
+    /// work, so buffers and textures cannot be read back. The device uses the default limits and
+    /// no optional features, so push constants are emulated. Returns `None` when `wgpu` was
+    /// built without the no-op backend.
+    #[cfg(any(test, feature = "recording"))]
+    pub fn new_recording() -> Option<Self> {
+        Self::new_recording_with_device(&Default::default())
+    }
+
+    /// Creates a renderer on the no-op backend with the features and limits of `descriptor`,
+    /// which the no-op adapter supports in full.
+    #[cfg(any(test, feature = "recording"))]
+    pub(crate) fn new_recording_with_device(descriptor: &wgpu::DeviceDescriptor) -> Option<Self> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::NOOP,
+            backend_options: wgpu::BackendOptions {
+                noop: wgpu::NoopBackendOptions { enable: true },
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        let adapter = match pollster::block_on(instance.request_adapter(&Default::default())) {
+            Ok(adapter) => adapter,
+            Err(error) => {
+                tracing::warn!("Could not request the no-op adapter: {error}");
+                return None;
+            }
+        };
+        let (device, queue) = match pollster::block_on(adapter.request_device(descriptor)) {
+            Ok(device) => device,
+            Err(error) => {
+                tracing::warn!("Could not request a device from the no-op adapter: {error}");
+                return None;
+            }
+        };
+
+        let mut renderer = Self::new(device, queue);
+        renderer.set_recording(true);
+        Some(renderer)
+    }
+
+    /// Enables or disables recording the passes and draws of every submission.
+    ///
+    /// Disabling it drops submissions that were not taken yet.
+    pub fn set_recording(&mut self, enabled: bool) {
+        match (enabled, self.recorder.is_some()) {
+            (true, false) => self.recorder = Some(Recorder::default()),
+            (false, true) => self.recorder = None,
+            _ => {}
+        }
+    }
+
+    /// Returns `true` when recording is enabled.
+    pub fn recording_enabled(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    /// Returns the submissions recorded since the last call, oldest first.
+    ///
+    /// Only the most recent 64 submissions are kept.
+    pub fn take_recorded_submissions(&mut self) -> Vec<RecordedSubmission> {
+        self.recorder
+            .as_mut()
+            .map(|recorder| recorder.completed.drain(..).collect())
+            .unwrap_or_default()
+    }
+
+    /// Records the start of a render or compute pass.
+    pub(super) fn record_pass(
+        &mut self,
+        label: &str,
+        render_target: Option<RenderTarget>,
+        depth_buffer: Option<DepthBufferId>,
+        depth_clear_value: Option<f32>,
+    ) {
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.passes.push(RecordedPass {
+                label: label.to_string(),
+                render_target,
+                depth_buffer,
+                depth_clear_value,
+                draws: Vec::new(),
+                dispatch: None,
+            });
+        }
+    }
+
+    /// Records several draws into the most recently begun render pass.
+    pub(super) fn record_draws(&mut self, draws: impl FnOnce() -> Vec<RecordedDraw>) {
+        if let Some(pass) = self
+            .recorder
+            .as_mut()
+            .and_then(|recorder| recorder.passes.last_mut())
+        {
+            pass.draws.extend(draws());
+        }
+    }
+
+    /// Records the dispatch of the most recently begun compute pass.
+    pub(super) fn record_dispatch(&mut self, dispatch: RecordedDispatch) {
+        if let Some(pass) = self
+            .recorder
+            .as_mut()
+            .and_then(|recorder| recorder.passes.last_mut())
+        {
+            pass.dispatch = Some(dispatch);
+        }
+    }
+
+    /// Records an update encoded after the passes begun so far.
+    pub(super) fn record_write(&mut self, target: RecordedWriteTarget) {
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.writes.push(RecordedWrite {
+                target,
+                passes_before: recorder.passes.len(),
+            });
+        }
+    }
+
+    /// Completes the recording of the submission that was just submitted.
+    pub(super) fn finish_recorded_submission(&mut self) {
+        let submission = self.submission_index;
+        let Some(recorder) = self.recorder.as_mut() else {
+            return;
+        };
+
+        if recorder.completed.len() == MAX_RECORDED_SUBMISSIONS {
+            recorder.completed.pop_front();
+        }
+        recorder.completed.push_back(RecordedSubmission {
+            submission,
+            passes: std::mem::take(&mut recorder.passes),
+            writes: std::mem::take(&mut recorder.writes),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Vec2, Vec4};
+
+    use super::*;
+    use crate::{
+        draw_list::{DrawList, DrawSortMode, ScissorRect, Viewport},
+        mesh::{AsInstanceBufferLayout, AsVertexBufferLayout, VertexAttribute, VertexFormat},
+        test_support::Scene,
+    };
+
+    const MESH_SHADER: &str = "
+        @vertex
+        fn vs_main(
+            @location(0) position: vec4<f32>,
+            @location(1) offset: vec4<f32>,
+        ) -> @builtin(position) vec4<f32> {
+            return position + offset;
+        }
+
+        @fragment
+        fn fs_main() -> @location(0) vec4<f32> {
+            return vec4<f32>(1.0);
+        }
+    ";
+
+    #[derive(crate::encase::ShaderType)]
+    struct Vertex {
+        position: Vec4,
+    }
+
+    impl AsVertexBufferLayout for Vertex {
+        const STRIDE: u64 = 16;
+        const ATTRIBUTES: &'static [VertexAttribute] = &[VertexAttribute {
+            format: VertexFormat::Float32x4,
+            offset: 0,
+        }];
+    }
+
+    #[derive(crate::encase::ShaderType)]
+    struct Instance {
+        offset: Vec4,
+    }
+
+    impl AsInstanceBufferLayout for Instance {
+        const STRIDE: u64 = 16;
+        const ATTRIBUTES: &'static [VertexAttribute] = &[VertexAttribute {
+            format: VertexFormat::Float32x4,
+            offset: 0,
+        }];
+    }
+
+    fn quad(renderer: &mut DrawListRenderer) -> MeshId {
+        let vertices: Vec<Vertex> = (0..4)
+            .map(|_| Vertex {
+                position: Vec4::ZERO,
+            })
+            .collect();
+        renderer.create_mesh("quad", &vertices, &[0, 1, 2, 2, 1, 3])
+    }
+
+    fn materials<'a>(draws: impl IntoIterator<Item = &'a RecordedDraw>) -> Vec<MaterialId> {
+        draws.into_iter().map(|draw| draw.material).collect()
+    }
+
+    #[test]
+    fn direct_draws_are_recorded_in_order() {
+        let mut scene = Scene::new();
+        let fill = scene.fill_material("fill");
+        let mesh_material = scene.material("mesh", MESH_SHADER);
+        let quad = quad(&mut scene.renderer);
+
+        let mut draw_list = DrawList::new();
+        draw_list.set_draw_label(Some("background"));
+        draw_list.draw(RenderTarget::Surface, fill, 3);
+        draw_list.set_draw_label(None);
+        draw_list.draw_mesh_instanced(
+            RenderTarget::Surface,
+            quad,
+            mesh_material,
+            &[Instance { offset: Vec4::X }, Instance { offset: Vec4::Y }],
+        );
+
+        // Draws outside a sorted range are encoded into a pass each.
+        let submission = scene.submit(&draw_list);
+        assert_eq!(submission.passes.len(), 2);
+        assert_eq!(
+            submission.draws().collect::<Vec<_>>(),
+            [
+                &RecordedDraw::direct(fill, None, 3, 1, Some("background")),
+                &RecordedDraw::direct(mesh_material, Some(quad), 6, 2, None),
+            ]
+        );
+
+        let stats = scene.renderer.stats().last_submission;
+        assert_eq!((stats.draws, stats.instances, stats.triangles), (2, 3, 5));
+    }
+
+    #[test]
+    fn draws_into_other_targets_start_new_passes() {
+        let mut scene = Scene::new();
+        let fill = scene.fill_material("fill");
+        let render_target = scene.renderer.create_render_target(
+            "offscreen",
+            crate::render_target::RenderTargetSize::SurfaceSize,
+            crate::render_target::RenderTargetFormat::RgbaSrgb,
+        );
+
+        let mut draw_list = DrawList::new();
+        draw_list.draw(RenderTarget::Custom(render_target), fill, 3);
+        draw_list.draw(RenderTarget::Surface, fill, 6);
+        draw_list.draw(RenderTarget::Custom(render_target), fill, 9);
+
+        let submission = scene.submit(&draw_list);
+        assert_eq!(
+            submission
+                .passes
+                .iter()
+                .map(|pass| pass.render_target)
+                .collect::<Vec<_>>(),
+            [
+                Some(RenderTarget::Custom(render_target)),
+                Some(RenderTarget::Surface),
+                Some(RenderTarget::Custom(render_target)),
+            ]
+        );
+        let elements: Vec<Option<u32>> = submission
+            .draws_into(RenderTarget::Custom(render_target))
+            .map(|draw| draw.elements)
+            .collect();
+        assert_eq!(elements, [Some(3), Some(9)]);
+    }
+
+    #[test]
+    fn sorted_draws_are_recorded_in_sorted_order() {
+        let mut scene = Scene::new();
+        let near = scene.fill_material("near");
+        let middle = scene.fill_material("middle");
+        let far = scene.fill_material("far");
+
+        for (mode, expected, passes) in [
+            (DrawSortMode::FrontToBack, [near, middle, far], 1),
+            (DrawSortMode::BackToFront, [far, middle, near], 1),
+            (DrawSortMode::None, [middle, far, near], 3),
+        ] {
+            let mut draw_list = DrawList::new();
+            draw_list.begin_sorted(mode);
+            for (material, depth) in [(middle, 2.0), (far, 3.0), (near, 1.0)] {
+                draw_list.set_sort_depth(depth);
+                draw_list.draw(RenderTarget::Surface, material, 3);
+            }
+            draw_list.end_sorted();
+
+            let submission = scene.submit(&draw_list);
+            assert_eq!(submission.passes.len(), passes, "{mode:?}");
+            assert_eq!(materials(submission.draws()), expected, "{mode:?}");
+        }
+    }
+
+    #[test]
+    fn bundled_draws_are_recorded_each_time_the_bundle_executes() {
+        let mut scene = Scene::new();
+        let first = scene.fill_material("first");
+        let second = scene.fill_material("second");
+        let direct = scene.fill_material("direct");
+
+        let mut bundle_list = DrawList::new();
+        bundle_list.draw(RenderTarget::Surface, first, 3);
+        bundle_list.draw(RenderTarget::Surface, second, 3);
+        let bundle = scene
+            .renderer
+            .create_draw_bundle("bundle", bundle_list)
+            .unwrap();
+
+        let mut draw_list = DrawList::new();
+        draw_list.execute_draw_bundle(bundle);
+        draw_list.draw(RenderTarget::Surface, direct, 3);
+        draw_list.execute_draw_bundle(bundle);
+
+        for _ in 0..2 {
+            let submission = scene.submit(&draw_list);
+            assert_eq!(
+                materials(submission.draws()),
+                [first, second, direct, first, second]
+            );
+            let bundles: Vec<Option<DrawBundleId>> =
+                submission.draws().map(|draw| draw.draw_bundle).collect();
+            assert_eq!(
+                bundles,
+                [Some(bundle), Some(bundle), None, Some(bundle), Some(bundle)]
+            );
+        }
+    }
+
+    #[test]
+    fn disabling_recording_drops_pending_submissions() {
+        let mut scene = Scene::new();
+        let fill = scene.fill_material("fill");
+        let mut draw_list = DrawList::new();
+        draw_list.draw(RenderTarget::Surface, fill, 3);
+
+        scene
+            .renderer
+            .submit_draw_list(scene.surface.frame_context(), &draw_list);
+        scene.renderer.set_recording(false);
+        assert!(!scene.renderer.recording_enabled());
+        scene
+            .renderer
+            .submit_draw_list(scene.surface.frame_context(), &draw_list);
+        scene.renderer.set_recording(true);
+
+        assert!(scene.renderer.take_recorded_submissions().is_empty());
+        assert_eq!(scene.renderer.stats().last_submission.draws, 1);
+    }
+
+    #[test]
+    fn draws_inside_their_pass_region_are_recorded() {
+        let mut scene = Scene::new();
+        let fill = scene.fill_material("fill");
+        let mut draw_list = DrawList::new();
+        draw_list.set_viewport(Some(Viewport::new(
+            Vec2::new(4.0, 0.0),
+            Vec2::new(4.0, 8.0),
+        )));
+        draw_list.set_scissor_rect(Some(ScissorRect::new(UVec2::new(4, 0), UVec2::new(4, 8))));
+        draw_list.draw(RenderTarget::Surface, fill, 3);
+
+        let submission = scene.submit(&draw_list);
+        assert_eq!(submission.passes.len(), 1);
+        assert_eq!(materials(submission.draws()), [fill]);
+    }
+
+    #[test]
+    fn rejected_pass_regions_skip_the_draw_before_a_pass_is_created() {
+        let regions = [
+            // Past the right edge of the 8x8 surface.
+            (
+                Some(Viewport::new(Vec2::new(4.0, 0.0), Vec2::new(8.0, 8.0))),
+                None,
+            ),
+            (
+                None,
+                Some(ScissorRect::new(UVec2::new(0, 6), UVec2::new(8, 4))),
+            ),
+            // Empty.
+            (Some(Viewport::new(Vec2::ZERO, Vec2::new(0.0, 8.0))), None),
+            (None, Some(ScissorRect::new(UVec2::ZERO, UVec2::new(8, 0)))),
+        ];
+
+        for sort_mode in [None, Some(DrawSortMode::State)] {
+            for (viewport, scissor_rect) in regions {
+                let mut scene = Scene::new();
+                let fill = scene.fill_material("fill");
+                let mut draw_list = DrawList::new();
+                if let Some(sort_mode) = sort_mode {
+                    draw_list.begin_sorted(sort_mode);
+                }
+                draw_list.set_viewport(viewport);
+                draw_list.set_scissor_rect(scissor_rect);
+                draw_list.draw(RenderTarget::Surface, fill, 3);
+                if sort_mode.is_some() {
+                    draw_list.end_sorted();
+                }
+
+                let submission = scene.submit(&draw_list);
+                assert!(submission.passes.is_empty());
+                assert_eq!(scene.renderer.stats().last_submission.passes, 0);
+                assert_eq!(scene.renderer.stats().last_submission.draws, 0);
+            }
+        }
+    }
+
+    #[test]
+    fn uniform_updates_are_copied_between_the_draws_around_them() {
+        #[derive(crate::encase::ShaderType)]
+        struct Tint {
+            color: Vec4,
+        }
+
+        impl crate::AsUniformBuffer for Tint {
+            const VISIBILITY: crate::ShaderVisibility = crate::ShaderVisibility::Fragment;
+        }
+
+        let mut scene = Scene::new();
+        let tint = scene
+            .renderer
+            .create_uniform("tint", &Tint { color: Vec4::ONE });
+        let material = scene
+            .renderer
+            .create_material_from_shader(
+                "tinted",
+                "
+                @group(0) @binding(0) var<uniform> tint: vec4<f32>;
+
+                @vertex
+                fn vs_main() -> @builtin(position) vec4<f32> {
+                    return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+                }
+
+                @fragment
+                fn fs_main() -> @location(0) vec4<f32> {
+                    return tint;
+                }
+                ",
+            )
+            .unwrap()
+            .uniform(0, 0, tint);
+        let material = scene.renderer.create_material(material);
+
+        let mut draw_list = DrawList::new();
+        draw_list.draw(RenderTarget::Surface, material, 3);
+        draw_list.update_uniform(tint, &Tint { color: Vec4::X });
+        draw_list.draw(RenderTarget::Surface, material, 3);
+        draw_list.update_uniform(tint, &Tint { color: Vec4::Y });
+
+        let submission = scene.submit(&draw_list);
+        assert_eq!(submission.passes.len(), 2);
+        assert_eq!(
+            submission.writes,
+            [1, 2].map(|passes_before| RecordedWrite {
+                target: RecordedWriteTarget::Uniform(tint),
+                passes_before,
+            })
+        );
+
+        // The staging buffers are reused by the next submission.
+        let submission = scene.submit(&draw_list);
+        assert_eq!(submission.writes.len(), 2);
+    }
+}
@@ -0,0 +1,1123 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet, BinaryHeap},
+};
+
+use glam::UVec2;
+
+use crate::{
+    ComputeMaterialId, DepthBufferId, DrawListRenderer, FrameContext, RenderTargetId,
+    StorageBufferId,
+    bindings::{DrawBinding, DrawBindingResource},
+    commands::FrameCommand,
+    common::Id,
+    draw_list::{DrawList, RenderTarget},
+    render_target::{RenderTargetFormat, RenderTargetRecord, create_render_target_texture},
+};
+
+/// A draw list along with the render targets, depth buffers, and storage buffers it reads and
+/// writes.
+///
+/// Build with [`RenderGraphPass::new`] and fluent setters, then add it to a [`RenderGraph`].
+/// Every render target and depth buffer the draw list touches must be declared: drawing into a
+/// render target or writing a depth buffer requires a write, sampling a render target through a
+/// material or depth-testing without writes requires a read. Storage buffers bound read-write,
+/// for example by a [dispatch](DrawList::dispatch) producing indirect draw arguments, must be
+/// declared as written; passes consuming them declare them as read to run after the writer.
+#[must_use]
+pub struct RenderGraphPass {
+    name: String,
+    draw_list: DrawList,
+    reads: Vec<RenderTargetId>,
+    writes: Vec<RenderTarget>,
+    depth_reads: Vec<DepthBufferId>,
+    depth_writes: Vec<DepthBufferId>,
+    storage_reads: Vec<StorageBufferId>,
+    storage_writes: Vec<StorageBufferId>,
+}
+
+impl RenderGraphPass {
+    /// Creates a pass that executes `draw_list`.
+    pub fn new(name: &str, draw_list: DrawList) -> Self {
+        Self {
+            name: name.to_string(),
+            draw_list,
+            reads: Vec::new(),
+            writes: Vec::new(),
+            depth_reads: Vec::new(),
+            depth_writes: Vec::new(),
+            storage_reads: Vec::new(),
+            storage_writes: Vec::new(),
+        }
+    }
+
+    /// Declares that materials drawn in this pass sample `render_target`.
+    pub fn reads_render_target(mut self, render_target: RenderTargetId) -> Self {
+        self.reads.push(render_target);
+        self
+    }
+
+    /// Declares that this pass draws into `render_target`.
+    pub fn writes_render_target(mut self, render_target: RenderTarget) -> Self {
+        self.writes.push(render_target);
+        self
+    }
+
+    /// Declares that this pass depth-tests against `depth_buffer` without writing it.
+    pub fn reads_depth_buffer(mut self, depth_buffer: DepthBufferId) -> Self {
+        self.depth_reads.push(depth_buffer);
+        self
+    }
+
+    /// Declares that this pass clears `depth_buffer` or draws with depth writes enabled.
+    pub fn writes_depth_buffer(mut self, depth_buffer: DepthBufferId) -> Self {
+        self.depth_writes.push(depth_buffer);
+        self
+    }
+
+    /// Declares that this pass reads `storage_buffer`, for example as the arguments of indirect
+    /// draws or through a material, after passes writing it.
+    pub fn reads_storage_buffer(mut self, storage_buffer: StorageBufferId) -> Self {
+        self.storage_reads.push(storage_buffer);
+        self
+    }
+
+    /// Declares that this pass writes `storage_buffer` through a read-write binding.
+    pub fn writes_storage_buffer(mut self, storage_buffer: StorageBufferId) -> Self {
+        self.storage_writes.push(storage_buffer);
+        self
+    }
+
+    fn read_resources(&self) -> impl Iterator<Item = GraphResource> + '_ {
+        self.reads
+            .iter()
+            .map(|&render_target| GraphResource::RenderTarget(RenderTarget::Custom(render_target)))
+            .chain(
+                self.depth_reads
+                    .iter()
+                    .copied()
+                    .map(GraphResource::DepthBuffer),
+            )
+            .chain(
+                self.storage_reads
+                    .iter()
+                    .copied()
+                    .map(GraphResource::StorageBuffer),
+            )
+    }
+
+    fn write_resources(&self) -> impl Iterator<Item = GraphResource> + '_ {
+        self.writes
+            .iter()
+            .copied()
+            .map(GraphResource::RenderTarget)
+            .chain(
+                self.depth_writes
+                    .iter()
+                    .copied()
+                    .map(GraphResource::DepthBuffer),
+            )
+            .chain(
+                self.storage_writes
+                    .iter()
+                    .copied()
+                    .map(GraphResource::StorageBuffer),
+            )
+    }
+
+    fn custom_render_targets(&self) -> impl Iterator<Item = RenderTargetId> + '_ {
+        self.reads
+            .iter()
+            .copied()
+            .chain(
+                self.writes
+                    .iter()
+                    .filter_map(|render_target| match render_target {
+                        RenderTarget::Surface => None,
+                        RenderTarget::Custom(id) => Some(*id),
+                    }),
+            )
+    }
+}
+
+/// A set of passes that are ordered by the resources they declare before executing.
+///
+/// Every pass that writes a resource runs before any pass that only reads it, so a pass
+/// sampling a render target always sees the final contents written in this graph. Passes writing
+/// the same resource, and otherwise independent passes, keep the order they were added in.
+/// Because each resource has a single set of final contents per graph, alternating between
+/// targets (for example a multi-step blur) uses a separate transient target per step; see
+/// [`DrawListRenderer::create_transient_render_target`].
+///
+/// Submit with [`DrawListRenderer::submit_render_graph`].
+#[derive(Default)]
+pub struct RenderGraph {
+    passes: Vec<RenderGraphPass>,
+}
+
+impl RenderGraph {
+    /// Creates an empty render graph.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a pass to the graph.
+    pub fn add_pass(&mut self, pass: RenderGraphPass) {
+        self.passes.push(pass);
+    }
+
+    /// Returns `true` when the graph has no passes.
+    pub fn is_empty(&self) -> bool {
+        self.passes.is_empty()
+    }
+}
+
+/// A render target, depth buffer, or storage buffer accessed by a render graph pass.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum GraphResource {
+    RenderTarget(RenderTarget),
+    DepthBuffer(DepthBufferId),
+    StorageBuffer(StorageBufferId),
+}
+
+/// A pooled surface-sized texture backing transient render targets.
+pub(super) struct TransientTexture {
+    format: RenderTargetFormat,
+    size: UVec2,
+    pub(super) texture: wgpu::Texture,
+    view: wgpu::TextureView,
+}
+
+impl TransientTexture {
+    fn new(device: &wgpu::Device, format: RenderTargetFormat, size: UVec2) -> Self {
+        let texture = create_render_target_texture(device, "transient_render_target", size, format);
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        Self {
+            format,
+            size,
+            texture,
+            view,
+        }
+    }
+}
+
+impl DrawListRenderer {
+    /// Creates a surface-sized render target whose texture is managed by render graphs.
+    ///
+    /// Each time a [`RenderGraph`] using the target is submitted, it is backed by a texture from
+    /// a pool shared with other transient targets of the same format. Targets whose passes do not
+    /// overlap in the graph's execution order share a texture, so contents are undefined before
+    /// the first pass writing the target and do not survive between submissions.
+    pub fn create_transient_render_target(
+        &mut self,
+        name: &str,
+        format: RenderTargetFormat,
+    ) -> RenderTargetId {
+        self.render_targets
+            .push(RenderTargetRecord::create_transient(name, format))
+    }
+
+    /// Orders, validates, and executes the passes of a render graph into the provided frame.
+    ///
+    /// Returns `false` without submitting anything when a pass uses a resource it did not
+    /// declare, samples a render target it draws into, reads a transient target no pass writes,
+    /// or when the passes depend on each other in a cycle.
+    pub fn submit_render_graph(
+        &mut self,
+        frame_context: FrameContext<'_>,
+        render_graph: &RenderGraph,
+    ) -> bool {
+        let Some(order) = self.compile_render_graph(render_graph) else {
+            return false;
+        };
+
+        self.assign_transient_render_targets(&frame_context, render_graph, &order);
+
+        let mut frame_instance_buffers: Vec<wgpu::Buffer> = Vec::new();
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("render_graph_encoder"),
+            });
+        self.encode_uploads(&mut encoder, self.upload_budget());
+
+        for index in order {
+            self.encode_draw_list(
+                frame_context,
+                &mut encoder,
+                &render_graph.passes[index].draw_list,
+                &mut frame_instance_buffers,
+            );
+        }
+        self.resolve_profiled_submission(&mut encoder);
+
+        self.finish_upload_buffers();
+        self.queue.submit(std::iter::once(encoder.finish()));
+        self.recall_upload_buffers();
+        self.push_constant_arena.recall();
+        self.poll_readbacks();
+        self.finish_profiled_submission();
+        self.finish_submission_stats();
+        self.finish_recorded_submission();
+        self.end_submission();
+
+        true
+    }
+
+    /// Validates every pass and returns the pass indices in execution order.
+    fn compile_render_graph(&self, render_graph: &RenderGraph) -> Option<Vec<usize>> {
+        let mut valid = true;
+        for pass in render_graph.passes.iter() {
+            valid &= self.validate_render_graph_pass(pass);
+        }
+
+        let mut accesses: BTreeMap<GraphResource, (Vec<usize>, Vec<usize>)> = BTreeMap::new();
+        for (index, pass) in render_graph.passes.iter().enumerate() {
+            let writes: BTreeSet<GraphResource> = pass.write_resources().collect();
+            let reads: BTreeSet<GraphResource> = pass
+                .read_resources()
+                .filter(|resource| !writes.contains(resource))
+                .collect();
+            for resource in writes {
+                accesses.entry(resource).or_default().0.push(index);
+            }
+            for resource in reads {
+                accesses.entry(resource).or_default().1.push(index);
+            }
+        }
+
+        for (resource, (writers, readers)) in accesses.iter() {
+            let GraphResource::RenderTarget(RenderTarget::Custom(render_target)) = *resource else {
+                continue;
+            };
+            let transient = self
+                .render_targets
+                .get(render_target)
+                .is_some_and(|record| record.transient);
+            if transient && writers.is_empty() {
+                for &reader in readers.iter() {
+                    tracing::warn!(
+                        "Render graph pass `{}` reads transient render target {render_target:?}, \
+                         but no pass in the graph writes it.",
+                        render_graph.passes[reader].name
+                    );
+                }
+                valid = false;
+            }
+        }
+
+        if !valid {
+            return None;
+        }
+
+        // Writers of a resource run in insertion order, and readers run after its last writer.
+        let mut dependents: Vec<BTreeSet<usize>> = vec![BTreeSet::new(); render_graph.passes.len()];
+        for (writers, readers) in accesses.values() {
+            for pair in writers.windows(2) {
+                dependents[pair[0]].insert(pair[1]);
+            }
+            if let Some(&last_writer) = writers.last() {
+                dependents[last_writer].extend(readers.iter().copied());
+            }
+        }
+
+        let mut dependency_counts = vec![0_usize; render_graph.passes.len()];
+        for &dependent in dependents.iter().flatten() {
+            dependency_counts[dependent] += 1;
+        }
+
+        // Always pick the earliest added pass that is ready, so independent passes keep their
+        // insertion order.
+        let mut ready: BinaryHeap<Reverse<usize>> = dependency_counts
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count == 0)
+            .map(|(index, _)| Reverse(index))
+            .collect();
+        let mut order = Vec::with_capacity(render_graph.passes.len());
+        while let Some(Reverse(index)) = ready.pop() {
+            order.push(index);
+            for &dependent in dependents[index].iter() {
+                dependency_counts[dependent] -= 1;
+                if dependency_counts[dependent] == 0 {
+                    ready.push(Reverse(dependent));
+                }
+            }
+        }
+
+        if order.len() != render_graph.passes.len() {
+            let cycle: Vec<&str> = dependency_counts
+                .iter()
+                .enumerate()
+                .filter(|(_, count)| **count > 0)
+                .map(|(index, _)| render_graph.passes[index].name.as_str())
+                .collect();
+            tracing::warn!(
+                "Render graph passes {cycle:?} depend on each other in a cycle; a target written \
+                 after it is read needs a separate transient target."
+            );
+            return None;
+        }
+
+        Some(order)
+    }
+
+    /// Checks that a pass declares every resource its commands use, and that no draw samples the
+    /// render target it draws into.
+    fn validate_render_graph_pass(&self, pass: &RenderGraphPass) -> bool {
+        let mut valid = true;
+
+        for &render_target in pass.reads.iter() {
+            if pass.writes.contains(&RenderTarget::Custom(render_target)) {
+                tracing::warn!(
+                    "Render graph pass `{}` both reads and writes render target \
+                     {render_target:?}, which would create a feedback loop.",
+                    pass.name
+                );
+                valid = false;
+            }
+        }
+
+        for command in pass.draw_list.commands.iter() {
+            match command {
+                FrameCommand::ExecuteDrawBundle(execute) => {
+                    let Some((_, commands)) = self.draw_bundle_commands(execute.draw_bundle) else {
+                        continue;
+                    };
+                    for command in commands.iter() {
+                        valid &= self.validate_render_graph_draw(pass, command);
+                    }
+                }
+                FrameCommand::ClearDepthBuffer(clear) => {
+                    if !pass.depth_writes.contains(&clear.depth_buffer) {
+                        tracing::warn!(
+                            "Render graph pass `{}` clears depth buffer {:?} without declaring \
+                             it as written.",
+                            pass.name,
+                            clear.depth_buffer
+                        );
+                        valid = false;
+                    }
+                }
+                FrameCommand::Dispatch(dispatch) => {
+                    valid &= self.validate_render_graph_dispatch(pass, dispatch.compute_material);
+                }
+                command => valid &= self.validate_render_graph_draw(pass, command),
+            }
+        }
+
+        valid
+    }
+
+    fn validate_render_graph_dispatch(
+        &self,
+        pass: &RenderGraphPass,
+        compute_material: ComputeMaterialId,
+    ) -> bool {
+        // Invalid compute materials are reported when the dispatch executes.
+        let Some(record) = self.compute_materials.get(compute_material) else {
+            return true;
+        };
+
+        let mut valid = true;
+        for binding in record.bindings.iter() {
+            match binding.resource {
+                DrawBindingResource::RenderTarget {
+                    render_target: sampled,
+                    ..
+                } if !pass.reads.contains(&sampled) => {
+                    tracing::warn!(
+                        "Render graph pass `{}` samples render target {sampled:?} with compute \
+                         material {compute_material:?} without declaring it as read.",
+                        pass.name
+                    );
+                    valid = false;
+                }
+                _ => valid &= Self::validate_storage_writes(pass, binding),
+            }
+        }
+        valid
+    }
+
+    /// Checks that a read-write storage buffer binding is declared as written by `pass`.
+    fn validate_storage_writes(pass: &RenderGraphPass, binding: &DrawBinding) -> bool {
+        let DrawBindingResource::StorageBuffer {
+            storage_buffer,
+            read_only: false,
+            ..
+        } = binding.resource
+        else {
+            return true;
+        };
+
+        if pass.storage_writes.contains(&storage_buffer) {
+            return true;
+        }
+        tracing::warn!(
+            "Render graph pass `{}` binds storage buffer {storage_buffer:?} read-write without \
+             declaring it as written.",
+            pass.name
+        );
+        false
+    }
+
+    fn validate_render_graph_draw(&self, pass: &RenderGraphPass, command: &FrameCommand) -> bool {
+        let (Some(render_target), Some(material)) = (command.render_target(), command.material())
+        else {
+            return true;
+        };
+
+        let mut valid = true;
+        if !pass.writes.contains(&render_target) {
+            tracing::warn!(
+                "Render graph pass `{}` draws into {render_target:?} without declaring it as \
+                 written.",
+                pass.name
+            );
+            valid = false;
+        }
+
+        // Invalid materials are reported when the draw executes.
+        let Some(material_record) = self.materials.get(material) else {
+            return valid;
+        };
+
+        for binding in material_record.bindings.iter() {
+            valid &= Self::validate_storage_writes(pass, binding);
+            let DrawBindingResource::RenderTarget {
+                render_target: sampled,
+                ..
+            } = binding.resource
+            else {
+                continue;
+            };
+
+            if render_target == RenderTarget::Custom(sampled) {
+                tracing::warn!(
+                    "Render graph pass `{}` draws into render target {sampled:?} with material \
+                     {material:?}, which samples it and would create a feedback loop.",
+                    pass.name
+                );
+                valid = false;
+            } else if !pass.reads.contains(&sampled) {
+                tracing::warn!(
+                    "Render graph pass `{}` samples render target {sampled:?} with material \
+                     {material:?} without declaring it as read.",
+                    pass.name
+                );
+                valid = false;
+            }
+        }
+
+        if let Some(depth_state) = material_record.depth_state {
+            let depth_buffer = depth_state.depth_buffer;
+            let declared = pass.depth_writes.contains(&depth_buffer)
+                || (!depth_state.write_enabled && pass.depth_reads.contains(&depth_buffer));
+            if !declared {
+                tracing::warn!(
+                    "Render graph pass `{}` uses depth buffer {depth_buffer:?} with material \
+                     {material:?} without declaring it as {}.",
+                    pass.name,
+                    if depth_state.write_enabled {
+                        "written"
+                    } else {
+                        "read"
+                    }
+                );
+                valid = false;
+            }
+        }
+
+        valid
+    }
+
+    /// Backs every transient render target in the graph with a pooled texture.
+    ///
+    /// Targets are assigned in execution order, and a texture is returned to the pool after the
+    /// last pass using its target, so targets with disjoint lifetimes alias the same texture.
+    /// Assignments are deterministic, so a graph submitted every frame keeps its bind groups.
+    fn assign_transient_render_targets(
+        &mut self,
+        frame_context: &FrameContext<'_>,
+        render_graph: &RenderGraph,
+        order: &[usize],
+    ) {
+        let mut lifetimes: BTreeMap<RenderTargetId, (usize, usize)> = BTreeMap::new();
+        for (position, &index) in order.iter().enumerate() {
+            for render_target in render_graph.passes[index].custom_render_targets() {
+                let transient = self
+                    .render_targets
+                    .get(render_target)
+                    .is_some_and(|record| record.transient);
+                if transient {
+                    lifetimes
+                        .entry(render_target)
+                        .and_modify(|(_, last)| *last = position)
+                        .or_insert((position, position));
+                }
+            }
+        }
+
+        // Textures allocated for a previous surface size cannot back anything this frame.
+        let stale_textures: Vec<Id> = self
+            .transient_textures
+            .iter()
+            .filter(|(_, texture)| texture.size != frame_context.size)
+            .map(|(id, _)| id)
+            .collect();
+        for id in stale_textures {
+            self.transient_textures.remove(id);
+        }
+
+        let mut free_textures: Vec<Id> = self.transient_textures.iter().map(|(id, _)| id).collect();
+        let mut live_textures: Vec<(usize, Id)> = Vec::new();
+        let mut assignments: Vec<(RenderTargetId, Id)> = Vec::with_capacity(lifetimes.len());
+        for position in 0..order.len() {
+            live_textures.retain(|&(last, texture)| {
+                if last < position {
+                    free_textures.push(texture);
+                }
+                last >= position
+            });
+
+            for (&render_target, &(first, last)) in lifetimes.iter() {
+                if first != position {
+                    continue;
+                }
+                let Some(format) = self
+                    .render_targets
+                    .get(render_target)
+                    .map(|record| record.format)
+                else {
+                    continue;
+                };
+
+                let free_index = free_textures.iter().position(|&texture| {
+                    self.transient_textures
+                        .get(texture)
+                        .is_some_and(|texture| texture.format == format)
+                });
+                let texture = match free_index {
+                    Some(free_index) => free_textures.remove(free_index),
+                    None => self.transient_textures.push(TransientTexture::new(
+                        &self.device,
+                        format,
+                        frame_context.size,
+                    )),
+                };
+                live_textures.push((last, texture));
+                assignments.push((render_target, texture));
+            }
+        }
+
+        let used_textures: BTreeSet<Id> = assignments.iter().map(|&(_, texture)| texture).collect();
+        let unused_textures: Vec<Id> = self
+            .transient_textures
+            .iter()
+            .map(|(id, _)| id)
+            .filter(|id| !used_textures.contains(id))
+            .collect();
+        for id in unused_textures {
+            self.transient_textures.remove(id);
+        }
+
+        for (render_target, texture_id) in assignments {
+            let Some(texture) = self.transient_textures.get(texture_id) else {
+                continue;
+            };
+            let (texture, view, size) =
+                (texture.texture.clone(), texture.view.clone(), texture.size);
+
+            let Some(record) = self.render_targets.get(render_target) else {
+                continue;
+            };
+            if record.transient_texture == Some(texture_id) && record.size == size {
+                continue;
+            }
+
+            // Bind groups sampling the target still reference the previous texture view.
+            self.evict_render_target_bind_groups(render_target);
+            if let Some(record) = self.render_targets.get_mut(render_target) {
+                record._texture = Some(texture);
+                record.view = Some(view);
+                record.size = size;
+                record.transient_texture = Some(texture_id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::UVec3;
+
+    use super::*;
+    use crate::{
+        MaterialId, SamplerId,
+        compute::ComputeMaterial,
+        recording::RecordingSurface,
+        render_target::RenderTargetSize,
+        sampler::{SamplerAddressing, SamplerFiltering},
+        test_support,
+    };
+
+    const SAMPLE_SHADER: &str = "
+        @group(0) @binding(0) var source: texture_2d<f32>;
+        @group(0) @binding(1) var source_sampler: sampler;
+
+        @vertex
+        fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+            let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+            return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+        }
+
+        @fragment
+        fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+            return textureSample(source, source_sampler, position.xy);
+        }
+    ";
+
+    struct Scene {
+        renderer: DrawListRenderer,
+        surface: RecordingSurface,
+        sampler: SamplerId,
+    }
+
+    impl Scene {
+        fn new() -> Self {
+            let test_support::Scene {
+                mut renderer,
+                surface,
+            } = test_support::Scene::new();
+            let sampler = renderer.create_sampler(
+                "sampler",
+                SamplerAddressing::ClampToEdge,
+                SamplerFiltering::Linear,
+            );
+            Self {
+                renderer,
+                surface,
+                sampler,
+            }
+        }
+
+        fn render_target(&mut self, name: &str) -> RenderTargetId {
+            self.renderer.create_render_target(
+                name,
+                RenderTargetSize::SurfaceSize,
+                RenderTargetFormat::RgbaSrgb,
+            )
+        }
+
+        fn fill_material(&mut self, name: &str) -> MaterialId {
+            test_support::fill_material(&mut self.renderer, name)
+        }
+
+        fn sample_material(&mut self, name: &str, source: RenderTargetId) -> MaterialId {
+            let material = self
+                .renderer
+                .create_material_from_shader(name, SAMPLE_SHADER)
+                .unwrap()
+                .render_target_texture(0, 0, source)
+                .sampler(0, 1, self.sampler);
+            self.renderer.create_material(material)
+        }
+
+        /// A pass drawing `material` into `render_target`, declaring only the write.
+        fn draw_pass(
+            &self,
+            name: &str,
+            render_target: RenderTarget,
+            material: MaterialId,
+        ) -> RenderGraphPass {
+            let mut draw_list = DrawList::new();
+            draw_list.draw(render_target, material, 3);
+            RenderGraphPass::new(name, draw_list).writes_render_target(render_target)
+        }
+
+        fn submit(&mut self, render_graph: &RenderGraph) -> bool {
+            self.renderer
+                .submit_render_graph(self.surface.frame_context(), render_graph)
+        }
+
+        /// Returns the material of the first draw of every pass of the last submission.
+        fn drawn_materials(&mut self) -> Vec<MaterialId> {
+            let submission = self.renderer.take_recorded_submissions().pop().unwrap();
+            submission
+                .passes
+                .iter()
+                .map(|pass| pass.draws[0].material)
+                .collect()
+        }
+
+        fn transient_texture(&self, render_target: RenderTargetId) -> Option<Id> {
+            self.renderer
+                .render_targets
+                .get(render_target)?
+                .transient_texture
+        }
+    }
+
+    #[test]
+    fn passes_writing_a_target_run_before_passes_reading_it() {
+        let mut scene = Scene::new();
+        let scene_color = scene.render_target("scene_color");
+        let fill = scene.fill_material("fill");
+        let composite = scene.sample_material("composite", scene_color);
+
+        let mut render_graph = RenderGraph::new();
+        render_graph.add_pass(
+            scene
+                .draw_pass("composite", RenderTarget::Surface, composite)
+                .reads_render_target(scene_color),
+        );
+        render_graph.add_pass(scene.draw_pass("scene", RenderTarget::Custom(scene_color), fill));
+
+        assert!(scene.submit(&render_graph));
+        assert_eq!(scene.drawn_materials(), [fill, composite]);
+    }
+
+    #[test]
+    fn independent_passes_keep_insertion_order() {
+        let mut scene = Scene::new();
+        let a = scene.render_target("a");
+        let b = scene.render_target("b");
+        let fill_a = scene.fill_material("fill_a");
+        let fill_b = scene.fill_material("fill_b");
+
+        let mut render_graph = RenderGraph::new();
+        render_graph.add_pass(scene.draw_pass("b", RenderTarget::Custom(b), fill_b));
+        render_graph.add_pass(scene.draw_pass("a", RenderTarget::Custom(a), fill_a));
+
+        assert!(scene.submit(&render_graph));
+        assert_eq!(scene.drawn_materials(), [fill_b, fill_a]);
+    }
+
+    #[test]
+    fn writers_keep_insertion_order_and_readers_follow_the_last_writer() {
+        let mut scene = Scene::new();
+        let scene_color = scene.render_target("scene_color");
+        let opaque = scene.fill_material("opaque");
+        let transparent = scene.fill_material("transparent");
+        let composite = scene.sample_material("composite", scene_color);
+
+        let mut render_graph = RenderGraph::new();
+        render_graph.add_pass(
+            scene
+                .draw_pass("composite", RenderTarget::Surface, composite)
+                .reads_render_target(scene_color),
+        );
+        render_graph.add_pass(scene.draw_pass("opaque", RenderTarget::Custom(scene_color), opaque));
+        render_graph.add_pass(scene.draw_pass(
+            "transparent",
+            RenderTarget::Custom(scene_color),
+            transparent,
+        ));
+
+        assert!(scene.submit(&render_graph));
+        assert_eq!(scene.drawn_materials(), [opaque, transparent, composite]);
+    }
+
+    #[test]
+    fn cyclic_passes_are_rejected() {
+        let mut scene = Scene::new();
+        let a = scene.render_target("a");
+        let b = scene.render_target("b");
+        let sample_a = scene.sample_material("sample_a", a);
+        let sample_b = scene.sample_material("sample_b", b);
+
+        let mut render_graph = RenderGraph::new();
+        render_graph.add_pass(
+            scene
+                .draw_pass("a_to_b", RenderTarget::Custom(b), sample_a)
+                .reads_render_target(a),
+        );
+        render_graph.add_pass(
+            scene
+                .draw_pass("b_to_a", RenderTarget::Custom(a), sample_b)
+                .reads_render_target(b),
+        );
+
+        assert!(!scene.submit(&render_graph));
+        assert!(scene.renderer.take_recorded_submissions().is_empty());
+    }
+
+    #[test]
+    fn undeclared_and_feedback_accesses_are_rejected() {
+        let mut scene = Scene::new();
+        let a = scene.render_target("a");
+        let fill = scene.fill_material("fill");
+        let sample_a = scene.sample_material("sample_a", a);
+
+        // Drawing into a target that is not declared as written.
+        let mut draw_list = DrawList::new();
+        draw_list.draw(RenderTarget::Custom(a), fill, 3);
+        let mut render_graph = RenderGraph::new();
+        render_graph.add_pass(RenderGraphPass::new("undeclared_write", draw_list));
+        assert!(!scene.submit(&render_graph));
+
+        // Sampling a target that is not declared as read.
+        let mut render_graph = RenderGraph::new();
+        render_graph.add_pass(scene.draw_pass("fill", RenderTarget::Custom(a), fill));
+        render_graph.add_pass(scene.draw_pass("undeclared_read", RenderTarget::Surface, sample_a));
+        assert!(!scene.submit(&render_graph));
+
+        // Sampling the target the pass draws into.
+        let mut render_graph = RenderGraph::new();
+        render_graph.add_pass(scene.draw_pass("feedback", RenderTarget::Custom(a), sample_a));
+        assert!(!scene.submit(&render_graph));
+    }
+
+    #[test]
+    fn reading_a_transient_target_nothing_writes_is_rejected() {
+        let mut scene = Scene::new();
+        let transient = scene
+            .renderer
+            .create_transient_render_target("transient", RenderTargetFormat::RgbaSrgb);
+        let sample = scene.sample_material("sample", transient);
+
+        let mut render_graph = RenderGraph::new();
+        render_graph.add_pass(
+            scene
+                .draw_pass("composite", RenderTarget::Surface, sample)
+                .reads_render_target(transient),
+        );
+
+        assert!(!scene.submit(&render_graph));
+    }
+
+    #[test]
+    fn transient_targets_alias_only_when_their_passes_do_not_overlap() {
+        let mut scene = Scene::new();
+        let targets: Vec<RenderTargetId> = (0..3)
+            .map(|index| {
+                scene.renderer.create_transient_render_target(
+                    &format!("step_{index}"),
+                    RenderTargetFormat::RgbaSrgb,
+                )
+            })
+            .collect();
+        let fill = scene.fill_material("fill");
+
+        // step_0 -> step_1 -> step_2 -> surface, each step sampling the previous one.
+        let mut render_graph = RenderGraph::new();
+        render_graph.add_pass(scene.draw_pass("step_0", RenderTarget::Custom(targets[0]), fill));
+        for (index, pair) in targets.windows(2).enumerate() {
+            let sample = scene.sample_material(&format!("sample_{index}"), pair[0]);
+            render_graph.add_pass(
+                scene
+                    .draw_pass(
+                        &format!("step_{}", index + 1),
+                        RenderTarget::Custom(pair[1]),
+                        sample,
+                    )
+                    .reads_render_target(pair[0]),
+            );
+        }
+        let present = scene.sample_material("present", targets[2]);
+        render_graph.add_pass(
+            scene
+                .draw_pass("present", RenderTarget::Surface, present)
+                .reads_render_target(targets[2]),
+        );
+
+        assert!(scene.submit(&render_graph));
+        let textures: Vec<Option<Id>> = targets
+            .iter()
+            .map(|&target| scene.transient_texture(target))
+            .collect();
+        // step_0 is no longer used once step_2 is written, so they share a texture, while
+        // step_1 overlaps both of them.
+        assert!(textures.iter().all(Option::is_some));
+        assert_eq!(textures[0], textures[2]);
+        assert_ne!(textures[0], textures[1]);
+        assert_eq!(scene.renderer.transient_textures.iter().count(), 2);
+    }
+
+    #[test]
+    fn pooled_transient_textures_are_counted_once_in_stats() {
+        let mut scene = Scene::new();
+        let first = scene
+            .renderer
+            .create_transient_render_target("first", RenderTargetFormat::RgbaSrgb);
+        let second = scene
+            .renderer
+            .create_transient_render_target("second", RenderTargetFormat::RgbaSrgb);
+        let fill = scene.fill_material("fill");
+        let present = scene.sample_material("present", second);
+
+        let mut render_graph = RenderGraph::new();
+        render_graph.add_pass(scene.draw_pass("first", RenderTarget::Custom(first), fill));
+        let sample = scene.sample_material("sample", first);
+        render_graph.add_pass(
+            scene
+                .draw_pass("second", RenderTarget::Custom(second), sample)
+                .reads_render_target(first),
+        );
+        render_graph.add_pass(
+            scene
+                .draw_pass("present", RenderTarget::Surface, present)
+                .reads_render_target(second),
+        );
+        assert!(scene.submit(&render_graph));
+        assert_eq!(scene.renderer.transient_textures.iter().count(), 2);
+
+        // Two 8x8 pooled textures of 4 bytes per texel, shared by the two transient targets.
+        let stats = scene.renderer.stats().render_targets;
+        assert_eq!(stats.count, 2);
+        assert_eq!(stats.bytes, 2 * 8 * 8 * 4);
+    }
+
+    #[test]
+    fn transient_targets_of_different_formats_do_not_alias() {
+        let mut scene = Scene::new();
+        let color = scene
+            .renderer
+            .create_transient_render_target("color", RenderTargetFormat::RgbaSrgb);
+        let hdr = scene
+            .renderer
+            .create_transient_render_target("hdr", RenderTargetFormat::Rgba16Float);
+        let fill = scene.fill_material("fill");
+        let sample_color = scene.sample_material("sample_color", color);
+        let sample_hdr = scene.sample_material("sample_hdr", hdr);
+
+        let mut render_graph = RenderGraph::new();
+        render_graph.add_pass(scene.draw_pass("color", RenderTarget::Custom(color), fill));
+        render_graph.add_pass(
+            scene
+                .draw_pass("present_color", RenderTarget::Surface, sample_color)
+                .reads_render_target(color),
+        );
+        render_graph.add_pass(scene.draw_pass("hdr", RenderTarget::Custom(hdr), fill));
+        render_graph.add_pass(
+            scene
+                .draw_pass("present_hdr", RenderTarget::Surface, sample_hdr)
+                .reads_render_target(hdr),
+        );
+
+        assert!(scene.submit(&render_graph));
+        assert_ne!(scene.transient_texture(color), scene.transient_texture(hdr));
+    }
+
+    #[test]
+    fn transient_textures_are_reallocated_when_the_surface_is_resized() {
+        let mut scene = Scene::new();
+        let transient = scene
+            .renderer
+            .create_transient_render_target("transient", RenderTargetFormat::RgbaSrgb);
+        let fill = scene.fill_material("fill");
+        let sample = scene.sample_material("sample", transient);
+
+        let mut render_graph = RenderGraph::new();
+        render_graph.add_pass(scene.draw_pass("fill", RenderTarget::Custom(transient), fill));
+        render_graph.add_pass(
+            scene
+                .draw_pass("present", RenderTarget::Surface, sample)
+                .reads_render_target(transient),
+        );
+
+        assert!(scene.submit(&render_graph));
+        let before = scene.transient_texture(transient);
+        assert!(scene.submit(&render_graph));
+        assert_eq!(scene.transient_texture(transient), before);
+
+        scene.surface = RecordingSurface::new(&scene.renderer, UVec2::new(16, 16));
+        assert!(scene.submit(&render_graph));
+        assert_ne!(scene.transient_texture(transient), before);
+        assert_eq!(scene.renderer.transient_textures.iter().count(), 1);
+        assert_eq!(
+            scene.renderer.render_targets.get(transient).unwrap().size,
+            UVec2::new(16, 16)
+        );
+    }
+
+    const COPY_SHADER: &str = "
+        @group(0) @binding(0) var<storage, read> source: array<u32>;
+        @group(0) @binding(1) var<storage, read_write> destination: array<u32>;
+
+        @compute @workgroup_size(64)
+        fn copy(@builtin(global_invocation_id) id: vec3<u32>) {
+            destination[id.x] = source[id.x];
+        }
+    ";
+
+    fn storage_buffer(renderer: &mut DrawListRenderer, name: &str) -> StorageBufferId {
+        renderer
+            .create_storage_buffer_bytes(name, wgpu::BufferSize::new(4).unwrap(), &[0; 64])
+            .unwrap()
+    }
+
+    /// Creates a compute material copying `source` into `destination`.
+    fn copy_material(
+        renderer: &mut DrawListRenderer,
+        name: &str,
+        source: StorageBufferId,
+        destination: StorageBufferId,
+    ) -> ComputeMaterialId {
+        let shader = renderer.create_shader(name, COPY_SHADER).unwrap();
+        let compute_shader = renderer.create_compute_shader(shader, "copy");
+        renderer.create_compute_material(
+            ComputeMaterial::new(compute_shader)
+                .name(name)
+                .storage_buffer(0, 0, source)
+                .storage_buffer_read_write(0, 1, destination),
+        )
+    }
+
+    fn dispatch(compute_material: ComputeMaterialId) -> DrawList {
+        let mut draw_list = DrawList::new();
+        draw_list.dispatch(compute_material, UVec3::ONE);
+        draw_list
+    }
+
+    fn pass_labels(renderer: &mut DrawListRenderer) -> Vec<String> {
+        let submission = renderer.take_recorded_submissions().pop().unwrap();
+        submission
+            .passes
+            .into_iter()
+            .map(|pass| pass.label)
+            .collect()
+    }
+
+    #[test]
+    fn dispatch_writing_a_storage_buffer_runs_before_its_readers() {
+        let mut scene = Scene::new();
+        let a = storage_buffer(&mut scene.renderer, "a");
+        let b = storage_buffer(&mut scene.renderer, "b");
+        let c = storage_buffer(&mut scene.renderer, "c");
+        let consume = copy_material(&mut scene.renderer, "consume", b, c);
+        let produce = copy_material(&mut scene.renderer, "produce", a, b);
+
+        let mut render_graph = RenderGraph::new();
+        render_graph.add_pass(
+            RenderGraphPass::new("consume", dispatch(consume))
+                .reads_storage_buffer(b)
+                .writes_storage_buffer(c),
+        );
+        render_graph.add_pass(
+            RenderGraphPass::new("produce", dispatch(produce))
+                .reads_storage_buffer(a)
+                .writes_storage_buffer(b),
+        );
+
+        assert!(scene.submit(&render_graph));
+        assert_eq!(
+            pass_labels(&mut scene.renderer),
+            ["produce_compute_pass", "consume_compute_pass"]
+        );
+    }
+
+    #[test]
+    fn dispatch_must_declare_read_write_storage_buffers_as_written() {
+        let mut scene = Scene::new();
+        let a = storage_buffer(&mut scene.renderer, "a");
+        let b = storage_buffer(&mut scene.renderer, "b");
+        let produce = copy_material(&mut scene.renderer, "produce", a, b);
+
+        let mut render_graph = RenderGraph::new();
+        render_graph
+            .add_pass(RenderGraphPass::new("produce", dispatch(produce)).reads_storage_buffer(b));
+
+        assert!(!scene.submit(&render_graph));
+    }
+}
@@ -1,12 +1,20 @@
 use glam::UVec2;
 
+use crate::common::Id;
+
 /// Pixel format for a render target.
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum RenderTargetFormat {
     /// 8-bit RGBA, linear color space.
     Rgba,
     /// 8-bit RGBA, sRGB color space.
     RgbaSrgb,
+    /// 16-bit floating point RGBA, for HDR content that is tonemapped later.
+    Rgba16Float,
+    /// 32-bit unsigned integer, for object ids; see [`crate::picking`].
+    ///
+    /// Draws into it are never blended, and it cannot be bound as a texture by materials.
+    R32Uint,
 }
 
 impl RenderTargetFormat {
@@ -14,8 +22,24 @@ impl RenderTargetFormat {
         match self {
             RenderTargetFormat::Rgba => wgpu::TextureFormat::Rgba8Unorm,
             RenderTargetFormat::RgbaSrgb => wgpu::TextureFormat::Rgba8UnormSrgb,
+            RenderTargetFormat::Rgba16Float => wgpu::TextureFormat::Rgba16Float,
+            RenderTargetFormat::R32Uint => wgpu::TextureFormat::R32Uint,
         }
     }
+
+    /// Whether the format holds float values, which materials can sample.
+    pub(super) fn is_float(self) -> bool {
+        is_float_format(self.to_wgpu())
+    }
+}
+
+/// Whether `format` holds float values. Only those can be blended into, and sampled as
+/// float textures.
+pub(super) fn is_float_format(format: wgpu::TextureFormat) -> bool {
+    matches!(
+        format.sample_type(None, None),
+        Some(wgpu::TextureSampleType::Float { .. })
+    )
 }
 
 /// Specifies how a render target's dimensions are determined.
@@ -36,6 +60,10 @@ pub struct RenderTargetRecord {
     pub format: RenderTargetFormat,
     pub _texture: Option<wgpu::Texture>,
     pub view: Option<wgpu::TextureView>,
+    /// Whether the texture is provided by a render graph from its pool of transient textures.
+    pub transient: bool,
+    /// The pooled transient texture currently backing this target, if any.
+    pub transient_texture: Option<Id>,
 }
 
 impl RenderTargetRecord {
@@ -49,6 +77,8 @@ impl RenderTargetRecord {
             format,
             _texture: None,
             view: None,
+            transient: false,
+            transient_texture: None,
         }
     }
 
@@ -62,29 +92,53 @@ impl RenderTargetRecord {
             format,
             _texture: None,
             view: None,
+            transient: false,
+            transient_texture: None,
+        }
+    }
+
+    /// Creates a record for a transient render target. Its texture is assigned from a pool shared
+    /// with other transient targets each time a render graph using it is submitted.
+    pub fn create_transient(name: &str, format: RenderTargetFormat) -> Self {
+        Self {
+            transient: true,
+            ..Self::create_surface_sized(name, format)
         }
     }
 
     /// Allocates (or reallocates) the GPU texture at the given size.
     /// Drops any previously held texture before creating the new one.
     pub fn allocate(&mut self, device: &wgpu::Device, size: UVec2) {
-        let texture = device.create_texture(&wgpu::TextureDescriptor {
-            label: Some(&self.name),
-            size: wgpu::Extent3d {
-                width: size.x,
-                height: size.y,
-                depth_or_array_layers: 1,
-            },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: self.format.to_wgpu(),
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
-            view_formats: &[],
-        });
+        let texture = create_render_target_texture(device, &self.name, size, self.format);
 
         self.view = Some(texture.create_view(&wgpu::TextureViewDescriptor::default()));
         self._texture = Some(texture);
         self.size = size;
+        self.transient_texture = None;
     }
 }
+
+/// Creates a texture that can be drawn into and sampled as a render target.
+pub(super) fn create_render_target_texture(
+    device: &wgpu::Device,
+    label: &str,
+    size: UVec2,
+    format: RenderTargetFormat,
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: format.to_wgpu(),
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+            | wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    })
+}
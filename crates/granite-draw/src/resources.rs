@@ -2,8 +2,8 @@ use glam::UVec2;
 use wgpu::{self, util::DeviceExt};
 
 use crate::{
-    AsStorageBufferElement, AsUniformBuffer, BindGroupBindingResourceKey, BlendMode, DepthBufferId,
-    DepthCompare, DrawListRenderer, FragmentShaderId, FrameContext, Material,
+    AsStorageBufferElement, AsUniformBuffer, BindGroupBindingResourceKey, BlendMode, BufferRecord,
+    DepthBufferId, DepthCompare, DrawListRenderer, FragmentShaderId, FrameContext, Material,
     MaterialDepthState, MaterialId, MaterialRecord, MeshId, RenderTargetId, SamplerId,
     ShaderModuleId, ShaderVisibility, StorageBufferId, StorageBufferRecord, TextureId, UniformId,
     UniformRecord, VertexShaderId,
@@ -14,33 +14,72 @@ use crate::{
     encode_storage_buffer_elements,
     mesh::{AsVertexBufferLayout, Mesh},
     render_target::{RenderTargetFormat, RenderTargetRecord, RenderTargetSize},
-    sampler::{SamplerAddressing, SamplerFiltering},
+    sampler::{SamplerAddressing, SamplerFiltering, SamplerRecord},
+    shader_error::{ShaderError, create_wgsl_module},
+    shader_preprocessor::{LineMap, ShaderDefines},
     storage_buffer_min_binding_size,
-    textures::{TextureFormat, TextureRecord},
+    textures::{TextureFormat, TextureRecord, check_texture_data},
 };
 
+/// Destination for buffer and texture writes.
+pub(super) enum WriteTarget<'a> {
+    /// Write through `wgpu::Queue`. The write lands before any command buffer submitted
+    /// afterwards, regardless of where it was issued relative to encoded commands.
+    Queue,
+    /// Encode a copy from the upload staging belt, ordered with the other commands in the
+    /// encoder.
+    Encoder(&'a mut wgpu::CommandEncoder),
+}
+
 pub(super) struct ShaderModule {
     pub shader_module: wgpu::ShaderModule,
+    pub name: String,
+    /// The preprocessed source, used to recreate the module on a new device.
+    pub source: String,
 }
 
 impl ShaderModule {
-    fn create(device: &wgpu::Device, name: &str, source: &str) -> Self {
-        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some(&format!("{name}_module")),
-            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(source)),
-        });
+    /// Compiles preprocessed `source`, locating errors in the original files with `line_map`.
+    pub(super) fn create(
+        device: &wgpu::Device,
+        name: &str,
+        source: &str,
+        line_map: Option<&LineMap>,
+    ) -> Result<Self, ShaderError> {
+        Ok(Self {
+            shader_module: create_wgsl_module(device, &module_label(name), source, line_map)?,
+            name: name.to_string(),
+            source: source.to_string(),
+        })
+    }
 
-        Self { shader_module }
+    /// Recreates the module on `device`.
+    pub(super) fn recreate(&mut self, device: &wgpu::Device) {
+        self.shader_module = create_shader_module(device, &self.name, &self.source);
     }
 }
 
+fn module_label(name: &str) -> String {
+    format!("{name}_module")
+}
+
+fn create_shader_module(device: &wgpu::Device, name: &str, source: &str) -> wgpu::ShaderModule {
+    device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some(&module_label(name)),
+        source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(source)),
+    })
+}
+
 pub(super) struct VertexShader {
     pub shader_module: ShaderModuleId,
     pub entry_point: Option<String>,
 }
 
 impl VertexShader {
-    fn create(shader_module: ShaderModuleId, entry_point: Option<impl Into<String>>) -> Self {
+    pub(super) fn create(
+        shader_module: ShaderModuleId,
+        entry_point: Option<impl Into<String>>,
+    ) -> Self {
         Self {
             shader_module,
             entry_point: entry_point.map(Into::into),
@@ -54,7 +93,10 @@ pub(super) struct FragmentShader {
 }
 
 impl FragmentShader {
-    fn create(shader_module: ShaderModuleId, entry_point: Option<impl Into<String>>) -> Self {
+    pub(super) fn create(
+        shader_module: ShaderModuleId,
+        entry_point: Option<impl Into<String>>,
+    ) -> Self {
         Self {
             shader_module,
             entry_point: entry_point.map(Into::into),
@@ -67,15 +109,21 @@ impl DrawListRenderer {
     ///
     /// Leaves both entry points unspecified, so the shader's only `@vertex`
     /// and `@fragment` entry points are used automatically.
-    pub fn create_material_from_shader(&mut self, name: &str, source: &str) -> Material {
-        let shader = self.create_shader(name, source);
+    ///
+    /// Returns a [`ShaderError`] when the source fails to preprocess, parse, or validate.
+    pub fn create_material_from_shader(
+        &mut self,
+        name: &str,
+        source: &str,
+    ) -> Result<Material, ShaderError> {
+        let shader = self.create_shader(name, source)?;
         let vertex_shader = self
             .vertex_shaders
             .push(VertexShader::create(shader, Option::<String>::None));
         let fragment_shader = self
             .fragment_shaders
             .push(FragmentShader::create(shader, Option::<String>::None));
-        Material::new(vertex_shader, fragment_shader)
+        Ok(Material::new(vertex_shader, fragment_shader).name(name))
     }
 
     /// Creates a new depth buffer that can be attached by materials during drawing.
@@ -250,15 +298,20 @@ impl DrawListRenderer {
         };
 
         // Evict stale bind groups before reallocating, since the old TextureView is going away.
+        self.evict_render_target_bind_groups(id);
+
+        if let Some(record) = self.render_targets.get_mut(id) {
+            record.allocate(&self.device, size);
+        }
+    }
+
+    /// Evicts cached bind groups that sample a render target whose texture view is changing.
+    pub(super) fn evict_render_target_bind_groups(&mut self, id: RenderTargetId) {
         self.bind_groups.retain_keys(|key| {
             !key.bindings
                 .iter()
                 .any(|b| b.resource == BindGroupBindingResourceKey::RenderTarget(id))
         });
-
-        if let Some(record) = self.render_targets.get_mut(id) {
-            record.allocate(&self.device, size);
-        }
     }
 
     pub(super) fn render_target_format(
@@ -305,11 +358,7 @@ impl DrawListRenderer {
         record.view = None;
 
         // Evict bind groups referencing the now-invalid TextureView.
-        self.bind_groups.retain_keys(|key| {
-            !key.bindings
-                .iter()
-                .any(|b| b.resource == BindGroupBindingResourceKey::RenderTarget(id))
-        });
+        self.evict_render_target_bind_groups(id);
     }
 
     /// Creates a mesh resource and returns a stable mesh handle.
@@ -328,13 +377,37 @@ impl DrawListRenderer {
             vertices,
             indices,
         );
+        self.submission_stats.bytes_uploaded +=
+            mesh.vertex_buffer.size() + mesh.index_buffer.size();
         self.meshes.push(mesh)
     }
 
     /// Creates a WGSL shader module from source text.
-    pub fn create_shader(&mut self, name: &str, source: &str) -> ShaderModuleId {
-        let shader = ShaderModule::create(&self.device, name, source);
-        self.shaders.push(shader)
+    ///
+    /// The source is run through the [shader preprocessor](crate::shader_preprocessor) without
+    /// defines first, so it can `#include` registered files, and is then compiled by `wgpu`
+    /// against the features enabled on the device. Returns a [`ShaderError`] with the location
+    /// and offending source line when preprocessing, parsing, or validation fails.
+    pub fn create_shader(
+        &mut self,
+        name: &str,
+        source: &str,
+    ) -> Result<ShaderModuleId, ShaderError> {
+        let source = self.preprocess_shader(name, source, &ShaderDefines::default())?;
+        self.create_shader_module(name, &source.source, Some(&source.line_map))
+    }
+
+    /// Validates and compiles WGSL source into a shader module without preprocessing.
+    ///
+    /// `line_map` locates errors in the original files of preprocessed source.
+    pub(super) fn create_shader_module(
+        &mut self,
+        name: &str,
+        source: &str,
+        line_map: Option<&LineMap>,
+    ) -> Result<ShaderModuleId, ShaderError> {
+        let shader = ShaderModule::create(&self.device, name, source, line_map)?;
+        Ok(self.shaders.push(shader))
     }
 
     /// Creates a vertex shader entry-point reference from a shader module.
@@ -359,12 +432,18 @@ impl DrawListRenderer {
 
     /// Registers a material and returns its handle.
     pub fn create_material(&mut self, material: Material) -> MaterialId {
+        if let Some(push_constants) = material.push_constants.as_ref() {
+            self.check_push_constant_layout(push_constants);
+        }
+
         self.materials.push(MaterialRecord {
+            name: material.name,
             vertex_shader: material.vertex_shader,
             fragment_shader: material.fragment_shader,
             bindings: material.bindings,
             blend_mode: material.blend_mode,
             depth_state: material.depth_state,
+            push_constants: material.push_constants,
         })
     }
 
@@ -374,6 +453,7 @@ impl DrawListRenderer {
         data: &[u8],
         usage: wgpu::BufferUsages,
     ) -> Id {
+        self.submission_stats.bytes_uploaded += data.len() as u64;
         let buffer = self
             .device
             .create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -382,16 +462,55 @@ impl DrawListRenderer {
                 usage,
             });
 
-        self.buffers.push(buffer)
+        self.buffers.push(BufferRecord {
+            buffer,
+            label: name.to_string(),
+            usage,
+            contents: data.to_vec(),
+        })
     }
 
-    fn write_buffer_bytes(&self, buffer_id: Id, data: &[u8]) -> bool {
-        let Some(buffer) = self.buffers.get(buffer_id) else {
+    fn write_buffer_bytes(
+        &mut self,
+        buffer_id: Id,
+        data: &[u8],
+        target: &mut WriteTarget<'_>,
+    ) -> bool {
+        let Some(record) = self.buffers.get_mut(buffer_id) else {
             tracing::warn!("Invalid buffer id ({buffer_id:?})");
             return false;
         };
+        self.submission_stats.bytes_uploaded += data.len() as u64;
 
-        self.queue.write_buffer(buffer, 0, data);
+        if record.keeps_contents(self.keep_buffer_contents) {
+            if record.contents.len() < data.len() {
+                record.contents.resize(data.len(), 0);
+            }
+            record.contents[..data.len()].copy_from_slice(data);
+        } else {
+            record.contents = Vec::new();
+        }
+        let buffer = &record.buffer;
+
+        match target {
+            WriteTarget::Queue => self.queue.write_buffer(buffer, 0, data),
+            WriteTarget::Encoder(encoder) => {
+                // Copies must be a multiple of `COPY_BUFFER_ALIGNMENT`. Buffers are created padded
+                // to that alignment, so the padded copy fits.
+                let copy_size = data
+                    .len()
+                    .next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT as usize)
+                    as wgpu::BufferAddress;
+                if let Some(copy_size) = wgpu::BufferSize::new(copy_size) {
+                    let mut staged =
+                        self.uploads
+                            .belt
+                            .write_buffer(encoder, buffer, 0, copy_size, &self.device);
+                    staged[..data.len()].copy_from_slice(data);
+                    staged[data.len()..].fill(0);
+                }
+            }
+        }
         true
     }
 
@@ -404,20 +523,36 @@ impl DrawListRenderer {
         let initial_bytes = initial_value
             .encode_bytes()
             .unwrap_or_else(|error| panic!("Could not encode uniform `{name}`: {error}"));
+        self.create_uniform_bytes(
+            name,
+            T::VISIBILITY,
+            T::min_binding_size(),
+            initial_bytes.as_slice(),
+        )
+    }
+
+    /// Creates a uniform buffer resource from already encoded bytes.
+    pub(super) fn create_uniform_bytes(
+        &mut self,
+        name: &str,
+        visibility: ShaderVisibility,
+        min_binding_size: wgpu::BufferSize,
+        data: &[u8],
+    ) -> UniformId {
         let buffer = self.create_buffer_with_usage(
             &format!("{name}_uniform"),
-            initial_bytes.as_slice(),
+            data,
             wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         );
         self.uniforms.push(UniformRecord {
             buffer,
-            visibility: T::VISIBILITY,
-            min_binding_size: T::min_binding_size(),
+            visibility,
+            min_binding_size,
         })
     }
 
     /// Writes a complete value into an existing uniform buffer.
-    pub fn write_uniform<T: AsUniformBuffer>(&self, uniform: UniformId, data: &T) -> bool {
+    pub fn write_uniform<T: AsUniformBuffer>(&mut self, uniform: UniformId, data: &T) -> bool {
         let encoded = match data.encode_bytes() {
             Ok(encoded) => encoded,
             Err(error) => {
@@ -425,10 +560,15 @@ impl DrawListRenderer {
                 return false;
             }
         };
-        self.write_uniform_bytes(uniform, encoded.as_slice())
+        self.write_uniform_bytes(uniform, encoded.as_slice(), &mut WriteTarget::Queue)
     }
 
-    pub(super) fn write_uniform_bytes(&self, uniform_id: UniformId, data: &[u8]) -> bool {
+    pub(super) fn write_uniform_bytes(
+        &mut self,
+        uniform_id: UniformId,
+        data: &[u8],
+        target: &mut WriteTarget<'_>,
+    ) -> bool {
         let Some(uniform) = self.uniforms.get(uniform_id) else {
             tracing::warn!("Invalid uniform id ({uniform_id:?})");
             return false;
@@ -448,10 +588,10 @@ impl DrawListRenderer {
             );
             return false;
         }
-        self.write_buffer_bytes(uniform.buffer, data)
+        self.write_buffer_bytes(uniform.buffer, data, target)
     }
 
-    fn evict_storage_buffer_bind_groups(&mut self, id: StorageBufferId) {
+    pub(super) fn evict_storage_buffer_bind_groups(&mut self, id: StorageBufferId) {
         self.bind_groups.retain_keys(|key| {
             !key.bindings
                 .iter()
@@ -459,11 +599,65 @@ impl DrawListRenderer {
         });
     }
 
-    /// Creates a read-only storage buffer array resource with initial elements.
+    /// Creates a storage buffer array resource with initial elements.
     pub fn create_storage_buffer<T: AsStorageBufferElement>(
         &mut self,
         name: &str,
         initial_values: &[T],
+    ) -> Option<StorageBufferId> {
+        self.create_storage_buffer_with_usage(name, initial_values, wgpu::BufferUsages::empty())
+    }
+
+    /// Creates a storage buffer array resource from raw bytes.
+    pub fn create_storage_buffer_bytes(
+        &mut self,
+        name: &str,
+        min_binding_size: wgpu::BufferSize,
+        data: &[u8],
+    ) -> Option<StorageBufferId> {
+        self.create_storage_buffer_bytes_with_usage(
+            name,
+            min_binding_size,
+            data,
+            wgpu::BufferUsages::empty(),
+        )
+    }
+
+    /// Creates a storage buffer array that can also be used as the argument buffer of indirect
+    /// draws, such as
+    /// [`DrawList::draw_indirect`](crate::draw_list::DrawList::draw_indirect).
+    ///
+    /// Elements must match the layout of `wgpu::util::DrawIndirectArgs` for non-indexed draws or
+    /// `wgpu::util::DrawIndexedIndirectArgs` for indexed draws.
+    pub fn create_indirect_storage_buffer<T: AsStorageBufferElement>(
+        &mut self,
+        name: &str,
+        initial_values: &[T],
+    ) -> Option<StorageBufferId> {
+        self.create_storage_buffer_with_usage(name, initial_values, wgpu::BufferUsages::INDIRECT)
+    }
+
+    /// Creates a storage buffer from raw bytes that can also be used as the argument buffer of
+    /// indirect draws.
+    pub fn create_indirect_storage_buffer_bytes(
+        &mut self,
+        name: &str,
+        min_binding_size: wgpu::BufferSize,
+        data: &[u8],
+    ) -> Option<StorageBufferId> {
+        self.create_storage_buffer_bytes_with_usage(
+            name,
+            min_binding_size,
+            data,
+            wgpu::BufferUsages::INDIRECT,
+        )
+    }
+
+    fn create_storage_buffer_with_usage<T: AsStorageBufferElement>(
+        &mut self,
+        name: &str,
+        initial_values: &[T],
+        extra_usage: wgpu::BufferUsages,
     ) -> Option<StorageBufferId> {
         if initial_values.is_empty() {
             tracing::warn!("Could not create storage buffer `{name}` with zero elements.");
@@ -472,31 +666,20 @@ impl DrawListRenderer {
 
         let initial_bytes = encode_storage_buffer_elements(initial_values)
             .unwrap_or_else(|error| panic!("Could not encode storage buffer `{name}`: {error}"));
-        let Ok(byte_len) = u64::try_from(initial_bytes.len()) else {
-            tracing::warn!(
-                "Could not create storage buffer `{name}`: byte length does not fit in u64."
-            );
-            return None;
-        };
-
-        let buffer = self.create_buffer_with_usage(
-            &format!("{name}_storage"),
+        self.create_storage_buffer_bytes_with_usage(
+            name,
+            storage_buffer_min_binding_size::<T>(),
             initial_bytes.as_slice(),
-            wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
-        );
-        Some(self.storage_buffers.push(StorageBufferRecord {
-            buffer,
-            min_binding_size: storage_buffer_min_binding_size::<T>(),
-            byte_len,
-        }))
+            extra_usage,
+        )
     }
 
-    /// Creates a read-only storage buffer array resource from raw bytes.
-    pub fn create_storage_buffer_bytes(
+    pub(super) fn create_storage_buffer_bytes_with_usage(
         &mut self,
         name: &str,
         min_binding_size: wgpu::BufferSize,
         data: &[u8],
+        extra_usage: wgpu::BufferUsages,
     ) -> Option<StorageBufferId> {
         if data.is_empty() {
             tracing::warn!("Could not create storage buffer `{name}` with zero bytes.");
@@ -518,15 +701,16 @@ impl DrawListRenderer {
             return None;
         }
 
-        let buffer = self.create_buffer_with_usage(
-            &format!("{name}_storage"),
-            data,
-            wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
-        );
+        let usage = wgpu::BufferUsages::STORAGE
+            | wgpu::BufferUsages::COPY_DST
+            | wgpu::BufferUsages::COPY_SRC
+            | extra_usage;
+        let buffer = self.create_buffer_with_usage(&format!("{name}_storage"), data, usage);
         Some(self.storage_buffers.push(StorageBufferRecord {
             buffer,
             min_binding_size,
             byte_len,
+            usage,
         }))
     }
 
@@ -557,53 +741,60 @@ impl DrawListRenderer {
         storage_buffer_id: StorageBufferId,
         data: &[u8],
     ) -> bool {
-        let Some(storage_buffer) = self.storage_buffers.get(storage_buffer_id) else {
-            tracing::warn!("Invalid storage buffer id ({storage_buffer_id:?})");
+        self.write_storage_buffer_bytes_to(storage_buffer_id, data, &mut WriteTarget::Queue)
+    }
+
+    /// Returns the `wgpu` buffer behind a storage buffer, for encoding work the renderer does not
+    /// cover, such as copies or passes recorded into a separate command encoder.
+    ///
+    /// The buffer is replaced when a write changes the byte length of the storage buffer and
+    /// when the device is replaced, so look it up again instead of keeping it.
+    pub fn storage_buffer_raw(&self, storage_buffer: StorageBufferId) -> Option<&wgpu::Buffer> {
+        let record = self.storage_buffers.get(storage_buffer)?;
+        Some(&self.buffers.get(record.buffer)?.buffer)
+    }
+
+    /// Writes raw bytes into an existing storage buffer through `target`.
+    ///
+    /// A write with a different byte length replaces the backing buffer, which is initialized
+    /// with `data` directly and needs no copy.
+    pub(super) fn write_storage_buffer_bytes_to(
+        &mut self,
+        storage_buffer_id: StorageBufferId,
+        data: &[u8],
+        target: &mut WriteTarget<'_>,
+    ) -> bool {
+        let Some(storage_buffer) = self.checked_storage_buffer_write(storage_buffer_id, data)
+        else {
             return false;
         };
         let buffer_id = storage_buffer.buffer;
-        let min_binding_size = storage_buffer.min_binding_size;
         let current_byte_len = storage_buffer.byte_len;
+        let usage = storage_buffer.usage;
+        let byte_len = data.len() as u64;
 
-        if data.is_empty() {
-            tracing::warn!("Storage buffer write rejected for {storage_buffer_id:?}: zero bytes.");
-            return false;
-        }
-
-        let Ok(byte_len) = u64::try_from(data.len()) else {
-            tracing::warn!(
-                "Storage buffer write rejected for {storage_buffer_id:?}: byte length does not fit in u64."
-            );
-            return false;
-        };
-        if byte_len < min_binding_size.get() {
-            tracing::warn!(
-                "Storage buffer write size mismatch for {storage_buffer_id:?}: minimum binding size is {} bytes, got {} bytes.",
-                min_binding_size.get(),
-                byte_len
-            );
-            return false;
-        }
         if byte_len == current_byte_len {
-            return self.write_buffer_bytes(buffer_id, data);
+            return self.write_buffer_bytes(buffer_id, data, target);
         }
 
         self.evict_storage_buffer_bind_groups(storage_buffer_id);
 
-        let Some(buffer) = self.buffers.get_mut(buffer_id) else {
+        let Some(record) = self.buffers.get_mut(buffer_id) else {
             tracing::warn!(
                 "Invalid buffer id ({:?}) for storage buffer ({storage_buffer_id:?})",
                 buffer_id
             );
             return false;
         };
-        *buffer = self
+        self.submission_stats.bytes_uploaded += byte_len;
+        record.buffer = self
             .device
             .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("storage_buffer"),
+                label: Some(&record.label),
                 contents: data,
-                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+                usage,
             });
+        record.set_contents(data, self.keep_buffer_contents);
 
         if let Some(storage_buffer) = self.storage_buffers.get_mut(storage_buffer_id) {
             storage_buffer.byte_len = byte_len;
@@ -614,6 +805,40 @@ impl DrawListRenderer {
         false
     }
 
+    /// Returns the storage buffer `data` is written to, or `None` when the write is rejected.
+    pub(super) fn checked_storage_buffer_write(
+        &self,
+        storage_buffer_id: StorageBufferId,
+        data: &[u8],
+    ) -> Option<&StorageBufferRecord> {
+        let Some(storage_buffer) = self.storage_buffers.get(storage_buffer_id) else {
+            tracing::warn!("Invalid storage buffer id ({storage_buffer_id:?})");
+            return None;
+        };
+
+        if data.is_empty() {
+            tracing::warn!("Storage buffer write rejected for {storage_buffer_id:?}: zero bytes.");
+            return None;
+        }
+
+        let Ok(byte_len) = u64::try_from(data.len()) else {
+            tracing::warn!(
+                "Storage buffer write rejected for {storage_buffer_id:?}: byte length does not fit in u64."
+            );
+            return None;
+        };
+        if byte_len < storage_buffer.min_binding_size.get() {
+            tracing::warn!(
+                "Storage buffer write size mismatch for {storage_buffer_id:?}: minimum binding size is {} bytes, got {} bytes.",
+                storage_buffer.min_binding_size.get(),
+                byte_len
+            );
+            return None;
+        }
+
+        Some(storage_buffer)
+    }
+
     /// Create a new texture with the pixels given.
     pub fn create_texture(
         &mut self,
@@ -622,57 +847,54 @@ impl DrawListRenderer {
         format: TextureFormat,
         data: &[u8],
     ) -> Option<TextureId> {
-        if size.x == 0 || size.y == 0 {
-            tracing::warn!("Cannot create texture with zero dimensions.");
+        self.create_texture_with_mips(name, size, format, 1, data)
+    }
+
+    /// Create a new texture with `mip_level_count` mip levels, whose pixels are given in `data`
+    /// one level after the other, largest first.
+    ///
+    /// Block-compressed formats need dimensions that are multiples of 4, and a device with the
+    /// features the format requires.
+    pub fn create_texture_with_mips(
+        &mut self,
+        name: &str,
+        size: UVec2,
+        format: TextureFormat,
+        mip_level_count: u32,
+        data: &[u8],
+    ) -> Option<TextureId> {
+        if !check_texture_data(size, &format, mip_level_count, data) {
             return None;
         }
-
-        let expected_size = (size.x as usize) * (size.y as usize) * format.bytes_per_pixel();
-        if data.len() != expected_size {
+        let required_features = format.to_wgpu().required_features();
+        if !self.device.features().contains(required_features) {
             tracing::warn!(
-                "Texture data size mismatch. Expected {expected_size} bytes, got {} bytes.",
-                data.len()
+                "Cannot create {format:?} texture: the device lacks {required_features:?}."
             );
             return None;
         }
 
-        let texture = self.device.create_texture_with_data(
+        self.submission_stats.bytes_uploaded += data.len() as u64;
+        let texture = TextureRecord::create(
+            &self.device,
             &self.queue,
-            &wgpu::TextureDescriptor {
-                label: Some(&format!("{name}_texture")),
-                size: wgpu::Extent3d {
-                    width: size.x,
-                    height: size.y,
-                    depth_or_array_layers: 1,
-                },
-                mip_level_count: 1,
-                sample_count: 1,
-                dimension: wgpu::TextureDimension::D2,
-                format: format.to_wgpu(),
-                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-                view_formats: &[],
-            },
-            wgpu::util::TextureDataOrder::LayerMajor,
-            data,
-        );
-        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-
-        let texture_id = self.textures.push(TextureRecord {
-            _texture: texture,
-            format,
-            view,
+            format!("{name}_texture"),
             size,
-        });
+            format,
+            mip_level_count,
+            data.to_vec(),
+        );
 
-        Some(texture_id)
+        Some(self.textures.push(texture))
     }
 
     pub(super) fn write_texture_rgba8_region(
-        &self,
+        &mut self,
         texture_id: TextureId,
         origin: UVec2,
         size: UVec2,
         data: &[u8],
+        target: &mut WriteTarget<'_>,
     ) -> bool {
         if size.x == 0 || size.y == 0 {
             tracing::warn!("Texture partial write rejected: zero-sized region.");
@@ -683,6 +905,12 @@ impl DrawListRenderer {
             tracing::warn!("Invalid texture id ({texture_id:?})");
             return false;
         };
+        if texture.format.is_block_compressed() {
+            tracing::warn!(
+                "Texture partial write rejected for {texture_id:?}: block-compressed textures cannot be written."
+            );
+            return false;
+        }
 
         let Some(end_x) = origin.x.checked_add(size.x) else {
             tracing::warn!("Texture partial write rejected: x-range overflow.");
@@ -715,29 +943,83 @@ impl DrawListRenderer {
             return false;
         }
 
-        self.queue.write_texture(
-            wgpu::TexelCopyTextureInfo {
-                texture: &texture._texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d {
-                    x: origin.x,
-                    y: origin.y,
-                    z: 0,
-                },
-                aspect: wgpu::TextureAspect::All,
-            },
-            data,
-            wgpu::TexelCopyBufferLayout {
-                offset: 0,
-                bytes_per_row: Some(size.x * texture.format.bytes_per_pixel() as u32),
-                rows_per_image: Some(size.y),
+        self.submission_stats.bytes_uploaded += data.len() as u64;
+        let destination = wgpu::TexelCopyTextureInfo {
+            texture: &texture._texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d {
+                x: origin.x,
+                y: origin.y,
+                z: 0,
             },
-            wgpu::Extent3d {
-                width: size.x,
-                height: size.y,
-                depth_or_array_layers: 1,
-            },
-        );
+            aspect: wgpu::TextureAspect::All,
+        };
+        let extent = wgpu::Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
+        };
+        let bytes_per_row = size.x * texture.format.bytes_per_pixel() as u32;
+
+        match target {
+            WriteTarget::Queue => self.queue.write_texture(
+                destination,
+                data,
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(bytes_per_row),
+                    rows_per_image: Some(size.y),
+                },
+                extent,
+            ),
+            WriteTarget::Encoder(encoder) => {
+                // Buffer-to-texture copies require rows aligned to
+                // `COPY_BYTES_PER_ROW_ALIGNMENT`, so pad each row in the staging buffer.
+                let padded_bytes_per_row =
+                    bytes_per_row.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+                let staging_size =
+                    wgpu::BufferSize::new(u64::from(padded_bytes_per_row) * u64::from(size.y))
+                        .expect("the region is not empty");
+                let alignment =
+                    wgpu::BufferSize::new(u64::from(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT))
+                        .expect("the row alignment is not zero");
+                let staged = self
+                    .uploads
+                    .belt
+                    .allocate(staging_size, alignment, &self.device);
+                {
+                    let mut view = staged.get_mapped_range_mut();
+                    for (src, dst) in data
+                        .chunks_exact(bytes_per_row as usize)
+                        .zip(view.chunks_exact_mut(padded_bytes_per_row as usize))
+                    {
+                        dst[..src.len()].copy_from_slice(src);
+                    }
+                }
+
+                encoder.copy_buffer_to_texture(
+                    wgpu::TexelCopyBufferInfo {
+                        buffer: staged.buffer(),
+                        layout: wgpu::TexelCopyBufferLayout {
+                            offset: staged.offset(),
+                            bytes_per_row: Some(padded_bytes_per_row),
+                            rows_per_image: Some(size.y),
+                        },
+                    },
+                    destination,
+                    extent,
+                );
+            }
+        }
+
+        if let Some(texture) = self.textures.get_mut(texture_id) {
+            let texture_bytes_per_row = texture.size.x as usize * texture.format.bytes_per_pixel();
+            let row_start = origin.x as usize * texture.format.bytes_per_pixel();
+            for (row, src) in data.chunks_exact(bytes_per_row as usize).enumerate() {
+                let start = (origin.y as usize + row) * texture_bytes_per_row + row_start;
+                texture.pixels[start..start + src.len()].copy_from_slice(src);
+            }
+        }
         true
     }
 
@@ -748,20 +1030,7 @@ impl DrawListRenderer {
         addressing: SamplerAddressing,
         filtering: SamplerFiltering,
     ) -> SamplerId {
-        let address_mode: wgpu::AddressMode = addressing.into();
-        let filter_mode: wgpu::FilterMode = filtering.into();
-
-        let sampler = self.device.create_sampler(&wgpu::SamplerDescriptor {
-            label: Some(&format!("{name}_sampler")),
-            address_mode_u: address_mode,
-            address_mode_v: address_mode,
-            address_mode_w: address_mode,
-            mag_filter: filter_mode,
-            min_filter: filter_mode,
-            mipmap_filter: wgpu::FilterMode::Nearest,
-            ..Default::default()
-        });
-
+        let sampler = SamplerRecord::create(&self.device, name, addressing, filtering);
         self.samplers.push(sampler)
     }
 
@@ -790,9 +1059,19 @@ impl Material {
             bindings: Vec::new(),
             blend_mode: BlendMode::default(),
             depth_state: None,
+            push_constants: None,
+            name: None,
         }
     }
 
+    /// Sets the name used to label the pipelines and bind groups created for this material in
+    /// graphics debuggers. Materials created with
+    /// [`DrawListRenderer::create_material_from_shader`] are named after their shader.
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
     fn push_binding(mut self, binding: DrawBinding) -> Self {
         self.bindings.push(binding);
         self
@@ -803,7 +1082,7 @@ impl Material {
         self.push_binding(DrawBinding::uniform(group, binding, uniform))
     }
 
-    /// Adds a storage buffer binding at `@group(group) @binding(binding)`.
+    /// Adds a read-only storage buffer binding at `@group(group) @binding(binding)`.
     pub fn storage_buffer(
         self,
         group: u32,
@@ -819,6 +1098,26 @@ impl Material {
         ))
     }
 
+    /// Adds a read-write storage buffer binding at `@group(group) @binding(binding)`, declared
+    /// as `var<storage, read_write>` in the shader.
+    ///
+    /// Binding read-write storage to vertex shaders requires
+    /// `wgpu::Features::VERTEX_WRITABLE_STORAGE`.
+    pub fn storage_buffer_read_write(
+        self,
+        group: u32,
+        binding: u32,
+        storage_buffer: StorageBufferId,
+        visibility: ShaderVisibility,
+    ) -> Self {
+        self.push_binding(DrawBinding::storage_buffer_read_write(
+            group,
+            binding,
+            storage_buffer,
+            visibility,
+        ))
+    }
+
     /// Adds a texture binding at `@group(group) @binding(binding)`.
     pub fn texture(self, group: u32, binding: u32, texture: TextureId) -> Self {
         self.push_binding(DrawBinding::texture(group, binding, texture))
@@ -33,3 +33,52 @@ impl From<SamplerFiltering> for wgpu::FilterMode {
         }
     }
 }
+
+pub(super) struct SamplerRecord {
+    pub name: String,
+    pub addressing: SamplerAddressing,
+    pub filtering: SamplerFiltering,
+    pub sampler: wgpu::Sampler,
+}
+
+impl SamplerRecord {
+    pub(super) fn create(
+        device: &wgpu::Device,
+        name: &str,
+        addressing: SamplerAddressing,
+        filtering: SamplerFiltering,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            addressing,
+            filtering,
+            sampler: create_sampler(device, name, addressing, filtering),
+        }
+    }
+
+    /// Recreates the sampler on `device`.
+    pub(super) fn recreate(&mut self, device: &wgpu::Device) {
+        self.sampler = create_sampler(device, &self.name, self.addressing, self.filtering);
+    }
+}
+
+fn create_sampler(
+    device: &wgpu::Device,
+    name: &str,
+    addressing: SamplerAddressing,
+    filtering: SamplerFiltering,
+) -> wgpu::Sampler {
+    let address_mode: wgpu::AddressMode = addressing.into();
+    let filter_mode: wgpu::FilterMode = filtering.into();
+
+    device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some(&format!("{name}_sampler")),
+        address_mode_u: address_mode,
+        address_mode_v: address_mode,
+        address_mode_w: address_mode,
+        mag_filter: filter_mode,
+        min_filter: filter_mode,
+        mipmap_filter: wgpu::FilterMode::Nearest,
+        ..Default::default()
+    })
+}
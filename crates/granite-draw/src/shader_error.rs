@@ -0,0 +1,325 @@
+//! Errors reported when WGSL source cannot be turned into a shader module.
+
+use std::fmt;
+
+use crate::shader_preprocessor::LineMap;
+
+/// The stage of shader creation that rejected the source.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShaderErrorKind {
+    /// The shader file could not be read from disk.
+    Io,
+    /// A [preprocessor](crate::shader_preprocessor) directive was invalid.
+    Preprocess,
+    /// The WGSL source could not be parsed.
+    Parse,
+    /// The WGSL source parsed but failed validation.
+    Validation,
+}
+
+/// Position of a shader error in the file named by [`ShaderError::name`], which may be an
+/// include.
+///
+/// Parse and validation errors are mapped from the preprocessed source back to the line they
+/// were expanded from. Columns inside the value of a substituted define point at the define name.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ShaderSourceLocation {
+    /// 1-based line number.
+    pub line: u32,
+    /// 1-based column, in bytes.
+    pub column: u32,
+}
+
+/// A shader that failed to compile, with enough context to point at the offending source.
+///
+/// The [`fmt::Display`] implementation renders `name:line:column: message` followed by the
+/// source snippet, ready to be logged.
+#[derive(Clone, Debug)]
+pub struct ShaderError {
+    /// Name or file path of the source containing the error: the shader itself, or a file it
+    /// `#include`s.
+    pub name: String,
+    pub kind: ShaderErrorKind,
+    pub message: String,
+    pub location: Option<ShaderSourceLocation>,
+    /// The offending line of the original source, with the error span underlined.
+    pub snippet: Option<String>,
+}
+
+impl ShaderError {
+    pub(super) fn new(name: &str, kind: ShaderErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            kind,
+            message: message.into(),
+            location: None,
+            snippet: None,
+        }
+    }
+
+    /// Sets the location of the error and underlines `length` bytes of the line in `source`.
+    pub(super) fn with_location(
+        self,
+        source: &str,
+        location: ShaderSourceLocation,
+        length: usize,
+    ) -> Self {
+        let line = source
+            .lines()
+            .nth(location.line.saturating_sub(1) as usize)
+            .unwrap_or_default();
+        self.with_line_location(line, location, length)
+    }
+
+    /// Sets the location of the error and underlines `length` bytes of `line`, the source line
+    /// at `location`.
+    fn with_line_location(
+        mut self,
+        line: &str,
+        location: ShaderSourceLocation,
+        length: usize,
+    ) -> Self {
+        self.location = Some(location);
+
+        let start = (location.column.saturating_sub(1) as usize).min(line.len());
+        let length = length.clamp(1, (line.len() - start).max(1));
+        let gutter = location.line.to_string().len();
+        self.snippet = Some(format!(
+            "{:gutter$} |\n{} | {line}\n{:gutter$} | {}{}",
+            "",
+            location.line,
+            "",
+            " ".repeat(start),
+            "^".repeat(length),
+        ));
+
+        self
+    }
+
+    /// Sets the location of a compilation error in `source`, mapped back to the original file
+    /// with `line_map` when the source was preprocessed.
+    fn with_compilation_location(
+        mut self,
+        source: &str,
+        line_map: Option<&LineMap>,
+        location: Option<wgpu::SourceLocation>,
+    ) -> Self {
+        let Some(location) = location else {
+            return self;
+        };
+        let length = location.length as usize;
+        let location = ShaderSourceLocation {
+            line: location.line_number,
+            column: location.line_position,
+        };
+
+        match line_map.and_then(|line_map| line_map.locate(source, location)) {
+            Some(mapped) => {
+                self.name = mapped.file.to_string();
+                self.with_line_location(mapped.line, mapped.location, length)
+            }
+            None => self.with_location(source, location, length),
+        }
+    }
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        if let Some(location) = self.location {
+            write!(f, ":{}:{}", location.line, location.column)?;
+        }
+        write!(f, ": {}", self.message)?;
+        if let Some(snippet) = &self.snippet {
+            write!(f, "\n{snippet}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ShaderError {}
+
+/// Compiles WGSL into a shader module on `device`.
+///
+/// `wgpu` validates the source against the features enabled on the device. Errors are taken from
+/// the module's compilation info, and `line_map` maps their location in preprocessed source back
+/// to the files it was expanded from.
+pub(super) fn create_wgsl_module(
+    device: &wgpu::Device,
+    name: &str,
+    source: &str,
+    line_map: Option<&LineMap>,
+) -> Result<wgpu::ShaderModule, ShaderError> {
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some(name),
+        source: wgpu::ShaderSource::Wgsl(source.into()),
+    });
+    let Some(error) = pollster::block_on(device.pop_error_scope()) else {
+        return Ok(module);
+    };
+
+    let compilation_info = pollster::block_on(module.get_compilation_info());
+    let Some(message) = compilation_info
+        .messages
+        .into_iter()
+        .find(|message| message.message_type == wgpu::CompilationMessageType::Error)
+    else {
+        return Err(ShaderError::new(
+            name,
+            ShaderErrorKind::Validation,
+            error.to_string(),
+        ));
+    };
+
+    let (kind, text) = compilation_message(&message.message);
+    Err(
+        ShaderError::new(name, kind, text).with_compilation_location(
+            source,
+            line_map,
+            message.location,
+        ),
+    )
+}
+
+/// Splits a compilation message rendered by naga into the stage that rejected the source and a
+/// single-line message: the headline followed by the notes explaining it.
+fn compilation_message(message: &str) -> (ShaderErrorKind, String) {
+    let mut lines = message
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty());
+    let headline = lines.next().unwrap_or_default();
+    let (kind, mut text) = match headline.split_once("parsing error: ") {
+        Some((_, text)) => (ShaderErrorKind::Parse, text.to_string()),
+        None => (
+            ShaderErrorKind::Validation,
+            headline
+                .strip_prefix("Shader validation error: ")
+                .unwrap_or(headline)
+                .to_string(),
+        ),
+    };
+
+    for note in lines.filter_map(|line| line.strip_prefix("= ")) {
+        text.push_str(": ");
+        text.push_str(note);
+    }
+    (kind, text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DrawListRenderer;
+
+    #[test]
+    fn parse_errors_are_located_in_the_source() {
+        let renderer = DrawListRenderer::new_recording().unwrap();
+        let error = create_wgsl_module(
+            &renderer.device,
+            "shader.wgsl",
+            "fn main() {\n    let x = missing;\n}",
+            None,
+        )
+        .unwrap_err();
+
+        assert_eq!(error.kind, ShaderErrorKind::Parse);
+        assert_eq!(
+            error.message,
+            "no definition in scope for identifier: `missing`"
+        );
+        assert_eq!(
+            error.location,
+            Some(ShaderSourceLocation {
+                line: 2,
+                column: 13
+            })
+        );
+        assert!(error.snippet.unwrap().ends_with("|             ^^^^^^^"));
+    }
+
+    #[test]
+    fn validation_errors_join_their_causes() {
+        let renderer = DrawListRenderer::new_recording().unwrap();
+        let error = create_wgsl_module(
+            &renderer.device,
+            "shader.wgsl",
+            "fn f() -> f32 {\n    return 1u;\n}",
+            None,
+        )
+        .unwrap_err();
+
+        assert_eq!(error.kind, ShaderErrorKind::Validation);
+        assert!(
+            error
+                .message
+                .starts_with("Function [0] 'f' is invalid: The `return` expression")
+        );
+        assert_eq!(error.location.map(|location| location.line), Some(1));
+    }
+
+    #[test]
+    fn shaders_using_missing_features_are_rejected_by_the_renderer() {
+        let mut renderer = DrawListRenderer::new_recording().unwrap();
+        assert!(
+            !renderer
+                .device
+                .features()
+                .contains(wgpu::Features::SHADER_F64)
+        );
+
+        let error = renderer
+            .create_shader(
+                "f64.wgsl",
+                "@compute @workgroup_size(1) fn main() {\n    let x: f64 = 1.0lf;\n}",
+            )
+            .unwrap_err();
+        assert_eq!(error.kind, ShaderErrorKind::Validation);
+        assert!(error.message.contains("FLOAT64"));
+        assert_eq!(
+            error.location,
+            Some(ShaderSourceLocation {
+                line: 2,
+                column: 18
+            })
+        );
+
+        assert!(
+            renderer
+                .create_shader(
+                    "cube_array.wgsl",
+                    "@group(0) @binding(0) var cubes: texture_cube_array<f32>;
+                    @compute @workgroup_size(1) fn main() { _ = textureNumLayers(cubes); }",
+                )
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn shaders_using_enabled_features_are_accepted() {
+        let renderer = DrawListRenderer::new_recording_with_device(&wgpu::DeviceDescriptor {
+            required_features: wgpu::Features::PUSH_CONSTANTS,
+            required_limits: wgpu::Limits {
+                max_push_constant_size: 4,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .unwrap();
+        let source = "
+            var<push_constant> scale: f32;
+
+            @fragment
+            fn main() -> @location(0) vec4<f32> {
+                return vec4<f32>(scale);
+            }
+        ";
+
+        assert!(create_wgsl_module(&renderer.device, "shader.wgsl", source, None).is_ok());
+
+        let renderer = DrawListRenderer::new_recording().unwrap();
+        let error = create_wgsl_module(&renderer.device, "shader.wgsl", source, None).unwrap_err();
+        assert_eq!(error.kind, ShaderErrorKind::Validation);
+    }
+}
@@ -0,0 +1,816 @@
+//! Preprocessing for WGSL sources before they are compiled into shader modules.
+//!
+//! Supported directives, each on its own line:
+//! - `#include "path"`: inserts a file registered with
+//!   [`DrawListRenderer::register_shader_include`]. Each file is inserted at most once per shader,
+//!   so shared code can be included from several files.
+//! - `#define NAME` and `#define NAME value`: defines a flag, optionally replacing whole-word
+//!   occurrences of `NAME` in the following lines with `value`.
+//! - `#undef NAME`: removes a define.
+//! - `#ifdef NAME`, `#ifndef NAME`, `#else`, `#endif`: conditionally keeps lines.
+//!
+//! `PUSH_CONSTANTS` is defined when the device supports native push constants; see
+//! [`Material::push_constants`].
+//!
+//! The preprocessor keeps track of the file and line every expanded line came from, so parse and
+//! validation errors are reported at their position in the original file, even inside includes
+//! or after define substitutions.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::{
+    DrawListRenderer, FragmentShaderId, Material, ShaderModuleId, ShaderTemplateId, VertexShaderId,
+    resources::{FragmentShader, ShaderModule, VertexShader},
+    shader_error::{ShaderError, ShaderErrorKind, ShaderSourceLocation},
+};
+
+/// A set of preprocessor defines selecting one permutation of a shader template.
+///
+/// Define sets are compared by content, so equal sets built in any order share a cached
+/// permutation.
+#[derive(Clone, Debug, Default, Hash, PartialEq, Eq)]
+pub struct ShaderDefines {
+    defines: BTreeMap<String, String>,
+}
+
+impl ShaderDefines {
+    /// Creates an empty define set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Defines a feature flag checked with `#ifdef`.
+    pub fn define(self, name: &str) -> Self {
+        self.define_value(name, "")
+    }
+
+    /// Defines `name`, replacing whole-word occurrences in the source with `value`.
+    pub fn define_value(mut self, name: &str, value: &str) -> Self {
+        self.defines.insert(name.to_string(), value.to_string());
+        self
+    }
+}
+
+pub(super) struct ShaderTemplate {
+    name: String,
+    source: String,
+}
+
+/// The compiled shader module and entry points of one template permutation.
+pub(super) struct ShaderPermutation {
+    shader: ShaderModuleId,
+    vertex_shader: Option<VertexShaderId>,
+    fragment_shader: Option<FragmentShaderId>,
+    /// Paths of the files the permutation `#include`s, directly or through other includes.
+    includes: Vec<String>,
+}
+
+impl DrawListRenderer {
+    /// Registers a virtual file that shaders can insert with `#include "path"`.
+    ///
+    /// Registering a path again replaces its contents for shaders created afterwards, and
+    /// recompiles the template permutations that include it, so materials created from them pick
+    /// up the new code on their next draw.
+    pub fn register_shader_include(&mut self, path: &str, source: &str) {
+        let previous = self
+            .shader_includes
+            .insert(path.to_string(), source.to_string());
+        if previous.is_some_and(|previous| previous != source) {
+            self.recompile_shader_permutations(&[path]);
+        }
+    }
+
+    /// Registers WGSL source with preprocessor directives, to be compiled per define set with
+    /// [`DrawListRenderer::create_shader_permutation`].
+    pub fn create_shader_template(&mut self, name: &str, source: &str) -> ShaderTemplateId {
+        self.shader_templates.push(ShaderTemplate {
+            name: name.to_string(),
+            source: source.to_string(),
+        })
+    }
+
+    /// Returns the shader module for a template compiled with `defines`, preprocessing and
+    /// compiling it on first use.
+    ///
+    /// Returns `None` and logs a warning when preprocessing or validation fails.
+    pub fn create_shader_permutation(
+        &mut self,
+        template: ShaderTemplateId,
+        defines: &ShaderDefines,
+    ) -> Option<ShaderModuleId> {
+        self.get_or_create_shader_permutation(template, defines)
+            .map(|permutation| permutation.shader)
+    }
+
+    /// Creates a [`Material`] from a template compiled with `defines`.
+    ///
+    /// Like [`DrawListRenderer::create_material_from_shader`], the permutation's only `@vertex`
+    /// and `@fragment` entry points are used, and the material is named after the template.
+    /// Materials created for the same permutation share their shader module and entry points.
+    pub fn create_material_permutation(
+        &mut self,
+        template: ShaderTemplateId,
+        defines: &ShaderDefines,
+    ) -> Option<Material> {
+        self.get_or_create_shader_permutation(template, defines)?;
+        let name = self.shader_templates.get(template)?.name.clone();
+        let permutation = self
+            .shader_permutations
+            .get_mut(&(template, defines.clone()))?;
+
+        let shader = permutation.shader;
+        let vertex_shader = *permutation.vertex_shader.get_or_insert_with(|| {
+            self.vertex_shaders
+                .push(VertexShader::create(shader, Option::<String>::None))
+        });
+        let fragment_shader = *permutation.fragment_shader.get_or_insert_with(|| {
+            self.fragment_shaders
+                .push(FragmentShader::create(shader, Option::<String>::None))
+        });
+
+        Some(Material::new(vertex_shader, fragment_shader).name(&name))
+    }
+
+    fn get_or_create_shader_permutation(
+        &mut self,
+        template: ShaderTemplateId,
+        defines: &ShaderDefines,
+    ) -> Option<&ShaderPermutation> {
+        let key = (template, defines.clone());
+        if self.shader_permutations.contains_key(&key) {
+            return self.shader_permutations.get(&key);
+        }
+
+        let Some(template_record) = self.shader_templates.get(template) else {
+            tracing::warn!("Invalid shader template id ({template:?})");
+            return None;
+        };
+
+        let source =
+            match self.preprocess_shader(&template_record.name, &template_record.source, defines) {
+                Ok(source) => source,
+                Err(error) => {
+                    tracing::warn!("Could not preprocess shader permutation {defines:?}: {error}");
+                    return None;
+                }
+            };
+
+        let name = template_record.name.clone();
+        let shader = match self.create_shader_module(&name, &source.source, Some(&source.line_map))
+        {
+            Ok(shader) => shader,
+            Err(error) => {
+                tracing::warn!("Could not compile shader permutation {defines:?}: {error}");
+                return None;
+            }
+        };
+        let permutation = ShaderPermutation {
+            shader,
+            vertex_shader: None,
+            fragment_shader: None,
+            includes: source.line_map.includes().map(str::to_string).collect(),
+        };
+
+        Some(self.shader_permutations.entry(key).or_insert(permutation))
+    }
+
+    /// Recompiles the cached template permutations that include any of `changed_includes`, and
+    /// returns how many were replaced.
+    ///
+    /// A permutation is replaced in place, so materials created from it keep their shader and
+    /// pick up the new code once their cached pipelines are evicted. Permutations that no longer
+    /// preprocess or validate keep their previous module, and a warning is logged.
+    pub(super) fn recompile_shader_permutations(&mut self, changed_includes: &[&str]) -> usize {
+        let dependents: Vec<(ShaderTemplateId, ShaderDefines)> = self
+            .shader_permutations
+            .iter()
+            .filter(|(_, permutation)| {
+                permutation
+                    .includes
+                    .iter()
+                    .any(|include| changed_includes.contains(&include.as_str()))
+            })
+            .map(|(key, _)| key.clone())
+            .collect();
+
+        let mut recompiled = 0;
+        for (template, defines) in dependents {
+            let Some(template_record) = self.shader_templates.get(template) else {
+                continue;
+            };
+            let name = template_record.name.clone();
+            let compiled = self
+                .preprocess_shader(&name, &template_record.source, &defines)
+                .and_then(|source| {
+                    let shader = ShaderModule::create(
+                        &self.device,
+                        &name,
+                        &source.source,
+                        Some(&source.line_map),
+                    )?;
+                    Ok((source.line_map, shader))
+                });
+            let (line_map, compiled) = match compiled {
+                Ok(compiled) => compiled,
+                Err(error) => {
+                    tracing::warn!(
+                        "{error}\nKeeping the previous module for shader permutation {defines:?}."
+                    );
+                    continue;
+                }
+            };
+
+            let Some(permutation) = self.shader_permutations.get_mut(&(template, defines)) else {
+                continue;
+            };
+            permutation.includes = line_map.includes().map(str::to_string).collect();
+            let shader = permutation.shader;
+            if let Some(shader_record) = self.shaders.get_mut(shader) {
+                *shader_record = compiled;
+            }
+            self.evict_shader_pipelines(shader);
+            recompiled += 1;
+        }
+        recompiled
+    }
+}
+
+impl DrawListRenderer {
+    /// Preprocesses `source` with the registered include files, `defines`, and the renderer's
+    /// built-in defines.
+    pub(super) fn preprocess_shader(
+        &self,
+        name: &str,
+        source: &str,
+        defines: &ShaderDefines,
+    ) -> Result<PreprocessedShader, ShaderError> {
+        let mut defines = defines.clone();
+        if self.push_constants_supported() {
+            defines = defines.define("PUSH_CONSTANTS");
+        }
+        preprocess(name, source, &self.shader_includes, &defines)
+    }
+}
+
+/// WGSL source after preprocessing, with the origin of each of its lines.
+pub(super) struct PreprocessedShader {
+    pub source: String,
+    pub line_map: LineMap,
+}
+
+/// Maps lines of preprocessed source back to the file and line they were expanded from.
+#[derive(Default)]
+pub(super) struct LineMap {
+    /// Names of the shader and the files it included, indexed by [`MappedLine::file`].
+    files: Vec<String>,
+    /// One entry per line of preprocessed source.
+    lines: Vec<MappedLine>,
+}
+
+struct MappedLine {
+    file: usize,
+    /// 1-based line number in the file.
+    line: u32,
+    /// Define substitutions made in the line, in order; empty when the line was copied as is.
+    substitutions: Vec<Substitution>,
+    /// The line before substitutions, when there were any.
+    original: Option<String>,
+}
+
+/// A define name replaced by its value, as byte ranges of the original and expanded line.
+struct Substitution {
+    original: std::ops::Range<usize>,
+    expanded: std::ops::Range<usize>,
+}
+
+/// A position in preprocessed source, resolved to the file it came from.
+pub(super) struct MappedLocation<'a> {
+    pub file: &'a str,
+    pub location: ShaderSourceLocation,
+    /// The line of the original file.
+    pub line: &'a str,
+}
+
+impl LineMap {
+    /// Returns the paths of the files that were included, in the order they were first included.
+    pub(super) fn includes(&self) -> impl Iterator<Item = &str> {
+        self.files.iter().skip(1).map(String::as_str)
+    }
+
+    /// Resolves a 1-based line and column of the preprocessed `source` to its original file.
+    pub(super) fn locate<'a>(
+        &'a self,
+        source: &'a str,
+        location: ShaderSourceLocation,
+    ) -> Option<MappedLocation<'a>> {
+        let line_index = location.line.checked_sub(1)? as usize;
+        let mapped = self.lines.get(line_index)?;
+
+        // Columns after a substitution shift by the length difference of the last one passed,
+        // and columns within a substituted value point at the start of the define name.
+        let expanded_column = location.column.saturating_sub(1) as usize;
+        let mut column = expanded_column;
+        for substitution in mapped.substitutions.iter() {
+            if expanded_column < substitution.expanded.start {
+                break;
+            }
+            if expanded_column < substitution.expanded.end {
+                column = substitution.original.start;
+                break;
+            }
+            column = expanded_column + substitution.original.end - substitution.expanded.end;
+        }
+
+        let line = match mapped.original.as_deref() {
+            Some(original) => original,
+            None => source.lines().nth(line_index).unwrap_or_default(),
+        };
+        Some(MappedLocation {
+            file: &self.files[mapped.file],
+            location: ShaderSourceLocation {
+                line: mapped.line,
+                column: column as u32 + 1,
+            },
+            line,
+        })
+    }
+}
+
+/// Expands directives in `source` using the registered include files and `defines`.
+///
+/// Errors name the file containing the offending directive, which may be an include.
+pub(super) fn preprocess(
+    name: &str,
+    source: &str,
+    includes: &HashMap<String, String>,
+    defines: &ShaderDefines,
+) -> Result<PreprocessedShader, ShaderError> {
+    let mut preprocessor = Preprocessor {
+        includes,
+        defines: defines.defines.clone(),
+        included: HashSet::default(),
+        output: String::with_capacity(source.len()),
+        line_map: LineMap::default(),
+    };
+    preprocessor.process(name, source)?;
+    Ok(PreprocessedShader {
+        source: preprocessor.output,
+        line_map: preprocessor.line_map,
+    })
+}
+
+struct Preprocessor<'a> {
+    includes: &'a HashMap<String, String>,
+    defines: BTreeMap<String, String>,
+    included: HashSet<&'a str>,
+    output: String,
+    line_map: LineMap,
+}
+
+/// An `#ifdef`/`#ifndef` block being processed.
+struct Condition {
+    active: bool,
+    seen_else: bool,
+}
+
+impl<'a> Preprocessor<'a> {
+    fn process(&mut self, file: &str, source: &str) -> Result<(), ShaderError> {
+        let mut conditions: Vec<Condition> = Vec::new();
+        let file_index = self.line_map.files.len();
+        self.line_map.files.push(file.to_string());
+
+        for (line_index, line) in source.lines().enumerate() {
+            let line_number = line_index + 1;
+            let active = conditions.iter().all(|condition| condition.active);
+
+            let Some(directive) = line.trim_start().strip_prefix('#') else {
+                if active {
+                    self.push_line(file_index, line_number as u32, line);
+                }
+                continue;
+            };
+
+            let directive = directive.trim();
+            let (keyword, argument) = directive
+                .split_once(char::is_whitespace)
+                .map_or((directive, ""), |(keyword, argument)| {
+                    (keyword, argument.trim())
+                });
+            let error = |message: &str| {
+                let location = ShaderSourceLocation {
+                    line: line_number as u32,
+                    column: (line.len() - line.trim_start().len()) as u32 + 1,
+                };
+                ShaderError::new(file, ShaderErrorKind::Preprocess, message).with_location(
+                    source,
+                    location,
+                    line.trim().len(),
+                )
+            };
+
+            match keyword {
+                "ifdef" | "ifndef" => {
+                    let name = parse_name(argument).ok_or_else(|| error("expected a name"))?;
+                    let defined = self.defines.contains_key(name);
+                    conditions.push(Condition {
+                        active: defined == (keyword == "ifdef"),
+                        seen_else: false,
+                    });
+                }
+                "else" => {
+                    let condition = conditions
+                        .last_mut()
+                        .ok_or_else(|| error("#else without #ifdef"))?;
+                    if condition.seen_else {
+                        return Err(error("duplicate #else"));
+                    }
+                    condition.active = !condition.active;
+                    condition.seen_else = true;
+                }
+                "endif" => {
+                    conditions
+                        .pop()
+                        .ok_or_else(|| error("#endif without #ifdef"))?;
+                }
+                _ if !active => {}
+                "define" => {
+                    let (name, value) = argument
+                        .split_once(char::is_whitespace)
+                        .map_or((argument, ""), |(name, value)| (name, value.trim()));
+                    let name = parse_name(name).ok_or_else(|| error("expected a name"))?;
+                    self.defines.insert(name.to_string(), value.to_string());
+                }
+                "undef" => {
+                    let name = parse_name(argument).ok_or_else(|| error("expected a name"))?;
+                    self.defines.remove(name);
+                }
+                "include" => {
+                    let path = argument
+                        .strip_prefix('"')
+                        .and_then(|argument| argument.strip_suffix('"'))
+                        .ok_or_else(|| error("expected a quoted path"))?;
+                    let Some((path, include_source)) = self.includes.get_key_value(path) else {
+                        return Err(error(&format!("unknown include \"{path}\"")));
+                    };
+                    if self.included.insert(path.as_str()) {
+                        self.process(path, include_source)?;
+                    }
+                }
+                _ => return Err(error(&format!("unknown directive #{keyword}"))),
+            }
+        }
+
+        if !conditions.is_empty() {
+            return Err(ShaderError::new(
+                file,
+                ShaderErrorKind::Preprocess,
+                "#ifdef without #endif",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Appends line `line_number` of file `file`, replacing defines that have a value.
+    fn push_line(&mut self, file: usize, line_number: u32, line: &str) {
+        let mut mapped = MappedLine {
+            file,
+            line: line_number,
+            substitutions: Vec::new(),
+            original: None,
+        };
+        if self.defines.values().all(String::is_empty) {
+            self.output.push_str(line);
+            self.output.push('\n');
+            self.line_map.lines.push(mapped);
+            return;
+        }
+
+        let line_start = self.output.len();
+        let mut rest = line;
+        while let Some(start) = rest.find(|c: char| c.is_ascii_alphanumeric() || c == '_') {
+            self.output.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            let word = &rest[..end];
+            // Number literals such as `1e3` are never replaced.
+            match self.defines.get(word) {
+                Some(value)
+                    if !value.is_empty() && !word.starts_with(|c: char| c.is_ascii_digit()) =>
+                {
+                    let original_start = line.len() - rest.len();
+                    let expanded_start = self.output.len() - line_start;
+                    mapped.substitutions.push(Substitution {
+                        original: original_start..original_start + word.len(),
+                        expanded: expanded_start..expanded_start + value.len(),
+                    });
+                    self.output.push_str(value)
+                }
+                _ => self.output.push_str(word),
+            }
+            rest = &rest[end..];
+        }
+        self.output.push_str(rest);
+        self.output.push('\n');
+
+        if !mapped.substitutions.is_empty() {
+            mapped.original = Some(line.to_string());
+        }
+        self.line_map.lines.push(mapped);
+    }
+}
+
+fn parse_name(argument: &str) -> Option<&str> {
+    let mut chars = argument.chars();
+    let first = chars.next()?;
+    ((first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_'))
+    .then_some(argument)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shader_error::create_wgsl_module;
+
+    fn includes(files: &[(&str, &str)]) -> HashMap<String, String> {
+        files
+            .iter()
+            .map(|(path, source)| (path.to_string(), source.to_string()))
+            .collect()
+    }
+
+    /// Compiles preprocessed source that is expected to fail.
+    fn compile_error(preprocessed: &PreprocessedShader) -> ShaderError {
+        let renderer = DrawListRenderer::new_recording().unwrap();
+        create_wgsl_module(
+            &renderer.device,
+            "shader.wgsl",
+            &preprocessed.source,
+            Some(&preprocessed.line_map),
+        )
+        .unwrap_err()
+    }
+
+    fn expand(source: &str, defines: &ShaderDefines) -> String {
+        preprocess("shader.wgsl", source, &HashMap::default(), defines)
+            .unwrap()
+            .source
+    }
+
+    #[test]
+    fn conditionals_keep_the_lines_of_active_branches() {
+        let source = "#ifdef A\na\n#ifndef B\nnot_b\n#else\nb\n#endif\n#else\nnot_a\n#endif\nend";
+
+        assert_eq!(
+            expand(source, &ShaderDefines::new().define("A")),
+            "a\nnot_b\nend\n"
+        );
+        assert_eq!(
+            expand(source, &ShaderDefines::new().define("A").define("B")),
+            "a\nb\nend\n"
+        );
+        assert_eq!(expand(source, &ShaderDefines::new()), "not_a\nend\n");
+    }
+
+    #[test]
+    fn defines_replace_whole_words_but_not_number_literals() {
+        let source =
+            "#define SIZE 64\nlet a = SIZE;\nlet b = SIZE_X + 1e3 + MAX;\n#undef SIZE\nSIZE";
+
+        assert_eq!(
+            expand(source, &ShaderDefines::new().define_value("MAX", "8u")),
+            "let a = 64;\nlet b = SIZE_X + 1e3 + 8u;\nSIZE\n"
+        );
+    }
+
+    #[test]
+    fn includes_are_inserted_once() {
+        let includes = includes(&[
+            ("common.wgsl", "const PI: f32 = 3.14;"),
+            ("lighting.wgsl", "#include \"common.wgsl\"\nfn light() {}"),
+        ]);
+        let source = "#include \"common.wgsl\"\n#include \"lighting.wgsl\"\nfn main() {}";
+
+        let preprocessed =
+            preprocess("shader.wgsl", source, &includes, &ShaderDefines::new()).unwrap();
+
+        assert_eq!(
+            preprocessed.source,
+            "const PI: f32 = 3.14;\nfn light() {}\nfn main() {}\n"
+        );
+    }
+
+    #[test]
+    fn invalid_directives_are_reported_in_the_file_containing_them() {
+        let includes = includes(&[("broken.wgsl", "fn a() {}\n  #frobnicate")]);
+        let error = |source: &str| {
+            let Err(error) = preprocess("shader.wgsl", source, &includes, &ShaderDefines::new())
+            else {
+                panic!("preprocessing `{source}` succeeded");
+            };
+            error
+        };
+
+        let included = error("// header\n#include \"broken.wgsl\"");
+        assert_eq!(included.kind, ShaderErrorKind::Preprocess);
+        assert_eq!(included.name, "broken.wgsl");
+        assert_eq!(
+            included.location,
+            Some(ShaderSourceLocation { line: 2, column: 3 })
+        );
+        assert_eq!(included.message, "unknown directive #frobnicate");
+
+        assert_eq!(
+            error("#include \"missing.wgsl\"").message,
+            "unknown include \"missing.wgsl\""
+        );
+        assert_eq!(
+            error("#include missing.wgsl").message,
+            "expected a quoted path"
+        );
+        assert_eq!(error("#ifdef A\n").message, "#ifdef without #endif");
+        assert_eq!(error("#endif").message, "#endif without #ifdef");
+        assert_eq!(
+            error("#ifdef A\n#else\n#else\n#endif").message,
+            "duplicate #else"
+        );
+        assert_eq!(error("#define 1A").message, "expected a name");
+    }
+
+    #[test]
+    fn validation_errors_are_located_in_the_included_file() {
+        let includes = includes(&[(
+            "lighting.wgsl",
+            "fn ambient() -> f32 {\n    return 0.1;\n}\nfn diffuse() -> f32 {\n    return undefined_value;\n}",
+        )]);
+        let source = "// first\n#include \"lighting.wgsl\"\nfn main() {}";
+        let preprocessed =
+            preprocess("shader.wgsl", source, &includes, &ShaderDefines::new()).unwrap();
+
+        let error = compile_error(&preprocessed);
+
+        assert_eq!(error.name, "lighting.wgsl");
+        assert_eq!(
+            error.location,
+            Some(ShaderSourceLocation {
+                line: 5,
+                column: 12
+            })
+        );
+        assert!(
+            error
+                .snippet
+                .unwrap()
+                .contains("5 |     return undefined_value;")
+        );
+    }
+
+    #[test]
+    fn errors_after_the_include_are_located_in_the_shader() {
+        let includes = includes(&[("a.wgsl", "fn a() {}\nfn b() {}\nfn c() {}")]);
+        let source = "#include \"a.wgsl\"\n\nfn main() {\n    let x = missing;\n}";
+        let preprocessed =
+            preprocess("shader.wgsl", source, &includes, &ShaderDefines::new()).unwrap();
+
+        let error = compile_error(&preprocessed);
+
+        assert_eq!(error.name, "shader.wgsl");
+        assert_eq!(
+            error.location,
+            Some(ShaderSourceLocation {
+                line: 4,
+                column: 13
+            })
+        );
+    }
+
+    #[test]
+    fn columns_are_mapped_across_define_substitutions() {
+        let source = "#define SCALE 2.0\nfn main() {\n    let x = SCALE * SCALE + missing;\n}";
+        let preprocessed = preprocess(
+            "shader.wgsl",
+            source,
+            &HashMap::default(),
+            &ShaderDefines::new(),
+        )
+        .unwrap();
+        assert_eq!(
+            preprocessed.source.lines().nth(1),
+            Some("    let x = 2.0 * 2.0 + missing;")
+        );
+
+        let error = compile_error(&preprocessed);
+
+        // `missing` starts at column 29 of the original line, and at column 25 after substitution.
+        assert_eq!(
+            error.location,
+            Some(ShaderSourceLocation {
+                line: 3,
+                column: 29
+            })
+        );
+        assert!(
+            error
+                .snippet
+                .unwrap()
+                .contains("3 |     let x = SCALE * SCALE + missing;")
+        );
+    }
+
+    #[test]
+    fn columns_inside_a_substituted_value_point_at_the_define_name() {
+        let mut line_map = LineMap::default();
+        line_map.files.push("shader.wgsl".to_string());
+        line_map.lines.push(MappedLine {
+            file: 0,
+            line: 7,
+            substitutions: vec![Substitution {
+                original: 4..8,
+                expanded: 4..15,
+            }],
+            original: Some("let SIZE;".to_string()),
+        });
+        let locate = |column| {
+            line_map
+                .locate("let some_value;", ShaderSourceLocation { line: 1, column })
+                .unwrap()
+                .location
+        };
+
+        assert_eq!(locate(2), ShaderSourceLocation { line: 7, column: 2 });
+        assert_eq!(locate(9), ShaderSourceLocation { line: 7, column: 5 });
+        assert_eq!(locate(16), ShaderSourceLocation { line: 7, column: 9 });
+    }
+
+    const TEMPLATE: &str = "
+        #include \"color.wgsl\"
+
+        @vertex
+        fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+            return vec4<f32>(f32(index), 0.0, 0.0, 1.0);
+        }
+
+        @fragment
+        fn fs_main() -> @location(0) vec4<f32> {
+            return color();
+        }
+    ";
+
+    fn color_include(value: &str) -> String {
+        format!("fn color() -> vec4<f32> {{ return vec4<f32>({value}); }}")
+    }
+
+    fn shader_source(renderer: &DrawListRenderer, shader: ShaderModuleId) -> &str {
+        &renderer.shaders.get(shader).unwrap().source
+    }
+
+    #[test]
+    fn changing_an_include_recompiles_the_permutations_using_it() {
+        let mut renderer = DrawListRenderer::new_recording().unwrap();
+        renderer.register_shader_include("color.wgsl", &color_include("1.0"));
+        renderer.register_shader_include("unused.wgsl", "const UNUSED: u32 = 0u;");
+        let template = renderer.create_shader_template("tinted", TEMPLATE);
+        let defines = ShaderDefines::new();
+        let material = renderer
+            .create_material_permutation(template, &defines)
+            .unwrap();
+        let shader = renderer
+            .create_shader_permutation(template, &defines)
+            .unwrap();
+        assert_eq!(material.name.as_deref(), Some("tinted"));
+        assert_eq!(
+            renderer.shader_permutations[&(template, defines.clone())].includes,
+            ["color.wgsl"]
+        );
+
+        renderer.register_shader_include("unused.wgsl", "const UNUSED: u32 = 1u;");
+        assert!(shader_source(&renderer, shader).contains("vec4<f32>(1.0)"));
+
+        renderer.register_shader_include("color.wgsl", &color_include("0.5"));
+        // The permutation keeps its module id, so existing materials use the new code.
+        assert_eq!(
+            renderer.create_shader_permutation(template, &defines),
+            Some(shader)
+        );
+        assert!(shader_source(&renderer, shader).contains("vec4<f32>(0.5)"));
+    }
+
+    #[test]
+    fn an_include_that_breaks_a_permutation_keeps_the_previous_module() {
+        let mut renderer = DrawListRenderer::new_recording().unwrap();
+        renderer.register_shader_include("color.wgsl", &color_include("1.0"));
+        let template = renderer.create_shader_template("tinted", TEMPLATE);
+        let shader = renderer
+            .create_shader_permutation(template, &ShaderDefines::new())
+            .unwrap();
+
+        assert_eq!(renderer.recompile_shader_permutations(&["color.wgsl"]), 1);
+        renderer.register_shader_include("color.wgsl", "fn color() -> vec4<f32> { oops }");
+        assert_eq!(renderer.recompile_shader_permutations(&["color.wgsl"]), 0);
+
+        assert!(shader_source(&renderer, shader).contains("vec4<f32>(1.0)"));
+    }
+}
@@ -0,0 +1,331 @@
+use std::{
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::{
+    DrawListRenderer, Material, RenderPipelineKey, ShaderModuleId,
+    resources::ShaderModule,
+    shader_error::{ShaderError, ShaderErrorKind},
+    shader_preprocessor::ShaderDefines,
+};
+
+/// A file on disk polled for modifications.
+pub(super) struct WatchedFile {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl WatchedFile {
+    fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            modified: modified_time(path),
+        }
+    }
+
+    /// Returns `true` once for every change to the file's modification time.
+    ///
+    /// A file that is temporarily missing (for example while an editor replaces it) is not
+    /// reported as changed.
+    fn poll_changed(&mut self) -> bool {
+        let Some(modified) = modified_time(&self.path) else {
+            return false;
+        };
+        if self.modified == Some(modified) {
+            return false;
+        }
+        self.modified = Some(modified);
+        true
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+impl DrawListRenderer {
+    /// Creates a shader module from a WGSL file that is recompiled when the file changes.
+    ///
+    /// The source is preprocessed and validated like [`DrawListRenderer::create_shader`], and a
+    /// [`ShaderError`] is returned when the file cannot be read or compiled. Call
+    /// [`DrawListRenderer::reload_changed_shaders`] to pick up changes.
+    pub fn create_shader_from_file(
+        &mut self,
+        path: impl AsRef<Path>,
+    ) -> Result<ShaderModuleId, ShaderError> {
+        let path = path.as_ref();
+        let shader = self.compile_shader_file(path)?;
+        let shader = self.shaders.push(shader);
+        self.shader_files.insert(shader, WatchedFile::new(path));
+        Ok(shader)
+    }
+
+    /// Creates a [`Material`] from a WGSL file that is recompiled when the file changes.
+    ///
+    /// Like [`DrawListRenderer::create_material_from_shader`], the file's only `@vertex` and
+    /// `@fragment` entry points are used, and the material is named after the file path.
+    pub fn create_material_from_shader_file(
+        &mut self,
+        path: impl AsRef<Path>,
+    ) -> Result<Material, ShaderError> {
+        let path = path.as_ref();
+        let shader = self.create_shader_from_file(path)?;
+        let vertex_shader = self
+            .vertex_shaders
+            .push(crate::resources::VertexShader::create(
+                shader,
+                Option::<String>::None,
+            ));
+        let fragment_shader = self
+            .fragment_shaders
+            .push(crate::resources::FragmentShader::create(
+                shader,
+                Option::<String>::None,
+            ));
+        Ok(Material::new(vertex_shader, fragment_shader).name(&path.display().to_string()))
+    }
+
+    /// Registers a WGSL file that shaders can insert with `#include "path"`.
+    ///
+    /// When the file changes, every file-backed shader is recompiled on the next
+    /// [`DrawListRenderer::reload_changed_shaders`]. Returns `false` and logs a warning when the
+    /// file cannot be read.
+    pub fn register_shader_include_file(
+        &mut self,
+        include_path: &str,
+        file: impl AsRef<Path>,
+    ) -> bool {
+        let file = file.as_ref();
+        let source = match std::fs::read_to_string(file) {
+            Ok(source) => source,
+            Err(error) => {
+                tracing::warn!(
+                    "Could not read shader include `{}`: {error}",
+                    file.display()
+                );
+                return false;
+            }
+        };
+
+        self.register_shader_include(include_path, &source);
+        self.shader_include_files
+            .insert(include_path.to_string(), WatchedFile::new(file));
+        true
+    }
+
+    /// Polls file-backed shaders and includes, recompiling shaders whose sources changed.
+    ///
+    /// A changed shader is only swapped in when it preprocesses and validates; otherwise a
+    /// warning is logged and the previous module stays in use. Cached pipelines built from a
+    /// swapped module are evicted, so materials pick up the new code on their next draw. Template
+    /// permutations that include a changed file are recompiled the same way. Returns the number
+    /// of shader modules that were replaced.
+    pub fn reload_changed_shaders(&mut self) -> usize {
+        let mut changed_includes: Vec<String> = Vec::new();
+        for (include_path, file) in self.shader_include_files.iter_mut() {
+            if !file.poll_changed() {
+                continue;
+            }
+            match std::fs::read_to_string(&file.path) {
+                Ok(source) => {
+                    self.shader_includes.insert(include_path.clone(), source);
+                    changed_includes.push(include_path.clone());
+                }
+                Err(error) => {
+                    tracing::warn!(
+                        "Could not read shader include `{}`: {error}",
+                        file.path.display()
+                    );
+                }
+            }
+        }
+        let includes_changed = !changed_includes.is_empty();
+        let changed_includes: Vec<&str> = changed_includes.iter().map(String::as_str).collect();
+        let recompiled_permutations = self.recompile_shader_permutations(&changed_includes);
+
+        let changed: Vec<ShaderModuleId> = self
+            .shader_files
+            .iter_mut()
+            .filter_map(|(shader, file)| {
+                (file.poll_changed() || includes_changed).then_some(*shader)
+            })
+            .collect();
+
+        let reloaded_files = changed
+            .into_iter()
+            .filter(|shader| self.reload_shader_file(*shader))
+            .count();
+        reloaded_files + recompiled_permutations
+    }
+
+    fn reload_shader_file(&mut self, shader: ShaderModuleId) -> bool {
+        let Some(path) = self.shader_files.get(&shader).map(|file| file.path.clone()) else {
+            return false;
+        };
+        let compiled = match self.compile_shader_file(&path) {
+            Ok(compiled) => compiled,
+            Err(error) => {
+                tracing::warn!(
+                    "{error}\nKeeping the previous module for `{}`.",
+                    path.display()
+                );
+                return false;
+            }
+        };
+
+        let Some(shader_record) = self.shaders.get_mut(shader) else {
+            return false;
+        };
+        *shader_record = compiled;
+        self.evict_shader_pipelines(shader);
+
+        tracing::info!("Reloaded shader `{}`.", path.display());
+        true
+    }
+
+    /// Reads, preprocesses, and compiles a WGSL file.
+    fn compile_shader_file(&self, path: &Path) -> Result<ShaderModule, ShaderError> {
+        let name = path.display().to_string();
+        let source = std::fs::read_to_string(path)
+            .map_err(|error| ShaderError::new(&name, ShaderErrorKind::Io, error.to_string()))?;
+        let source = self.preprocess_shader(&name, &source, &ShaderDefines::default())?;
+        ShaderModule::create(&self.device, &name, &source.source, Some(&source.line_map))
+    }
+
+    /// Evicts cached pipelines, and draw bundles recorded with them, that use `shader`.
+    pub(super) fn evict_shader_pipelines(&mut self, shader: ShaderModuleId) {
+        let evicted: Vec<RenderPipelineKey> = self
+            .render_pipeline_cache
+            .keys()
+            .filter(|key| {
+                self.vertex_shaders
+                    .get(key.vertex_shader)
+                    .is_some_and(|vertex_shader| vertex_shader.shader_module == shader)
+                    || self
+                        .fragment_shaders
+                        .get(key.fragment_shader)
+                        .is_some_and(|fragment_shader| fragment_shader.shader_module == shader)
+            })
+            .copied()
+            .collect();
+
+        for key in evicted.iter() {
+            self.render_pipeline_cache.remove(key);
+        }
+        self.invalidate_draw_bundles_for_pipelines(&evicted);
+
+        let compute_shaders = &self.compute_shaders;
+        self.compute_pipeline_cache.retain(|key, _| {
+            compute_shaders
+                .get(key.compute_shader)
+                .is_none_or(|compute_shader| compute_shader.shader_module != shader)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    const SHADER: &str = "
+        #include \"color.wgsl\"
+
+        @vertex
+        fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+            return vec4<f32>(f32(index), 0.0, 0.0, 1.0);
+        }
+
+        @fragment
+        fn fs_main() -> @location(0) vec4<f32> {
+            return color();
+        }
+    ";
+
+    /// A directory of shader files, removed when dropped.
+    struct ShaderDirectory(PathBuf);
+
+    impl ShaderDirectory {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "granite-shader-reload-{name}-{}",
+                std::process::id()
+            ));
+            std::fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+
+        /// Writes `file`, moving its modification time forward so the change is always seen.
+        fn write(&self, file: &str, source: &str) -> PathBuf {
+            let path = self.0.join(file);
+            let previous = modified_time(&path);
+            std::fs::write(&path, source).unwrap();
+            if let Some(previous) = previous {
+                std::fs::File::options()
+                    .write(true)
+                    .open(&path)
+                    .unwrap()
+                    .set_modified(previous + Duration::from_secs(1))
+                    .unwrap();
+            }
+            path
+        }
+    }
+
+    impl Drop for ShaderDirectory {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn color_include(value: &str) -> String {
+        format!("fn color() -> vec4<f32> {{ return vec4<f32>({value}); }}")
+    }
+
+    #[test]
+    fn materials_from_files_are_named_after_the_file() {
+        let directory = ShaderDirectory::new("named");
+        let mut renderer = DrawListRenderer::new_recording().unwrap();
+        renderer.register_shader_include("color.wgsl", &color_include("1.0"));
+        let path = directory.write("tinted.wgsl", SHADER);
+
+        let material = renderer.create_material_from_shader_file(&path).unwrap();
+
+        assert_eq!(material.name, Some(path.display().to_string()));
+    }
+
+    #[test]
+    fn changed_include_files_reload_shader_files_and_permutations() {
+        let directory = ShaderDirectory::new("includes");
+        let mut renderer = DrawListRenderer::new_recording().unwrap();
+        let include = directory.write("color.wgsl", &color_include("1.0"));
+        assert!(renderer.register_shader_include_file("color.wgsl", &include));
+        let shader = renderer
+            .create_shader_from_file(directory.write("tinted.wgsl", SHADER))
+            .unwrap();
+        let template = renderer.create_shader_template("tinted", SHADER);
+        let permutation = renderer
+            .create_shader_permutation(template, &ShaderDefines::new())
+            .unwrap();
+        assert_eq!(renderer.reload_changed_shaders(), 0);
+
+        directory.write("color.wgsl", &color_include("0.5"));
+
+        assert_eq!(renderer.reload_changed_shaders(), 2);
+        for shader in [shader, permutation] {
+            assert!(
+                renderer
+                    .shaders
+                    .get(shader)
+                    .unwrap()
+                    .source
+                    .contains("vec4<f32>(0.5)"),
+                "shader {shader:?} was not reloaded"
+            );
+        }
+    }
+}
@@ -0,0 +1,233 @@
+//! Per-submission counters and estimates of the GPU memory used by resources.
+//!
+//! See [`DrawListRenderer::stats`].
+
+use crate::DrawListRenderer;
+
+/// Work recorded for one submission.
+///
+/// Counters include everything encoded since the previous submission, so resources created
+/// between frames count towards the next one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SubmissionStats {
+    /// Index of the submission, counted from the creation of the renderer.
+    pub submission: u64,
+    /// Render and compute passes begun, including depth buffer clears.
+    pub passes: u32,
+    /// Draw calls issued, counting each draw of a multi-draw and of an executed draw bundle.
+    pub draws: u32,
+    /// Compute dispatches issued.
+    pub dispatches: u32,
+    pub pipeline_switches: u32,
+    pub bind_group_switches: u32,
+    /// Triangles of direct draws. Indirect draws are not included, as their counts are only
+    /// known on the GPU.
+    pub triangles: u64,
+    /// Instances of direct draws.
+    pub instances: u64,
+    pub pipeline_cache_hits: u32,
+    pub pipeline_cache_misses: u32,
+    pub bind_group_cache_hits: u32,
+    pub bind_group_cache_misses: u32,
+    /// Bytes copied from the CPU into GPU buffers and textures.
+    pub bytes_uploaded: u64,
+}
+
+impl SubmissionStats {
+    /// Records `draw_count` draws of `elements` vertices or indices per instance, or `None` for
+    /// indirect draws.
+    pub(super) fn record_draws(&mut self, draw_count: u32, elements: Option<u32>, instances: u32) {
+        self.draws += draw_count;
+        if let Some(elements) = elements {
+            self.instances += u64::from(instances);
+            self.triangles += u64::from(elements / 3) * u64::from(instances);
+        }
+    }
+
+    /// Adds the draw and binding counters of `other`, as gathered while encoding a pass or bundle.
+    pub(super) fn add_draws(&mut self, other: &SubmissionStats) {
+        self.draws += other.draws;
+        self.pipeline_switches += other.pipeline_switches;
+        self.bind_group_switches += other.bind_group_switches;
+        self.triangles += other.triangles;
+        self.instances += other.instances;
+    }
+}
+
+/// Live resources of one class.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ResourceStats {
+    pub count: usize,
+    /// Estimated GPU memory in bytes, without driver padding or alignment.
+    pub bytes: u64,
+}
+
+impl ResourceStats {
+    fn add(&mut self, bytes: u64) {
+        self.count += 1;
+        self.bytes += bytes;
+    }
+}
+
+/// Snapshot returned by [`DrawListRenderer::stats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RendererStats {
+    /// Counters of the last completed submission.
+    pub last_submission: SubmissionStats,
+    pub textures: ResourceStats,
+    /// Render targets, including the pooled textures backing transient targets. Transient
+    /// targets and targets whose texture is not allocated yet count towards `count` only; the
+    /// memory of each pooled texture is counted once.
+    pub render_targets: ResourceStats,
+    /// Depth buffers. Buffers whose texture is not allocated yet count towards `count` only.
+    pub depth_buffers: ResourceStats,
+    /// Uniform, storage, vertex, and index buffers.
+    pub buffers: ResourceStats,
+}
+
+impl RendererStats {
+    /// Returns the estimated GPU memory of all resource classes, in bytes.
+    pub fn total_bytes(&self) -> u64 {
+        self.textures.bytes
+            + self.render_targets.bytes
+            + self.depth_buffers.bytes
+            + self.buffers.bytes
+    }
+}
+
+/// Estimates the memory of a texture, including all of its mip levels.
+fn texture_bytes(texture: &wgpu::Texture) -> u64 {
+    format_bytes(
+        texture.format(),
+        texture.size(),
+        texture.mip_level_count(),
+        texture.sample_count(),
+    )
+}
+
+/// Estimates the memory of `mip_level_count` mip levels of a texture of `format` and `size`.
+fn format_bytes(
+    format: wgpu::TextureFormat,
+    size: wgpu::Extent3d,
+    mip_level_count: u32,
+    sample_count: u32,
+) -> u64 {
+    // Depth formats have no defined copy size; assume 4 bytes per texel.
+    let block_size = format
+        .block_copy_size(None)
+        .or_else(|| format.block_copy_size(Some(wgpu::TextureAspect::DepthOnly)))
+        .unwrap_or(4);
+    // Block-compressed formats store `block_size` bytes per block of texels.
+    let (block_width, block_height) = format.block_dimensions();
+
+    (0..mip_level_count)
+        .map(|mip_level| {
+            let width = (size.width >> mip_level).max(1).div_ceil(block_width);
+            let height = (size.height >> mip_level).max(1).div_ceil(block_height);
+            u64::from(width) * u64::from(height) * u64::from(size.depth_or_array_layers)
+        })
+        .sum::<u64>()
+        * u64::from(block_size)
+        * u64::from(sample_count)
+}
+
+impl DrawListRenderer {
+    /// Returns the counters of the last completed submission together with live resource
+    /// counts and their estimated GPU memory.
+    ///
+    /// Memory estimates are computed from resource sizes and formats, so they are cheap enough
+    /// to query every frame for a performance overlay or budget assertions.
+    pub fn stats(&self) -> RendererStats {
+        let mut stats = RendererStats {
+            last_submission: self.last_submission_stats,
+            ..Default::default()
+        };
+
+        for (_, texture) in self.textures.iter() {
+            stats.textures.add(texture_bytes(&texture._texture));
+        }
+
+        for (_, render_target) in self.render_targets.iter() {
+            // Transient targets share pooled textures, which are counted once below.
+            let bytes = if render_target.transient {
+                0
+            } else {
+                render_target._texture.as_ref().map_or(0, texture_bytes)
+            };
+            stats.render_targets.add(bytes);
+        }
+        for (_, transient_texture) in self.transient_textures.iter() {
+            stats.render_targets.bytes += texture_bytes(&transient_texture.texture);
+        }
+
+        for (_, depth_buffer) in self.depth_buffers.iter() {
+            stats
+                .depth_buffers
+                .add(depth_buffer._texture.as_ref().map_or(0, texture_bytes));
+        }
+
+        for (_, buffer) in self.buffers.iter() {
+            stats.buffers.add(buffer.buffer.size());
+        }
+        for (_, mesh) in self.meshes.iter() {
+            stats.buffers.add(mesh.vertex_buffer.size());
+            stats.buffers.add(mesh.index_buffer.size());
+        }
+
+        stats
+    }
+
+    /// Completes the counters of the submission that was just submitted.
+    pub(super) fn finish_submission_stats(&mut self) {
+        self.submission_stats.submission = self.submission_index;
+        self.last_submission_stats = std::mem::take(&mut self.submission_stats);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extent(width: u32, height: u32) -> wgpu::Extent3d {
+        wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        }
+    }
+
+    #[test]
+    fn uncompressed_textures_count_every_mip_level() {
+        // 8x8 + 4x4 + 2x2 + 1x1 texels of 4 bytes.
+        assert_eq!(
+            format_bytes(wgpu::TextureFormat::Rgba8Unorm, extent(8, 8), 4, 1),
+            85 * 4
+        );
+        assert_eq!(
+            format_bytes(wgpu::TextureFormat::Rgba8Unorm, extent(8, 8), 1, 4),
+            64 * 4 * 4
+        );
+    }
+
+    #[test]
+    fn compressed_textures_count_whole_blocks() {
+        // 2x2 blocks, then a single partial block for each of the 4x4, 2x2, and 1x1 levels.
+        assert_eq!(
+            format_bytes(wgpu::TextureFormat::Bc1RgbaUnorm, extent(8, 8), 4, 1),
+            (4 + 1 + 1 + 1) * 8
+        );
+        // 5x3 texels round up to 2x1 blocks.
+        assert_eq!(
+            format_bytes(wgpu::TextureFormat::Bc7RgbaUnorm, extent(5, 3), 1, 1),
+            2 * 16
+        );
+    }
+
+    #[test]
+    fn depth_textures_assume_four_bytes_per_texel() {
+        assert_eq!(
+            format_bytes(wgpu::TextureFormat::Depth24PlusStencil8, extent(4, 4), 1, 1),
+            16 * 4
+        );
+    }
+}
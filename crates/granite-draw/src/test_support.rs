@@ -0,0 +1,84 @@
+//! Fixtures shared by the tests of several modules, which draw through the recording renderer.
+
+use glam::UVec2;
+
+use crate::{
+    DrawListRenderer, MaterialId,
+    draw_list::DrawList,
+    recording::{RecordedSubmission, RecordingSurface},
+};
+
+/// Size of the surface a [`Scene`] draws into.
+pub(crate) const SURFACE_SIZE: UVec2 = UVec2::new(8, 8);
+
+/// Fills the render target with white, drawn with 3 vertices.
+pub(crate) const FILL_SHADER: &str = "
+    @vertex
+    fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+        let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+        return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    }
+
+    @fragment
+    fn fs_main() -> @location(0) vec4<f32> {
+        return vec4<f32>(1.0);
+    }
+";
+
+/// Creates a material drawing [`FILL_SHADER`].
+pub(crate) fn fill_material(renderer: &mut DrawListRenderer, name: &str) -> MaterialId {
+    let material = renderer
+        .create_material_from_shader(name, FILL_SHADER)
+        .unwrap();
+    renderer.create_material(material)
+}
+
+/// Creates a renderer on the default adapter, which may be a software rasterizer, for tests that
+/// read back what was drawn, as the no-op backend keeps no texture contents.
+///
+/// Returns `None` when no adapter is available.
+pub(crate) fn gpu_renderer() -> Option<DrawListRenderer> {
+    let instance = wgpu::Instance::default();
+    let adapter =
+        pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))
+            .ok()?;
+    let (device, queue) =
+        pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default())).ok()?;
+    Some(DrawListRenderer::new(device, queue))
+}
+
+/// A recording renderer and a surface of [`SURFACE_SIZE`] to draw into.
+pub(crate) struct Scene {
+    pub renderer: DrawListRenderer,
+    pub surface: RecordingSurface,
+}
+
+impl Scene {
+    pub(crate) fn new() -> Self {
+        let renderer = DrawListRenderer::new_recording().unwrap();
+        let surface = RecordingSurface::new(&renderer, SURFACE_SIZE);
+        Self { renderer, surface }
+    }
+
+    /// Creates a material from a shader with `vs_main` and `fs_main` entry points.
+    pub(crate) fn material(&mut self, name: &str, source: &str) -> MaterialId {
+        let material = self
+            .renderer
+            .create_material_from_shader(name, source)
+            .unwrap();
+        self.renderer.create_material(material)
+    }
+
+    pub(crate) fn fill_material(&mut self, name: &str) -> MaterialId {
+        fill_material(&mut self.renderer, name)
+    }
+
+    /// Submits `draw_list` and returns its recorded submission.
+    pub(crate) fn submit(&mut self, draw_list: &DrawList) -> RecordedSubmission {
+        self.renderer
+            .submit_draw_list(self.surface.frame_context(), draw_list);
+        let mut submissions = self.renderer.take_recorded_submissions();
+        assert_eq!(submissions.len(), 1);
+        submissions.pop().unwrap()
+    }
+}
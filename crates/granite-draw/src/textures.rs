@@ -1,6 +1,12 @@
 use glam::UVec2;
+use wgpu::util::DeviceExt;
 
 /// Pixel format for a texture resource.
+///
+/// The block-compressed `Bc*` formats store 4x4 pixel blocks and need
+/// [`wgpu::Features::TEXTURE_COMPRESSION_BC`]. They are usually loaded from
+/// [image files](crate::image).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum TextureFormat {
     /// 8-bit RGBA, linear color space.
     Rgba,
@@ -8,29 +14,257 @@ pub enum TextureFormat {
     RgbaSrgb,
     /// Single-channel 8-bit (red only).
     Mono,
+    /// 16-bit floating point RGBA, for HDR images.
+    Rgba16Float,
+    /// RGB with 1-bit alpha, linear color space.
+    Bc1,
+    /// RGB with 1-bit alpha, sRGB color space.
+    Bc1Srgb,
+    /// RGBA with 4-bit alpha, linear color space.
+    Bc2,
+    /// RGBA with 4-bit alpha, sRGB color space.
+    Bc2Srgb,
+    /// RGBA with interpolated alpha, linear color space.
+    Bc3,
+    /// RGBA with interpolated alpha, sRGB color space.
+    Bc3Srgb,
+    /// Single channel (red only).
+    Bc4,
+    /// Two channels (red and green), such as tangent-space normal maps.
+    Bc5,
+    /// Unsigned floating point RGB, for HDR images.
+    Bc6h,
+    /// High quality RGBA, linear color space.
+    Bc7,
+    /// High quality RGBA, sRGB color space.
+    Bc7Srgb,
 }
 
 impl TextureFormat {
-    /// Returns the number of bytes per pixel for this format.
+    /// Returns the number of bytes per pixel for this format, or per 4x4 block for
+    /// block-compressed formats.
     pub fn bytes_per_pixel(&self) -> usize {
         match self {
             TextureFormat::Rgba | TextureFormat::RgbaSrgb => 4,
             TextureFormat::Mono => 1,
+            TextureFormat::Rgba16Float => 8,
+            TextureFormat::Bc1 | TextureFormat::Bc1Srgb | TextureFormat::Bc4 => 8,
+            TextureFormat::Bc2
+            | TextureFormat::Bc2Srgb
+            | TextureFormat::Bc3
+            | TextureFormat::Bc3Srgb
+            | TextureFormat::Bc5
+            | TextureFormat::Bc6h
+            | TextureFormat::Bc7
+            | TextureFormat::Bc7Srgb => 16,
         }
     }
 
-    pub(crate) fn to_wgpu(&self) -> wgpu::TextureFormat {
+    /// Returns `true` for formats that store 4x4 pixel blocks.
+    pub fn is_block_compressed(&self) -> bool {
+        self.to_wgpu().is_compressed()
+    }
+
+    /// Returns the number of bytes of `mip_level_count` mip levels of a texture of `size`.
+    pub fn data_len(&self, size: UVec2, mip_level_count: u32) -> usize {
+        let block_size = if self.is_block_compressed() { 4 } else { 1 };
+        (0..mip_level_count)
+            .map(|level| {
+                let level_size = mip_level_size(size, level);
+                let blocks_x = level_size.x.div_ceil(block_size) as usize;
+                let blocks_y = level_size.y.div_ceil(block_size) as usize;
+                blocks_x * blocks_y * self.bytes_per_pixel()
+            })
+            .sum()
+    }
+
+    pub(crate) fn to_wgpu(self) -> wgpu::TextureFormat {
         match self {
             TextureFormat::Rgba => wgpu::TextureFormat::Rgba8Unorm,
             TextureFormat::RgbaSrgb => wgpu::TextureFormat::Rgba8UnormSrgb,
             TextureFormat::Mono => wgpu::TextureFormat::R8Unorm,
+            TextureFormat::Rgba16Float => wgpu::TextureFormat::Rgba16Float,
+            TextureFormat::Bc1 => wgpu::TextureFormat::Bc1RgbaUnorm,
+            TextureFormat::Bc1Srgb => wgpu::TextureFormat::Bc1RgbaUnormSrgb,
+            TextureFormat::Bc2 => wgpu::TextureFormat::Bc2RgbaUnorm,
+            TextureFormat::Bc2Srgb => wgpu::TextureFormat::Bc2RgbaUnormSrgb,
+            TextureFormat::Bc3 => wgpu::TextureFormat::Bc3RgbaUnorm,
+            TextureFormat::Bc3Srgb => wgpu::TextureFormat::Bc3RgbaUnormSrgb,
+            TextureFormat::Bc4 => wgpu::TextureFormat::Bc4RUnorm,
+            TextureFormat::Bc5 => wgpu::TextureFormat::Bc5RgUnorm,
+            TextureFormat::Bc6h => wgpu::TextureFormat::Bc6hRgbUfloat,
+            TextureFormat::Bc7 => wgpu::TextureFormat::Bc7RgbaUnorm,
+            TextureFormat::Bc7Srgb => wgpu::TextureFormat::Bc7RgbaUnormSrgb,
         }
     }
 }
 
+/// Returns the size of mip `level` of a texture of `size`.
+pub(crate) fn mip_level_size(size: UVec2, level: u32) -> UVec2 {
+    (size >> level).max(UVec2::ONE)
+}
+
 pub struct TextureRecord {
+    pub label: String,
     pub size: UVec2,
     pub format: TextureFormat,
+    pub mip_level_count: u32,
+    /// The pixels of every mip level last written from the CPU, used to recreate the texture on
+    /// a new device.
+    pub pixels: Vec<u8>,
     pub _texture: wgpu::Texture,
     pub view: wgpu::TextureView,
 }
+
+impl TextureRecord {
+    /// Creates a texture filled with `pixels`, which must hold `mip_level_count` levels of
+    /// `size` and `format`, largest first.
+    pub fn create(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        label: String,
+        size: UVec2,
+        format: TextureFormat,
+        mip_level_count: u32,
+        pixels: Vec<u8>,
+    ) -> Self {
+        let texture = create_texture(
+            device,
+            queue,
+            &label,
+            size,
+            &format,
+            mip_level_count,
+            &pixels,
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        Self {
+            label,
+            size,
+            format,
+            mip_level_count,
+            pixels,
+            _texture: texture,
+            view,
+        }
+    }
+
+    /// Creates a texture without filling it, leaving `pixels` to be uploaded later.
+    pub fn create_uninitialized(
+        device: &wgpu::Device,
+        label: String,
+        size: UVec2,
+        format: TextureFormat,
+        pixels: Vec<u8>,
+    ) -> Self {
+        let texture = device.create_texture(&texture_descriptor(&label, size, &format, 1));
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        Self {
+            label,
+            size,
+            format,
+            mip_level_count: 1,
+            pixels,
+            _texture: texture,
+            view,
+        }
+    }
+
+    /// Recreates the texture on `device` from the retained pixels.
+    pub fn recreate(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let texture = create_texture(
+            device,
+            queue,
+            &self.label,
+            self.size,
+            &self.format,
+            self.mip_level_count,
+            &self.pixels,
+        );
+        self.view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        self._texture = texture;
+    }
+}
+
+fn create_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    label: &str,
+    size: UVec2,
+    format: &TextureFormat,
+    mip_level_count: u32,
+    pixels: &[u8],
+) -> wgpu::Texture {
+    device.create_texture_with_data(
+        queue,
+        &texture_descriptor(label, size, format, mip_level_count),
+        wgpu::util::TextureDataOrder::LayerMajor,
+        pixels,
+    )
+}
+
+fn texture_descriptor<'a>(
+    label: &'a str,
+    size: UVec2,
+    format: &TextureFormat,
+    mip_level_count: u32,
+) -> wgpu::TextureDescriptor<'a> {
+    wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: format.to_wgpu(),
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    }
+}
+
+/// Returns `true` when `data` holds exactly the pixels of `mip_level_count` levels of a texture
+/// of `size` and `format`.
+pub(super) fn check_texture_data(
+    size: UVec2,
+    format: &TextureFormat,
+    mip_level_count: u32,
+    data: &[u8],
+) -> bool {
+    if size.x == 0 || size.y == 0 {
+        tracing::warn!("Cannot create texture with zero dimensions.");
+        return false;
+    }
+    if format.is_block_compressed() && !(size.x.is_multiple_of(4) && size.y.is_multiple_of(4)) {
+        tracing::warn!(
+            "Cannot create block-compressed texture of {}x{}; dimensions must be multiples of 4.",
+            size.x,
+            size.y
+        );
+        return false;
+    }
+    let max_mip_level_count = 32 - size.max_element().leading_zeros();
+    if mip_level_count == 0 || mip_level_count > max_mip_level_count {
+        tracing::warn!(
+            "Cannot create texture with {mip_level_count} mip levels; {}x{} allows 1 to {max_mip_level_count}.",
+            size.x,
+            size.y
+        );
+        return false;
+    }
+
+    let expected_size = format.data_len(size, mip_level_count);
+    if data.len() != expected_size {
+        tracing::warn!(
+            "Texture data size mismatch. Expected {expected_size} bytes, got {} bytes.",
+            data.len()
+        );
+        return false;
+    }
+
+    true
+}
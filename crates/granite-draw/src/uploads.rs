@@ -0,0 +1,706 @@
+//! Staged uploads that are spread over several submissions.
+//!
+//! Resources created with [`DrawListRenderer::create_texture`] or
+//! [`DrawListRenderer::create_mesh`] are filled when they are created, which stalls the frame
+//! for large data. The staged variants allocate the resource empty and queue its data instead.
+//! Every submission then copies queued data through a staging belt, in chunks, until the
+//! [upload budget](DrawListRenderer::set_upload_budget) for the submission is spent. Large
+//! textures are copied a band of rows at a time.
+//!
+//! Each staged upload returns an [`UploadHandle`], which can be polled with
+//! [`DrawListRenderer::is_upload_complete`] to hold back drawing a resource until all of its
+//! data is in place. Draws that use a resource earlier see whatever was uploaded so far.
+
+use std::collections::VecDeque;
+
+use glam::UVec2;
+
+use crate::{
+    AsStorageBufferElement, BufferRecord, DrawListRenderer, Id, MeshId, StableVec, StorageBufferId,
+    TextureId, encode_storage_buffer_elements,
+    mesh::{AsVertexBufferLayout, Mesh},
+    textures::{TextureFormat, TextureRecord, check_texture_data},
+};
+
+/// Bytes copied per submission by default.
+const DEFAULT_UPLOAD_BUDGET: u64 = 16 << 20;
+
+/// Upload chunks are never larger than this, and the staging belt allocates buffers of this
+/// size.
+const UPLOAD_CHUNK_SIZE: u64 = 4 << 20;
+
+/// Identifies a staged upload; see [`DrawListRenderer::is_upload_complete`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct UploadHandle(u64);
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum UploadTarget {
+    Buffer(Id),
+    Texture(TextureId),
+    MeshVertices(MeshId),
+    MeshIndices(MeshId),
+}
+
+struct PendingUpload {
+    handle: UploadHandle,
+    /// Several targets share a handle when one resource needs more than one upload.
+    target: UploadTarget,
+    /// Bytes of the source data copied so far.
+    uploaded: u64,
+}
+
+/// The data an upload copies and the resource it copies it into.
+enum UploadSource<'a> {
+    Buffer {
+        buffer: &'a wgpu::Buffer,
+        bytes: &'a [u8],
+    },
+    Texture(&'a TextureRecord),
+}
+
+impl UploadSource<'_> {
+    fn len(&self) -> u64 {
+        match self {
+            UploadSource::Buffer { bytes, .. } => bytes.len() as u64,
+            UploadSource::Texture(texture) => texture.pixels.len() as u64,
+        }
+    }
+
+    /// Returns the smallest number of bytes a chunk can copy.
+    fn min_chunk_len(&self) -> u64 {
+        match self {
+            UploadSource::Buffer { .. } => wgpu::COPY_BUFFER_ALIGNMENT,
+            UploadSource::Texture(texture) => texture_row_len(texture),
+        }
+    }
+}
+
+pub(super) struct UploadQueue {
+    /// Staging buffers for pending uploads, and for the updates encoded in draw lists.
+    pub(super) belt: wgpu::util::StagingBelt,
+    pending: VecDeque<PendingUpload>,
+    budget: u64,
+    next_handle: u64,
+}
+
+impl Default for UploadQueue {
+    fn default() -> Self {
+        Self {
+            belt: wgpu::util::StagingBelt::new(UPLOAD_CHUNK_SIZE),
+            pending: VecDeque::new(),
+            budget: DEFAULT_UPLOAD_BUDGET,
+            next_handle: 0,
+        }
+    }
+}
+
+impl UploadQueue {
+    /// Drops every pending upload and the staging buffers, which belong to a lost device.
+    ///
+    /// Handles that were not complete yet report completion afterwards.
+    pub(super) fn reset(&mut self) {
+        self.belt = wgpu::util::StagingBelt::new(UPLOAD_CHUNK_SIZE);
+        self.pending.clear();
+    }
+}
+
+impl DrawListRenderer {
+    /// Creates a texture and queues its pixels to be uploaded over the next submissions.
+    ///
+    /// The texture samples as black until its first rows are uploaded.
+    pub fn create_texture_staged(
+        &mut self,
+        name: &str,
+        size: UVec2,
+        format: TextureFormat,
+        data: &[u8],
+    ) -> Option<(TextureId, UploadHandle)> {
+        if !check_texture_data(size, &format, 1, data) {
+            return None;
+        }
+        if format.is_block_compressed() {
+            tracing::warn!("Cannot stage the upload of block-compressed texture `{name}`.");
+            return None;
+        }
+
+        let texture = TextureRecord::create_uninitialized(
+            &self.device,
+            format!("{name}_texture"),
+            size,
+            format,
+            data.to_vec(),
+        );
+        let texture = self.textures.push(texture);
+        let handle = self.queue_upload(&[UploadTarget::Texture(texture)]);
+        Some((texture, handle))
+    }
+
+    /// Creates a mesh and queues its vertices and indices to be uploaded over the next
+    /// submissions.
+    pub fn create_mesh_staged<V: AsVertexBufferLayout>(
+        &mut self,
+        name: &str,
+        vertices: &[V],
+        indices: &[u32],
+    ) -> (MeshId, UploadHandle) {
+        let vertex_buffer_layout_id = self.get_or_create_vertex_buffer_layout(V::layout());
+
+        let mesh = Mesh::create_uninitialized(
+            &self.device,
+            name,
+            vertex_buffer_layout_id,
+            vertices,
+            indices,
+        );
+        let mesh = self.meshes.push(mesh);
+        let handle = self.queue_upload(&[
+            UploadTarget::MeshVertices(mesh),
+            UploadTarget::MeshIndices(mesh),
+        ]);
+        (mesh, handle)
+    }
+
+    /// Writes a complete array into an existing typed storage buffer over the next
+    /// submissions.
+    pub fn write_storage_buffer_staged<T: AsStorageBufferElement>(
+        &mut self,
+        storage_buffer: StorageBufferId,
+        data: &[T],
+    ) -> Option<UploadHandle> {
+        if data.is_empty() {
+            tracing::warn!("Storage buffer write rejected for {storage_buffer:?}: zero elements.");
+            return None;
+        }
+
+        let encoded = match encode_storage_buffer_elements(data) {
+            Ok(encoded) => encoded,
+            Err(error) => {
+                tracing::warn!("Could not encode storage buffer for {storage_buffer:?}: {error}");
+                return None;
+            }
+        };
+        self.write_storage_buffer_bytes_staged(storage_buffer, encoded.as_slice())
+    }
+
+    /// Writes raw bytes into an existing storage buffer over the next submissions.
+    ///
+    /// A write with a different byte length replaces the backing buffer with an empty one.
+    pub fn write_storage_buffer_bytes_staged(
+        &mut self,
+        storage_buffer_id: StorageBufferId,
+        data: &[u8],
+    ) -> Option<UploadHandle> {
+        let storage_buffer = self.checked_storage_buffer_write(storage_buffer_id, data)?;
+        let buffer_id = storage_buffer.buffer;
+        let usage = storage_buffer.usage;
+        let byte_len = data.len() as u64;
+        let resized = byte_len != storage_buffer.byte_len;
+
+        if resized {
+            self.evict_storage_buffer_bind_groups(storage_buffer_id);
+        }
+
+        let Some(record) = self.buffers.get_mut(buffer_id) else {
+            tracing::warn!(
+                "Invalid buffer id ({:?}) for storage buffer ({storage_buffer_id:?})",
+                buffer_id
+            );
+            return None;
+        };
+        if resized {
+            record.buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(&record.label),
+                size: byte_len.next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT),
+                usage,
+                mapped_at_creation: false,
+            });
+        }
+        record.contents = data.to_vec();
+
+        if let Some(storage_buffer) = self.storage_buffers.get_mut(storage_buffer_id) {
+            storage_buffer.byte_len = byte_len;
+        }
+
+        Some(self.queue_upload(&[UploadTarget::Buffer(buffer_id)]))
+    }
+
+    /// Returns `true` once all data of a staged upload was copied.
+    ///
+    /// The copies are submitted at that point, so draws in the next submission see the
+    /// complete resource.
+    pub fn is_upload_complete(&self, handle: UploadHandle) -> bool {
+        !self
+            .uploads
+            .pending
+            .iter()
+            .any(|upload| upload.handle == handle)
+    }
+
+    /// Returns the number of bytes that staged uploads still have to copy.
+    pub fn pending_upload_bytes(&self) -> u64 {
+        self.uploads
+            .pending
+            .iter()
+            .filter_map(|upload| {
+                let source = upload_source(&self.buffers, &self.textures, &self.meshes, upload)?;
+                Some(source.len().saturating_sub(upload.uploaded))
+            })
+            .sum()
+    }
+
+    /// Sets how many bytes of staged uploads every submission copies.
+    ///
+    /// A submission copies at least one chunk, so uploads make progress even with a budget
+    /// smaller than a texture row.
+    pub fn set_upload_budget(&mut self, bytes: u64) {
+        self.uploads.budget = bytes;
+    }
+
+    /// Returns how many bytes of staged uploads every submission copies.
+    pub fn upload_budget(&self) -> u64 {
+        self.uploads.budget
+    }
+
+    /// Submits every pending staged upload at once, ignoring the upload budget.
+    pub fn flush_uploads(&mut self) {
+        if self.uploads.pending.is_empty() {
+            return;
+        }
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("upload_encoder"),
+            });
+        self.encode_uploads(&mut encoder, u64::MAX);
+        self.finish_upload_buffers();
+        self.queue.submit(std::iter::once(encoder.finish()));
+        self.recall_upload_buffers();
+    }
+
+    /// Queues uploads of `targets` under one handle.
+    ///
+    /// A target that is still being uploaded starts over with its current data, and keeps the
+    /// handle it was queued with.
+    fn queue_upload(&mut self, targets: &[UploadTarget]) -> UploadHandle {
+        let mut handle = None;
+        for upload in self.uploads.pending.iter_mut() {
+            if targets.contains(&upload.target) {
+                upload.uploaded = 0;
+                handle = Some(upload.handle);
+            }
+        }
+        if let Some(handle) = handle {
+            return handle;
+        }
+
+        let handle = UploadHandle(self.uploads.next_handle);
+        self.uploads.next_handle += 1;
+        self.uploads
+            .pending
+            .extend(targets.iter().map(|&target| PendingUpload {
+                handle,
+                target,
+                uploaded: 0,
+            }));
+        handle
+    }
+
+    /// Encodes copies of pending uploads into `encoder`, oldest first, until `budget` bytes
+    /// were copied.
+    ///
+    /// The first chunk is copied even when it exceeds the budget, so every call makes progress.
+    pub(super) fn encode_uploads(&mut self, encoder: &mut wgpu::CommandEncoder, budget: u64) {
+        let mut copied = 0;
+        while let Some(upload) = self.uploads.pending.front_mut() {
+            let Some(source) = upload_source(&self.buffers, &self.textures, &self.meshes, upload)
+            else {
+                // The resource was destroyed before its upload finished.
+                self.uploads.pending.pop_front();
+                continue;
+            };
+
+            let mut chunk_budget = budget.saturating_sub(copied).min(UPLOAD_CHUNK_SIZE);
+            if copied == 0 {
+                chunk_budget = chunk_budget.max(source.min_chunk_len());
+            }
+            let chunk = match source {
+                UploadSource::Buffer { buffer, bytes } => copy_buffer_chunk(
+                    &mut self.uploads.belt,
+                    &self.device,
+                    encoder,
+                    buffer,
+                    bytes,
+                    upload.uploaded,
+                    chunk_budget,
+                ),
+                UploadSource::Texture(texture) => copy_texture_rows(
+                    &mut self.uploads.belt,
+                    &self.device,
+                    encoder,
+                    texture,
+                    upload.uploaded,
+                    chunk_budget,
+                ),
+            };
+
+            copied += chunk;
+            upload.uploaded += chunk;
+            if upload.uploaded >= source.len() {
+                // Staged writes keep their data only until it is uploaded.
+                if let UploadTarget::Buffer(id) = upload.target
+                    && let Some(record) = self.buffers.get_mut(id)
+                    && !record.keeps_contents(self.keep_buffer_contents)
+                {
+                    record.contents = Vec::new();
+                }
+                self.uploads.pending.pop_front();
+            } else if chunk == 0 {
+                break;
+            }
+        }
+
+        self.submission_stats.bytes_uploaded += copied;
+    }
+
+    /// Closes the staging buffers written since the last submission, before the encoder
+    /// copying from them is submitted.
+    pub(super) fn finish_upload_buffers(&mut self) {
+        self.uploads.belt.finish();
+    }
+
+    /// Makes the staging buffers of submitted uploads available again.
+    pub(super) fn recall_upload_buffers(&mut self) {
+        self.uploads.belt.recall();
+    }
+}
+
+fn upload_source<'a>(
+    buffers: &'a StableVec<BufferRecord>,
+    textures: &'a StableVec<TextureRecord>,
+    meshes: &'a StableVec<Mesh>,
+    upload: &PendingUpload,
+) -> Option<UploadSource<'a>> {
+    Some(match upload.target {
+        UploadTarget::Buffer(id) => {
+            let record = buffers.get(id)?;
+            UploadSource::Buffer {
+                buffer: &record.buffer,
+                bytes: &record.contents,
+            }
+        }
+        UploadTarget::Texture(id) => UploadSource::Texture(textures.get(id)?),
+        UploadTarget::MeshVertices(id) => {
+            let mesh = meshes.get(id)?;
+            UploadSource::Buffer {
+                buffer: &mesh.vertex_buffer,
+                bytes: &mesh.vertex_bytes,
+            }
+        }
+        UploadTarget::MeshIndices(id) => {
+            let mesh = meshes.get(id)?;
+            UploadSource::Buffer {
+                buffer: &mesh.index_buffer,
+                bytes: &mesh.index_bytes,
+            }
+        }
+    })
+}
+
+/// Copies up to `budget` bytes of `bytes`, starting at `offset`, into `buffer` and returns the
+/// number of bytes copied.
+fn copy_buffer_chunk(
+    belt: &mut wgpu::util::StagingBelt,
+    device: &wgpu::Device,
+    encoder: &mut wgpu::CommandEncoder,
+    buffer: &wgpu::Buffer,
+    bytes: &[u8],
+    offset: u64,
+    budget: u64,
+) -> u64 {
+    let remaining = (bytes.len() as u64).saturating_sub(offset);
+    let mut len = remaining.min(budget);
+    if len < remaining {
+        // Copies start at aligned offsets, so only the last chunk may end unaligned.
+        len -= len % wgpu::COPY_BUFFER_ALIGNMENT;
+    }
+    let Some(size) = wgpu::BufferSize::new(len.next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT))
+    else {
+        return 0;
+    };
+
+    let data = &bytes[offset as usize..(offset + len) as usize];
+    let mut view = belt.write_buffer(encoder, buffer, offset, size, device);
+    view[..data.len()].copy_from_slice(data);
+    view[data.len()..].fill(0);
+    len
+}
+
+/// Copies as many rows of `texture` as fit in `budget` bytes, starting at byte `offset` of its
+/// pixels, and returns the number of bytes copied.
+fn copy_texture_rows(
+    belt: &mut wgpu::util::StagingBelt,
+    device: &wgpu::Device,
+    encoder: &mut wgpu::CommandEncoder,
+    texture: &TextureRecord,
+    offset: u64,
+    budget: u64,
+) -> u64 {
+    let row_len = texture_row_len(texture);
+    let first_row = offset / row_len;
+    let rows = (budget / row_len).min(u64::from(texture.size.y).saturating_sub(first_row));
+    let padded_row_len = row_len.next_multiple_of(u64::from(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT));
+    let Some(size) = wgpu::BufferSize::new(padded_row_len * rows) else {
+        return 0;
+    };
+    let alignment = wgpu::BufferSize::new(u64::from(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT))
+        .expect("the row alignment is not zero");
+
+    let slice = belt.allocate(size, alignment, device);
+    {
+        let mut view = slice.get_mapped_range_mut();
+        let pixels = &texture.pixels[(first_row * row_len) as usize..];
+        for (row, staged) in pixels
+            .chunks_exact(row_len as usize)
+            .zip(view.chunks_exact_mut(padded_row_len as usize))
+        {
+            staged[..row.len()].copy_from_slice(row);
+        }
+    }
+
+    encoder.copy_buffer_to_texture(
+        wgpu::TexelCopyBufferInfo {
+            buffer: slice.buffer(),
+            layout: wgpu::TexelCopyBufferLayout {
+                offset: slice.offset(),
+                bytes_per_row: Some(padded_row_len as u32),
+                rows_per_image: Some(rows as u32),
+            },
+        },
+        wgpu::TexelCopyTextureInfo {
+            texture: &texture._texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d {
+                x: 0,
+                y: first_row as u32,
+                z: 0,
+            },
+            aspect: wgpu::TextureAspect::All,
+        },
+        wgpu::Extent3d {
+            width: texture.size.x,
+            height: rows as u32,
+            depth_or_array_layers: 1,
+        },
+    );
+    row_len * rows
+}
+
+fn texture_row_len(texture: &TextureRecord) -> u64 {
+    u64::from(texture.size.x) * texture.format.bytes_per_pixel() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec4;
+
+    use super::*;
+    use crate::{
+        draw_list::DrawList,
+        mesh::{VertexAttribute, VertexFormat},
+        recording::RecordingSurface,
+    };
+
+    #[derive(crate::encase::ShaderType)]
+    struct Vertex {
+        position: Vec4,
+    }
+
+    impl AsVertexBufferLayout for Vertex {
+        const STRIDE: u64 = 16;
+        const ATTRIBUTES: &'static [VertexAttribute] = &[VertexAttribute {
+            format: VertexFormat::Float32x4,
+            offset: 0,
+        }];
+    }
+
+    struct Uploads {
+        renderer: DrawListRenderer,
+        surface: RecordingSurface,
+    }
+
+    impl Uploads {
+        fn new(budget: u64) -> Self {
+            let mut renderer = DrawListRenderer::new_recording().unwrap();
+            renderer.set_upload_budget(budget);
+            let surface = RecordingSurface::new(&renderer, UVec2::new(4, 4));
+            Self { renderer, surface }
+        }
+
+        /// Submits an empty draw list and returns the bytes it uploaded.
+        fn submit(&mut self) -> u64 {
+            self.renderer
+                .submit_draw_list(self.surface.frame_context(), &DrawList::new());
+            self.renderer.stats().last_submission.bytes_uploaded
+        }
+
+        /// Submits until `handle` completes and returns the bytes each submission uploaded.
+        fn submit_until_complete(&mut self, handle: UploadHandle) -> Vec<u64> {
+            let mut uploaded = Vec::new();
+            while !self.renderer.is_upload_complete(handle) {
+                assert!(uploaded.len() < 1000, "the upload does not make progress");
+                uploaded.push(self.submit());
+            }
+            uploaded
+        }
+
+        /// Creates a storage buffer, and submits once so its initial contents are not counted
+        /// as uploaded by later submissions.
+        fn storage_buffer(&mut self, len: usize) -> StorageBufferId {
+            let storage_buffer = self
+                .renderer
+                .create_storage_buffer_bytes(
+                    "storage",
+                    wgpu::BufferSize::new(4).unwrap(),
+                    &vec![0; len],
+                )
+                .unwrap();
+            self.submit();
+            storage_buffer
+        }
+    }
+
+    #[test]
+    fn texture_uploads_copy_whole_rows_within_the_budget() {
+        let mut uploads = Uploads::new(200);
+        // 16 rows of 64 bytes, of which 3 fit in the budget.
+        let (_, handle) = uploads
+            .renderer
+            .create_texture_staged(
+                "texture",
+                UVec2::new(16, 16),
+                TextureFormat::Rgba,
+                &[7; 16 * 16 * 4],
+            )
+            .unwrap();
+        assert_eq!(uploads.renderer.pending_upload_bytes(), 1024);
+
+        assert_eq!(uploads.submit(), 192);
+        assert!(!uploads.renderer.is_upload_complete(handle));
+        assert_eq!(uploads.renderer.pending_upload_bytes(), 1024 - 192);
+
+        assert_eq!(
+            uploads.submit_until_complete(handle),
+            [192, 192, 192, 192, 64]
+        );
+        assert_eq!(uploads.renderer.pending_upload_bytes(), 0);
+    }
+
+    #[test]
+    fn budgets_smaller_than_a_row_still_copy_a_row() {
+        let mut uploads = Uploads::new(1);
+        let (_, handle) = uploads
+            .renderer
+            .create_texture_staged("texture", UVec2::new(8, 4), TextureFormat::Rgba, &[0; 128])
+            .unwrap();
+
+        assert_eq!(uploads.submit_until_complete(handle), [32; 4]);
+    }
+
+    #[test]
+    fn buffer_chunks_are_aligned_except_for_the_last() {
+        let mut uploads = Uploads::new(10);
+        let storage_buffer = uploads.storage_buffer(30);
+        let handle = uploads
+            .renderer
+            .write_storage_buffer_bytes_staged(storage_buffer, &[1; 30])
+            .unwrap();
+
+        // A 10 byte budget rounds down to 8 byte copies, leaving 6 bytes for the last chunk.
+        assert_eq!(uploads.submit_until_complete(handle), [8, 8, 8, 6]);
+    }
+
+    #[test]
+    fn uploads_larger_than_a_chunk_complete_within_one_submission() {
+        let mut uploads = Uploads::new(u64::MAX);
+        let len = UPLOAD_CHUNK_SIZE as usize * 2 + 100;
+        let storage_buffer = uploads.storage_buffer(len);
+        let handle = uploads
+            .renderer
+            .write_storage_buffer_bytes_staged(storage_buffer, &vec![1; len])
+            .unwrap();
+
+        assert_eq!(uploads.submit_until_complete(handle), [len as u64]);
+    }
+
+    #[test]
+    fn uploads_sharing_a_handle_complete_together() {
+        let mut uploads = Uploads::new(64);
+        let vertices: Vec<Vertex> = (0..8)
+            .map(|_| Vertex {
+                position: Vec4::ONE,
+            })
+            .collect();
+        let (_, handle) =
+            uploads
+                .renderer
+                .create_mesh_staged("mesh", &vertices, &[0, 1, 2, 3, 4, 5]);
+        assert_eq!(uploads.renderer.pending_upload_bytes(), 128 + 24);
+
+        // The vertices take two submissions and the indices a third.
+        assert_eq!(uploads.submit_until_complete(handle), [64, 64, 24]);
+    }
+
+    #[test]
+    fn rewriting_a_pending_upload_starts_over_with_the_same_handle() {
+        let mut uploads = Uploads::new(8);
+        let storage_buffer = uploads.storage_buffer(16);
+        let handle = uploads
+            .renderer
+            .write_storage_buffer_bytes_staged(storage_buffer, &[1; 16])
+            .unwrap();
+        assert_eq!(uploads.submit(), 8);
+
+        let rewritten = uploads
+            .renderer
+            .write_storage_buffer_bytes_staged(storage_buffer, &[2; 16])
+            .unwrap();
+        assert_eq!(rewritten, handle);
+        assert_eq!(uploads.renderer.pending_upload_bytes(), 16);
+        assert_eq!(uploads.submit_until_complete(handle), [8, 8]);
+    }
+
+    #[test]
+    fn flushing_completes_every_upload_at_once() {
+        let mut uploads = Uploads::new(1);
+        let (_, texture) = uploads
+            .renderer
+            .create_texture_staged("texture", UVec2::new(8, 8), TextureFormat::Rgba, &[0; 256])
+            .unwrap();
+        let storage_buffer = uploads.storage_buffer(64);
+        let buffer = uploads
+            .renderer
+            .write_storage_buffer_bytes_staged(storage_buffer, &[3; 64])
+            .unwrap();
+
+        uploads.renderer.flush_uploads();
+        assert!(uploads.renderer.is_upload_complete(texture));
+        assert!(uploads.renderer.is_upload_complete(buffer));
+        assert_eq!(uploads.renderer.pending_upload_bytes(), 0);
+    }
+
+    #[test]
+    fn replacing_the_device_drops_pending_uploads() {
+        let mut uploads = Uploads::new(1);
+        let (_, handle) = uploads
+            .renderer
+            .create_texture_staged("texture", UVec2::new(8, 8), TextureFormat::Rgba, &[0; 256])
+            .unwrap();
+
+        let replacement = DrawListRenderer::new_recording().unwrap();
+        uploads
+            .renderer
+            .replace_device(replacement.device.clone(), replacement.queue.clone());
+        assert!(uploads.renderer.is_upload_complete(handle));
+    }
+}
@@ -26,6 +26,19 @@ pub fn uniform_buffer(attr: TokenStream, item: TokenStream) -> TokenStream {
     expand_uniform_buffer_attribute(input, visibility).into()
 }
 
+#[proc_macro_derive(AsPushConstants, attributes(push_constants_visibility))]
+pub fn derive_as_push_constants(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_as_push_constants(input).into()
+}
+
+#[proc_macro_attribute]
+pub fn push_constants(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let visibility = parse_macro_input!(attr as Path);
+    let input = parse_macro_input!(item as DeriveInput);
+    expand_push_constants_attribute(input, visibility).into()
+}
+
 #[proc_macro_attribute]
 pub fn storage_buffer_element(_attr: TokenStream, item: TokenStream) -> TokenStream {
     let input = parse_macro_input!(item as DeriveInput);
@@ -60,7 +73,7 @@ fn expand_as_uniform_buffer(input: DeriveInput) -> proc_macro2::TokenStream {
     let name = &input.ident;
     let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
 
-    let visibility = match parse_uniform_visibility(&input.attrs) {
+    let visibility = match parse_visibility_attribute(&input.attrs, "uniform_visibility") {
         Ok(visibility) => normalize_shader_visibility_path(visibility),
         Err(error) => return error.to_compile_error(),
     };
@@ -72,11 +85,32 @@ fn expand_as_uniform_buffer(input: DeriveInput) -> proc_macro2::TokenStream {
     }
 }
 
+fn expand_as_push_constants(input: DeriveInput) -> proc_macro2::TokenStream {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let visibility = match parse_visibility_attribute(&input.attrs, "push_constants_visibility") {
+        Ok(visibility) => normalize_shader_visibility_path(visibility),
+        Err(error) => return error.to_compile_error(),
+    };
+
+    quote! {
+        impl #impl_generics ::granite_draw::AsPushConstants for #name #ty_generics #where_clause {
+            const VISIBILITY: ::granite_draw::ShaderVisibility = #visibility;
+        }
+    }
+}
+
 fn expand_uniform_buffer_attribute(
     mut input: DeriveInput,
     visibility: Path,
 ) -> proc_macro2::TokenStream {
-    if let Some(error) = validate_uniform_buffer_attribute_usage(&input) {
+    if let Some(error) = validate_visibility_attribute_usage(
+        &input,
+        "uniform_buffer",
+        "AsUniformBuffer",
+        "uniform_visibility",
+    ) {
         return error.to_compile_error();
     }
 
@@ -93,6 +127,32 @@ fn expand_uniform_buffer_attribute(
     }
 }
 
+fn expand_push_constants_attribute(
+    mut input: DeriveInput,
+    visibility: Path,
+) -> proc_macro2::TokenStream {
+    if let Some(error) = validate_visibility_attribute_usage(
+        &input,
+        "push_constants",
+        "AsPushConstants",
+        "push_constants_visibility",
+    ) {
+        return error.to_compile_error();
+    }
+
+    ensure_shader_type_derive(&mut input);
+    input
+        .attrs
+        .push(parse_quote!(#[derive(::granite_macros::AsPushConstants)]));
+    input
+        .attrs
+        .push(parse_quote!(#[push_constants_visibility(#visibility)]));
+
+    quote! {
+        #input
+    }
+}
+
 fn expand_storage_buffer_element_attribute(mut input: DeriveInput) -> proc_macro2::TokenStream {
     ensure_shader_type_derive(&mut input);
 
@@ -137,6 +197,7 @@ fn expand_as_layout(input: DeriveInput, target: LayoutTarget) -> proc_macro2::To
     };
 
     let mut attributes = Vec::new();
+    let mut semantics = Vec::new();
     let fields_iter = match &data.fields {
         Fields::Named(fields) => fields.named.iter().enumerate().collect::<Vec<_>>(),
         Fields::Unnamed(fields) => fields.unnamed.iter().enumerate().collect::<Vec<_>>(),
@@ -154,6 +215,18 @@ fn expand_as_layout(input: DeriveInput, target: LayoutTarget) -> proc_macro2::To
             continue;
         }
 
+        match (config.semantic, target) {
+            (Some(path), LayoutTarget::Vertex) => {
+                let semantic = normalize_vertex_semantic_path(path);
+                semantics.push(quote!(::core::option::Option::Some(#semantic)));
+            }
+            (Some(path), LayoutTarget::Instance) => {
+                return syn::Error::new_spanned(path, "`semantic` is only supported on vertices")
+                    .to_compile_error();
+            }
+            (None, _) => semantics.push(quote!(::core::option::Option::None)),
+        }
+
         let format_tokens = if let Some(path) = config.format {
             normalize_vertex_format_path(path)
         } else {
@@ -185,6 +258,13 @@ fn expand_as_layout(input: DeriveInput, target: LayoutTarget) -> proc_macro2::To
         LayoutTarget::Vertex => quote!(::granite_draw::mesh::VertexStepMode::Vertex),
         LayoutTarget::Instance => quote!(::granite_draw::mesh::VertexStepMode::Instance),
     };
+    let semantics = match target {
+        LayoutTarget::Vertex => quote! {
+            const SEMANTICS: &'static [::core::option::Option<::granite_draw::mesh::VertexSemantic>] =
+                &[#(#semantics),*];
+        },
+        LayoutTarget::Instance => quote!(),
+    };
 
     let mut generics = input.generics.clone();
     generics.make_where_clause().predicates.push(parse_quote!(
@@ -198,6 +278,7 @@ fn expand_as_layout(input: DeriveInput, target: LayoutTarget) -> proc_macro2::To
             const STRIDE: u64 = <Self as ::granite_draw::encase::ShaderSize>::SHADER_SIZE.get();
             const STEP_MODE: ::granite_draw::mesh::VertexStepMode = #step_mode;
             const ATTRIBUTES: &'static [::granite_draw::mesh::VertexAttribute] = &[#(#attributes),*];
+            #semantics
         }
     }
 }
@@ -206,6 +287,7 @@ fn expand_as_layout(input: DeriveInput, target: LayoutTarget) -> proc_macro2::To
 struct LayoutConfig {
     skip: bool,
     format: Option<Path>,
+    semantic: Option<Path>,
 }
 
 fn parse_layout_config(attributes: &[Attribute]) -> syn::Result<LayoutConfig> {
@@ -225,6 +307,9 @@ fn parse_layout_config(attributes: &[Attribute]) -> syn::Result<LayoutConfig> {
                 if config.format.is_some() {
                     return Err(meta.error("`skip` cannot be combined with `format`"));
                 }
+                if config.semantic.is_some() {
+                    return Err(meta.error("`skip` cannot be combined with `semantic`"));
+                }
 
                 config.skip = true;
                 return Ok(());
@@ -244,18 +329,34 @@ fn parse_layout_config(attributes: &[Attribute]) -> syn::Result<LayoutConfig> {
                 return Ok(());
             }
 
-            Err(meta.error("unsupported layout option; expected `skip` or `format = ...`"))
+            if meta.path.is_ident("semantic") {
+                if config.skip {
+                    return Err(meta.error("`semantic` cannot be combined with `skip`"));
+                }
+                if config.semantic.is_some() {
+                    return Err(meta.error("duplicate `semantic`"));
+                }
+
+                let value = meta.value()?;
+                let path: Path = value.parse()?;
+                config.semantic = Some(path);
+                return Ok(());
+            }
+
+            Err(meta.error(
+                "unsupported layout option; expected `skip`, `format = ...` or `semantic = ...`",
+            ))
         })?;
     }
 
     Ok(config)
 }
 
-fn parse_uniform_visibility(attributes: &[Attribute]) -> syn::Result<Path> {
+fn parse_visibility_attribute(attributes: &[Attribute], attribute_name: &str) -> syn::Result<Path> {
     let mut visibility: Option<Path> = None;
 
     for attribute in attributes {
-        if !attribute.path().is_ident("uniform_visibility") {
+        if !attribute.path().is_ident(attribute_name) {
             continue;
         }
 
@@ -263,7 +364,7 @@ fn parse_uniform_visibility(attributes: &[Attribute]) -> syn::Result<Path> {
         if visibility.is_some() {
             return Err(syn::Error::new_spanned(
                 attribute,
-                "duplicate `uniform_visibility` attribute",
+                format!("duplicate `{attribute_name}` attribute"),
             ));
         }
         visibility = Some(parsed);
@@ -272,27 +373,34 @@ fn parse_uniform_visibility(attributes: &[Attribute]) -> syn::Result<Path> {
     visibility.ok_or_else(|| {
         syn::Error::new(
             proc_macro2::Span::call_site(),
-            "missing #[uniform_visibility(...)] attribute",
+            format!("missing #[{attribute_name}(...)] attribute"),
         )
     })
 }
 
-fn validate_uniform_buffer_attribute_usage(input: &DeriveInput) -> Option<syn::Error> {
-    if has_derive(&input.attrs, "AsUniformBuffer") {
+/// Rejects a manual derive or visibility attribute on a type that uses a visibility attribute
+/// macro such as `#[uniform_buffer(...)]`, since the macro adds both.
+fn validate_visibility_attribute_usage(
+    input: &DeriveInput,
+    macro_name: &str,
+    derive_name: &str,
+    visibility_attribute_name: &str,
+) -> Option<syn::Error> {
+    if has_derive(&input.attrs, derive_name) {
         return Some(syn::Error::new_spanned(
             &input.ident,
-            "remove `AsUniformBuffer` from #[derive(...)] when using #[uniform_buffer(...)]",
+            format!("remove `{derive_name}` from #[derive(...)] when using #[{macro_name}(...)]"),
         ));
     }
 
     if let Some(attribute) = input
         .attrs
         .iter()
-        .find(|attribute| attribute.path().is_ident("uniform_visibility"))
+        .find(|attribute| attribute.path().is_ident(visibility_attribute_name))
     {
         return Some(syn::Error::new_spanned(
             attribute,
-            "remove #[uniform_visibility(...)] when using #[uniform_buffer(...)]",
+            format!("remove #[{visibility_attribute_name}(...)] when using #[{macro_name}(...)]"),
         ));
     }
 
@@ -366,6 +474,15 @@ fn normalize_vertex_format_path(path: Path) -> proc_macro2::TokenStream {
     }
 }
 
+fn normalize_vertex_semantic_path(path: Path) -> proc_macro2::TokenStream {
+    if path.segments.len() == 1 {
+        let ident = &path.segments[0].ident;
+        quote!(::granite_draw::mesh::VertexSemantic::#ident)
+    } else {
+        quote!(#path)
+    }
+}
+
 fn infer_vertex_format_path(ty: &Type) -> Option<proc_macro2::TokenStream> {
     match ty {
         Type::Path(type_path) => {
@@ -46,54 +46,62 @@ impl SceneBuilder for MinimalBuilder {
     type Target = Minimal;
 
     fn build(self, renderer: &mut Renderer) -> Self::Target {
-        let shader = renderer
-            .device
-            .create_shader_module(wgpu::ShaderModuleDescriptor {
-                label: Some("minimal_triangle_shader"),
-                source: wgpu::ShaderSource::Wgsl(SHADER.into()),
-            });
+        Self::Target {
+            pipeline: create_pipeline(renderer),
+        }
+    }
+}
 
-        let layout = renderer
-            .device
-            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("minimal_triangle_layout"),
-                bind_group_layouts: &[],
-                push_constant_ranges: &[],
-            });
+fn create_pipeline(renderer: &Renderer) -> wgpu::RenderPipeline {
+    let shader = renderer
+        .device
+        .create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("minimal_triangle_shader"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
 
-        let pipeline = renderer
-            .device
-            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: Some("minimal_triangle_pipeline"),
-                layout: Some(&layout),
-                vertex: wgpu::VertexState {
-                    module: &shader,
-                    entry_point: None,
-                    compilation_options: wgpu::PipelineCompilationOptions::default(),
-                    buffers: &[],
-                },
-                primitive: wgpu::PrimitiveState::default(),
-                depth_stencil: None,
-                multisample: wgpu::MultisampleState::default(),
-                fragment: Some(wgpu::FragmentState {
-                    module: &shader,
-                    entry_point: None,
-                    compilation_options: wgpu::PipelineCompilationOptions::default(),
-                    targets: &[Some(wgpu::ColorTargetState {
-                        format: renderer.surface_format(),
-                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
-                        write_mask: wgpu::ColorWrites::ALL,
-                    })],
-                }),
-                multiview: None,
-                cache: None,
-            });
+    let layout = renderer
+        .device
+        .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("minimal_triangle_layout"),
+            bind_group_layouts: &[],
+            push_constant_ranges: &[],
+        });
 
-        Self::Target { pipeline }
-    }
+    renderer
+        .device
+        .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("minimal_triangle_pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: None,
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                buffers: &[],
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: None,
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: renderer.surface_format(),
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+            cache: None,
+        })
 }
 
 impl Scene for Minimal {
+    fn device_lost(&mut self, renderer: &Renderer) {
+        self.pipeline = create_pipeline(renderer);
+    }
+
     fn frame(&mut self, renderer: &Renderer, frame: &Frame, _delta_time: f32) {
         let mut encoder = renderer
             .device
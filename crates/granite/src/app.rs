@@ -10,6 +10,9 @@ use winit::{
 
 use crate::{renderer::Renderer, scene::Scene};
 
+/// How many redraws in a row may fail to recreate a lost device before the application exits.
+const MAX_DEVICE_RECREATE_ATTEMPTS: u32 = 5;
+
 pub trait SceneBuilder {
     type Target: Scene;
 
@@ -49,6 +52,8 @@ where
         scene: Builder::Target,
         /// The last [std::time::Instant] that a frame was rendered to the display.
         last_frame_time: std::time::Instant,
+        /// The number of failed attempts to recreate a lost device since it was lost.
+        device_recreate_attempts: u32,
     },
 }
 
@@ -80,6 +85,7 @@ where
             renderer,
             scene,
             last_frame_time: std::time::Instant::now(),
+            device_recreate_attempts: 0,
         };
     }
 
@@ -94,7 +100,7 @@ where
             renderer,
             scene,
             last_frame_time,
-            ..
+            device_recreate_attempts,
         } = self
         else {
             // Window events while we are suspended?
@@ -123,6 +129,24 @@ where
                 let delta_time = (now - *last_frame_time).as_secs_f32();
                 *last_frame_time = now;
 
+                if renderer.is_device_lost() {
+                    tracing::warn!("GPU device lost, recreating it.");
+                    if let Err(error) = renderer.recreate_device() {
+                        *device_recreate_attempts += 1;
+                        if *device_recreate_attempts >= MAX_DEVICE_RECREATE_ATTEMPTS {
+                            tracing::error!("Could not recreate the device, exiting: {error}");
+                            event_loop.exit();
+                        } else {
+                            // The adapter may still be resetting; try again on the next redraw.
+                            tracing::warn!("Could not recreate the device: {error}");
+                            window.request_redraw();
+                        }
+                        return;
+                    }
+                    *device_recreate_attempts = 0;
+                    scene.device_lost(renderer);
+                }
+
                 {
                     let frame = renderer.begin_frame().expect("Could not begin frame");
                     scene.frame(renderer, &frame, delta_time);
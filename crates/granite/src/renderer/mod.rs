@@ -5,7 +5,10 @@
 //!
 //! For the higher-level draw-list/material layer, use the companion `granite-draw` crate.
 
-use std::sync::Arc;
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
 
 use thiserror::Error;
 use winit::window::Window;
@@ -48,8 +51,11 @@ pub struct Renderer {
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
 
+    instance: wgpu::Instance,
     surface: wgpu::Surface<'static>,
     surface_config: wgpu::SurfaceConfiguration,
+    /// Set by `wgpu` once `device` is lost.
+    device_lost: DeviceLostWatch,
 }
 
 impl Renderer {
@@ -61,40 +67,52 @@ impl Renderer {
             .create_surface(window)
             .map_err(|error| RendererCreateError::CreateSurface(error.to_string()))?;
 
-        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
-            compatible_surface: Some(&surface),
-            ..Default::default()
-        }))
-        .map_err(|error| RendererCreateError::RequestAdapter(error.to_string()))?;
-
-        let Some(surface_config) =
-            surface.get_default_config(&adapter, width.max(1), height.max(1))
-        else {
-            return Err(RendererCreateError::DetermineConfigurtation);
-        };
-
-        let (device, queue) = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
-            required_features: wgpu::Features::TEXTURE_BINDING_ARRAY
-                | wgpu::Features::SAMPLED_TEXTURE_AND_STORAGE_BUFFER_ARRAY_NON_UNIFORM_INDEXING,
-            required_limits: wgpu::Limits {
-                max_binding_array_elements_per_shader_stage: 1024,
-                ..Default::default()
-            },
-            ..Default::default()
-        }))
-        .map_err(|error| RendererCreateError::RequestDevice(error.to_string()))?;
-
+        let (adapter, device, queue, surface_config) =
+            request_device(&instance, &surface, width, height)?;
         surface.configure(&device, &surface_config);
 
         Ok(Self {
             _adapter: adapter,
+            device_lost: DeviceLostWatch::new(&device),
             device,
             queue,
+            instance,
             surface,
             surface_config,
         })
     }
 
+    /// Returns `true` once the device was lost, for example after a driver reset or when the
+    /// adapter was removed.
+    pub fn is_device_lost(&self) -> bool {
+        // Loss is only reported while the device is polled.
+        let _ = self.device.poll(wgpu::PollType::Poll);
+        self.device_lost.is_lost()
+    }
+
+    /// Replaces a lost device with a new one from a freshly requested adapter, and reconfigures
+    /// the surface for it.
+    ///
+    /// Everything created on the previous device is unusable afterwards and has to be created
+    /// again on [`Renderer::device`].
+    pub fn recreate_device(&mut self) -> Result<(), RendererCreateError> {
+        let (adapter, device, queue, surface_config) = request_device(
+            &self.instance,
+            &self.surface,
+            self.surface_config.width,
+            self.surface_config.height,
+        )?;
+        self.surface.configure(&device, &surface_config);
+
+        self.device_lost = DeviceLostWatch::new(&device);
+        self._adapter = adapter;
+        self.device = device;
+        self.queue = queue;
+        self.surface_config = surface_config;
+
+        Ok(())
+    }
+
     /// Get the current surface size.
     pub fn surface_size(&self) -> (u32, u32) {
         (self.surface_config.width, self.surface_config.height)
@@ -160,3 +178,88 @@ impl Renderer {
         }
     }
 }
+
+/// Requests an adapter compatible with `surface` and creates a device and queue on it, along
+/// with the default surface configuration for that adapter.
+fn request_device(
+    instance: &wgpu::Instance,
+    surface: &wgpu::Surface<'static>,
+    width: u32,
+    height: u32,
+) -> Result<
+    (
+        wgpu::Adapter,
+        wgpu::Device,
+        wgpu::Queue,
+        wgpu::SurfaceConfiguration,
+    ),
+    RendererCreateError,
+> {
+    let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+        compatible_surface: Some(surface),
+        ..Default::default()
+    }))
+    .map_err(|error| RendererCreateError::RequestAdapter(error.to_string()))?;
+
+    let Some(surface_config) = surface.get_default_config(&adapter, width.max(1), height.max(1))
+    else {
+        return Err(RendererCreateError::DetermineConfigurtation);
+    };
+
+    // Pipeline caches, push constants and timestamp queries are optional; they are only
+    // supported by some backends. Push constants are emulated with uniforms where they are
+    // missing, and profiling falls back to CPU timings without timestamp queries.
+    let optional_features = adapter.features()
+        & (wgpu::Features::PIPELINE_CACHE
+            | wgpu::Features::PUSH_CONSTANTS
+            | wgpu::Features::TIMESTAMP_QUERY);
+    let max_push_constant_size = if optional_features.contains(wgpu::Features::PUSH_CONSTANTS) {
+        adapter.limits().max_push_constant_size
+    } else {
+        0
+    };
+
+    let (device, queue) = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+        required_features: wgpu::Features::TEXTURE_BINDING_ARRAY
+            | wgpu::Features::SAMPLED_TEXTURE_AND_STORAGE_BUFFER_ARRAY_NON_UNIFORM_INDEXING
+            | optional_features,
+        required_limits: wgpu::Limits {
+            max_binding_array_elements_per_shader_stage: 1024,
+            max_push_constant_size,
+            ..Default::default()
+        },
+        ..Default::default()
+    }))
+    .map_err(|error| RendererCreateError::RequestDevice(error.to_string()))?;
+
+    Ok((adapter, device, queue, surface_config))
+}
+
+/// Tracks whether a device was lost, through its device lost callback.
+///
+/// A device keeps a single device lost callback, so this must be its only user: [`Renderer`]
+/// watches its own device, and layers drawing with it, such as `granite-draw`, rely on
+/// [`Renderer::is_device_lost`] instead of setting their own callback.
+#[derive(Clone, Debug)]
+pub struct DeviceLostWatch {
+    lost: Arc<AtomicBool>,
+}
+
+impl DeviceLostWatch {
+    /// Sets the device lost callback of `device`, replacing any callback set before.
+    pub fn new(device: &wgpu::Device) -> Self {
+        let lost = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&lost);
+        device.set_device_lost_callback(move |reason, message| {
+            tracing::warn!("GPU device lost ({reason:?}) {message}");
+            flag.store(true, Ordering::Release);
+        });
+        Self { lost }
+    }
+
+    /// Returns `true` once the device was lost. `wgpu` only reports the loss while the device is
+    /// polled.
+    pub fn is_lost(&self) -> bool {
+        self.lost.load(Ordering::Acquire)
+    }
+}
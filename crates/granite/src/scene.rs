@@ -8,5 +8,11 @@ pub trait Scene {
         let _ = event;
     }
 
+    /// Called after the GPU device was lost and [Renderer::device] was replaced by a new one.
+    /// Everything created on the previous device has to be created again.
+    fn device_lost(&mut self, renderer: &Renderer) {
+        let _ = renderer;
+    }
+
     fn frame(&mut self, renderer: &Renderer, frame: &Frame, delta_time: f32);
 }